@@ -27,7 +27,7 @@ async fn main() {
     }
     println!("");
 
-    let result = egg_mode::place::reverse_geocode(51.507222, -0.1275)
+    let result = egg_mode::place::reverse_geocode((51.507222, -0.1275))
         .granularity(PlaceType::City)
         .call(&config.token)
         .await
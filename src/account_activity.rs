@@ -0,0 +1,162 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Methods to manage webhooks and subscriptions for the Account Activity API.
+//!
+//! Rather than polling for new tweets, DMs, follows, and the like, the Account Activity API sends
+//! events for the users subscribed to it to a webhook you run. Each webhook lives in a dev
+//! environment that you set up in the Twitter developer portal, which is the `env` parameter that
+//! all of these functions take.
+//!
+//! Setting this up takes a couple steps:
+//!
+//! 1. Register the URL of your webhook with [`register_webhook`]. Before it accepts the URL,
+//!    Twitter will send a "challenge-response check" (CRC) to it, in the form of a GET request with
+//!    a `crc_token` query parameter. Your webhook needs to respond to it with a JSON object with a
+//!    `response_token` field, with the value from [`challenge_response`]. Twitter will keep
+//!    sending these periodically, so make sure to keep answering them!
+//! 2. Subscribe a user to the environment with [`subscribe`]. This needs a user token for the user
+//!    whose events you want to receive.
+//!
+//! The webhooks you've registered can be loaded with [`list_webhooks`], and removed with
+//! [`delete_webhook`].
+//!
+//! [`register_webhook`]: fn.register_webhook.html
+//! [`challenge_response`]: fn.challenge_response.html
+//! [`subscribe`]: fn.subscribe.html
+//! [`list_webhooks`]: fn.list_webhooks.html
+//! [`delete_webhook`]: fn.delete_webhook.html
+
+use chrono;
+use hmac::{Hmac, Mac, NewMac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use crate::common::*;
+use crate::error::Result;
+use crate::{auth, links};
+
+/// A webhook registered with the Account Activity API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Webhook {
+    /// The ID of this webhook, for use with `delete_webhook`.
+    pub id: String,
+    /// The URL Twitter sends events to.
+    pub url: String,
+    /// Whether the webhook passed its most recent challenge-response check.
+    pub valid: bool,
+    /// UTC timestamp from when this webhook was registered.
+    #[serde(rename = "created_timestamp", deserialize_with = "deserialize_iso8601")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+fn webhooks_url(env: &str) -> String {
+    format!(
+        "{}/{}/webhooks.json",
+        links::account_activity::ENV_STEM,
+        env
+    )
+}
+
+/// Registers the given URL as a webhook for the given environment.
+///
+/// Twitter will send a challenge-response check to the URL before this call returns, so your
+/// webhook needs to be up and answering it (see [`challenge_response`]) before you call this.
+///
+/// [`challenge_response`]: fn.challenge_response.html
+pub async fn register_webhook(
+    env: &str,
+    url: &str,
+    token: &auth::Token,
+) -> Result<Response<Webhook>> {
+    let params = ParamList::new().add_param("url", url.to_string());
+    let req = post(&webhooks_url(env), token, Some(&params));
+    request_with_json_response(req).await
+}
+
+/// Loads the webhooks registered for the given environment.
+///
+/// Twitter expects a Bearer token for this call.
+pub async fn list_webhooks(env: &str, token: &auth::Token) -> Result<Response<Vec<Webhook>>> {
+    let req = get(&webhooks_url(env), token, None);
+    request_with_json_response(req).await
+}
+
+/// Removes the webhook with the given ID from the given environment.
+///
+/// Removing a webhook doesn't remove the subscriptions to its environment, so if you register a
+/// new one afterward, it will start getting events for the same users.
+pub async fn delete_webhook(env: &str, id: &str, token: &auth::Token) -> Result<Response<()>> {
+    let url = format!(
+        "{}/{}/webhooks/{}.json",
+        links::account_activity::ENV_STEM,
+        env,
+        id
+    );
+    let req = auth::raw::delete(&url, token, None);
+    request_with_empty_response(req).await
+}
+
+/// Subscribes the user the given token belongs to to the given environment, so that their events
+/// are sent to its webhook.
+///
+/// This needs a user token (the `Access` variant of `Token`); a Bearer token can't subscribe
+/// anyone.
+pub async fn subscribe(env: &str, token: &auth::Token) -> Result<Response<()>> {
+    let url = format!(
+        "{}/{}/subscriptions.json",
+        links::account_activity::ENV_STEM,
+        env
+    );
+    let req = post(&url, token, None);
+    request_with_empty_response(req).await
+}
+
+/// Computes the answer to a challenge-response check from Twitter.
+///
+/// When Twitter sends a GET request to your webhook with a `crc_token` query parameter, it expects
+/// a JSON response like `{"response_token": "sha256=..."}`, where the value is what this function
+/// returns for that `crc_token`. `consumer_secret` is the secret half of your app's consumer
+/// token.
+///
+/// ```rust
+/// let answer = egg_mode::account_activity::challenge_response("crc-token", "consumer-secret");
+/// assert!(answer.starts_with("sha256="));
+/// ```
+pub fn challenge_response(crc_token: &str, consumer_secret: &str) -> String {
+    let mut digest =
+        Hmac::<Sha256>::new_varkey(consumer_secret.as_bytes()).expect("Wrong key length");
+    digest.update(crc_token.as_bytes());
+    format!("sha256={}", base64::encode(digest.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{challenge_response, Webhook};
+    use crate::common::tests::load_file;
+
+    #[test]
+    fn crc_response() {
+        // this is test case 2 from RFC 4231, as the CRC is just a base64-encoded HMAC-SHA256
+        assert_eq!(
+            challenge_response("what do ya want for nothing?", "Jefe"),
+            "sha256=W9zBRr9gdU5qBCQmCJV1x1oAPwidJzmDnexYuWTsOEM="
+        );
+    }
+
+    #[test]
+    fn parse_webhooks() {
+        let content = load_file("sample_payloads/webhooks.json");
+        let hooks = serde_json::from_str::<Vec<Webhook>>(&content).unwrap();
+
+        assert_eq!(hooks.len(), 1);
+        assert_eq!(hooks[0].id, "1234567890");
+        assert_eq!(hooks[0].url, "https://example.com/webhook/twitter");
+        assert!(hooks[0].valid);
+        assert_eq!(
+            hooks[0].created_at,
+            chrono::DateTime::parse_from_rfc3339("2016-06-04T22:31:29Z").unwrap()
+        );
+    }
+}
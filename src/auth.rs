@@ -68,6 +68,11 @@
 //! # }
 //! ```
 //!
+//! If you'd rather not manage the Bearer token yourself, an [`AppAuth`] can load it the first time
+//! it's needed, and load it again if Twitter ever says it's no longer valid.
+//!
+//! [`AppAuth`]: struct.AppAuth.html
+//!
 //! ## Access Tokens
 //!
 //! Access tokens are for when you want to perform your requests on behalf of a specific user. This
@@ -181,14 +186,65 @@
 //! // token can be given to any egg_mode method that asks for a token
 //! ```
 //!
+//! ### Shortcut: Signing In From a Desktop or CLI App
+//!
+//! With the `local_redirect` feature, apps that run on the user's own computer can use
+//! [`sign_in_locally`] to run the whole web-based flow at once. It starts a small web server on
+//! `127.0.0.1` to receive the callback, so the user doesn't have to copy a PIN back into the app.
+//! See [`LocalRedirect`] for details.
+//!
+//! [`sign_in_locally`]: fn.sign_in_locally.html
+//! [`LocalRedirect`]: struct.LocalRedirect.html
+//!
+//! ### xAuth
+//!
+//! Apps that Twitter has specifically approved for xAuth can exchange a user's username and
+//! password directly for an access token, using the [`xauth`] function. Twitter doesn't grant this
+//! access to most apps, so unless yours has been approved, use one of the flows above instead.
+//!
+//! [`xauth`]: fn.xauth.html
+//!
+//! ## OAuth 2.0 User Tokens
+//!
+//! Some of Twitter's newer endpoints accept (or require) a user token from OAuth 2.0 instead of an
+//! Access token. These tokens are granted for a limited set of scopes, and are obtained with the
+//! Authorization Code flow with PKCE, which works a lot like the web-based sign-in for Access
+//! tokens. The [`oauth2`] module has the functions and types to get one, and the resulting `Token`
+//! can be used like any other.
+//!
+//! [`oauth2`]: oauth2/index.html
+//!
+//! ## Saving Tokens
+//!
+//! Once a user has signed in, their token can be saved so they don't need to sign in again the
+//! next time your app starts. The [`TokenStore`] trait describes a place to save tokens by user
+//! ID, and [`FileTokenStore`] is a simple one that saves each token as a JSON file. An
+//! `oauth2::UserAuth` can also save its token to a `TokenStore` every time it's refreshed.
+//!
+//! [`TokenStore`]: trait.TokenStore.html
+//! [`FileTokenStore`]: struct.FileTokenStore.html
+//!
+//! ## Using Several Tokens
+//!
+//! Apps that have several tokens available, such as a Bearer token and a few users' Access
+//! tokens, can spread their calls across them with a [`TokenPool`]. It keeps track of the
+//! rate-limit information returned with each call, and sends each new call with whichever token
+//! has the most calls left on that endpoint.
+//!
+//! [`TokenPool`]: struct.TokenPool.html
+//!
 //! For more information on the individual steps of the authentication process, see the
 //! documentation for the functions in this module.
 
 use std::borrow::Cow;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use hyper::Method;
 use serde::{Serialize, Deserialize};
 use serde_json;
+use sha2::{Digest, Sha256};
 
 use crate::common::*;
 use crate::{
@@ -196,7 +252,18 @@ use crate::{
     links,
 };
 
+#[cfg(feature = "local_redirect")]
+mod local;
+pub mod oauth2;
+mod pool;
 pub(crate) mod raw;
+mod store;
+
+#[cfg(feature = "local_redirect")]
+pub use self::local::{sign_in_locally, LocalRedirect};
+pub use self::pool::TokenPool;
+pub use self::raw::sign_request;
+pub use self::store::{FileTokenStore, TokenStore};
 
 use raw::RequestBuilder;
 
@@ -219,7 +286,11 @@ use raw::RequestBuilder;
 /// ```rust
 /// let con_token = egg_mode::KeyPair::new("consumer key", "consumer token");
 /// ```
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// The secret is left out of the `Debug` output, so a `KeyPair` can be logged safely. With the
+/// `zeroize` feature, secrets held in a `String` are also wiped from memory when the `KeyPair` is
+/// dropped.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct KeyPair {
     ///A key used to identify an application or user.
     pub key: Cow<'static, str>,
@@ -252,19 +323,52 @@ impl KeyPair {
     }
 }
 
+impl fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("key", &self.key)
+            .field("secret", &Redacted)
+            .finish()
+    }
+}
+
+// this is implemented with or without `zeroize`, so that turning the feature on doesn't change
+// which code compiles (like moving the fields out of a `KeyPair`)
+impl Drop for KeyPair {
+    fn drop(&mut self) {
+        #[cfg(feature = "zeroize")]
+        {
+            if let Cow::Owned(ref mut secret) = self.secret {
+                zeroize::Zeroize::zeroize(secret);
+            }
+        }
+    }
+}
+
+/// Stands in for a secret in `Debug` output.
+pub(crate) struct Redacted;
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
 /// A token that can be used to sign requests to Twitter.
 ///
 /// Conceptually, a Token represents your authorization to call the Twitter API. It can either be a
 /// [Bearer token], representing a "logged-out" view of Twitter coming from your app itself; or an
 /// [Access token], representing a combination of your app's "consumer" key with a specific user
-/// granting access for your app to use the Twitter API on their behalf. For more information, see
-/// the [authentication documentation][auth].
+/// granting access for your app to use the Twitter API on their behalf; or an [OAuth 2.0 user
+/// token], representing a user granting your app a specific set of scopes. For more information,
+/// see the [authentication documentation][auth].
 ///
 /// [Bearer token]: index.html#bearer-tokens
 /// [Access token]: index.html#access-tokens
+/// [OAuth 2.0 user token]: index.html#oauth-20-user-tokens
 /// [auth]: index.html
 ///
-/// Once you have obtained a Token of either kind, the keys within may be saved and reused in the
+/// Once you have obtained a Token of any kind, the keys within may be saved and reused in the
 /// future, as long as the access has not been revoked. **Note** that the keys saved in this type
 /// work just like a password, and they should be handled with care when you save them! If you
 /// believe your keys have been compromised, you can generate a new consumer token in [Twitter's
@@ -273,7 +377,13 @@ impl KeyPair {
 ///
 /// [apps]: https://developer.twitter.com/en/apps
 /// [invalidate]: fn.invalidate_bearer.html
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// To keep these keys out of logs, the `Debug` output of a `Token` leaves out its secrets: only
+/// the consumer and access keys of an Access token are shown. With the `zeroize` feature, the
+/// secrets are also wiped from memory when the `Token` is dropped. Since `Token` implements `Drop`
+/// then, the keys can't be moved out of it by value; match on it by reference and clone them
+/// instead.
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Token {
     /// An OAuth Access token indicating the request is coming from a specific user.
     Access {
@@ -285,6 +395,161 @@ pub enum Token {
     /// An OAuth Bearer token indicating the request is coming from the application itself, not a
     /// particular user.
     Bearer(String),
+    /// An OAuth 2.0 token indicating the request is coming from a specific user, limited to the
+    /// scopes they granted. See the [`oauth2`] module for how to get one.
+    ///
+    /// [`oauth2`]: oauth2/index.html
+    OAuth2(oauth2::UserToken),
+}
+
+impl Token {
+    /// Replaces the consumer key in this token, keeping the same user authorization.
+    ///
+    /// This is meant for rotating your app's consumer key without having every user sign in
+    /// again. An Access token is tied to the app the user authorized rather than to a particular
+    /// consumer key, so if you regenerate the consumer key and secret for your app in [Twitter's
+    /// Apps Dashboard][apps], existing access keys can be used with the new consumer key. The
+    /// requests made with the returned `Token` will be signed with `new_con_token`.
+    ///
+    /// [apps]: https://developer.twitter.com/en/apps
+    ///
+    /// This is only valid if `new_con_token` belongs to the same app as the original consumer key.
+    /// Access keys granted to one app can't be used with the consumer key of another, and Twitter
+    /// will reject any requests signed that way.
+    ///
+    /// Bearer tokens and OAuth 2.0 tokens don't carry a consumer key, so they're returned
+    /// unchanged. Note that
+    /// regenerating your consumer key also invalidates your app's Bearer token, so you'll need to
+    /// request a new one with [`bearer_token`] after rotating your keys.
+    ///
+    /// [`bearer_token`]: fn.bearer_token.html
+    pub fn with_consumer(self, new_con_token: KeyPair) -> Token {
+        match self {
+            Token::Access { ref access, .. } => Token::Access {
+                consumer: new_con_token,
+                access: access.clone(),
+            },
+            token => token,
+        }
+    }
+
+    /// Exchanges an OAuth 2.0 token for a new one before it expires, using its refresh token.
+    ///
+    /// This is the same as calling `Client::refresh` on the `oauth2::UserToken` inside, with the
+    /// `client` it was granted to. Access and Bearer tokens don't expire, so they're returned
+    /// unchanged. To have egg-mode refresh a token whenever it's needed instead, see
+    /// [`oauth2::UserAuth`].
+    ///
+    /// [`oauth2::UserAuth`]: oauth2/struct.UserAuth.html
+    pub async fn refresh(&self, client: &oauth2::Client) -> Result<Token> {
+        match self {
+            Token::OAuth2(user) => client.refresh(user).await,
+            token => Ok(token.clone()),
+        }
+    }
+
+    /// Returns a short string that identifies this token, which is safe to write to logs.
+    ///
+    /// The fingerprint is the first 8 bytes of a SHA-256 hash of the token's access key (or of the
+    /// Bearer token or OAuth 2.0 access token itself), written in hex. The same token always gets
+    /// the same fingerprint, across runs and machines, and different tokens get different ones, so
+    /// it can be used to tell which token made which request. The token's keys can't be recovered
+    /// from it.
+    ///
+    /// Access tokens are fingerprinted by their access key alone, so two tokens for the same user
+    /// authorization have the same fingerprint even if one was given a new consumer key with
+    /// `with_consumer`.
+    pub fn fingerprint(&self) -> String {
+        let hash = match self {
+            Token::Access { access, .. } => Sha256::new().chain("access:").chain(&*access.key),
+            Token::Bearer(token) => Sha256::new().chain("bearer:").chain(token),
+            Token::OAuth2(user) => Sha256::new().chain("oauth2:").chain(&user.access_token),
+        }
+        .finalize();
+        hash[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+}
+
+impl fmt::Debug for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Access { consumer, access } => f
+                .debug_struct("Access")
+                .field("consumer", consumer)
+                .field("access", access)
+                .finish(),
+            Token::Bearer(_) => f.debug_tuple("Bearer").field(&Redacted).finish(),
+            Token::OAuth2(user) => f.debug_tuple("OAuth2").field(user).finish(),
+        }
+    }
+}
+
+// like `KeyPair`, this is implemented with or without `zeroize`
+impl Drop for Token {
+    fn drop(&mut self) {
+        // the KeyPairs and UserToken wipe themselves
+        #[cfg(feature = "zeroize")]
+        {
+            if let Token::Bearer(ref mut bearer) = self {
+                zeroize::Zeroize::zeroize(bearer);
+            }
+        }
+    }
+}
+
+/// The hash function used to sign requests made with an Access token.
+///
+/// OAuth 1.0a signatures are an HMAC of a string describing the request, keyed with the consumer
+/// and access secrets. Twitter has traditionally used `HMAC-SHA1` for this, but it also accepts
+/// `HMAC-SHA256`, which some setups require. egg-mode uses `HmacSha1` unless told otherwise, which
+/// can be changed for every request with [`set_signature_method`], or for a single request with
+/// `RequestBuilder::with_signature_method` in the `raw::auth` module.
+///
+/// [`set_signature_method`]: fn.set_signature_method.html
+///
+/// Requests made with a Bearer token aren't signed, so this setting doesn't affect them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SignatureMethod {
+    /// Sign requests with `HMAC-SHA1`. This is the default.
+    HmacSha1,
+    /// Sign requests with `HMAC-SHA256`.
+    HmacSha256,
+}
+
+impl SignatureMethod {
+    /// Returns the name of this method, as given in the `oauth_signature_method` parameter.
+    fn oauth_name(self) -> &'static str {
+        match self {
+            SignatureMethod::HmacSha1 => "HMAC-SHA1",
+            SignatureMethod::HmacSha256 => "HMAC-SHA256",
+        }
+    }
+}
+
+static USE_HMAC_SHA256: AtomicBool = AtomicBool::new(false);
+
+/// Sets the method used to sign requests made with an Access token.
+///
+/// This setting is shared by every request made by this process, except for requests assembled
+/// with a `RequestBuilder` that was given its own method. The default is
+/// `SignatureMethod::HmacSha1`. See [`SignatureMethod`] for details.
+///
+/// [`SignatureMethod`]: enum.SignatureMethod.html
+pub fn set_signature_method(method: SignatureMethod) {
+    USE_HMAC_SHA256.store(method == SignatureMethod::HmacSha256, Ordering::Relaxed);
+}
+
+/// Returns the method used to sign requests made with an Access token.
+///
+/// See [`set_signature_method`] for details.
+///
+/// [`set_signature_method`]: fn.set_signature_method.html
+pub fn signature_method() -> SignatureMethod {
+    if USE_HMAC_SHA256.load(Ordering::Relaxed) {
+        SignatureMethod::HmacSha256
+    } else {
+        SignatureMethod::HmacSha1
+    }
 }
 
 /// With the given consumer KeyPair, ask Twitter for a request KeyPair that can be used to request
@@ -512,7 +777,65 @@ pub async fn access_token<S: Into<String>>(
         .request_keys(&con_token, Some(request_token));
 
     let (_headers, urlencoded) = raw_request(request).await?;
-    let urlencoded = std::str::from_utf8(&urlencoded).map_err(|_| {
+
+    parse_access_token(con_token, &urlencoded)
+}
+
+/// Exchange a username and password directly for an access token, using xAuth.
+///
+/// **This function will not work for most apps.** Twitter restricts xAuth to apps that have been
+/// specifically approved to use it, and any other app will receive a `401 Unauthorized` response
+/// no matter what credentials it sends. New apps are generally not granted xAuth access. If your
+/// app has not been approved for it, use the PIN-based or web-based flows described in the
+/// [authentication overview][auth] instead.
+///
+/// [auth]: index.html
+///
+/// When Twitter rejects the request, this function returns [`Error::XAuthUnauthorized`] instead
+/// of a plain `BadStatus` error. Twitter doesn't distinguish between an app without xAuth access
+/// and an incorrect username or password, so either one can be the cause.
+///
+/// [`Error::XAuthUnauthorized`]: ../error/enum.Error.html#variant.XAuthUnauthorized
+///
+/// Like `access_token`, this function consumes `con_token` to insert it into the returned
+/// `Token`. On success, it yields the access token, the ID of the authenticated user, and their
+/// screen name.
+pub async fn xauth<U: Into<String>, P: Into<String>>(
+    con_token: KeyPair,
+    username: U,
+    password: P,
+) -> Result<(Token, u64, String)> {
+    let params = ParamList::new()
+        .add_param("x_auth_username", username.into())
+        .add_param("x_auth_password", password.into())
+        .add_param("x_auth_mode", "client_auth");
+
+    let request = RequestBuilder::new(Method::POST, links::auth::ACCESS_TOKEN)
+        .with_body_params(&params)
+        .request_keys(&con_token, None);
+
+    let (_headers, urlencoded) = raw_request(request).await.map_err(xauth_error)?;
+
+    parse_access_token(con_token, &urlencoded)
+}
+
+/// Replaces the generic error Twitter gives for a rejected xAuth request with one that explains
+/// what happened.
+fn xauth_error(err: error::Error) -> error::Error {
+    match err {
+        error::Error::BadStatus(hyper::StatusCode::UNAUTHORIZED) => error::Error::XAuthUnauthorized,
+        // code 87: "Client is not permitted to perform this action."
+        error::Error::TwitterError(_, ref errors) if errors.errors.iter().any(|e| e.code == 87) => {
+            error::Error::XAuthUnauthorized
+        }
+        err => err,
+    }
+}
+
+/// Parses the urlencoded response from `POST oauth/access_token` into a `Token` with the given
+/// consumer key, the ID of the authenticated user, and their screen name.
+fn parse_access_token(con_token: KeyPair, urlencoded: &[u8]) -> Result<(Token, u64, String)> {
+    let urlencoded = std::str::from_utf8(urlencoded).map_err(|_| {
         std::io::Error::new(
             std::io::ErrorKind::InvalidData,
             "stream did not contain valid UTF-8",
@@ -642,3 +965,412 @@ pub async fn verify_tokens(token: &Token) -> Result<Response<crate::user::Twitte
     let req = get(links::auth::VERIFY_CREDENTIALS, token, None);
     request_with_json_response(req).await
 }
+
+/// A `Token` along with the user it authenticates, as returned by `whoami`.
+///
+/// Many calls need the ID of the authenticated user, so this keeps it on hand alongside the token
+/// once it's been loaded, without asking Twitter again. If you already have the user's ID and
+/// screen name (for example, from `access_token`), you can make one with `new` instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthedToken {
+    token: Token,
+    user_id: u64,
+    screen_name: String,
+}
+
+impl AuthedToken {
+    /// Creates a new `AuthedToken` for the given token and the user it authenticates.
+    ///
+    /// This doesn't check with Twitter that the token belongs to that user; use `whoami` for
+    /// that.
+    pub fn new(token: Token, user_id: u64, screen_name: impl Into<String>) -> AuthedToken {
+        AuthedToken {
+            token,
+            user_id,
+            screen_name: screen_name.into(),
+        }
+    }
+
+    /// Returns the token, to hand to other egg-mode functions.
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    /// Returns the ID of the authenticated user.
+    pub fn user_id(&self) -> u64 {
+        self.user_id
+    }
+
+    /// Returns the screen name of the authenticated user, as of when the token was checked.
+    pub fn screen_name(&self) -> &str {
+        &self.screen_name
+    }
+
+    /// Returns the authenticated user as a `UserID`, to hand to functions that look up a user.
+    pub fn user(&self) -> crate::user::UserID {
+        crate::user::UserID::ID(self.user_id)
+    }
+
+    /// Discards the user information, returning the token.
+    pub fn into_token(self) -> Token {
+        self.token
+    }
+}
+
+impl AsRef<Token> for AuthedToken {
+    fn as_ref(&self) -> &Token {
+        &self.token
+    }
+}
+
+/// Checks that the given token is valid, and returns it along with the user it authenticates.
+///
+/// This calls the same endpoint as `verify_tokens`, and saves the user's ID and screen name in the
+/// returned `AuthedToken` so they can be used later without another call. Like `verify_tokens`,
+/// this returns an error from Twitter if the user has revoked your app's access, and it only works
+/// with tokens for a user, not with Bearer tokens.
+pub async fn whoami(token: &Token) -> Result<Response<AuthedToken>> {
+    let user = verify_tokens(token).await?;
+    Ok(authed_token(token, user))
+}
+
+/// Pairs the given token with the user that `verify_tokens` loaded for it.
+fn authed_token(token: &Token, user: Response<crate::user::TwitterUser>) -> Response<AuthedToken> {
+    Response::map(user, |user| {
+        AuthedToken::new(token.clone(), user.id, user.screen_name)
+    })
+}
+
+/// A source of Bearer tokens that loads the token on first use.
+///
+/// `AppAuth` holds onto your consumer KeyPair, and calls [`bearer_token`] the first time you ask it
+/// for a token. That token is then cached and handed out to later requests. If Twitter ever
+/// rejects the cached token as invalid (for example, because it was [invalidated]), `call` will
+/// load a fresh token and retry the request once before giving up.
+///
+/// [`bearer_token`]: fn.bearer_token.html
+/// [invalidated]: fn.invalidate_bearer.html
+///
+/// An `AppAuth` can be shared between tasks (for example, in an `Arc`), and concurrent requests
+/// will share the same token, even while it's being loaded.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn main() {
+/// use egg_mode::auth::AppAuth;
+///
+/// let auth = AppAuth::new(egg_mode::KeyPair::new("consumer key", "consumer secret"));
+///
+/// // the first request loads the Bearer token...
+/// let user = auth
+///     .call(|token| async move { egg_mode::user::show("rustlang", &token).await })
+///     .await
+///     .unwrap();
+///
+/// // ...and later requests reuse it
+/// let tweet = auth
+///     .call(|token| async move { egg_mode::tweet::show(20, &token).await })
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AppAuth {
+    con_token: KeyPair,
+    bearer: futures::lock::Mutex<Option<Token>>,
+}
+
+impl AppAuth {
+    /// Creates a new `AppAuth` with the given consumer KeyPair. This doesn't contact Twitter; the
+    /// Bearer token is loaded when it's first needed.
+    pub fn new(con_token: KeyPair) -> AppAuth {
+        AppAuth {
+            con_token,
+            bearer: futures::lock::Mutex::new(None),
+        }
+    }
+
+    /// Returns the cached Bearer token, loading it from Twitter first if necessary.
+    pub async fn token(&self) -> Result<Token> {
+        self.token_with(|| bearer_token(&self.con_token)).await
+    }
+
+    /// Discards the cached Bearer token, so that the next request will load it again.
+    pub async fn reset(&self) {
+        *self.bearer.lock().await = None;
+    }
+
+    /// Runs the given request with the Bearer token, loading the token first if necessary.
+    ///
+    /// If the request fails because Twitter says the token is invalid or expired, the token is
+    /// loaded again and the request is retried once. Any other error, or a second invalid-token
+    /// error, is returned as-is.
+    pub async fn call<F, Fut, T>(&self, request: F) -> Result<T>
+    where
+        F: FnMut(Token) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.call_with(|| bearer_token(&self.con_token), request)
+            .await
+    }
+
+    async fn token_with<M, MFut>(&self, mint: M) -> Result<Token>
+    where
+        M: FnOnce() -> MFut,
+        MFut: Future<Output = Result<Token>>,
+    {
+        // hold the lock while loading the token, so that concurrent requests don't all load it
+        let mut bearer = self.bearer.lock().await;
+        match *bearer {
+            Some(ref token) => Ok(token.clone()),
+            None => {
+                let token = mint().await?;
+                *bearer = Some(token.clone());
+                Ok(token)
+            }
+        }
+    }
+
+    async fn call_with<M, MFut, F, Fut, T>(&self, mut mint: M, mut request: F) -> Result<T>
+    where
+        M: FnMut() -> MFut,
+        MFut: Future<Output = Result<Token>>,
+        F: FnMut(Token) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let token = self.token_with(&mut mint).await?;
+        match request(token.clone()).await {
+            Err(ref e) if is_invalid_token(e) => {
+                {
+                    // only throw out the token if another request hasn't already replaced it
+                    let mut bearer = self.bearer.lock().await;
+                    if same_bearer(bearer.as_ref(), &token) {
+                        *bearer = None;
+                    }
+                }
+                let token = self.token_with(&mut mint).await?;
+                request(token).await
+            }
+            resp => resp,
+        }
+    }
+}
+
+/// Returns whether the given error means that Twitter rejected the token used for a request.
+fn is_invalid_token(err: &error::Error) -> bool {
+    match err {
+        error::Error::BadStatus(status) => *status == hyper::StatusCode::UNAUTHORIZED,
+        // 89: "Invalid or expired token"
+        error::Error::TwitterError(_, errors) => errors.errors.iter().any(|e| e.code == 89),
+        _ => false,
+    }
+}
+
+fn same_bearer(cached: Option<&Token>, used: &Token) -> bool {
+    match (cached, used) {
+        (Some(Token::Bearer(cached)), Token::Bearer(used)) => cached == used,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{authed_token, xauth_error, AppAuth, AuthedToken, KeyPair, Token};
+    use crate::error::{Error, TwitterErrorCode, TwitterErrors};
+
+    #[test]
+    fn token_fingerprints() {
+        let token = |key: &'static str| Token::Access {
+            consumer: KeyPair::new("consumer key", "consumer secret"),
+            access: KeyPair::new(key, "access secret"),
+        };
+
+        // the fingerprint shouldn't change between versions, or old logs won't line up
+        let fingerprint = token("access key").fingerprint();
+        assert_eq!(fingerprint, "833ecc6de444ae10");
+        assert_eq!(fingerprint, token("access key").fingerprint());
+        assert_eq!(
+            fingerprint,
+            token("access key")
+                .with_consumer(KeyPair::new("new consumer", "new secret"))
+                .fingerprint()
+        );
+
+        assert_ne!(fingerprint, token("other access key").fingerprint());
+        let bearer = || Token::Bearer("access key".to_string());
+        assert_ne!(fingerprint, bearer().fingerprint());
+        assert_eq!(bearer().fingerprint(), bearer().fingerprint());
+    }
+
+    #[test]
+    fn rotate_consumer() {
+        let token = Token::Access {
+            consumer: KeyPair::new("old consumer", "old secret"),
+            access: KeyPair::new("access key", "access secret"),
+        };
+        let token = token.with_consumer(KeyPair::new("new consumer", "new secret"));
+
+        match token {
+            Token::Access {
+                ref consumer,
+                ref access,
+            } => {
+                assert_eq!(consumer.key, "new consumer");
+                assert_eq!(consumer.secret, "new secret");
+                assert_eq!(access.key, "access key");
+                assert_eq!(access.secret, "access secret");
+            }
+            _ => panic!("rotating an access token returned a different kind of token"),
+        }
+
+        let req = super::raw::get(crate::links::statuses::HOME_TIMELINE, &token, None);
+        let auth = req.headers()[hyper::header::AUTHORIZATION]
+            .to_str()
+            .unwrap();
+        assert!(auth.contains("oauth_consumer_key=\"new%20consumer\""));
+        assert!(auth.contains("oauth_token=\"access%20key\""));
+        assert!(!auth.contains("old"));
+
+        let bearer = Token::Bearer("bearer".to_string()).with_consumer(KeyPair::new("a", "b"));
+        assert!(matches!(bearer, Token::Bearer(ref b) if b == "bearer"));
+    }
+
+    #[test]
+    fn debug_hides_secrets() {
+        let token = Token::Access {
+            consumer: KeyPair::new("consumer key", "consumer secret".to_string()),
+            access: KeyPair::new("access key", "access secret"),
+        };
+        let debug = format!("{:?}", token);
+        assert!(debug.contains("consumer key"));
+        assert!(debug.contains("access key"));
+        assert!(!debug.contains("secret\""));
+        assert!(debug.contains("<redacted>"));
+
+        let debug = format!("{:?}", Token::Bearer("bearer token".to_string()));
+        assert_eq!(debug, "Bearer(<redacted>)");
+
+        let user = crate::auth::oauth2::UserToken {
+            access_token: "access token".to_string(),
+            refresh_token: Some("refresh token".to_string()),
+            scopes: vec![],
+            expires_at: None,
+        };
+        let debug = format!("{:?}", Token::OAuth2(user));
+        assert!(!debug.contains("token\""));
+        assert!(debug.contains("refresh_token: Some(<redacted>)"));
+    }
+
+    #[test]
+    fn xauth_unauthorized() {
+        let err = xauth_error(Error::BadStatus(hyper::StatusCode::UNAUTHORIZED));
+        assert!(matches!(err, Error::XAuthUnauthorized));
+        assert!(err.to_string().contains("not be permitted to use xAuth"));
+
+        let errors = TwitterErrors {
+            errors: vec![TwitterErrorCode {
+                message: "Client is not permitted to perform this action.".to_string(),
+                code: 87,
+            }],
+        };
+        let err = xauth_error(Error::TwitterError(Default::default(), errors));
+        assert!(matches!(err, Error::XAuthUnauthorized));
+
+        let err = xauth_error(Error::BadStatus(hyper::StatusCode::FORBIDDEN));
+        assert!(matches!(
+            err,
+            Error::BadStatus(hyper::StatusCode::FORBIDDEN)
+        ));
+        let err = xauth_error(Error::RateLimit(12345));
+        assert!(matches!(err, Error::RateLimit(12345)));
+    }
+
+    #[test]
+    fn whoami_user() {
+        use crate::common::tests::load_file;
+        use crate::common::{RateLimit, Response};
+        use crate::user::{TwitterUser, UserID};
+
+        let sample = load_file("sample_payloads/user-verified-type.json");
+        let user = serde_json::from_str::<TwitterUser>(&sample).unwrap();
+        let rate_limit = RateLimit {
+            limit: 75,
+            remaining: 74,
+            reset: 0,
+        };
+        let token = Token::Bearer("bearer".to_string());
+        let authed = authed_token(&token, Response::new(rate_limit, user));
+
+        assert_eq!(authed.rate_limit_status.remaining, 74);
+        assert_eq!(authed.user_id(), 2244994945);
+        assert_eq!(authed.screen_name(), "TwitterDev");
+        assert!(matches!(authed.user(), UserID::ID(2244994945)));
+        assert_eq!(authed.token().fingerprint(), token.fingerprint());
+
+        let saved = serde_json::to_string(&authed.response).unwrap();
+        let loaded = serde_json::from_str::<AuthedToken>(&saved).unwrap();
+        assert_eq!(loaded.user_id(), 2244994945);
+        assert!(matches!(loaded.into_token(), Token::Bearer(ref b) if b == "bearer"));
+    }
+
+    #[tokio::test]
+    async fn app_auth_mints_once() {
+        use std::cell::Cell;
+
+        let auth = AppAuth::new(KeyPair::new("consumer", "secret"));
+        let mints = Cell::new(0);
+        let mint = || {
+            mints.set(mints.get() + 1);
+            futures::future::ready(Ok(Token::Bearer(format!("bearer {}", mints.get()))))
+        };
+        let bearer = |token: Token| match token {
+            Token::Bearer(ref token) => futures::future::ready(Ok(token.clone())),
+            _ => panic!("AppAuth handed out a non-bearer token"),
+        };
+
+        // the first request loads the token, and later ones reuse it
+        assert_eq!(auth.call_with(mint, bearer).await.unwrap(), "bearer 1");
+        assert_eq!(auth.call_with(mint, bearer).await.unwrap(), "bearer 1");
+        assert_eq!(mints.get(), 1);
+
+        // an invalid token is replaced, and the request is sent again with the new one
+        let tries = Cell::new(0);
+        let flaky = |token: Token| {
+            tries.set(tries.get() + 1);
+            futures::future::ready(match token {
+                Token::Bearer(ref t) if t == "bearer 1" => {
+                    Err(Error::BadStatus(hyper::StatusCode::UNAUTHORIZED))
+                }
+                token => Ok(token),
+            })
+        };
+        let token = auth.call_with(mint, flaky).await.unwrap();
+        assert!(matches!(token, Token::Bearer(ref t) if t == "bearer 2"));
+        assert_eq!((mints.get(), tries.get()), (2, 2));
+
+        // but only once; if the new token fails too, the error is returned
+        tries.set(0);
+        let invalid = |_: Token| {
+            tries.set(tries.get() + 1);
+            let errors = TwitterErrors {
+                errors: vec![TwitterErrorCode {
+                    message: "Invalid or expired token".to_string(),
+                    code: 89,
+                }],
+            };
+            let err = Error::TwitterError(Default::default(), errors);
+            futures::future::ready(Err::<(), _>(err))
+        };
+        let err = auth.call_with(mint, invalid).await.unwrap_err();
+        assert!(matches!(err, Error::TwitterError(..)));
+        assert_eq!((mints.get(), tries.get()), (3, 2));
+
+        // other errors don't cause the token to be replaced
+        let limited = |_: Token| futures::future::ready(Err::<(), _>(Error::RateLimit(0)));
+        let err = auth.call_with(mint, limited).await.unwrap_err();
+        assert!(matches!(err, Error::RateLimit(0)));
+        assert_eq!(mints.get(), 3);
+    }
+}
@@ -0,0 +1,221 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A temporary local web server to receive the sign-in callback, for desktop and CLI apps.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use futures::channel::oneshot;
+use hyper::server::conn::AddrIncoming;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+
+use crate::error::{self, Result};
+
+use super::{access_token, authorize_url, request_token, KeyPair, Token};
+
+/// A web server on `127.0.0.1` that waits for Twitter to send the user back after they sign in.
+///
+/// Apps that run on the user's own computer can use this to finish the web-based sign-in flow
+/// without asking the user to copy a PIN back into the app. Bind a `LocalRedirect`, use its
+/// `callback` as the callback URL given to [`request_token`], send the user to the resulting
+/// [`authorize_url`], and then wait for the verifier with `verifier`. The whole flow is also
+/// available as [`sign_in_locally`].
+///
+/// [`request_token`]: fn.request_token.html
+/// [`authorize_url`]: fn.authorize_url.html
+/// [`sign_in_locally`]: fn.sign_in_locally.html
+///
+/// Twitter only redirects to callback URLs that are registered for your app, so add
+/// `http://127.0.0.1:{port}/callback` to your app's settings in the developer portal, and bind
+/// the same port here.
+///
+/// This is only available with the `local_redirect` feature.
+#[derive(Debug)]
+pub struct LocalRedirect {
+    incoming: AddrIncoming,
+    callback: String,
+}
+
+impl LocalRedirect {
+    /// Starts listening on the given port on `127.0.0.1`. If `port` is zero, any free port is
+    /// used, which is only useful if your app's settings allow it.
+    pub fn bind(port: u16) -> Result<LocalRedirect> {
+        let incoming = AddrIncoming::bind(&SocketAddr::from(([127, 0, 0, 1], port)))?;
+        let port = incoming.local_addr().port();
+        Ok(LocalRedirect {
+            incoming,
+            callback: format!("http://127.0.0.1:{}/callback", port),
+        })
+    }
+
+    /// Returns the callback URL to hand to `request_token`.
+    pub fn callback(&self) -> &str {
+        &self.callback
+    }
+
+    /// Waits for Twitter to send the user back to `callback` after signing in with the given
+    /// request token, and returns the verifier to hand to `access_token`.
+    ///
+    /// The user is shown a short page saying they can go back to the app. Requests for other
+    /// pages, or for a different request token, are turned away, and this keeps waiting. If the
+    /// user declines to sign in, this returns `Error::AuthorizationDenied`. This waits as long as
+    /// it takes, so wrap it in a timeout if the user may never come back.
+    pub async fn verifier(self, request_token: &KeyPair) -> Result<String> {
+        let expected = request_token.key.to_string();
+        let outcome = Arc::new(Mutex::new(None));
+        let (done_tx, done_rx) = oneshot::channel::<()>();
+        let done_tx = Arc::new(Mutex::new(Some(done_tx)));
+
+        let slot = outcome.clone();
+        let make_service = make_service_fn(move |_| {
+            let expected = expected.clone();
+            let slot = slot.clone();
+            let done_tx = done_tx.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let found = callback_outcome(req.uri().path(), req.uri().query(), &expected);
+                    let response = callback_response(found.as_ref());
+                    if let Some(found) = found {
+                        let mut slot = slot.lock().unwrap();
+                        if slot.is_none() {
+                            *slot = Some(found);
+                            if let Some(done_tx) = done_tx.lock().unwrap().take() {
+                                let _ = done_tx.send(());
+                            }
+                        }
+                    }
+                    futures::future::ready(Ok::<_, Infallible>(response))
+                }))
+            }
+        });
+
+        // let the page finish loading before shutting down
+        Server::builder(self.incoming)
+            .serve(make_service)
+            .with_graceful_shutdown(async {
+                let _ = done_rx.await;
+            })
+            .await?;
+
+        let outcome = outcome.lock().unwrap().take();
+        outcome.unwrap_or(Err(error::Error::AuthorizationDenied))
+    }
+}
+
+/// Runs the whole web-based sign-in flow for a desktop or CLI app, with a [`LocalRedirect`] on the
+/// given port receiving the callback.
+///
+/// [`LocalRedirect`]: struct.LocalRedirect.html
+///
+/// This requests a request token with the local callback URL, hands the authorize URL to
+/// `show_url` (which should open it in the user's browser, or print it for them to open), waits
+/// for the user to come back, and exchanges the verifier for an access token. Like
+/// `access_token`, it yields the access token, the ID of the authenticated user, and their screen
+/// name. See `LocalRedirect` for how to register the callback URL with Twitter.
+///
+/// This is only available with the `local_redirect` feature.
+pub async fn sign_in_locally<F>(
+    con_token: KeyPair,
+    port: u16,
+    show_url: F,
+) -> Result<(Token, u64, String)>
+where
+    F: FnOnce(&str),
+{
+    let redirect = LocalRedirect::bind(port)?;
+    let request_token = request_token(&con_token, redirect.callback()).await?;
+    show_url(&authorize_url(&request_token));
+    let verifier = redirect.verifier(&request_token).await?;
+    access_token(con_token, &request_token, verifier).await
+}
+
+/// Checks whether a request to the local server is the callback from Twitter for the given
+/// request token, returning the verifier if so.
+///
+/// When the user declines to sign in, Twitter sends them back with a `denied` parameter instead.
+fn callback_outcome(path: &str, query: Option<&str>, expected: &str) -> Option<Result<String>> {
+    if path != "/callback" {
+        return None;
+    }
+
+    let mut token = None;
+    let mut verifier = None;
+    for (key, value) in url::form_urlencoded::parse(query.unwrap_or("").as_bytes()) {
+        match key.as_ref() {
+            "denied" if value == expected => return Some(Err(error::Error::AuthorizationDenied)),
+            "oauth_token" => token = Some(value),
+            "oauth_verifier" => verifier = Some(value),
+            _ => (),
+        }
+    }
+
+    match (token, verifier) {
+        (Some(token), Some(verifier)) if token == expected => Some(Ok(verifier.into_owned())),
+        _ => None,
+    }
+}
+
+/// Builds the page shown to the user for a request to the local server.
+fn callback_response(outcome: Option<&Result<String>>) -> Response<Body> {
+    let (status, message) = match outcome {
+        Some(Ok(_)) => (
+            StatusCode::OK,
+            "Signed in! You can close this window and go back to the app.",
+        ),
+        Some(Err(_)) => (
+            StatusCode::OK,
+            "Sign-in was cancelled. You can close this window.",
+        ),
+        None => (StatusCode::NOT_FOUND, "Not found."),
+    };
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .header(hyper::header::CONNECTION, "close")
+        .body(Body::from(message))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{callback_outcome, LocalRedirect};
+    use crate::auth::KeyPair;
+    use crate::error::Error;
+
+    #[test]
+    fn callback_params() {
+        let query = Some("oauth_token=request&oauth_verifier=verifier");
+        let found = callback_outcome("/callback", query, "request");
+        assert_eq!(found.unwrap().unwrap(), "verifier");
+
+        assert!(callback_outcome("/favicon.ico", query, "request").is_none());
+        assert!(callback_outcome("/callback", query, "another request").is_none());
+        assert!(callback_outcome("/callback", None, "request").is_none());
+
+        let denied = callback_outcome("/callback", Some("denied=request"), "request");
+        assert!(matches!(denied, Some(Err(Error::AuthorizationDenied))));
+    }
+
+    #[tokio::test]
+    async fn receives_verifier() {
+        let redirect = LocalRedirect::bind(0).unwrap();
+        let callback = redirect.callback().to_string();
+        let request_token = KeyPair::new("request", "secret");
+        let waiting = tokio::spawn(async move { redirect.verifier(&request_token).await });
+
+        let client = hyper::Client::new();
+        let favicon = callback.replace("/callback", "/favicon.ico");
+        let resp = client.get(favicon.parse().unwrap()).await.unwrap();
+        assert_eq!(resp.status(), hyper::StatusCode::NOT_FOUND);
+
+        let uri = format!("{}?oauth_token=request&oauth_verifier=verifier", callback);
+        let resp = client.get(uri.parse().unwrap()).await.unwrap();
+        assert_eq!(resp.status(), hyper::StatusCode::OK);
+
+        assert_eq!(waiting.await.unwrap().unwrap(), "verifier");
+    }
+}
@@ -0,0 +1,898 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! OAuth 2.0 user authentication, using the Authorization Code flow with PKCE.
+//!
+//! Some of Twitter's v2 endpoints need a user context that comes from OAuth 2.0 instead of the
+//! OAuth 1.0a Access tokens described in the [module above][auth]. These tokens are granted for a
+//! set of [`Scope`]s the user agrees to, and expire after a couple of hours unless they're
+//! refreshed.
+//!
+//! [auth]: ../index.html
+//! [`Scope`]: enum.Scope.html
+//!
+//! Getting one of these tokens works much like the web-based sign-in flow for Access tokens:
+//!
+//! 1. Register your app's OAuth 2.0 client ID (and client secret, if it's a confidential client)
+//!    and redirect URI in the Twitter developer portal, and load them into a [`Client`].
+//! 2. Make a new [`PkceCode`] and a random `state` string, and save them for the user's session.
+//! 3. Send the user to the URL given by [`Client::authorize_url`].
+//! 4. When Twitter sends the user back to your redirect URI, check that the `state` parameter
+//!    matches, then hand the `code` parameter and the same `PkceCode` to
+//!    [`Client::exchange_code`].
+//!
+//! [`Client`]: struct.Client.html
+//! [`PkceCode`]: struct.PkceCode.html
+//! [`Client::authorize_url`]: struct.Client.html#method.authorize_url
+//! [`Client::exchange_code`]: struct.Client.html#method.exchange_code
+//!
+//! The resulting `Token` can be given to any egg-mode function, just like the other kinds of
+//! `Token`. If you asked for the `offline.access` scope, it also comes with a refresh token, which
+//! [`Client::refresh`] can use to get a new token once the old one expires.
+//!
+//! [`Client::refresh`]: struct.Client.html#method.refresh
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//! use egg_mode::auth::oauth2::{Client, PkceCode, Scope};
+//!
+//! let client = Client::new("client id", "https://example.com/callback");
+//! let pkce = PkceCode::new();
+//! let scopes = [Scope::TweetRead, Scope::UsersRead, Scope::OfflineAccess];
+//! let url = client.authorize_url(&scopes, "some random state", &pkce);
+//!
+//! // send the user to `url`, and wait for them to come back to the redirect URI...
+//! # let code = "";
+//!
+//! let token = client.exchange_code(code, &pkce).await.unwrap();
+//! // token can be given to any egg_mode method that asks for a token
+//! # }
+//! ```
+
+use std::fmt;
+use std::future::Future;
+
+use hyper::Method;
+use rand::{self, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::common::*;
+use crate::error::{self, Result};
+use crate::links;
+
+use super::raw::RequestBuilder;
+use super::{is_invalid_token, KeyPair, Redacted, Token, TokenStore};
+
+/// A permission that a user can grant to an app using OAuth 2.0.
+///
+/// Each OAuth 2.0 token is only allowed to call the endpoints covered by the scopes it was
+/// granted. For the full list, and which endpoints need which scopes, see [Twitter's
+/// documentation][scopes].
+///
+/// [scopes]: https://developer.twitter.com/en/docs/authentication/oauth-2-0/authorization-code
+///
+/// Any scope that egg-mode doesn't know about is kept as `Other`, so that tokens can still be
+/// loaded when Twitter adds new ones.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Scope {
+    /// `tweet.read`: See tweets, including those from protected accounts.
+    TweetRead,
+    /// `tweet.write`: Post and delete tweets.
+    TweetWrite,
+    /// `tweet.moderate.write`: Hide and unhide replies to the user's tweets.
+    TweetModerateWrite,
+    /// `users.read`: See any account, including protected ones.
+    UsersRead,
+    /// `follows.read`: See who the user follows, and who follows them.
+    FollowsRead,
+    /// `follows.write`: Follow and unfollow accounts.
+    FollowsWrite,
+    /// `offline.access`: Keep access after the token expires, by refreshing it.
+    OfflineAccess,
+    /// `space.read`: See Spaces.
+    SpaceRead,
+    /// `mute.read`: See the accounts the user has muted.
+    MuteRead,
+    /// `mute.write`: Mute and unmute accounts.
+    MuteWrite,
+    /// `like.read`: See the tweets the user has liked, and who has liked tweets.
+    LikeRead,
+    /// `like.write`: Like and unlike tweets.
+    LikeWrite,
+    /// `list.read`: See lists, their members, and their followers.
+    ListRead,
+    /// `list.write`: Create and manage lists.
+    ListWrite,
+    /// `block.read`: See the accounts the user has blocked.
+    BlockRead,
+    /// `block.write`: Block and unblock accounts.
+    BlockWrite,
+    /// `bookmark.read`: See the user's bookmarked tweets.
+    BookmarkRead,
+    /// `bookmark.write`: Bookmark and remove bookmarks from tweets.
+    BookmarkWrite,
+    /// Any other scope, given by its name.
+    Other(String),
+}
+
+impl Scope {
+    /// Returns the name Twitter uses for this scope.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Scope::TweetRead => "tweet.read",
+            Scope::TweetWrite => "tweet.write",
+            Scope::TweetModerateWrite => "tweet.moderate.write",
+            Scope::UsersRead => "users.read",
+            Scope::FollowsRead => "follows.read",
+            Scope::FollowsWrite => "follows.write",
+            Scope::OfflineAccess => "offline.access",
+            Scope::SpaceRead => "space.read",
+            Scope::MuteRead => "mute.read",
+            Scope::MuteWrite => "mute.write",
+            Scope::LikeRead => "like.read",
+            Scope::LikeWrite => "like.write",
+            Scope::ListRead => "list.read",
+            Scope::ListWrite => "list.write",
+            Scope::BlockRead => "block.read",
+            Scope::BlockWrite => "block.write",
+            Scope::BookmarkRead => "bookmark.read",
+            Scope::BookmarkWrite => "bookmark.write",
+            Scope::Other(scope) => scope,
+        }
+    }
+}
+
+impl From<String> for Scope {
+    fn from(scope: String) -> Scope {
+        match scope.as_str() {
+            "tweet.read" => Scope::TweetRead,
+            "tweet.write" => Scope::TweetWrite,
+            "tweet.moderate.write" => Scope::TweetModerateWrite,
+            "users.read" => Scope::UsersRead,
+            "follows.read" => Scope::FollowsRead,
+            "follows.write" => Scope::FollowsWrite,
+            "offline.access" => Scope::OfflineAccess,
+            "space.read" => Scope::SpaceRead,
+            "mute.read" => Scope::MuteRead,
+            "mute.write" => Scope::MuteWrite,
+            "like.read" => Scope::LikeRead,
+            "like.write" => Scope::LikeWrite,
+            "list.read" => Scope::ListRead,
+            "list.write" => Scope::ListWrite,
+            "block.read" => Scope::BlockRead,
+            "block.write" => Scope::BlockWrite,
+            "bookmark.read" => Scope::BookmarkRead,
+            "bookmark.write" => Scope::BookmarkWrite,
+            _ => Scope::Other(scope),
+        }
+    }
+}
+
+impl From<Scope> for String {
+    fn from(scope: Scope) -> String {
+        match scope {
+            Scope::Other(scope) => scope,
+            scope => scope.as_str().to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An OAuth 2.0 access token for a specific user, along with the scopes it was granted.
+///
+/// This is the token held by `Token::OAuth2`, which is how it's handed to the rest of egg-mode.
+/// Like the other kinds of `Token`, the keys in here work just like a password, so handle them
+/// with care if you save them.
+///
+/// The tokens are left out of the `Debug` output, and with the `zeroize` feature, they're wiped
+/// from memory when the `UserToken` is dropped.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UserToken {
+    /// The token that's sent with each request, as a Bearer token.
+    pub access_token: String,
+    /// The token that can be given to `Client::refresh` to get a new access token, if the user
+    /// granted the `offline.access` scope.
+    pub refresh_token: Option<String>,
+    /// The scopes the user granted to this token.
+    pub scopes: Vec<Scope>,
+    /// When the access token expires, if Twitter said.
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl UserToken {
+    /// Returns whether the user granted the given scope to this token.
+    pub fn has_scope(&self, scope: &Scope) -> bool {
+        self.scopes.contains(scope)
+    }
+
+    /// Returns whether this token has expired, and needs to be refreshed before it can be used.
+    ///
+    /// If Twitter didn't say when the token expires, this returns `false`.
+    pub fn is_expired(&self) -> bool {
+        self.expires_within(chrono::Duration::zero())
+    }
+
+    /// Returns whether this token will have expired by the time `margin` has passed.
+    fn expires_within(&self, margin: chrono::Duration) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= chrono::Utc::now() + margin)
+    }
+}
+
+impl fmt::Debug for UserToken {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let refresh_token = self.refresh_token.as_ref().map(|_| Redacted);
+        f.debug_struct("UserToken")
+            .field("access_token", &Redacted)
+            .field("refresh_token", &refresh_token)
+            .field("scopes", &self.scopes)
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+// like `KeyPair`, this is implemented with or without `zeroize`
+impl Drop for UserToken {
+    fn drop(&mut self) {
+        #[cfg(feature = "zeroize")]
+        {
+            zeroize::Zeroize::zeroize(&mut self.access_token);
+            if let Some(ref mut refresh_token) = self.refresh_token {
+                zeroize::Zeroize::zeroize(refresh_token);
+            }
+        }
+    }
+}
+
+/// The response from `POST oauth2/token`.
+#[derive(Deserialize)]
+struct RawUserToken {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<i64>,
+    scope: Option<String>,
+}
+
+impl From<RawUserToken> for UserToken {
+    fn from(raw: RawUserToken) -> UserToken {
+        UserToken {
+            access_token: raw.access_token,
+            refresh_token: raw.refresh_token,
+            scopes: raw
+                .scope
+                .iter()
+                .flat_map(|scope| scope.split_whitespace())
+                .map(|scope| Scope::from(scope.to_string()))
+                .collect(),
+            expires_at: raw
+                .expires_in
+                .map(|secs| chrono::Utc::now() + chrono::Duration::seconds(secs)),
+        }
+    }
+}
+
+/// A PKCE code verifier, which ties the request for an authorization code to the request that
+/// exchanges it for a token.
+///
+/// PKCE ("Proof Key for Code Exchange") keeps a stolen authorization code from being used by
+/// anyone but the app that asked for it. The challenge derived from the verifier goes into the
+/// authorize URL, and the verifier itself is sent when exchanging the code, so make a new
+/// `PkceCode` for each sign-in and keep it until the user comes back. egg-mode always uses the
+/// `S256` challenge method.
+///
+/// The verifier is left out of the `Debug` output, and with the `zeroize` feature, it's wiped from
+/// memory when the `PkceCode` is dropped.
+#[derive(Clone)]
+pub struct PkceCode {
+    verifier: String,
+}
+
+impl PkceCode {
+    /// Makes a new, random `PkceCode`.
+    pub fn new() -> PkceCode {
+        let mut rng = rand::thread_rng();
+        let verifier = ::std::iter::repeat(())
+            .map(|()| rng.sample(rand::distributions::Alphanumeric))
+            .take(64)
+            .collect::<String>();
+        PkceCode { verifier }
+    }
+
+    /// Loads a `PkceCode` from a verifier made earlier, for example one saved in the user's
+    /// session while they signed in.
+    pub fn from_verifier(verifier: impl Into<String>) -> PkceCode {
+        PkceCode {
+            verifier: verifier.into(),
+        }
+    }
+
+    /// Returns the code verifier, which is sent when exchanging the authorization code.
+    pub fn verifier(&self) -> &str {
+        &self.verifier
+    }
+
+    /// Returns the code challenge, which is sent in the authorize URL.
+    ///
+    /// This is the SHA-256 hash of the verifier, encoded as unpadded URL-safe base64.
+    pub fn challenge(&self) -> String {
+        let hash = Sha256::digest(self.verifier.as_bytes());
+        base64::encode_config(hash, base64::URL_SAFE_NO_PAD)
+    }
+}
+
+impl Default for PkceCode {
+    fn default() -> PkceCode {
+        PkceCode::new()
+    }
+}
+
+impl fmt::Debug for PkceCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PkceCode")
+            .field("verifier", &Redacted)
+            .finish()
+    }
+}
+
+// like `KeyPair`, this is implemented with or without `zeroize`
+impl Drop for PkceCode {
+    fn drop(&mut self) {
+        #[cfg(feature = "zeroize")]
+        zeroize::Zeroize::zeroize(&mut self.verifier);
+    }
+}
+
+/// The OAuth 2.0 client credentials for an app, used to sign users in and get tokens for them.
+///
+/// Apps registered as a "public client" (like a mobile or desktop app) only have a client ID.
+/// Apps registered as a "confidential client" (like a web app with a server) also have a client
+/// secret, which can be given with `with_secret`.
+///
+/// The client secret is left out of the `Debug` output, and with the `zeroize` feature, it's wiped
+/// from memory when the `Client` is dropped.
+#[derive(Clone)]
+pub struct Client {
+    client_id: String,
+    client_secret: Option<String>,
+    redirect_uri: String,
+}
+
+impl Client {
+    /// Creates a new `Client` for a public client, with the given client ID and redirect URI.
+    ///
+    /// The redirect URI must match one registered for your app in the Twitter developer portal.
+    pub fn new(client_id: impl Into<String>, redirect_uri: impl Into<String>) -> Client {
+        Client {
+            client_id: client_id.into(),
+            client_secret: None,
+            redirect_uri: redirect_uri.into(),
+        }
+    }
+
+    /// Sets the client secret, for apps registered as a confidential client.
+    ///
+    /// Confidential clients authenticate to the token endpoint with their client ID and secret,
+    /// instead of just sending their client ID.
+    pub fn with_secret(mut self, client_secret: impl Into<String>) -> Client {
+        self.client_secret = Some(client_secret.into());
+        self
+    }
+
+    /// Returns the URL to send a user to, so they can allow your app to act on their behalf with
+    /// the given scopes.
+    ///
+    /// Twitter hands `state` back with the authorization code, unchanged, when sending the user to
+    /// your redirect URI. It should be a random string that's saved with the user's session and
+    /// checked when they come back, to make sure the code came from a sign-in your app started.
+    /// `pkce` needs to be given to `exchange_code` along with the code.
+    pub fn authorize_url(&self, scopes: &[Scope], state: &str, pkce: &PkceCode) -> String {
+        let scope = scopes
+            .iter()
+            .map(Scope::as_str)
+            .collect::<Vec<_>>()
+            .join(" ");
+        let params = ParamList::new()
+            .add_param("response_type", "code")
+            .add_param("client_id", self.client_id.clone())
+            .add_param("redirect_uri", self.redirect_uri.clone())
+            .add_param("scope", scope)
+            .add_param("state", state.to_string())
+            .add_param("code_challenge", pkce.challenge())
+            .add_param("code_challenge_method", "S256");
+
+        let query = params.to_urlencoded();
+        format!("{}?{}", links::auth::OAUTH2_AUTHORIZE, query)
+    }
+
+    /// Exchanges the authorization code Twitter gave to your redirect URI for a token.
+    ///
+    /// `pkce` needs to be the same `PkceCode` that was given to `authorize_url` when the user was
+    /// sent to sign in. On success, this returns a `Token::OAuth2` that can be given to the rest
+    /// of egg-mode.
+    pub async fn exchange_code(&self, code: &str, pkce: &PkceCode) -> Result<Token> {
+        let params = ParamList::new()
+            .add_param("grant_type", "authorization_code")
+            .add_param("code", code.to_string())
+            .add_param("redirect_uri", self.redirect_uri.clone())
+            .add_param("code_verifier", pkce.verifier().to_string());
+
+        Ok(Token::OAuth2(self.request_token(params).await?))
+    }
+
+    /// Exchanges the refresh token in the given token for a new token.
+    ///
+    /// The new token has the same scopes, and comes with a new refresh token; the old refresh
+    /// token can't be used again. If the given token doesn't have a refresh token, because the
+    /// user wasn't asked for the `offline.access` scope, this returns
+    /// `Error::MissingValue("refresh_token")`.
+    ///
+    /// Since each refresh token only works once, save the new token somewhere the next refresh
+    /// can find it. To have egg-mode refresh a token as needed, see [`UserAuth`].
+    ///
+    /// [`UserAuth`]: struct.UserAuth.html
+    pub async fn refresh(&self, token: &UserToken) -> Result<Token> {
+        Ok(Token::OAuth2(self.refresh_user(token).await?))
+    }
+
+    /// Exchanges the refresh token in the given token for a new token.
+    async fn refresh_user(&self, token: &UserToken) -> Result<UserToken> {
+        let refresh_token = token
+            .refresh_token
+            .clone()
+            .ok_or(error::Error::MissingValue("refresh_token"))?;
+        let params = ParamList::new()
+            .add_param("grant_type", "refresh_token")
+            .add_param("refresh_token", refresh_token);
+
+        self.request_token(params).await
+    }
+
+    /// Sends the given parameters to `POST oauth2/token`, and loads the token it returns.
+    async fn request_token(&self, params: ParamList) -> Result<UserToken> {
+        let request = self.token_request(params);
+        let token = request_with_json_response::<RawUserToken>(request).await?;
+        Ok(token.response.into())
+    }
+
+    /// Assembles a request to `POST oauth2/token` with the given parameters, identifying the app
+    /// the way its kind of client is supposed to.
+    fn token_request(&self, params: ParamList) -> hyper::Request<hyper::Body> {
+        let request = RequestBuilder::new(Method::POST, links::auth::OAUTH2_TOKEN);
+        match self.client_secret {
+            Some(ref secret) => request
+                .with_body_params(&params)
+                .request_consumer_bearer(&KeyPair::new(self.client_id.clone(), secret.clone())),
+            None => {
+                let params = params.add_param("client_id", self.client_id.clone());
+                request.with_body_params(&params).request_unauthenticated()
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let client_secret = self.client_secret.as_ref().map(|_| Redacted);
+        f.debug_struct("Client")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &client_secret)
+            .field("redirect_uri", &self.redirect_uri)
+            .finish()
+    }
+}
+
+// like `KeyPair`, this is implemented with or without `zeroize`
+impl Drop for Client {
+    fn drop(&mut self) {
+        #[cfg(feature = "zeroize")]
+        {
+            if let Some(ref mut client_secret) = self.client_secret {
+                zeroize::Zeroize::zeroize(client_secret);
+            }
+        }
+    }
+}
+
+/// How long before a token expires that `UserAuth` refreshes it, so that it doesn't expire while a
+/// request is being sent.
+const REFRESH_MARGIN_SECS: i64 = 60;
+
+/// An OAuth 2.0 user token that refreshes itself before it expires.
+///
+/// OAuth 2.0 user tokens only last a couple of hours, so a long-running app needs to refresh them
+/// with [`Client::refresh`] to keep working. `UserAuth` holds onto the current token and the
+/// `Client` that can refresh it, and refreshes the token whenever it's about to expire, before
+/// handing it to a request. If Twitter rejects the token as invalid or expired anyway, `call`
+/// refreshes it and retries the request once. The refreshed token is kept and used for every
+/// request after that.
+///
+/// [`Client::refresh`]: struct.Client.html#method.refresh
+///
+/// The token needs a refresh token for any of this to work, which means the user needs to have
+/// granted the `offline.access` scope. Since each refresh token only works once, use `current`
+/// to save the latest token when your app shuts down, so that it can pick up where it left off.
+///
+/// A `UserAuth` can be shared between tasks (for example, in an `Arc`), and concurrent requests
+/// will share the same token, even while it's being refreshed. To save each refreshed token as
+/// soon as it's loaded, give it a [`TokenStore`] with `with_store`.
+///
+/// [`TokenStore`]: ../trait.TokenStore.html
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn main() {
+/// use egg_mode::auth::oauth2::{Client, UserAuth};
+/// # let saved_token: egg_mode::auth::oauth2::UserToken = unimplemented!();
+///
+/// let client = Client::new("client id", "https://example.com/callback");
+/// let auth = UserAuth::new(client, saved_token);
+///
+/// let user = auth
+///     .call(|token| async move { egg_mode::auth::verify_tokens(&token).await })
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub struct UserAuth {
+    client: Client,
+    token: futures::lock::Mutex<UserToken>,
+    store: Option<(u64, Box<dyn TokenStore + Send + Sync>)>,
+}
+
+impl fmt::Debug for UserAuth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UserAuth")
+            .field("client", &self.client)
+            .field("token", &self.token)
+            .field("store", &self.store.as_ref().map(|(user_id, _)| user_id))
+            .finish()
+    }
+}
+
+impl UserAuth {
+    /// Creates a new `UserAuth` with the given client and user token. This doesn't contact
+    /// Twitter; the token is refreshed when it's next needed.
+    pub fn new(client: Client, token: UserToken) -> UserAuth {
+        UserAuth {
+            client,
+            token: futures::lock::Mutex::new(token),
+            store: None,
+        }
+    }
+
+    /// Saves the token to the given store, as the token for the given user, every time it's
+    /// refreshed.
+    ///
+    /// If saving a refreshed token fails, the request that needed it returns the error, but the
+    /// new token is still kept and used for later requests.
+    ///
+    /// The token is saved while the lock on it is still held, so that the saves happen in the
+    /// same order as the refreshes and the store always ends up with the newest refresh token.
+    /// That means other requests wait for the save to finish before they can use the new token,
+    /// so the store's `save` should be quick; see the notes on [`TokenStore`].
+    ///
+    /// [`TokenStore`]: ../trait.TokenStore.html
+    pub fn with_store<S>(self, user_id: u64, store: S) -> UserAuth
+    where
+        S: TokenStore + Send + Sync + 'static,
+    {
+        UserAuth {
+            store: Some((user_id, Box::new(store))),
+            ..self
+        }
+    }
+
+    /// Returns the current user token, without refreshing it.
+    pub async fn current(&self) -> UserToken {
+        self.token.lock().await.clone()
+    }
+
+    /// Returns the user token, refreshing it first if it's about to expire.
+    pub async fn token(&self) -> Result<Token> {
+        let token = self.token_with(|old| self.refresh(old)).await?;
+        Ok(Token::OAuth2(token))
+    }
+
+    /// Runs the given request with the user token, refreshing the token first if it's about to
+    /// expire.
+    ///
+    /// If the request fails because Twitter says the token is invalid or expired, the token is
+    /// refreshed and the request is retried once. Any other error, or a second invalid-token
+    /// error, is returned as-is.
+    pub async fn call<F, Fut, T>(&self, request: F) -> Result<T>
+    where
+        F: FnMut(Token) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.call_with(|old| self.refresh(old), request).await
+    }
+
+    async fn refresh(&self, old: UserToken) -> Result<UserToken> {
+        self.client.refresh_user(&old).await
+    }
+
+    async fn token_with<R, RFut>(&self, refresh: R) -> Result<UserToken>
+    where
+        R: FnOnce(UserToken) -> RFut,
+        RFut: Future<Output = Result<UserToken>>,
+    {
+        // hold the lock while refreshing, since the refresh token can only be used once
+        let mut token = self.token.lock().await;
+        if token.expires_within(chrono::Duration::seconds(REFRESH_MARGIN_SECS)) {
+            *token = refresh(token.clone()).await?;
+            self.save(&token)?;
+        }
+        Ok(token.clone())
+    }
+
+    /// Saves the given token to the store, if there is one.
+    fn save(&self, token: &UserToken) -> Result<()> {
+        match self.store {
+            Some((user_id, ref store)) => store.save(user_id, &Token::OAuth2(token.clone())),
+            None => Ok(()),
+        }
+    }
+
+    async fn call_with<R, RFut, F, Fut, T>(&self, mut refresh: R, mut request: F) -> Result<T>
+    where
+        R: FnMut(UserToken) -> RFut,
+        RFut: Future<Output = Result<UserToken>>,
+        F: FnMut(Token) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let used = self.token_with(&mut refresh).await?;
+        match request(Token::OAuth2(used.clone())).await {
+            Err(ref e) if is_invalid_token(e) => {
+                let token = {
+                    let mut token = self.token.lock().await;
+                    // only refresh the token if another request hasn't already replaced it
+                    if token.access_token == used.access_token {
+                        *token = refresh(token.clone()).await?;
+                        self.save(&token)?;
+                    }
+                    token.clone()
+                };
+                request(Token::OAuth2(token)).await
+            }
+            resp => resp,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Client, PkceCode, RawUserToken, Scope, UserAuth, UserToken};
+    use crate::auth::Token;
+    use crate::error::Error;
+
+    #[test]
+    fn pkce_challenge() {
+        let pkce = PkceCode::from_verifier("dBjftJeZ4CVP-mJ0kzDiK9hQjqklBcC8Tyg2wPQ0A_Q");
+        let challenge = "jg7VMJvgcil88b2SaWu7K_b_LwKA-y7xLWZNqb45Af0";
+        assert_eq!(pkce.challenge(), challenge);
+
+        let random = PkceCode::new();
+        assert_eq!(random.verifier().len(), 64);
+        assert_ne!(random.verifier(), PkceCode::new().verifier());
+    }
+
+    #[test]
+    fn debug_hides_secrets() {
+        let pkce = PkceCode::from_verifier("verifier");
+        assert_eq!(format!("{:?}", pkce), "PkceCode { verifier: <redacted> }");
+
+        let client =
+            Client::new("client id", "https://example.com/callback").with_secret("client secret");
+        let debug = format!("{:?}", client);
+        assert!(debug.contains("client id"));
+        assert!(!debug.contains("client secret"));
+        assert!(debug.contains("client_secret: Some(<redacted>)"));
+
+        let token = UserToken {
+            access_token: "access token".into(),
+            refresh_token: None,
+            scopes: vec![],
+            expires_at: None,
+        };
+        let debug = format!("{:?}", UserAuth::new(client, token));
+        assert!(!debug.contains("client secret"));
+        assert!(!debug.contains("access token"));
+    }
+
+    #[test]
+    fn authorize_url() {
+        let client = Client::new("client", "https://example.com/callback");
+        let pkce = PkceCode::from_verifier("dBjftJeZ4CVP-mJ0kzDiK9hQjqklBcC8Tyg2wPQ0A_Q");
+        let url = client.authorize_url(&[Scope::TweetRead, Scope::UsersRead], "xyz", &pkce);
+
+        let url = url::Url::parse(&url).unwrap();
+        assert_eq!(url.host_str(), Some("twitter.com"));
+        assert_eq!(url.path(), "/i/oauth2/authorize");
+        let pairs = url.query_pairs().into_owned().collect::<Vec<_>>();
+        let get = |key: &str| {
+            let pair = pairs.iter().find(|(k, _)| k == key);
+            pair.map(|(_, v)| v.as_str())
+        };
+        assert_eq!(get("response_type"), Some("code"));
+        assert_eq!(get("client_id"), Some("client"));
+        assert_eq!(get("redirect_uri"), Some("https://example.com/callback"));
+        assert_eq!(get("scope"), Some("tweet.read users.read"));
+        assert_eq!(get("state"), Some("xyz"));
+        assert_eq!(get("code_challenge"), Some(pkce.challenge().as_str()));
+        assert_eq!(get("code_challenge_method"), Some("S256"));
+    }
+
+    #[test]
+    fn token_requests() {
+        use hyper::header::AUTHORIZATION;
+
+        let public = Client::new("client", "https://example.com/callback");
+        let request = public.token_request(crate::common::ParamList::new());
+        assert!(request.headers().get(AUTHORIZATION).is_none());
+
+        let confidential = public.with_secret("secret");
+        let request = confidential.token_request(crate::common::ParamList::new());
+        let auth = request.headers().get(AUTHORIZATION).unwrap();
+        assert_eq!(auth, "Basic Y2xpZW50OnNlY3JldA==");
+
+        // requests made with the resulting token send its access token as a Bearer token
+        let token = Token::OAuth2(UserToken {
+            access_token: "access".into(),
+            refresh_token: None,
+            scopes: vec![Scope::TweetRead],
+            expires_at: None,
+        });
+        let request = crate::auth::raw::get(crate::links::auth::VERIFY_CREDENTIALS, &token, None);
+        let auth = request.headers().get(AUTHORIZATION).unwrap();
+        assert_eq!(auth, "Bearer access");
+    }
+
+    #[test]
+    fn parse_token_response() {
+        let response = r#"{
+            "token_type": "bearer",
+            "expires_in": 7200,
+            "access_token": "access",
+            "scope": "tweet.read users.read offline.access space.write",
+            "refresh_token": "refresh"
+        }"#;
+        let raw = serde_json::from_str::<RawUserToken>(response).unwrap();
+        let token = UserToken::from(raw);
+
+        assert_eq!(token.access_token, "access");
+        assert_eq!(token.refresh_token.as_deref(), Some("refresh"));
+        assert!(token.has_scope(&Scope::OfflineAccess));
+        assert!(!token.has_scope(&Scope::TweetWrite));
+        assert_eq!(token.scopes[3], Scope::Other("space.write".into()));
+        assert!(!token.is_expired());
+
+        // the token survives being saved and loaded again
+        let saved = serde_json::to_string(&Token::OAuth2(token)).unwrap();
+        match serde_json::from_str::<Token>(&saved).unwrap() {
+            Token::OAuth2(ref loaded) => {
+                assert_eq!(loaded.scopes[0], Scope::TweetRead);
+                assert_eq!(loaded.scopes[3], Scope::Other("space.write".into()));
+            }
+            other => panic!("unexpected token: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn user_auth_refreshes() {
+        use std::cell::Cell;
+
+        let user_token = |access: &str, expires_in: i64| UserToken {
+            access_token: access.to_string(),
+            refresh_token: Some(format!("refresh {}", access)),
+            scopes: vec![Scope::TweetRead, Scope::OfflineAccess],
+            expires_at: Some(chrono::Utc::now() + chrono::Duration::seconds(expires_in)),
+        };
+        let refreshes = Cell::new(0);
+        let refresh = |old: UserToken| {
+            refreshes.set(refreshes.get() + 1);
+            let expected = format!("refresh {}", old.access_token);
+            assert_eq!(old.refresh_token, Some(expected));
+            let access = format!("access {}", refreshes.get());
+            futures::future::ready(Ok(user_token(&access, 7200)))
+        };
+        let access = |token: Token| match token {
+            Token::OAuth2(ref user) => futures::future::ready(Ok(user.access_token.clone())),
+            _ => panic!("UserAuth handed out a non-OAuth2 token"),
+        };
+
+        // a token that's about to expire is refreshed before it's used, and then reused
+        let auth = UserAuth::new(Client::new("client", "uri"), user_token("access 0", 30));
+        assert_eq!(auth.call_with(refresh, access).await.unwrap(), "access 1");
+        assert_eq!(auth.call_with(refresh, access).await.unwrap(), "access 1");
+        assert_eq!(refreshes.get(), 1);
+        assert_eq!(auth.current().await.access_token, "access 1");
+
+        // a token that twitter rejects is refreshed, and the request is sent again
+        let tries = Cell::new(0);
+        let flaky = |token: Token| {
+            tries.set(tries.get() + 1);
+            futures::future::ready(match token {
+                Token::OAuth2(ref user) if user.access_token == "access 1" => {
+                    Err(Error::BadStatus(hyper::StatusCode::UNAUTHORIZED))
+                }
+                Token::OAuth2(ref user) => Ok(user.access_token.clone()),
+                _ => panic!("UserAuth handed out a non-OAuth2 token"),
+            })
+        };
+        assert_eq!(auth.call_with(refresh, flaky).await.unwrap(), "access 2");
+        assert_eq!((refreshes.get(), tries.get()), (2, 2));
+        assert_eq!(auth.call_with(refresh, access).await.unwrap(), "access 2");
+        assert_eq!(refreshes.get(), 2);
+
+        // other errors don't cause the token to be refreshed
+        let limited = |_: Token| futures::future::ready(Err::<(), _>(Error::RateLimit(0)));
+        let err = auth.call_with(refresh, limited).await.unwrap_err();
+        assert!(matches!(err, Error::RateLimit(0)));
+        assert_eq!(refreshes.get(), 2);
+    }
+
+    #[tokio::test]
+    async fn user_auth_saves_refreshed_tokens() {
+        use crate::auth::{FileTokenStore, TokenStore};
+
+        let dir = std::env::temp_dir().join(format!("egg-mode-refresh-{}", std::process::id()));
+        let store = FileTokenStore::new(&dir);
+        let expired = UserToken {
+            access_token: "old".into(),
+            refresh_token: Some("refresh".into()),
+            scopes: vec![Scope::OfflineAccess],
+            expires_at: Some(chrono::Utc::now()),
+        };
+        let auth = UserAuth::new(Client::new("client", "uri"), expired).with_store(20, store);
+
+        let refresh = |_: UserToken| {
+            futures::future::ready(Ok(UserToken {
+                access_token: "new".into(),
+                refresh_token: Some("new refresh".into()),
+                scopes: vec![Scope::OfflineAccess],
+                expires_at: None,
+            }))
+        };
+        let token = auth.token_with(refresh).await.unwrap();
+        assert_eq!(token.access_token, "new");
+
+        match FileTokenStore::new(&dir).load(20).unwrap() {
+            Some(Token::OAuth2(ref saved)) => {
+                assert_eq!(saved.refresh_token.as_deref(), Some("new refresh"))
+            }
+            other => panic!("unexpected token: {:?}", other),
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refresh_without_refresh_token() {
+        let user = UserToken {
+            access_token: "access".into(),
+            refresh_token: None,
+            scopes: vec![Scope::TweetRead],
+            expires_at: None,
+        };
+        let client = Client::new("client", "uri");
+        let err = Token::OAuth2(user).refresh(&client).await.unwrap_err();
+        assert!(matches!(err, Error::MissingValue("refresh_token")));
+
+        // tokens that don't expire come back as-is
+        let bearer = Token::Bearer("bearer".into());
+        let bearer = bearer.refresh(&client).await.unwrap();
+        assert!(matches!(bearer, Token::Bearer(ref b) if b == "bearer"));
+    }
+}
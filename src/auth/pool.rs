@@ -0,0 +1,330 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! A set of tokens that spreads calls across their rate limits.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+use crate::common::{RateLimit, Response};
+use crate::error::{self, Result};
+
+use super::Token;
+
+/// A set of tokens that sends each call with whichever token has the most calls left.
+///
+/// Twitter tracks rate limits separately for each token and each endpoint. A `TokenPool` keeps
+/// track of the rate-limit information from the responses to each call it makes, and uses it to
+/// choose the token with the most calls remaining on the endpoint for the next call. Tokens that
+/// haven't been used with an endpoint yet, or whose rate-limit window has reset since, count as
+/// having every call left. Calls that are still waiting on a response count against the token
+/// they were sent with, so several calls made at once are spread across the pool instead of all
+/// going to the same token.
+///
+/// The pool can hold any mix of Bearer tokens and user tokens, but every token in the pool needs
+/// to be able to make the calls that are sent through it. For calls that need a user context,
+/// use a pool of user tokens only.
+///
+/// Spreading calls across several tokens doesn't change what's allowed by Twitter's developer
+/// terms; make sure your use of the tokens stays within them.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn main() {
+/// use egg_mode::auth::TokenPool;
+/// # let first: egg_mode::Token = unimplemented!();
+/// # let second: egg_mode::Token = unimplemented!();
+///
+/// let pool = TokenPool::new(vec![first, second]);
+/// let user = pool
+///     .call("users/show", |token| async move {
+///         egg_mode::user::show("rustlang", &token).await
+///     })
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TokenPool {
+    tokens: Vec<Token>,
+    limits: Mutex<HashMap<(usize, String), Usage>>,
+}
+
+/// What the pool knows about one token's use of one endpoint.
+#[derive(Debug, Default)]
+struct Usage {
+    /// The rate-limit information from the last response, if there's been one.
+    rate: Option<RateLimit>,
+    /// The number of calls sent with this token that haven't finished yet.
+    in_flight: i32,
+}
+
+/// A call that's been given a token, but hasn't finished yet. Dropping this takes the call back
+/// out of the token's in-flight count, even if the call was cancelled partway through.
+struct Reservation<'a> {
+    pool: &'a TokenPool,
+    index: usize,
+    endpoint: &'a str,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        let mut limits = self.pool.limits.lock().unwrap();
+        if let Some(usage) = limits.get_mut(&(self.index, self.endpoint.to_string())) {
+            usage.in_flight -= 1;
+        }
+    }
+}
+
+impl TokenPool {
+    /// Creates a new `TokenPool` with the given tokens.
+    pub fn new(tokens: impl IntoIterator<Item = Token>) -> TokenPool {
+        TokenPool {
+            tokens: tokens.into_iter().collect(),
+            limits: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the number of tokens in the pool.
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Returns whether the pool has no tokens in it.
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Returns the token with the most calls left on the given endpoint, or `None` if the pool is
+    /// empty.
+    ///
+    /// `endpoint` can be any string that names the endpoint, as long as the same string is used
+    /// for every call to it; the endpoint's URL or its path in the API documentation both work.
+    /// If every token has run out of calls, this returns the one whose rate limit resets first.
+    ///
+    /// Calls made with the returned token aren't tracked by the pool. Use `call` to have the pool
+    /// count the call against the token and remember the rate-limit information it gets back.
+    pub fn pick(&self, endpoint: &str) -> Option<Token> {
+        let index = self.pick_at(endpoint, chrono::Utc::now().timestamp())?;
+        Some(self.tokens[index].clone())
+    }
+
+    /// Runs the given request with the token with the most calls left on the given endpoint, and
+    /// remembers the rate-limit information from its response.
+    ///
+    /// If the request fails because the rate limit was reached, that token is counted as having
+    /// no calls left until the limit resets, and the error is returned, so the next call can use a
+    /// different token. If the pool is empty, this returns `Error::MissingValue("token")` without
+    /// calling `request`.
+    pub async fn call<F, Fut, T>(&self, endpoint: &str, request: F) -> Result<Response<T>>
+    where
+        F: FnOnce(Token) -> Fut,
+        Fut: Future<Output = Result<Response<T>>>,
+    {
+        let reservation = self
+            .reserve_at(endpoint, chrono::Utc::now().timestamp())
+            .ok_or(error::Error::MissingValue("token"))?;
+        let index = reservation.index;
+        let result = request(self.tokens[index].clone()).await;
+        match result {
+            Ok(ref resp) => self.record(index, endpoint, resp.rate_limit_status),
+            Err(error::Error::RateLimit(reset)) => {
+                let exhausted = RateLimit {
+                    limit: -1,
+                    remaining: 0,
+                    reset,
+                };
+                self.record(index, endpoint, exhausted);
+            }
+            Err(_) => (),
+        }
+        result
+    }
+
+    /// Remembers the given rate-limit information for the given token and endpoint, unless
+    /// Twitter didn't send any.
+    fn record(&self, index: usize, endpoint: &str, rate: RateLimit) {
+        if rate.remaining >= 0 {
+            let key = (index, endpoint.to_string());
+            let mut limits = self.limits.lock().unwrap();
+            limits.entry(key).or_default().rate = Some(rate);
+        }
+    }
+
+    /// Picks a token for the given endpoint, as if the current time was `now`.
+    fn pick_at(&self, endpoint: &str, now: i64) -> Option<usize> {
+        let limits = self.limits.lock().unwrap();
+        self.best(&limits, endpoint, now)
+    }
+
+    /// Picks a token for the given endpoint, as if the current time was `now`, and counts a call
+    /// against it until the returned `Reservation` is dropped.
+    ///
+    /// This picks and reserves the token without letting go of the lock in between, so calls made
+    /// at the same time can't all pick the same token.
+    fn reserve_at<'a>(&'a self, endpoint: &'a str, now: i64) -> Option<Reservation<'a>> {
+        let mut limits = self.limits.lock().unwrap();
+        let index = self.best(&limits, endpoint, now)?;
+        limits
+            .entry((index, endpoint.to_string()))
+            .or_default()
+            .in_flight += 1;
+        Some(Reservation {
+            pool: self,
+            index,
+            endpoint,
+        })
+    }
+
+    /// Returns the token with the most calls left on the given endpoint, given the pool's usage.
+    fn best(
+        &self,
+        limits: &HashMap<(usize, String), Usage>,
+        endpoint: &str,
+        now: i64,
+    ) -> Option<usize> {
+        // rank tokens by the calls they have left, then by how soon any empty ones reset
+        let rank = |index: usize| {
+            let usage = limits.get(&(index, endpoint.to_string()));
+            let in_flight = usage.map_or(0, |usage| usage.in_flight);
+            match usage.and_then(|usage| usage.rate) {
+                Some(rate) if i64::from(rate.reset) > now => {
+                    (rate.remaining.saturating_sub(in_flight), -rate.reset)
+                }
+                _ => (i32::MAX - in_flight, 0),
+            }
+        };
+        // max_by_key keeps the last of several equal tokens, so go backward to prefer the first
+        let indices = (0..self.tokens.len()).rev();
+        indices.max_by_key(|&index| rank(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenPool;
+    use crate::auth::Token;
+    use crate::common::{RateLimit, Response};
+    use crate::error::Error;
+
+    fn bearer(index: usize) -> Token {
+        Token::Bearer(format!("bearer {}", index))
+    }
+
+    fn rate_limit(remaining: i32, reset: i32) -> RateLimit {
+        RateLimit {
+            limit: 900,
+            remaining,
+            reset,
+        }
+    }
+
+    #[test]
+    fn picks_least_constrained() {
+        let pool = TokenPool::new((0..3).map(bearer));
+        assert_eq!(pool.pick_at("users/show", 100), Some(0));
+
+        pool.record(0, "users/show", rate_limit(10, 1000));
+        pool.record(1, "users/show", rate_limit(500, 1000));
+        pool.record(2, "users/show", rate_limit(20, 1000));
+        assert_eq!(pool.pick_at("users/show", 100), Some(1));
+        // each endpoint is tracked separately
+        assert_eq!(pool.pick_at("statuses/show", 100), Some(0));
+        // once a window resets, that token has every call back
+        assert_eq!(pool.pick_at("users/show", 1000), Some(0));
+
+        // when everything is used up, pick the one that resets first
+        pool.record(0, "users/show", rate_limit(0, 1500));
+        pool.record(1, "users/show", rate_limit(0, 1200));
+        pool.record(2, "users/show", rate_limit(0, 1800));
+        assert_eq!(pool.pick_at("users/show", 100), Some(1));
+
+        // responses without rate-limit headers don't count
+        pool.record(1, "users/show", rate_limit(-1, -1));
+        assert_eq!(pool.pick_at("users/show", 100), Some(1));
+
+        assert_eq!(TokenPool::new(None).pick_at("users/show", 100), None);
+    }
+
+    #[tokio::test]
+    async fn call_records_rate_limits() {
+        let pool = TokenPool::new((0..2).map(bearer));
+        let reset = chrono::Utc::now().timestamp() as i32 + 900;
+        let remaining = |left: i32| {
+            move |token: Token| async move { Ok(Response::new(rate_limit(left, reset), token)) }
+        };
+
+        let used = pool.call("users/show", remaining(5)).await.unwrap();
+        assert!(matches!(used.response, Token::Bearer(ref t) if t == "bearer 0"));
+        let used = pool.call("users/show", remaining(50)).await.unwrap();
+        assert!(matches!(used.response, Token::Bearer(ref t) if t == "bearer 1"));
+        let used = pool.call("users/show", remaining(40)).await.unwrap();
+        assert!(matches!(used.response, Token::Bearer(ref t) if t == "bearer 1"));
+
+        // a rate-limit error empties that token until the reset
+        let limited = |_: Token| async move { Err::<Response<()>, _>(Error::RateLimit(reset)) };
+        let err = pool.call("users/show", limited).await.unwrap_err();
+        assert!(matches!(err, Error::RateLimit(_)));
+        let token = pool.pick("users/show").unwrap();
+        assert!(matches!(token, Token::Bearer(ref t) if t == "bearer 0"));
+
+        let empty = TokenPool::new(None);
+        let err = empty.call("users/show", remaining(5)).await.unwrap_err();
+        assert!(matches!(err, Error::MissingValue("token")));
+    }
+
+    #[tokio::test]
+    async fn concurrent_calls_spread_out() {
+        use futures::channel::oneshot;
+        use futures::FutureExt;
+
+        let pool = TokenPool::new((0..3).map(bearer));
+        let reset = chrono::Utc::now().timestamp() as i32 + 900;
+        pool.record(0, "users/show", rate_limit(2, reset));
+        pool.record(1, "users/show", rate_limit(1, reset));
+        pool.record(2, "users/show", rate_limit(1, reset));
+
+        // every call picks its token before any of them gets a response
+        let (release, released) = oneshot::channel::<()>();
+        let released = released.shared();
+        let calls = (0..4).map(|_| {
+            let released = released.clone();
+            pool.call("users/show", move |token| async move {
+                released.await.unwrap();
+                Ok(Response::new(rate_limit(100, reset), token))
+            })
+        });
+        let (results, ()) = futures::join!(futures::future::join_all(calls), async {
+            release.send(()).unwrap();
+        });
+
+        let mut used = results
+            .into_iter()
+            .map(|resp| match resp.unwrap().response {
+                Token::Bearer(ref token) => token.clone(),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        used.sort();
+        // token 0 has room for two calls, and the others for one each
+        assert_eq!(used, ["bearer 0", "bearer 0", "bearer 1", "bearer 2"]);
+
+        // a call that's dropped before it finishes gives its token back
+        pool.record(0, "statuses/show", rate_limit(1, reset));
+        pool.record(1, "statuses/show", rate_limit(0, reset - 100));
+        pool.record(2, "statuses/show", rate_limit(0, reset));
+        let pending = pool.call("statuses/show", |token| async move {
+            futures::future::pending::<()>().await;
+            Ok(Response::new(rate_limit(0, reset), token))
+        });
+        let mut pending = Box::pin(pending);
+        assert!((&mut pending).now_or_never().is_none());
+        assert_eq!(pool.pick_at("statuses/show", 100), Some(1));
+        drop(pending);
+        assert_eq!(pool.pick_at("statuses/show", 100), Some(0));
+    }
+}
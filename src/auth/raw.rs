@@ -15,10 +15,11 @@ use hyper::header::{AUTHORIZATION, CONTENT_TYPE};
 use hyper::{Body, Method, Request};
 use rand::{self, Rng};
 use sha1::Sha1;
+use sha2::Sha256;
 
 use crate::common::*;
 
-use super::{Token, KeyPair};
+use super::{KeyPair, SignatureMethod, Token};
 
 // n.b. this type is exported in `raw::auth` - these docs are public!
 /// Builder struct to assemble and sign an API request.
@@ -32,6 +33,7 @@ pub struct RequestBuilder<'a> {
     query: Option<String>,
     body: Option<(Body, &'static str)>,
     addon: OAuthAddOn,
+    signature_method: SignatureMethod,
 }
 
 impl<'a> RequestBuilder<'a> {
@@ -44,6 +46,7 @@ impl<'a> RequestBuilder<'a> {
             query: None,
             body: None,
             addon: OAuthAddOn::None,
+            signature_method: super::signature_method(),
         }
     }
 
@@ -140,6 +143,17 @@ impl<'a> RequestBuilder<'a> {
         }
     }
 
+    /// Sets the hash function used to sign this request, if it's signed with OAuth 1.0a.
+    ///
+    /// By default, `RequestBuilder` uses the method set with `auth::set_signature_method`, which
+    /// starts out as `HMAC-SHA1`.
+    pub fn with_signature_method(self, method: SignatureMethod) -> Self {
+        RequestBuilder {
+            signature_method: method,
+            ..self
+        }
+    }
+
     /// Formats this `RequestBuilder` into a complete `Request`, signing it with the given keys.
     ///
     /// While the `token` parameter is an Option here, it should only be `None` when generating a
@@ -148,6 +162,7 @@ impl<'a> RequestBuilder<'a> {
     pub fn request_keys(self, consumer_key: &KeyPair, token: Option<&KeyPair>) -> Request<Body> {
         let oauth = OAuthParams::from_keys(consumer_key.clone(), token.cloned())
             .with_addon(self.addon.clone())
+            .with_signature_method(self.signature_method)
             .sign_request(self.method.clone(), self.base_uri, self.params.as_ref());
         self.request_authorization(oauth.to_string())
     }
@@ -157,12 +172,15 @@ impl<'a> RequestBuilder<'a> {
     /// If the given `Token` is an Access token, the request will be signed using OAuth 1.0a, using
     /// the given URI, HTTP method, and parameters to create a signature.
     ///
-    /// If the given `Token` is a Bearer token, the request will be authenticated using OAuth 2.0,
-    /// specifying the given Bearer token as authorization.
+    /// If the given `Token` is a Bearer token or an OAuth 2.0 user token, the request will be
+    /// authenticated using OAuth 2.0, specifying the token's access token as authorization.
     pub fn request_token(self, token: &Token) -> Request<Body> {
         match token {
             Token::Access { consumer, access } => self.request_keys(consumer, Some(access)),
             Token::Bearer(bearer) => self.request_authorization(format!("Bearer {}", bearer)),
+            Token::OAuth2(user) => {
+                self.request_authorization(format!("Bearer {}", user.access_token))
+            }
         }
     }
 
@@ -181,19 +199,33 @@ impl<'a> RequestBuilder<'a> {
         self.request_authorization(bearer_request(consumer_key))
     }
 
+    /// Formats this `RequestBuilder` into a complete `Request`, without an Authorization header.
+    ///
+    /// This is only useful for the few endpoints that identify the app some other way, like the
+    /// OAuth 2.0 token endpoint, which accepts the client ID of a public client in the request
+    /// body.
+    pub fn request_unauthenticated(self) -> Request<Body> {
+        self.assemble(None)
+    }
+
     /// Assembles the final `Request` with the given Authorization header. This is private to
     /// require that a well-formed header is constructed given, as constructed from the other
     /// `request_*` methods.
     fn request_authorization(self, authorization: String) -> Request<Body> {
+        self.assemble(Some(authorization))
+    }
+
+    /// Assembles the final `Request`, with the given Authorization header if there is one.
+    fn assemble(self, authorization: Option<String>) -> Request<Body> {
         let full_url = if let Some(query) = self.query {
             format!("{}?{}", self.base_uri, query)
         } else {
             self.base_uri.to_string()
         };
-        let request = Request::builder()
-            .method(self.method)
-            .uri(full_url)
-            .header(AUTHORIZATION, authorization);
+        let mut request = Request::builder().method(self.method).uri(full_url);
+        if let Some(authorization) = authorization {
+            request = request.header(AUTHORIZATION, authorization);
+        }
 
         if let Some((body, content)) = self.body {
             request.header(CONTENT_TYPE, content)
@@ -219,6 +251,8 @@ struct OAuthParams {
     timestamp: u64,
     /// A callback or verifier parameter, if necessary.
     addon: OAuthAddOn,
+    /// The hash function used to create the signature.
+    signature_method: SignatureMethod,
 }
 
 impl OAuthParams {
@@ -244,6 +278,7 @@ impl OAuthParams {
             nonce,
             timestamp,
             addon: OAuthAddOn::None,
+            signature_method: SignatureMethod::HmacSha1,
         }
     }
 
@@ -267,6 +302,14 @@ impl OAuthParams {
         }
     }
 
+    /// Sets the hash function used to sign the request.
+    fn with_signature_method(self, signature_method: SignatureMethod) -> OAuthParams {
+        OAuthParams {
+            signature_method,
+            ..self
+        }
+    }
+
     /// Uses the parameters in this `OAuthParams` instance to generate a signature for the given
     /// request, returning it as a `SignedHeader`.
     fn sign_request(self, method: Method, uri: &str, params: Option<&ParamList>) -> SignedHeader {
@@ -276,10 +319,10 @@ impl OAuthParams {
                 .unwrap_or_default()
                 .add_param("oauth_consumer_key", self.consumer_key.key.clone())
                 .add_param("oauth_nonce", self.nonce.clone())
-                .add_param("oauth_signature_method", "HMAC-SHA1")
+                .add_param("oauth_signature_method", self.signature_method.oauth_name())
                 .add_param("oauth_timestamp", format!("{}", self.timestamp.clone()))
                 .add_param("oauth_version", "1.0")
-                .add_opt_param("oauth_token", self.token.clone().map(|k| k.key))
+                .add_opt_param("oauth_token", self.token.as_ref().map(|k| k.key.clone()))
                 .add_opt_param("oauth_callback", self.addon.as_callback().map(|s| s.to_string()))
                 .add_opt_param("oauth_verifier", self.addon.as_verifier().map(|s| s.to_string()));
 
@@ -304,17 +347,32 @@ impl OAuthParams {
             percent_encode(&self.token.as_ref().unwrap_or(&KeyPair::new("", "")).secret)
         );
 
-        // TODO check if key is correct length? Can this fail?
-        let mut digest = Hmac::<Sha1>::new_varkey(key.as_bytes()).expect("Wrong key length");
-        digest.update(base_str.as_bytes());
+        let signature = match self.signature_method {
+            SignatureMethod::HmacSha1 => {
+                // TODO check if key is correct length? Can this fail?
+                let mut digest =
+                    Hmac::<Sha1>::new_varkey(key.as_bytes()).expect("Wrong key length");
+                digest.update(base_str.as_bytes());
+                base64::encode(digest.finalize().into_bytes())
+            }
+            SignatureMethod::HmacSha256 => {
+                let mut digest =
+                    Hmac::<Sha256>::new_varkey(key.as_bytes()).expect("Wrong key length");
+                digest.update(base_str.as_bytes());
+                base64::encode(digest.finalize().into_bytes())
+            }
+        };
 
         let mut params: BTreeMap<&'static str, Cow<'static, str>> = BTreeMap::new();
-        params.insert("oauth_signature_method", "HMAC-SHA1".into());
+        params.insert(
+            "oauth_signature_method",
+            self.signature_method.oauth_name().into(),
+        );
         params.insert("oauth_version", "1.0".into());
 
-        params.insert("oauth_consumer_key", self.consumer_key.key);
+        params.insert("oauth_consumer_key", self.consumer_key.key.clone());
         if let Some(token) = self.token {
-            params.insert("oauth_token", token.key);
+            params.insert("oauth_token", token.key.clone());
         }
 
         params.insert("oauth_nonce", self.nonce.into());
@@ -330,7 +388,7 @@ impl OAuthParams {
             OAuthAddOn::None => (),
         }
 
-        params.insert("oauth_signature", base64::encode(&digest.finalize().into_bytes()).into());
+        params.insert("oauth_signature", signature.into());
 
         SignedHeader {
             params,
@@ -413,7 +471,7 @@ fn bearer_request(con_token: &KeyPair) -> String {
 /// Assemble a signed GET request to the given URL with the given parameters.
 ///
 /// The given parameters, if present, will be appended to the given `uri` as a percent-encoded
-/// query string. If the given `token` is not a Bearer token, the parameters will also be used to
+/// query string. If the given `token` is an Access token, the parameters will also be used to
 /// create the OAuth signature.
 pub fn get(uri: &str, token: &Token, params: Option<&ParamList>) -> Request<Body> {
     let mut request = RequestBuilder::new(Method::GET, uri);
@@ -427,7 +485,7 @@ pub fn get(uri: &str, token: &Token, params: Option<&ParamList>) -> Request<Body
 /// Assemble a signed DELETE request to the given URL with the given parameters.
 ///
 /// The given parameters, if present, will be appended to the given `uri` as a percent-encoded
-/// query string. If the given `token` is not a Bearer token, the parameters will also be used to
+/// query string. If the given `token` is an Access token, the parameters will also be used to
 /// create the OAuth signature.
 pub fn delete(uri: &str, token: &Token, params: Option<&ParamList>) -> Request<Body> {
     let mut request = RequestBuilder::new(Method::DELETE, uri);
@@ -442,7 +500,7 @@ pub fn delete(uri: &str, token: &Token, params: Option<&ParamList>) -> Request<B
 ///
 /// The given parameters, if present, will be percent-encoded and included in the POST body
 /// formatted with a content-type of `application/x-www-form-urlencoded`. If the given `token` is
-/// not a Bearer token, the parameters will also be used to create the OAuth signature.
+/// an Access token, the parameters will also be used to create the OAuth signature.
 pub fn post(uri: &str, token: &Token, params: Option<&ParamList>) -> Request<Body> {
     let mut request = RequestBuilder::new(Method::POST, uri);
     if let Some(params) = params {
@@ -464,9 +522,46 @@ pub fn post_json<B: serde::Serialize>(uri: &str, token: &Token, body: B) -> Requ
         .request_token(token)
 }
 
+/// Computes the `Authorization` header for a request to the given URL with the given parameters,
+/// without assembling the request itself.
+///
+/// This is the same header that egg-mode sends with its own requests, so it can be used to call
+/// endpoints egg-mode doesn't wrap with whichever HTTP client you like. If the given `token` is an
+/// Access token, the request is signed with OAuth 1.0a, using the HTTP method, URL, and parameters
+/// to create the signature; the parameters need to be the same ones sent with the request, whether
+/// in the query string or in an `application/x-www-form-urlencoded` body. Since the query string
+/// is covered by `params`, `uri` shouldn't include one. For Bearer tokens and OAuth 2.0 tokens,
+/// the header just holds the token itself.
+///
+/// This can also be used for [OAuth Echo], where another service verifies a user's identity by
+/// calling `account/verify_credentials` on your behalf: sign a GET request to that URL, and hand
+/// the result to the service in the `X-Verify-Credentials-Authorization` header.
+///
+/// [OAuth Echo]: https://developer.twitter.com/en/docs/authentication/oauth-echo
+pub fn sign_request(
+    method: Method,
+    uri: &str,
+    params: Option<&ParamList>,
+    token: &Token,
+) -> String {
+    match token {
+        Token::Access { consumer, access } => {
+            OAuthParams::from_keys(consumer.clone(), Some(access.clone()))
+                .with_signature_method(super::signature_method())
+                .sign_request(method, uri, params)
+                .to_string()
+        }
+        Token::Bearer(bearer) => format!("Bearer {}", bearer),
+        Token::OAuth2(user) => format!("Bearer {}", user.access_token),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::bearer_request;
+    use super::{bearer_request, OAuthParams};
+    use crate::auth::{KeyPair, SignatureMethod};
+    use crate::common::ParamList;
+    use hyper::Method;
 
     #[test]
     fn bearer_header() {
@@ -478,4 +573,75 @@ mod tests {
 
         assert_eq!(output, "Basic eHZ6MWV2RlM0d0VFUFRHRUZQSEJvZzpMOHFxOVBaeVJnNmllS0dFS2hab2xHQzB2SldMdzhpRUo4OERSZHlPZw==");
     }
+
+    #[test]
+    fn signature_methods() {
+        // the example request from Twitter's "Creating a signature" documentation
+        let params = OAuthParams {
+            nonce: "kYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg".to_string(),
+            timestamp: 1318622958,
+            ..OAuthParams::from_keys(
+                KeyPair::new(
+                    "xvz1evFS4wEEPTGEFPHBog",
+                    "kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw",
+                ),
+                Some(KeyPair::new(
+                    "370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb",
+                    "LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE",
+                )),
+            )
+        };
+        let request_params = ParamList::new()
+            .add_param("include_entities", "true")
+            .add_param(
+                "status",
+                "Hello Ladies + Gentlemen, a signed OAuth request!",
+            );
+        let sign = |method| {
+            params
+                .clone()
+                .with_signature_method(method)
+                .sign_request(
+                    Method::POST,
+                    "https://api.twitter.com/1.1/statuses/update.json",
+                    Some(&request_params),
+                )
+                .params
+        };
+
+        let sha1 = sign(SignatureMethod::HmacSha1);
+        assert_eq!(sha1["oauth_signature_method"], "HMAC-SHA1");
+        assert_eq!(sha1["oauth_signature"], "hCtSmYh+iHYCEqBWrE7C7hYmtUk=");
+
+        let sha256 = sign(SignatureMethod::HmacSha256);
+        assert_eq!(sha256["oauth_signature_method"], "HMAC-SHA256");
+        assert_eq!(
+            sha256["oauth_signature"],
+            "PLbq+OWUE2vwiOZeZBSR06GFvymUHoaBdCIHyD66IcM="
+        );
+    }
+
+    #[test]
+    fn sign_arbitrary_request() {
+        use crate::auth::Token;
+
+        let token = Token::Access {
+            consumer: KeyPair::new("consumer key", "consumer secret"),
+            access: KeyPair::new("access key", "access secret"),
+        };
+        let params = ParamList::new().add_param("screen_name", "rustlang");
+        let uri = "https://api.twitter.com/1.1/users/show.json";
+        let header = super::sign_request(Method::GET, uri, Some(&params), &token);
+
+        assert!(header.starts_with("OAuth "));
+        assert!(header.contains("oauth_consumer_key=\"consumer%20key\""));
+        assert!(header.contains("oauth_token=\"access%20key\""));
+        assert!(header.contains("oauth_signature=\""));
+        // the request's own parameters are signed, but not sent in the header
+        assert!(!header.contains("screen_name"));
+
+        let bearer = Token::Bearer("bearer token".into());
+        let header = super::sign_request(Method::GET, uri, Some(&params), &bearer);
+        assert_eq!(header, "Bearer bearer token");
+    }
 }
@@ -0,0 +1,252 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Storage for tokens between runs of an app.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::error::Result;
+
+use super::Token;
+
+/// A place to keep users' tokens, so they don't need to sign in again every time your app starts.
+///
+/// Tokens are stored by the ID of the user they belong to: for an Access token, this is the user
+/// ID returned by [`access_token`] alongside the token. [`FileTokenStore`] keeps each token in a
+/// JSON file, but any other storage can be used by implementing this trait. To have refreshed
+/// OAuth 2.0 tokens saved automatically, hand a store to [`oauth2::UserAuth::with_store`].
+///
+/// [`access_token`]: fn.access_token.html
+/// [`FileTokenStore`]: struct.FileTokenStore.html
+/// [`oauth2::UserAuth::with_store`]: oauth2/struct.UserAuth.html#method.with_store
+///
+/// **Note** that the tokens given to a `TokenStore` work just like passwords, so make sure
+/// wherever they're stored is kept private.
+///
+/// The methods of a `TokenStore` are called directly from async code, like the refresh in
+/// `oauth2::UserAuth`, so they block whatever task calls them until they return. Stores that can
+/// take a long time, like ones that go over the network, should hand the work off to another
+/// thread instead of doing it in place.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn main() {
+/// use egg_mode::auth::{FileTokenStore, TokenStore};
+/// # let con_token = egg_mode::KeyPair::new("", "");
+/// # let request_token = egg_mode::KeyPair::new("", "");
+/// # let verifier = "";
+///
+/// let store = FileTokenStore::new("tokens");
+/// let (token, user_id, _screen_name) =
+///     egg_mode::auth::access_token(con_token, &request_token, verifier)
+///         .await
+///         .unwrap();
+/// store.save(user_id, &token).unwrap();
+///
+/// // ...and the next time the app starts:
+/// let token = store.load(user_id).unwrap();
+/// # }
+/// ```
+pub trait TokenStore {
+    /// Loads the token saved for the given user, or `None` if there isn't one.
+    fn load(&self, user_id: u64) -> Result<Option<Token>>;
+
+    /// Saves the given token for the given user, replacing any token that was saved before.
+    fn save(&self, user_id: u64, token: &Token) -> Result<()>;
+
+    /// Deletes the token saved for the given user, if there is one.
+    fn delete(&self, user_id: u64) -> Result<()>;
+}
+
+/// A `TokenStore` that keeps each user's token in a JSON file in a directory.
+///
+/// The token for each user is saved as `{user_id}.json`, in the same format as serializing the
+/// `Token` with `serde_json`. The directory is created when the first token is saved. Each file
+/// is written in full before it replaces the old one, so a crash partway through saving a token
+/// won't leave a half-written file behind, and several saves of the same user's token at once
+/// (even from different processes) each write their own file before one of them wins.
+///
+/// On Unix, the directory is created so only its owner can open it, and each token file so only
+/// its owner can read it.
+///
+/// The files are read and written with blocking calls from `std::fs`. Token files are small, so
+/// this is usually quick, but it does hold up the task that calls the store while it runs.
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    dir: PathBuf,
+}
+
+impl FileTokenStore {
+    /// Creates a new `FileTokenStore` that keeps its tokens in the given directory.
+    pub fn new(dir: impl Into<PathBuf>) -> FileTokenStore {
+        FileTokenStore { dir: dir.into() }
+    }
+
+    /// Returns the path of the file holding the token for the given user.
+    fn path(&self, user_id: u64) -> PathBuf {
+        self.dir.join(format!("{}.json", user_id))
+    }
+
+    /// Returns a path to write the token for the given user to before it replaces the saved one.
+    /// Each call returns a different path, so saves running at the same time don't write to the
+    /// same file.
+    fn partial_path(&self, user_id: u64) -> PathBuf {
+        static SAVES: AtomicUsize = AtomicUsize::new(0);
+        let save = SAVES.fetch_add(1, Ordering::Relaxed);
+        self.dir.join(format!(
+            "{}.json.{}-{}.partial",
+            user_id,
+            std::process::id(),
+            save
+        ))
+    }
+}
+
+impl TokenStore for FileTokenStore {
+    fn load(&self, user_id: u64) -> Result<Option<Token>> {
+        match fs::read(self.path(user_id)) {
+            Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, user_id: u64, token: &Token) -> Result<()> {
+        create_private_dir(&self.dir)?;
+        let contents = serde_json::to_vec(token)?;
+        let partial = self.partial_path(user_id);
+        // a leftover file from an earlier process with the same ID would keep whatever permissions
+        // it was made with
+        match fs::remove_file(&partial) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            result => result?,
+        }
+        let written = create_private_file(&partial)
+            .and_then(|mut file| file.write_all(&contents))
+            .and_then(|()| fs::rename(&partial, self.path(user_id)));
+        if written.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        Ok(written?)
+    }
+
+    fn delete(&self, user_id: u64) -> Result<()> {
+        match fs::remove_file(self.path(user_id)) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => Ok(result?),
+        }
+    }
+}
+
+/// Creates the given directory and its parents, readable only by the current user on Unix.
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(dir)
+}
+
+/// Creates a new file at the given path, readable only by the current user on Unix.
+fn create_private_file(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileTokenStore, TokenStore};
+    use crate::auth::{KeyPair, Token};
+
+    #[test]
+    fn file_store_round_trip() {
+        let dir = std::env::temp_dir().join(format!("egg-mode-tokens-{}", std::process::id()));
+        let store = FileTokenStore::new(dir.join("nested"));
+        let token = Token::Access {
+            consumer: KeyPair::new("consumer key", "consumer secret"),
+            access: KeyPair::new("access key", "access secret"),
+        };
+
+        assert!(store.load(1).unwrap().is_none());
+        store.save(1, &token).unwrap();
+        store.save(2, &Token::Bearer("bearer".into())).unwrap();
+        match store.load(1).unwrap() {
+            Some(Token::Access {
+                ref consumer,
+                ref access,
+            }) => {
+                assert_eq!(consumer.key, "consumer key");
+                assert_eq!(access.secret, "access secret");
+            }
+            other => panic!("unexpected token: {:?}", other),
+        }
+
+        // saving again replaces the old token
+        store.save(1, &Token::Bearer("replaced".into())).unwrap();
+        let replaced = store.load(1).unwrap();
+        assert!(matches!(replaced, Some(Token::Bearer(ref b)) if b == "replaced"));
+
+        store.delete(1).unwrap();
+        store.delete(1).unwrap();
+        assert!(store.load(1).unwrap().is_none());
+        assert!(store.load(2).unwrap().is_some());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn file_store_concurrent_saves() {
+        let dir = std::env::temp_dir().join(format!("egg-mode-racing-{}", std::process::id()));
+        let store = FileTokenStore::new(&dir);
+
+        let saves = (0..8)
+            .map(|i| {
+                let store = store.clone();
+                std::thread::spawn(move || {
+                    for j in 0..100 {
+                        let token = Token::Bearer(format!("bearer {}-{}", i, j));
+                        store.save(1, &token).unwrap();
+                        // another thread's save can't leave a half-written token in place
+                        assert!(store.load(1).unwrap().is_some());
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for save in saves {
+            save.join().unwrap();
+        }
+
+        assert!(matches!(store.load(1).unwrap(), Some(Token::Bearer(_))));
+        // every save cleans up after itself
+        let files = std::fs::read_dir(&dir).unwrap().count();
+        assert_eq!(files, 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_store_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("egg-mode-private-{}", std::process::id()));
+        let store = FileTokenStore::new(dir.join("nested"));
+        store.save(1, &Token::Bearer("bearer".into())).unwrap();
+
+        let mode =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir.join("nested")), 0o700);
+        assert_eq!(mode(&dir.join("nested").join("1.json")), 0o600);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
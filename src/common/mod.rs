@@ -57,10 +57,27 @@
 //! the format Twitter uses for timestamps, and `serde_via_string` uses `Display` and `FromStr` to
 //! save a string representation of the original type.
 //!
+//! `deserialize_twitter_timestamp` and `deserialize_iso8601` are the two timestamp parsers behind
+//! all of that, for use with `#[serde(deserialize_with = "...")]`. The first one reads the
+//! `"Wed Aug 27 13:08:45 +0000 2008"` format that the v1.1 API uses for tweets, users, and lists;
+//! the second one reads the `"2008-08-27T13:08:45.000Z"` format that the v2 API uses. Both of them
+//! hand back a `DateTime<Utc>`, so the models don't have to care which one Twitter used.
+//! `deserialize_opt_iso8601` is the same as the second one, for fields the v2 API can leave out.
+//!
+//! `Lookup` is the response type shared by the v2 lookups. It's generic over the type of its
+//! `includes`, and hydrates its `data` from them as it's loaded, so each module only needs to
+//! define its own includes type and `Hydrate` impl, and name its lookup with a type alias.
+//!
 //! `merge_by` and its companion type `MergeBy` is a copy of the iterator adapter of the same name
 //! from itertools, because i didn't want to add another dependency onto the great towering pile
 //! that is my dep tree. `>_>`
 //!
+//! `rate_limit_backoff` decides how long to sleep after hitting a rate limit, for functions that
+//! page through several calls on the user's behalf. It uses the reset time Twitter gave, falling
+//! back to an exponential backoff if that time has already passed. `RateLimitGate` lets several
+//! of these functions running at once share that wait, so that when one of them is rate-limited,
+//! the rest hold off too.
+//!
 //! `max_opt` and `min_opt` are helper functions because i didn't realize that `Option` derived
 //! `PartialOrd` and `Ord` at the time. Strictly speaking they're subtly different because
 //! `std::cmp::{min,max}` require `Ord` and `min_opt` won't reach for the None if it's there,
@@ -84,12 +101,25 @@
 //! `raw_request` (which just calls `serde_json` and loads up the rate-limit headers)
 //! then deserializes the json response to given type.
 //!
+//! `with_cancel` races any of these futures against a caller-provided signal, so that a request can
+//! be dropped partway through. `raw_request` also enforces the limit on response body size from the
+//! `ClientConfig` given to `set_client_config`, and follows redirects up to that config's limit,
+//! without sending credentials to other hosts along the way. The hyper client behind all of this
+//! is built from that `ClientConfig` too.
+//!
+//! `RateLimitGate` and `RetryBudget` are shared by the functions that retry calls after hitting the
+//! rate limit: the gate makes every task behind it wait together, and the budget (set process-wide
+//! with `set_retry_budget`) caps how many retries they can make in total. `retry_reset` decides
+//! which errors those functions retry: rate limits and server errors always, but timeouts and
+//! dropped connections only for requests that `is_idempotent` says are safe to send twice.
+//! `lookup_chunked` uses all of these to load more IDs than a lookup endpoint takes in one call.
+//!
 //! `rate_headers` is an infra function that takes the `Headers` and returns an empty `Response`
 //! with the rate-limit info parsed out. It's only exported for a couple functions in `list` which
 //! need to get that info even on an error.
 
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::iter::Peekable;
 use std::pin::Pin;
@@ -102,6 +132,7 @@ mod response;
 pub use crate::auth::raw::{get, post, post_json};
 
 pub use crate::common::response::*;
+use crate::tweet::v2::{Hydrate, PageMeta, Paged, Problem};
 use crate::{error, list, user};
 
 /// Macro to create a `Serialize`/`Deserialize` implementation allowing for deserialization via the
@@ -218,6 +249,38 @@ macro_rules! round_trip {
     };
 }
 
+/// Generates an enum of the field names, expansions, or other fixed values a v2 API endpoint takes
+/// as a comma-separated parameter, along with an `as_str` method that gives the name Twitter uses
+/// for each variant.
+///
+/// ```rust,ignore
+/// field_names! {
+///     ///Doc comment for the enum.
+///     TweetField {
+///         ///Doc comment for the variant.
+///         CreatedAt => "created_at",
+///     }
+/// }
+/// ```
+macro_rules! field_names {
+    ($(#[$attr:meta])* $name:ident { $($(#[$vattr:meta])* $variant:ident => $value:expr,)* }) => {
+        $(#[$attr])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $($(#[$vattr])* $variant,)*
+        }
+
+        impl $name {
+            ///Returns the name Twitter uses for this value in a request.
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $($name::$variant => $value,)*
+                }
+            }
+        }
+    };
+}
+
 /// Types that implement `Deserialize` either by loading from upstream JSON, or via a "round-trip"
 /// serialization.
 ///
@@ -272,8 +335,8 @@ pub type CowStr = Cow<'static, str>;
 // n.b. this type is re-exported in the `raw` module - these docs are public!
 /// Represents a list of parameters to a Twitter API call.
 ///
-/// This type is a wrapper around a `HashMap<Cow<'static, str>, Cow<'static, str>>` to collect a
-/// set of parameter key/value pairs. These are then used to assemble and sign a Twitter API
+/// This type is a wrapper around a `HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>` to collect
+/// a set of parameter key/value pairs. These are then used to assemble and sign a Twitter API
 /// request. The `Cow` type is used to avoid having to allocate a `String` if a string literal is
 /// used for a parameter. All the functions that add parameters to this `ParamList` accept `impl
 /// Into<Cow<'static, str>>`, meaning that either a string literal or an owned `String` may be
@@ -291,8 +354,33 @@ pub type CowStr = Cow<'static, str>;
 ///     .extended_tweets()
 ///     .add_user_param("rustlang".into());
 /// ```
-#[derive(Debug, Clone, Default, derive_more::Deref, derive_more::DerefMut, derive_more::From)]
-pub struct ParamList(HashMap<Cow<'static, str>, Cow<'static, str>>);
+///
+/// Some Twitter API calls accept a parameter repeated several times with different values. These
+/// can be added with `push_param`, and every accessor on `ParamList` sees all of them: `get`
+/// returns the first value for a key, `get_all` returns every one, and `iter` (along with
+/// `to_urlencoded` and the request signature) visits each key/value pair in turn.
+#[derive(Debug, Clone, Default)]
+pub struct ParamList(HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>);
+
+impl From<HashMap<Cow<'static, str>, Cow<'static, str>>> for ParamList {
+    fn from(params: HashMap<Cow<'static, str>, Cow<'static, str>>) -> Self {
+        Self(params.into_iter().map(|(k, v)| (k, vec![v])).collect())
+    }
+}
+
+impl<K, V> std::iter::FromIterator<(K, V)> for ParamList
+where
+    K: Into<Cow<'static, str>>,
+    V: Into<Cow<'static, str>>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(pairs: I) -> Self {
+        let mut params = ParamList::new();
+        for (k, v) in pairs {
+            params.add_param_ref(k, v);
+        }
+        params
+    }
+}
 
 impl ParamList {
     /// Creates a new, empty `ParamList`.
@@ -300,6 +388,26 @@ impl ParamList {
         Self(HashMap::new())
     }
 
+    /// Creates a new `ParamList` from the given key/value pairs.
+    ///
+    /// This is the same as `collect`ing the pairs into a `ParamList`. As with `add_param`, if a
+    /// key is given more than once, only the last value will be kept.
+    ///
+    /// ```
+    /// use egg_mode::raw::ParamList;
+    ///
+    /// let pairs = vec![("screen_name".to_string(), "rustlang".to_string())];
+    /// let params = ParamList::from_pairs(pairs);
+    /// # assert_eq!(params.get("screen_name").map(|v| v.as_ref()), Some("rustlang"));
+    /// ```
+    pub fn from_pairs<K, V>(pairs: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<Cow<'static, str>>,
+        V: Into<Cow<'static, str>>,
+    {
+        pairs.into_iter().collect()
+    }
+
     /// Adds the `tweet_mode=extended` parameter to this `ParamList`. Not including this parameter
     /// will cause tweets to be loaded with legacy parameters, and a potentially-truncated `text`
     /// if the tweet is longer than 140 characters. The `Deserialize` impl for `Tweet`s (or
@@ -342,7 +450,65 @@ impl ParamList {
         key: impl Into<Cow<'static, str>>,
         value: impl Into<Cow<'static, str>>,
     ) {
-        self.0.insert(key.into(), value.into());
+        self.insert(key.into(), value.into());
+    }
+
+    /// Sets the given key to the given value, replacing any value it had before, including ones
+    /// added with `push_param`. Returns the first value the key had before, if any.
+    pub fn insert(
+        &mut self,
+        key: Cow<'static, str>,
+        value: Cow<'static, str>,
+    ) -> Option<Cow<'static, str>> {
+        self.0
+            .insert(key, vec![value])
+            .and_then(|old| old.into_iter().next())
+    }
+
+    /// Removes every value for the given key, including ones added with `push_param`. Returns the
+    /// first value the key had, if any.
+    pub fn remove(&mut self, key: &str) -> Option<Cow<'static, str>> {
+        self.0.remove(key).and_then(|old| old.into_iter().next())
+    }
+
+    /// Returns the first value set for the given key, if any.
+    pub fn get(&self, key: &str) -> Option<&Cow<'static, str>> {
+        self.0.get(key).and_then(|values| values.first())
+    }
+
+    /// Returns every value set for the given key, in the order they were added. This is empty if
+    /// the key isn't set.
+    pub fn get_all(&self, key: &str) -> &[Cow<'static, str>] {
+        self.0.get(key).map_or(&[], |values| values.as_slice())
+    }
+
+    /// Returns whether the given key has any value set.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+
+    /// Returns the number of key/value pairs in this `ParamList`, counting each repeated value
+    /// added with `push_param` separately.
+    pub fn len(&self) -> usize {
+        self.0.values().map(Vec::len).sum()
+    }
+
+    /// Returns whether this `ParamList` has no parameters at all.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Adds the given key/value to this `ParamList` by mutating it in place, keeping any value
+    /// that was already set for the same key.
+    ///
+    /// If the key is already present, the parameter will be sent a second time with the new value,
+    /// e.g. `key=value1&key=value2`. Unlike `add_param_ref`, this will never overwrite a value.
+    pub fn push_param(
+        &mut self,
+        key: impl Into<Cow<'static, str>>,
+        value: impl Into<Cow<'static, str>>,
+    ) {
+        self.0.entry(key.into()).or_default().push(value.into());
     }
 
     /// Adds the given `UserID` as a parameter to this `ParamList` by adding either a `user_id` or
@@ -374,16 +540,32 @@ impl ParamList {
     }
 
     /// Merge the parameters from the given `ParamList` into this one.
+    ///
+    /// Keys set in `other` replace every value they had here, including repeated ones.
     pub(crate) fn combine(&mut self, other: ParamList) {
         self.0.extend(other.0);
     }
 
+    /// Iterates over every key/value pair in this `ParamList`, including repeated parameters added
+    /// with `push_param`.
+    ///
+    /// The pairs are sorted by key, so that the same set of parameters always comes out in the
+    /// same order, regardless of the order of the underlying `HashMap`. Repeated parameters keep
+    /// the order they were added in.
+    pub fn iter(&self) -> impl Iterator<Item = (&Cow<'static, str>, &Cow<'static, str>)> {
+        let mut keys = self.0.keys().collect::<Vec<_>>();
+        keys.sort();
+        keys.into_iter()
+            .flat_map(move |k| self.0[k].iter().map(move |v| (k, v)))
+    }
+
     /// Renders this `ParamList` as an `application/x-www-form-urlencoded` string.
     ///
     /// The key/value pairs are printed as `key1=value1&key2=value2`, with all keys and values
-    /// being percent-encoded according to Twitter's requirements.
+    /// being percent-encoded according to Twitter's requirements. The pairs are sorted by key, so
+    /// the same parameters always render to the same string.
     pub fn to_urlencoded(&self) -> String {
-        self.0.iter()
+        self.iter()
             .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
             .collect::<Vec<_>>()
             .join("&")
@@ -423,6 +605,329 @@ where
 pub(crate) type FutureResponse<T> =
     Pin<Box<dyn Future<Output = error::Result<Response<T>>> + Send>>;
 
+///The longest that `rate_limit_backoff` will ever wait: one full rate-limit window.
+const MAX_RATE_LIMIT_WAIT: u64 = 15 * 60;
+
+///Computes how long to wait after a rate-limit error before trying the call again.
+///
+///`reset` is the UTC Unix timestamp from `Error::RateLimit`, and `attempt` is the number of
+///rate-limit errors that were received in a row before this one. If the reset time is still in
+///the future, this waits until one second after it. Otherwise (e.g. if the local clock is ahead of
+///Twitter's), this backs off exponentially from one second, up to a full 15-minute window.
+pub(crate) fn rate_limit_backoff(reset: i32, attempt: u32) -> std::time::Duration {
+    rate_limit_backoff_from(reset, chrono::Utc::now().timestamp(), attempt)
+}
+
+///POST endpoints that can safely be sent again if it's unclear whether the first try went through.
+///
+///The lookup endpoints only load data, and are POSTed to so that more IDs fit in the request.
+///Marking DMs as read or showing the typing indicator has no further effect the second time.
+const IDEMPOTENT_POSTS: &[&str] = &[
+    crate::links::users::LOOKUP,
+    crate::links::statuses::LOOKUP,
+    crate::links::direct::MARK_READ,
+    crate::links::direct::INDICATE_TYPING,
+];
+
+///Returns whether sending the request with the given method and URL twice has the same effect as
+///sending it once.
+///
+///`GET`, `HEAD`, `PUT`, `DELETE`, and `OPTIONS` requests are idempotent, as HTTP defines them.
+///`POST` requests are not, unless they're to one of the `IDEMPOTENT_POSTS` endpoints: sending a
+///tweet or DM again, for example, posts it twice. Any query string on the URL is ignored.
+pub(crate) fn is_idempotent(method: &hyper::Method, url: &str) -> bool {
+    let endpoint = url.split('?').next().unwrap_or(url);
+    match *method {
+        hyper::Method::POST => IDEMPOTENT_POSTS.contains(&endpoint),
+        ref method => method.is_idempotent(),
+    }
+}
+
+///How many times in a row a call is retried after a server error or a dropped connection.
+const MAX_TRANSIENT_RETRIES: u32 = 3;
+
+///Returns whether a call that failed with the given error should be tried again, as the reset time
+///to hand to `rate_limit_backoff`, or `None` if the error should be returned.
+///
+///Rate-limit errors and other `429 Too Many Requests` responses are always retried, since Twitter
+///turned the request away before doing anything with it. `5xx` server errors are retried up to
+///`MAX_TRANSIENT_RETRIES` times in a row, for any request. Timeouts and dropped connections are
+///different: the request may have gone through before the connection was lost, so they're only
+///retried (again up to `MAX_TRANSIENT_RETRIES` times) if `idempotent` is set, as given by
+///`is_idempotent`. `attempt` is the number of times the call has already been retried.
+pub(crate) fn retry_reset(err: &error::Error, idempotent: bool, attempt: u32) -> Option<i32> {
+    use crate::error::Error::*;
+
+    let transient = attempt < MAX_TRANSIENT_RETRIES;
+    match *err {
+        RateLimit(reset) => Some(reset),
+        BadStatus(hyper::StatusCode::TOO_MANY_REQUESTS) => Some(0),
+        BadStatus(status) if status.is_server_error() && transient => Some(0),
+        UpstreamHtml { status, .. } if status.is_server_error() && transient => Some(0),
+        NetError(ref e) if is_transport_error(e) && idempotent && transient => Some(0),
+        IOError(ref e) if is_connection_error(e.kind()) && idempotent && transient => Some(0),
+        _ => None,
+    }
+}
+
+///Returns whether the given error from hyper means the request timed out, or its connection was
+///lost or couldn't be made.
+fn is_transport_error(err: &hyper::Error) -> bool {
+    err.is_timeout() || err.is_connect() || err.is_closed() || err.is_incomplete_message()
+}
+
+///Returns whether the given kind of I/O error means a connection timed out or was lost.
+fn is_connection_error(kind: std::io::ErrorKind) -> bool {
+    use std::io::ErrorKind::*;
+
+    matches!(
+        kind,
+        TimedOut
+            | ConnectionReset
+            | ConnectionAborted
+            | ConnectionRefused
+            | BrokenPipe
+            | UnexpectedEof
+    )
+}
+
+///Shared state for several tasks drawing from the same rate limit, so that when one of them hits
+///the limit, all of them wait for it to reset.
+#[derive(Debug, Default)]
+pub(crate) struct RateLimitGate {
+    open_at: std::sync::Mutex<Option<std::time::Instant>>,
+    ///The retry budget to draw from, if not the process-wide one.
+    budget: Option<std::sync::Arc<RetryBudget>>,
+}
+
+impl RateLimitGate {
+    ///Waits until no task is holding the gate closed.
+    pub(crate) async fn wait(&self) {
+        loop {
+            let remaining = self
+                .open_at
+                .lock()
+                .unwrap()
+                .and_then(|at| at.checked_duration_since(std::time::Instant::now()));
+            match remaining {
+                Some(wait) if wait > std::time::Duration::from_secs(0) => {
+                    tokio::time::delay_for(wait).await
+                }
+                _ => return,
+            }
+        }
+    }
+
+    ///Holds the gate closed for the given duration, unless it's already being held for longer.
+    pub(crate) fn hold_for(&self, wait: std::time::Duration) {
+        let until = std::time::Instant::now() + wait;
+        let mut open_at = self.open_at.lock().unwrap();
+        if *open_at < Some(until) {
+            *open_at = Some(until);
+        }
+    }
+
+    ///Returns whether the tasks behind this gate may retry a call, spending one retry from the
+    ///retry budget if so.
+    pub(crate) fn try_retry(&self) -> bool {
+        match self.budget {
+            Some(ref budget) => budget.try_acquire(),
+            None => retry_budget().try_acquire(),
+        }
+    }
+
+    ///Creates a gate that draws retries from the given budget instead of the process-wide one.
+    #[cfg(test)]
+    pub(crate) fn with_budget(budget: RetryBudget) -> RateLimitGate {
+        RateLimitGate {
+            open_at: Default::default(),
+            budget: Some(std::sync::Arc::new(budget)),
+        }
+    }
+}
+
+///Loads the given items in chunks of at most `chunk_size`, running up to `concurrency` chunks at
+///once, and returns all the results in the same order as the chunks.
+///
+///The chunks share one `RateLimitGate`, so a chunk that hits the rate limit is retried once it
+///resets, and the other chunks wait for it too. Any other error ends the whole lookup. The
+///rate-limit information in the returned `Response` is from the last chunk.
+pub(crate) async fn lookup_chunked<I, T, F, Fut>(
+    items: Vec<I>,
+    chunk_size: usize,
+    concurrency: usize,
+    idempotent: bool,
+    load: F,
+) -> error::Result<Response<Vec<T>>>
+where
+    I: Clone,
+    F: Fn(Vec<I>) -> Fut,
+    Fut: Future<Output = error::Result<Response<Vec<T>>>>,
+{
+    use futures::{stream, StreamExt, TryStreamExt};
+
+    let gate = RateLimitGate::default();
+    let load_chunk = |chunk: Vec<I>| {
+        let gate = &gate;
+        let load = &load;
+        async move {
+            let mut attempt = 0;
+            loop {
+                gate.wait().await;
+                match load(chunk.clone()).await {
+                    Ok(resp) => return Ok(resp),
+                    Err(e) => match retry_reset(&e, idempotent, attempt) {
+                        Some(reset) if gate.try_retry() => {
+                            gate.hold_for(rate_limit_backoff(reset, attempt));
+                            attempt += 1;
+                        }
+                        _ => return Err(e),
+                    },
+                }
+            }
+        }
+    };
+
+    let chunks = items
+        .chunks(std::cmp::max(chunk_size, 1))
+        .map(|chunk| chunk.to_vec())
+        .collect::<Vec<_>>();
+    let responses = stream::iter(chunks)
+        .map(load_chunk)
+        .buffered(std::cmp::max(concurrency, 1))
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    let mut merged = Response::new(
+        RateLimit {
+            limit: -1,
+            remaining: -1,
+            reset: -1,
+        },
+        Vec::new(),
+    );
+    for resp in responses {
+        merged.rate_limit_status = resp.rate_limit_status;
+        merged.response.extend(resp.response);
+    }
+    Ok(merged)
+}
+
+///A limit on how often egg-mode will retry calls that failed, shared by every call that uses it.
+///
+///Functions in egg-mode that load many pages or send many requests, like
+///`Timeline::collect_up_to` or `direct::send_many`, retry a call when Twitter says the rate limit
+///has been reached or has trouble answering it. Across many concurrent tasks, these retries can
+///add up, which is unhelpful if Twitter is having trouble. A `RetryBudget` caps the total number
+///of retries in a given span of time: each retry spends one from the budget, and the budget
+///refills gradually, up to its original size. When the budget is used up, calls fail right away
+///with the error that would have been retried, instead of waiting and trying again.
+///
+///Hand a budget to `set_retry_budget` to apply it to every call in the process. By default,
+///retries are unlimited.
+#[derive(Debug)]
+pub struct RetryBudget {
+    bucket: Option<std::sync::Mutex<RetryBucket>>,
+}
+
+#[derive(Debug)]
+struct RetryBucket {
+    ///The most retries that can be saved up.
+    capacity: f64,
+    ///How many retries are added back each second.
+    refill_per_sec: f64,
+    ///How many retries are available right now.
+    available: f64,
+    ///When `available` was last brought up to date.
+    updated: std::time::Instant,
+}
+
+impl RetryBudget {
+    ///Creates a budget that allows `retries` retries in any span of time `per`, starting out
+    ///full.
+    ///
+    ///Unused retries aren't saved up past `retries`. If `retries` is zero, no calls are retried.
+    pub fn new(retries: u32, per: std::time::Duration) -> RetryBudget {
+        let secs = per.as_secs_f64();
+        RetryBudget {
+            bucket: Some(std::sync::Mutex::new(RetryBucket {
+                capacity: f64::from(retries),
+                refill_per_sec: if secs > 0.0 {
+                    f64::from(retries) / secs
+                } else {
+                    0.0
+                },
+                available: f64::from(retries),
+                updated: std::time::Instant::now(),
+            })),
+        }
+    }
+
+    ///Creates a budget that allows any number of retries. This is the default.
+    pub fn unlimited() -> RetryBudget {
+        RetryBudget { bucket: None }
+    }
+
+    ///Spends one retry from this budget, returning whether there was one to spend.
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_at(std::time::Instant::now())
+    }
+
+    fn try_acquire_at(&self, now: std::time::Instant) -> bool {
+        let mut bucket = match self.bucket {
+            Some(ref bucket) => bucket.lock().unwrap(),
+            None => return true,
+        };
+
+        if let Some(elapsed) = now.checked_duration_since(bucket.updated) {
+            bucket.available = f64::min(
+                bucket.capacity,
+                bucket.available + elapsed.as_secs_f64() * bucket.refill_per_sec,
+            );
+            bucket.updated = now;
+        }
+
+        if bucket.available >= 1.0 {
+            bucket.available -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref RETRY_BUDGET: std::sync::RwLock<std::sync::Arc<RetryBudget>> =
+        std::sync::RwLock::new(std::sync::Arc::new(RetryBudget::unlimited()));
+}
+
+// n.b. this function is re-exported at the crate root - these docs are public!
+///Sets the retry budget shared by every call made by this process.
+///
+///See [`RetryBudget`] for details. This replaces any budget that was set before, with its
+///retries starting out full.
+///
+///[`RetryBudget`]: struct.RetryBudget.html
+pub fn set_retry_budget(budget: RetryBudget) {
+    *RETRY_BUDGET.write().unwrap() = std::sync::Arc::new(budget);
+}
+
+///Returns the process-wide retry budget.
+pub(crate) fn retry_budget() -> std::sync::Arc<RetryBudget> {
+    RETRY_BUDGET.read().unwrap().clone()
+}
+
+fn rate_limit_backoff_from(reset: i32, now: i64, attempt: u32) -> std::time::Duration {
+    let remaining = i64::from(reset) - now;
+    let secs = if remaining > 0 {
+        remaining as u64 + 1
+    } else {
+        1u64.checked_shl(attempt).unwrap_or(MAX_RATE_LIMIT_WAIT)
+    };
+
+    std::time::Duration::from_secs(std::cmp::min(secs, MAX_RATE_LIMIT_WAIT))
+}
+
 pub fn codepoints_to_bytes(&mut (ref mut start, ref mut end): &mut (usize, usize), text: &str) {
     let mut byte_start = *start;
     let mut byte_end = *end;
@@ -484,29 +989,154 @@ where
     }
 }
 
-pub mod serde_datetime {
-    use serde::{Serializer, Deserialize, Deserializer};
-    use serde::de::Error;
-    use chrono::TimeZone;
+const TWITTER_DATE_FORMAT: &str = "%a %b %d %T %z %Y";
+
+/// Deserializes a timestamp in the format the v1.1 API uses, like `"Wed Aug 27 13:08:45 +0000
+/// 2008"`.
+pub fn deserialize_twitter_timestamp<'de, D>(
+    ser: D,
+) -> Result<chrono::DateTime<chrono::Utc>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Deserialize, Error};
 
-    const DATE_FORMAT: &'static str = "%a %b %d %T %z %Y";
+    let s = String::deserialize(ser)?;
+    chrono::DateTime::parse_from_str(&s, TWITTER_DATE_FORMAT)
+        .map(|date| date.with_timezone(&chrono::Utc))
+        .map_err(D::Error::custom)
+}
+
+/// Deserializes an ISO-8601 timestamp, like the `"2008-08-27T13:08:45.000Z"` the v2 API uses.
+///
+/// This also accepts the `"2008-08-27 13:08:45 +0000"` form that the Account Activity API uses for
+/// webhooks.
+pub fn deserialize_iso8601<'de, D>(ser: D) -> Result<chrono::DateTime<chrono::Utc>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Deserialize, Error};
+
+    let s = String::deserialize(ser)?;
+    parse_iso8601(&s).map_err(D::Error::custom)
+}
+
+/// Deserializes an optional ISO-8601 timestamp, as with `deserialize_iso8601`.
+pub fn deserialize_opt_iso8601<'de, D>(
+    ser: D,
+) -> Result<Option<chrono::DateTime<chrono::Utc>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Deserialize, Error};
+
+    match Option::<String>::deserialize(ser)? {
+        Some(s) => parse_iso8601(&s).map(Some).map_err(D::Error::custom),
+        None => Ok(None),
+    }
+}
+
+fn parse_iso8601(s: &str) -> chrono::ParseResult<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::parse_from_rfc3339(s)
+        .or_else(|e| chrono::DateTime::parse_from_str(s, "%Y-%m-%d %T %z").map_err(|_| e))
+        .map(|date| date.with_timezone(&chrono::Utc))
+}
+
+/// Joins the given field names into the comma-separated list the v2 API takes, leaving out
+/// repeats, or returns `None` if there aren't any.
+pub fn join_field_names(names: impl Iterator<Item = &'static str>) -> Option<String> {
+    let mut seen = HashSet::new();
+    let names = names.filter(|name| seen.insert(*name)).collect::<Vec<_>>();
+    if names.is_empty() {
+        None
+    } else {
+        Some(names.join(","))
+    }
+}
+
+/// Deserializes an optional numeric ID that the v2 API sends as a string, like `"2244994945"`.
+pub fn deserialize_opt_string_id<'de, D>(ser: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::{Deserialize, Error};
+
+    match Option::<String>::deserialize(ser)? {
+        Some(id) => id.parse().map(Some).map_err(D::Error::custom),
+        None => Ok(None),
+    }
+}
+
+/// The response from a version 2 lookup of tweets, users, Spaces, or lists.
+///
+/// `data` holds the objects that were loaded: a `Vec` from calls that load several, or an `Option`
+/// from calls that load just one. `I` is the type of the related objects loaded alongside them,
+/// like `tweet::v2::Includes` for tweets, and the objects in `data` are hydrated from it as they're
+/// loaded.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(
+    from = "RawLookup<T, I>",
+    bound(deserialize = "T: serde::Deserialize<'de> + Default + Hydrate<I>, \
+                         I: serde::Deserialize<'de> + Default")
+)]
+pub struct Lookup<T, I> {
+    /// The objects that were loaded.
+    pub data: T,
+    /// The related objects asked for with `Fields::expansions`, or the equivalent for that call.
+    pub includes: I,
+    /// The objects that couldn't be loaded, and why.
+    pub errors: Vec<Problem>,
+    /// For calls that load a list of results a page at a time, where this page falls in the list.
+    pub meta: PageMeta,
+}
+
+/// A `Lookup` as Twitter sends it, before its `data` is hydrated.
+#[derive(serde::Deserialize)]
+struct RawLookup<T, I> {
+    #[serde(default)]
+    data: T,
+    #[serde(default)]
+    includes: I,
+    #[serde(default)]
+    errors: Vec<Problem>,
+    #[serde(default)]
+    meta: PageMeta,
+}
+
+impl<T: Hydrate<I>, I> From<RawLookup<T, I>> for Lookup<T, I> {
+    fn from(raw: RawLookup<T, I>) -> Lookup<T, I> {
+        let mut data = raw.data;
+        data.hydrate(&raw.includes);
+        Lookup {
+            data,
+            includes: raw.includes,
+            errors: raw.errors,
+            meta: raw.meta,
+        }
+    }
+}
+
+impl<T, I> Paged for Lookup<T, I> {
+    fn next_token(&self) -> Option<String> {
+        self.meta.next_token.clone()
+    }
+}
+
+pub mod serde_datetime {
+    use serde::{Serializer, Deserializer};
 
     pub fn deserialize<'de, D>(ser: D) -> Result<chrono::DateTime<chrono::Utc>, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let s = String::deserialize(ser)?;
-        let date = (chrono::Utc)
-            .datetime_from_str(&s, DATE_FORMAT)
-            .map_err(|e| D::Error::custom(e))?;
-        Ok(date)
+        super::deserialize_twitter_timestamp(ser)
     }
 
     pub fn serialize<S>(src: &chrono::DateTime<chrono::Utc>, ser: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        ser.collect_str(&src.format(DATE_FORMAT))
+        ser.collect_str(&src.format(super::TWITTER_DATE_FORMAT))
     }
 }
 
@@ -566,6 +1196,48 @@ pub(crate) mod tests {
         content
     }
 
+    #[tokio::test]
+    async fn lookup_in_chunks() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let rate_limit = |remaining| RateLimit {
+            limit: 900,
+            remaining,
+            reset: 0,
+        };
+        let calls = AtomicUsize::new(0);
+        let rate_limited = AtomicUsize::new(0);
+
+        // the chunk starting at 100 hits the rate limit the first time it's loaded
+        let load = |chunk: Vec<u64>| {
+            let call = calls.fetch_add(1, Ordering::SeqCst);
+            let limited = chunk[0] == 100 && rate_limited.fetch_add(1, Ordering::SeqCst) == 0;
+            async move {
+                if limited {
+                    Err(error::Error::RateLimit(0))
+                } else {
+                    assert!(chunk.len() <= 100);
+                    Ok(Response::new(rate_limit(900 - call as i32), chunk))
+                }
+            }
+        };
+        let ids = (0..250).collect::<Vec<u64>>();
+        let resp = lookup_chunked(ids.clone(), 100, 2, true, load)
+            .await
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+        assert_eq!(resp.response, ids);
+        assert!(resp.rate_limit_status.remaining < 900);
+
+        let resp = lookup_chunked(Vec::<u64>::new(), 100, 2, true, |chunk| async move {
+            Ok(Response::new(rate_limit(900), chunk))
+        })
+        .await
+        .unwrap();
+        assert!(resp.response.is_empty());
+    }
+
     #[test]
     fn test_codepoints_to_bytes() {
         let unicode = "frônt Iñtërnâtiônàližætiøn ënd";
@@ -579,4 +1251,290 @@ pub(crate) mod tests {
         codepoints_to_bytes(&mut range, unicode);
         assert_eq!(&unicode[range.0..range.1], "Iñtërnâtiônàližætiøn ënd");
     }
+
+    #[test]
+    fn test_timestamp_formats() {
+        #[derive(serde::Deserialize)]
+        struct Both {
+            #[serde(deserialize_with = "deserialize_twitter_timestamp")]
+            v1: chrono::DateTime<chrono::Utc>,
+            #[serde(deserialize_with = "deserialize_iso8601")]
+            v2: chrono::DateTime<chrono::Utc>,
+            #[serde(deserialize_with = "deserialize_twitter_timestamp")]
+            v1_offset: chrono::DateTime<chrono::Utc>,
+            #[serde(deserialize_with = "deserialize_iso8601")]
+            v2_offset: chrono::DateTime<chrono::Utc>,
+            #[serde(deserialize_with = "deserialize_iso8601")]
+            webhook: chrono::DateTime<chrono::Utc>,
+            #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+            v2_missing: Option<chrono::DateTime<chrono::Utc>>,
+            #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+            v2_null: Option<chrono::DateTime<chrono::Utc>>,
+        }
+
+        let both: Both = serde_json::from_str(
+            r#"{
+                "v1": "Wed Aug 27 13:08:45 +0000 2008",
+                "v2": "2008-08-27T13:08:45.000Z",
+                "v1_offset": "Wed Aug 27 15:08:45 +0200 2008",
+                "v2_offset": "2008-08-27T08:08:45-05:00",
+                "webhook": "2008-08-27 13:08:45 +0000",
+                "v2_null": null
+            }"#,
+        )
+        .unwrap();
+
+        let expected = chrono::DateTime::parse_from_rfc3339("2008-08-27T13:08:45Z").unwrap();
+        assert_eq!(both.v1, expected);
+        assert_eq!(both.v2, expected);
+        assert_eq!(both.v1_offset, expected);
+        assert_eq!(both.v2_offset, expected);
+        assert_eq!(both.webhook, expected);
+        assert_eq!(both.v2_missing, None);
+        assert_eq!(both.v2_null, None);
+
+        assert!(serde_json::from_str::<Both>(
+            r#"{
+                "v1": "2008-08-27T13:08:45.000Z",
+                "v2": "2008-08-27T13:08:45.000Z",
+                "v1_offset": "Wed Aug 27 15:08:45 +0200 2008",
+                "v2_offset": "2008-08-27T08:08:45-05:00",
+                "webhook": "2008-08-27 13:08:45 +0000"
+            }"#,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_param_list_from_pairs() {
+        let pairs = vec![
+            ("screen_name".to_string(), "rustlang".to_string()),
+            ("count".to_string(), "200".to_string()),
+            ("include_rts".to_string(), "false".to_string()),
+        ];
+
+        let params = ParamList::from_pairs(pairs.clone());
+        assert_eq!(params.len(), 3);
+        for (k, v) in &pairs {
+            assert_eq!(params.get(k.as_str()).map(|v| v.as_ref()), Some(v.as_str()));
+        }
+
+        let collected: ParamList = pairs
+            .into_iter()
+            .chain(Some(("count".to_string(), "20".to_string())))
+            .collect();
+        assert_eq!(collected.len(), 3);
+        assert_eq!(collected.get("count").map(|v| v.as_ref()), Some("20"));
+    }
+
+    #[test]
+    fn params_render_in_stable_order() {
+        let render = |reversed: bool| {
+            let mut pairs = vec![
+                ("granularity", "city"),
+                ("lat", "37.78"),
+                ("long", "-122.4"),
+            ];
+            if reversed {
+                pairs.reverse();
+            }
+            let mut params = pairs.into_iter().collect::<ParamList>();
+            params.push_param("attribute:street_address", "795 Folsom St");
+            params.push_param("attribute:phone", "+1 415 555 0100");
+            params.push_param("attribute:phone", "+1 415 555 0199");
+            params.to_urlencoded()
+        };
+
+        let expected = render(false);
+        assert!(expected.starts_with("attribute%3Aphone=%2B1%20415%20555%200100&"));
+        assert!(expected.ends_with("&lat=37.78&long=-122.4"));
+        // each ParamList hashes its keys differently, so build a few to shake out any reordering
+        for i in 0..32 {
+            assert_eq!(render(i % 2 == 0), expected);
+        }
+    }
+
+    #[test]
+    fn replacing_repeated_params() {
+        let mut params = ParamList::new();
+        params.push_param("attribute:phone", "+1 415 555 0100");
+        params.push_param("attribute:phone", "+1 415 555 0199");
+        params.push_param("attribute:street_address", "795 Folsom St");
+
+        params.add_param_ref("attribute:phone", "+1 415 555 0123");
+        assert_eq!(
+            params.to_urlencoded(),
+            "attribute%3Aphone=%2B1%20415%20555%200123&\
+             attribute%3Astreet_address=795%20Folsom%20St"
+        );
+
+        params.push_param("attribute:phone", "+1 415 555 0199");
+        params.remove("attribute:phone");
+        assert_eq!(
+            params.to_urlencoded(),
+            "attribute%3Astreet_address=795%20Folsom%20St"
+        );
+
+        params.push_param("attribute:street_address", "1 Market St");
+        params.combine(ParamList::new().add_param("attribute:street_address", "2 Market St"));
+        assert_eq!(
+            params.to_urlencoded(),
+            "attribute%3Astreet_address=2%20Market%20St"
+        );
+    }
+
+    #[test]
+    fn repeated_params_accessors() {
+        let mut params = ParamList::new().add_param("granularity", "city");
+        params.push_param("attribute:phone", "+1 415 555 0100");
+        params.push_param("attribute:phone", "+1 415 555 0199");
+
+        assert_eq!(params.len(), 3);
+        assert!(params.contains_key("attribute:phone"));
+        assert_eq!(
+            params.get("attribute:phone").map(|v| v.as_ref()),
+            Some("+1 415 555 0100")
+        );
+        assert_eq!(
+            params.get_all("attribute:phone"),
+            ["+1 415 555 0100", "+1 415 555 0199"]
+        );
+        assert!(params.get_all("lat").is_empty());
+        assert_eq!(
+            params
+                .iter()
+                .map(|(k, v)| (k.as_ref(), v.as_ref()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("attribute:phone", "+1 415 555 0100"),
+                ("attribute:phone", "+1 415 555 0199"),
+                ("granularity", "city"),
+            ]
+        );
+    }
+
+    #[test]
+    fn retry_budget_refills() {
+        use std::time::{Duration, Instant};
+
+        let budget = RetryBudget::new(2, Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(budget.try_acquire_at(start));
+        assert!(budget.try_acquire_at(start));
+        assert!(!budget.try_acquire_at(start));
+        // one retry comes back every 30 seconds, up to the original two
+        assert!(!budget.try_acquire_at(start + Duration::from_secs(20)));
+        assert!(budget.try_acquire_at(start + Duration::from_secs(31)));
+        assert!(!budget.try_acquire_at(start + Duration::from_secs(31)));
+        assert!(budget.try_acquire_at(start + Duration::from_secs(600)));
+        assert!(budget.try_acquire_at(start + Duration::from_secs(600)));
+        assert!(!budget.try_acquire_at(start + Duration::from_secs(600)));
+
+        let none = RetryBudget::new(0, Duration::from_secs(60));
+        assert!(!none.try_acquire_at(start + Duration::from_secs(600)));
+
+        let unlimited = RetryBudget::unlimited();
+        assert!((0..1000).all(|_| unlimited.try_acquire()));
+    }
+
+    #[test]
+    fn retry_only_idempotent_timeouts() {
+        use crate::error::Error;
+        use crate::links;
+        use hyper::{Method, StatusCode};
+        use std::io;
+
+        let post_tweet = is_idempotent(&Method::POST, links::statuses::UPDATE);
+        let get_timeline = is_idempotent(&Method::GET, links::statuses::USER_TIMELINE);
+        assert!(!post_tweet);
+        assert!(get_timeline);
+        let lookup = format!("{}?id=20", links::statuses::LOOKUP);
+        assert!(is_idempotent(&Method::POST, &lookup));
+
+        let timeout = Error::IOError(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+        assert_eq!(retry_reset(&timeout, post_tweet, 0), None);
+        assert_eq!(retry_reset(&timeout, get_timeline, 0), Some(0));
+        let retried = MAX_TRANSIENT_RETRIES;
+        assert_eq!(retry_reset(&timeout, get_timeline, retried), None);
+
+        // a 429 or 5xx means twitter didn't get as far as posting anything
+        let too_many = Error::BadStatus(StatusCode::TOO_MANY_REQUESTS);
+        let unavailable = Error::BadStatus(StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(retry_reset(&Error::RateLimit(30), post_tweet, 5), Some(30));
+        assert_eq!(retry_reset(&too_many, post_tweet, 0), Some(0));
+        assert_eq!(retry_reset(&unavailable, post_tweet, 0), Some(0));
+        assert_eq!(retry_reset(&unavailable, post_tweet, retried), None);
+        let not_found = Error::BadStatus(StatusCode::NOT_FOUND);
+        assert_eq!(retry_reset(&not_found, get_timeline, 0), None);
+    }
+
+    #[test]
+    fn test_rate_limit_backoff() {
+        use std::time::Duration;
+
+        // waits until just after the reset time if it's in the future
+        assert_eq!(
+            rate_limit_backoff_from(1_000_030, 1_000_000, 0),
+            Duration::from_secs(31)
+        );
+        assert_eq!(
+            rate_limit_backoff_from(1_000_030, 1_000_000, 5),
+            Duration::from_secs(31)
+        );
+
+        // otherwise backs off exponentially, capped at a full window
+        assert_eq!(
+            rate_limit_backoff_from(1_000_000, 1_000_000, 0),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            rate_limit_backoff_from(1_000_000, 1_000_000, 3),
+            Duration::from_secs(8)
+        );
+        assert_eq!(
+            rate_limit_backoff_from(1_000_000, 1_000_000, 20),
+            Duration::from_secs(900)
+        );
+        assert_eq!(
+            rate_limit_backoff_from(1_000_000, 1_000_000, 200),
+            Duration::from_secs(900)
+        );
+        assert_eq!(
+            rate_limit_backoff_from(1_100_000, 1_000_000, 0),
+            Duration::from_secs(900)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_with_cancel() {
+        use futures::channel::oneshot;
+        use std::time::{Duration, Instant};
+
+        // a response body that never finishes arriving
+        let stalled = async {
+            let body =
+                hyper::Body::wrap_stream(futures::stream::pending::<std::io::Result<Vec<u8>>>());
+            Ok(hyper::body::to_bytes(body).await?.to_vec())
+        };
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        tokio::spawn(async move {
+            tokio::time::delay_for(Duration::from_millis(20)).await;
+            cancel.send(()).unwrap();
+        });
+
+        let start = Instant::now();
+        let result = with_cancel(stalled, cancelled).await;
+        assert!(matches!(result, Err(error::Error::Cancelled)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        // requests that finish first aren't affected
+        let result = with_cancel(async { Ok(5) }, futures::future::pending::<()>()).await;
+        assert_eq!(result.unwrap(), 5);
+
+        // dropping the sender counts as cancelling
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        drop(cancel);
+        let result = with_cancel(futures::future::pending::<error::Result<()>>(), cancelled).await;
+        assert!(matches!(result, Err(error::Error::Cancelled)));
+    }
 }
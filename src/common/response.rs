@@ -4,6 +4,7 @@
 use crate::error::Error::{self, *};
 use crate::error::{Result, TwitterErrors};
 
+use hyper::body::{Bytes, HttpBody};
 use hyper::client::ResponseFuture;
 use hyper::{self, Body, Request};
 #[cfg(feature = "hyper-rustls")]
@@ -14,6 +15,8 @@ use serde::{de::DeserializeOwned, Deserialize};
 use serde_json;
 
 use std::convert::TryFrom;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
 
 use super::Headers;
 
@@ -151,21 +154,91 @@ impl<T: Iterator> Iterator for ResponseIter<T> {
 // n.b. this function is re-exported in the `raw` module - these docs are public!
 /// Converts the given request into a raw `ResponseFuture` from hyper.
 pub fn get_response(request: Request<Body>) -> ResponseFuture {
-    let connector = HttpsConnector::new();
-    let client = hyper::Client::builder().build(connector);
-    client.request(request)
+    client_config().client_for(request.uri()).request(request)
 }
 
 // n.b. this function is re-exported in the `raw` module - these docs are public!
 /// Loads the given request, parses the headers and response for potential errors given by Twitter,
 /// and returns the headers and raw bytes returned from the response.
+///
+/// Redirects are followed up to the limit set with `set_max_redirects`. If a redirect goes to a
+/// different host, the `Authorization` header is left off of the redirected request, so your
+/// credentials are only ever sent to the host the request was made for.
 pub async fn raw_request(request: Request<Body>) -> Result<(Headers, Vec<u8>)> {
-    let connector = HttpsConnector::new();
-    let client = hyper::Client::builder().build(connector);
-    let resp = client.request(request).await?;
+    raw_request_with_problem(request, |_, _| None).await
+}
+
+/// Loads the given request like `raw_request`, but first gives `problem` a chance to turn an
+/// unsuccessful response into a more specific error than `BadStatus`.
+///
+/// Version 2 endpoints describe why a request failed in the body of the response, instead of with
+/// the error codes `TwitterError` carries, so this is how they can tell one failure apart from
+/// another.
+async fn raw_request_with_problem<P>(
+    request: Request<Body>,
+    problem: P,
+) -> Result<(Headers, Vec<u8>)>
+where
+    P: FnOnce(hyper::StatusCode, &[u8]) -> Option<Error>,
+{
+    #[cfg(feature = "tracing")]
+    {
+        use tracing::Instrument;
+        let span = request_span(request.method(), request.uri());
+        send_request(request, problem).instrument(span).await
+    }
+    #[cfg(not(feature = "tracing"))]
+    {
+        send_request(request, problem).await
+    }
+}
+
+/// Sends the given request for `raw_request`, following redirects and checking the response for
+/// errors.
+async fn send_request<P>(request: Request<Body>, problem: P) -> Result<(Headers, Vec<u8>)>
+where
+    P: FnOnce(hyper::StatusCode, &[u8]) -> Option<Error>,
+{
+    send_request_with(&client_config(), request, problem).await
+}
+
+/// Sends the given request like `send_request`, using the given clients and their settings.
+async fn send_request_with<P>(
+    clients: &Clients,
+    request: Request<Body>,
+    problem: P,
+) -> Result<(Headers, Vec<u8>)>
+where
+    P: FnOnce(hyper::StatusCode, &[u8]) -> Option<Error>,
+{
+    let config = &clients.config;
+    let client = clients.client_for(request.uri());
+
+    // the body is buffered up front so it can be sent again if the request is redirected
+    let (parts, body) = request.into_parts();
+    let mut request = Request::from_parts(parts, hyper::body::to_bytes(body).await?);
+    let mut redirects = 0;
+    let resp = loop {
+        let resp = client.request(copy_request(&request)).await?;
+        if redirects >= config.max_redirects {
+            break resp;
+        }
+        match redirect_request(&request, resp.status(), resp.headers()) {
+            Some(next) => {
+                request = next;
+                redirects += 1;
+            }
+            None => break resp,
+        }
+    };
     let (parts, body) = resp.into_parts();
-    let body: Vec<_> = hyper::body::to_bytes(body).await?.to_vec();
-    if let Ok(errors) = serde_json::from_slice::<TwitterErrors>(&body) {
+    #[cfg(feature = "tracing")]
+    record_response(&tracing::Span::current(), parts.status, &parts.headers);
+    let body = read_body(body, config.max_response_size).await?;
+    if let Some(err) = html_error(parts.status, &parts.headers, &body) {
+        return Err(err);
+    }
+    if let Ok(errors) = parse_json::<TwitterErrors>(&body) {
         if errors.errors.iter().any(|e| e.code == 88)
             && parts.headers.contains_key(X_RATE_LIMIT_RESET)
         {
@@ -175,11 +248,395 @@ pub async fn raw_request(request: Request<Body>) -> Result<(Headers, Vec<u8>)> {
         }
     }
     if !parts.status.is_success() {
-        return Err(BadStatus(parts.status));
+        return Err(problem(parts.status, &body).unwrap_or(BadStatus(parts.status)));
     }
     Ok((parts.headers, body))
 }
 
+/// Creates the span that `raw_request` runs in when the `tracing` feature is enabled.
+///
+/// The query string is left out of the recorded endpoint, since it can carry user data.
+#[cfg(feature = "tracing")]
+fn request_span(method: &hyper::Method, uri: &hyper::Uri) -> tracing::Span {
+    let endpoint = match (uri.scheme_str(), uri.host()) {
+        (Some(scheme), Some(host)) => match uri.port_u16() {
+            Some(port) => format!("{}://{}:{}{}", scheme, host, port, uri.path()),
+            None => format!("{}://{}{}", scheme, host, uri.path()),
+        },
+        _ => uri.path().to_string(),
+    };
+    tracing::info_span!(
+        "twitter_request",
+        method = %method,
+        endpoint = %endpoint,
+        status = tracing::field::Empty,
+        rate_limit_remaining = tracing::field::Empty,
+    )
+}
+
+/// Records the status and remaining rate limit of a response on the given request span.
+#[cfg(feature = "tracing")]
+fn record_response(span: &tracing::Span, status: hyper::StatusCode, headers: &Headers) {
+    span.record("status", status.as_u16());
+    if let Ok(Some(remaining)) = rate_limit_remaining(headers) {
+        span.record("rate_limit_remaining", remaining);
+    }
+}
+
+// n.b. this function is re-exported at the crate root and in the `raw` module - these docs are
+// public!
+/// Sets the number of redirects egg-mode will follow for a single request.
+///
+/// This is a shortcut for changing `ClientConfig::max_redirects` in the settings given to
+/// `set_client_config`, keeping the rest of them as they are. See that method for details.
+pub fn set_max_redirects(limit: usize) {
+    update_client_config(|config| config.max_redirects = limit);
+}
+
+// n.b. this function is re-exported at the crate root and in the `raw` module - these docs are
+// public!
+/// Returns the number of redirects egg-mode will follow for a single request.
+///
+/// See `ClientConfig::max_redirects` for details.
+pub fn max_redirects() -> usize {
+    client_config().config.max_redirects
+}
+
+// n.b. this type is re-exported at the crate root and in the `raw` module - these docs are public!
+/// Settings for the HTTP client that egg-mode sends requests with.
+///
+/// By default, egg-mode leaves the HTTP version up to the TLS connection to negotiate. Depending
+/// on the TLS backend, this may never pick HTTP/2, even when the server supports it. If you'd
+/// rather send every request (or every request to certain hosts) over HTTP/2, so that concurrent
+/// requests can share a connection, build a `ClientConfig` and hand it to `set_client_config`:
+///
+/// ```
+/// use egg_mode::ClientConfig;
+///
+/// egg_mode::set_client_config(ClientConfig::new().http2_prior_knowledge("api.twitter.com"));
+/// ```
+///
+/// These settings start HTTP/2 connections with "prior knowledge", skipping negotiation entirely.
+/// If the requests go through a proxy that doesn't speak HTTP/2, like some TLS-intercepting
+/// corporate proxies, those requests will fail instead of falling back to HTTP/1.1. If that
+/// happens, go back to the default settings with `set_client_config(ClientConfig::new())`.
+///
+/// `ClientConfig` also sets how large of a response egg-mode will load, with `max_response_size`,
+/// and how many redirects it will follow, with `max_redirects`.
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    http2_only: bool,
+    http2_hosts: Vec<String>,
+    max_response_size: usize,
+    max_redirects: usize,
+}
+
+/// The default value for `ClientConfig::max_response_size`: 50 MiB.
+const DEFAULT_MAX_RESPONSE_SIZE: usize = 50 * 1024 * 1024;
+
+/// The default value for `ClientConfig::max_redirects`.
+const DEFAULT_MAX_REDIRECTS: usize = 5;
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            http2_only: false,
+            http2_hosts: Vec::new(),
+            max_response_size: DEFAULT_MAX_RESPONSE_SIZE,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+        }
+    }
+}
+
+impl ClientConfig {
+    /// Creates a new `ClientConfig` with the default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the largest response body, in bytes, that egg-mode will load from Twitter.
+    ///
+    /// If a response is larger than this, egg-mode stops reading it and returns
+    /// `Error::ResponseTooLarge` instead, so that a misbehaving server can't make it buffer an
+    /// unbounded amount of data. The default limit is 50 MiB, which is far larger than anything
+    /// Twitter is expected to send.
+    ///
+    /// This limit doesn't apply to the `stream` module, which reads its messages as they arrive.
+    pub fn max_response_size(self, limit: usize) -> Self {
+        ClientConfig {
+            max_response_size: limit,
+            ..self
+        }
+    }
+
+    /// Sets the number of redirects egg-mode will follow for a single request.
+    ///
+    /// If a response redirects more times than this, the last redirect response is treated like
+    /// any other response, which usually means returning `Error::BadStatus`. Setting this to zero
+    /// turns off redirects entirely. The default is 5.
+    ///
+    /// Whatever the limit is, the `Authorization` header is never sent along when a redirect goes
+    /// to a different host than the original request.
+    pub fn max_redirects(self, limit: usize) -> Self {
+        ClientConfig {
+            max_redirects: limit,
+            ..self
+        }
+    }
+
+    /// Sets whether to send every request over HTTP/2, without negotiating the HTTP version first.
+    /// The default is `false`.
+    pub fn http2_only(self, http2_only: bool) -> Self {
+        ClientConfig { http2_only, ..self }
+    }
+
+    /// Sends requests to the given host over HTTP/2, without negotiating the HTTP version first.
+    ///
+    /// This can be called several times to add more hosts. Hosts are matched without regard to
+    /// case, and don't include a port.
+    pub fn http2_prior_knowledge(mut self, host: impl Into<String>) -> Self {
+        self.http2_hosts.push(host.into().to_ascii_lowercase());
+        self
+    }
+
+    /// Returns whether requests to the given URI should start out speaking HTTP/2.
+    fn uses_http2(&self, uri: &hyper::Uri) -> bool {
+        if self.http2_only {
+            return true;
+        }
+        match uri.host() {
+            Some(host) => {
+                let host = host.to_ascii_lowercase();
+                self.http2_hosts.contains(&host)
+            }
+            None => false,
+        }
+    }
+}
+
+type HttpsClient = hyper::Client<HttpsConnector<hyper::client::HttpConnector>>;
+
+/// The HTTP clients built from a `ClientConfig`, kept with it so that every request made with the
+/// same settings shares the same connection pools.
+struct Clients {
+    config: ClientConfig,
+    http1: HttpsClient,
+    http2: HttpsClient,
+}
+
+impl From<ClientConfig> for Clients {
+    fn from(config: ClientConfig) -> Clients {
+        Clients {
+            config,
+            http1: hyper::Client::builder().build(HttpsConnector::new()),
+            http2: hyper::Client::builder()
+                .http2_only(true)
+                .build(HttpsConnector::new()),
+        }
+    }
+}
+
+impl Clients {
+    /// Returns the client to send a request to the given URI with.
+    fn client_for(&self, uri: &hyper::Uri) -> &HttpsClient {
+        if self.config.uses_http2(uri) {
+            &self.http2
+        } else {
+            &self.http1
+        }
+    }
+}
+
+lazy_static::lazy_static! {
+    static ref CLIENT_CONFIG: RwLock<Arc<Clients>> =
+        RwLock::new(Arc::new(ClientConfig::new().into()));
+}
+
+// n.b. this function is re-exported at the crate root and in the `raw` module - these docs are
+// public!
+/// Sets the HTTP client settings used by every request made by this process.
+///
+/// See [`ClientConfig`] for details. Requests that have already started keep the settings they
+/// started with. Every request sent with the same settings reuses the same connections, so calling
+/// this starts over with new ones.
+///
+/// [`ClientConfig`]: struct.ClientConfig.html
+pub fn set_client_config(config: ClientConfig) {
+    *CLIENT_CONFIG.write().unwrap() = Arc::new(config.into());
+}
+
+/// Returns the HTTP client settings set with `set_client_config`, along with the clients built
+/// from them.
+fn client_config() -> Arc<Clients> {
+    CLIENT_CONFIG.read().unwrap().clone()
+}
+
+/// Changes the HTTP client settings used by every request, for the setters that only change one
+/// setting.
+fn update_client_config(update: impl FnOnce(&mut ClientConfig)) {
+    let mut current = CLIENT_CONFIG.write().unwrap();
+    let mut config = current.config.clone();
+    update(&mut config);
+    // the clients don't depend on these settings, so they (and their connections) can be kept
+    *current = Arc::new(Clients {
+        config,
+        http1: current.http1.clone(),
+        http2: current.http2.clone(),
+    });
+}
+
+/// Makes a copy of the given buffered request that can be sent with hyper.
+fn copy_request(request: &Request<Bytes>) -> Request<Body> {
+    let mut copy = Request::new(Body::from(request.body().clone()));
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    copy
+}
+
+/// If the given response is a redirect, returns the request to make to follow it.
+///
+/// `303 See Other` responses, and `301`/`302` responses to a `POST`, are followed with a `GET` and
+/// no body, as browsers do; other redirects repeat the original request at the new location. If
+/// the new location is on a different host, the `Authorization` header is removed.
+fn redirect_request(
+    request: &Request<Bytes>,
+    status: hyper::StatusCode,
+    headers: &Headers,
+) -> Option<Request<Bytes>> {
+    use hyper::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, HOST, LOCATION};
+    use hyper::{Method, StatusCode, Uri};
+
+    if !status.is_redirection() || status == StatusCode::NOT_MODIFIED {
+        return None;
+    }
+
+    let base = url::Url::parse(&request.uri().to_string()).ok()?;
+    let location = base.join(headers.get(LOCATION)?.to_str().ok()?).ok()?;
+    let uri = location.as_str().parse::<Uri>().ok()?;
+
+    let as_get = status == StatusCode::SEE_OTHER
+        || (request.method() == Method::POST
+            && (status == StatusCode::MOVED_PERMANENTLY || status == StatusCode::FOUND));
+
+    let mut next = if as_get {
+        let mut next = Request::new(Bytes::new());
+        *next.method_mut() = Method::GET;
+        next
+    } else {
+        let mut next = Request::new(request.body().clone());
+        *next.method_mut() = request.method().clone();
+        next
+    };
+    *next.uri_mut() = uri;
+    *next.version_mut() = request.version();
+    *next.headers_mut() = request.headers().clone();
+
+    let headers = next.headers_mut();
+    headers.remove(HOST);
+    if as_get {
+        headers.remove(CONTENT_LENGTH);
+        headers.remove(CONTENT_TYPE);
+    }
+    let same_host = base.scheme() == location.scheme()
+        && base.host_str() == location.host_str()
+        && base.port_or_known_default() == location.port_or_known_default();
+    if !same_host {
+        headers.remove(AUTHORIZATION);
+    }
+
+    Some(next)
+}
+
+/// The most characters of an HTML page to keep in `UpstreamHtml`.
+const HTML_SNIPPET_LEN: usize = 200;
+
+/// Checks whether the given response is an HTML page rather than the JSON that Twitter's API
+/// returns, and if so, returns an `UpstreamHtml` error describing it.
+///
+/// A response counts as HTML if its body starts like an HTML page, and its `Content-Type` either
+/// says it's `text/html` or is missing. The `Content-Type` isn't enough on its own, since Twitter
+/// labels the urlencoded bodies it returns from `oauth/request_token` and `oauth/access_token` as
+/// HTML, along with the plain-text and XML bodies of the `401`s those endpoints send. Those need
+/// to come back as the usual `BadStatus` or `TwitterError`, so that failed sign-ins can be told
+/// apart from Twitter being down.
+fn html_error(status: hyper::StatusCode, headers: &Headers, body: &[u8]) -> Option<Error> {
+    let content_type = headers
+        .get(hyper::header::CONTENT_TYPE)
+        .and_then(|ct| ct.to_str().ok());
+    let labelled = match content_type {
+        Some(ct) => ct.to_ascii_lowercase().starts_with("text/html"),
+        None => true,
+    };
+    if !labelled || !looks_like_html(body) {
+        return None;
+    }
+
+    let text = String::from_utf8_lossy(body);
+    let snippet = text
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(HTML_SNIPPET_LEN)
+        .collect();
+    Some(UpstreamHtml { status, snippet })
+}
+
+/// Returns whether the given body starts with a doctype or tag that begins an HTML page.
+fn looks_like_html(body: &[u8]) -> bool {
+    let start = match body.iter().position(|b| !b.is_ascii_whitespace()) {
+        Some(start) => &body[start..],
+        None => return false,
+    };
+    let start = start[..start.len().min(16)].to_ascii_lowercase();
+    ["<!doctype html", "<html", "<head", "<body"]
+        .iter()
+        .any(|tag| start.starts_with(tag.as_bytes()))
+}
+
+// n.b. this function is re-exported at the crate root and in the `raw` module - these docs are
+// public!
+/// Sets the largest response body, in bytes, that egg-mode will load from Twitter.
+///
+/// This is a shortcut for changing `ClientConfig::max_response_size` in the settings given to
+/// `set_client_config`, keeping the rest of them as they are. See that method for details.
+pub fn set_max_response_size(limit: usize) {
+    update_client_config(|config| config.max_response_size = limit);
+}
+
+// n.b. this function is re-exported at the crate root and in the `raw` module - these docs are
+// public!
+/// Returns the largest response body, in bytes, that egg-mode will load from Twitter.
+///
+/// See `ClientConfig::max_response_size` for details.
+pub fn max_response_size() -> usize {
+    client_config().config.max_response_size
+}
+
+/// Reads the given response body into memory, failing with `ResponseTooLarge` as soon as it's
+/// clear the body is larger than `limit` bytes.
+async fn read_body(mut body: Body, limit: usize) -> Result<Vec<u8>> {
+    let too_large = || ResponseTooLarge { limit };
+
+    // if the server told us how big the body is, we can bail out before reading any of it
+    let size_hint = body.size_hint();
+    if size_hint.lower() > limit as u64 {
+        return Err(too_large());
+    }
+
+    let mut bytes = Vec::with_capacity(std::cmp::min(size_hint.lower() as usize, limit));
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
 // n.b. this function is re-exported in the `raw` module - these docs are public!
 /// Loads the given request and discards the response body after parsing it for rate-limit and
 /// error information, returning the rate-limit information from the headers.
@@ -192,14 +649,49 @@ pub async fn request_with_empty_response(request: Request<Body>) -> Result<Respo
     })
 }
 
+/// Parses a response body as JSON.
+///
+/// Every response body egg-mode reads goes through here, so that the JSON parser can be swapped
+/// out without touching the `Deserialize` types or the request functions themselves. By default
+/// this uses `serde_json`; with the `simd-json` feature, it uses `simd-json` instead.
+#[cfg(not(feature = "simd-json"))]
+fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Result<T> {
+    Ok(serde_json::from_slice(body)?)
+}
+
+/// Parses a response body as JSON, with `simd-json`.
+///
+/// Errors from `simd-json` are turned into `serde_json` errors, so that they're returned as
+/// `DeserializeError` either way.
+#[cfg(feature = "simd-json")]
+fn parse_json<T: DeserializeOwned>(body: &[u8]) -> Result<T> {
+    // simd-json parses in place, so it needs its own copy of the body
+    let mut body = body.to_vec();
+    simd_json::serde::from_slice(&mut body)
+        .map_err(|e| <serde_json::Error as serde::de::Error>::custom(e).into())
+}
+
 // n.b. this function is re-exported in the `raw` module - these docs are public!
 /// Loads the given request and parses the response as JSON into the given type, including
 /// rate-limit headers.
 pub async fn request_with_json_response<T: DeserializeOwned>(
     request: Request<Body>,
 ) -> Result<Response<T>> {
-    let (headers, body) = raw_request(request).await?;
-    let response = serde_json::from_slice(&body)?;
+    request_with_json_problem(request, |_, _| None).await
+}
+
+/// Loads the given request like `request_with_json_response`, letting `problem` turn an
+/// unsuccessful response into a more specific error first, like `raw_request_with_problem`.
+pub(crate) async fn request_with_json_problem<T, P>(
+    request: Request<Body>,
+    problem: P,
+) -> Result<Response<T>>
+where
+    T: DeserializeOwned,
+    P: FnOnce(hyper::StatusCode, &[u8]) -> Option<Error>,
+{
+    let (headers, body) = raw_request_with_problem(request, problem).await?;
+    let response = parse_json(&body)?;
     let rate_limit_status = RateLimit::try_from(&headers)?;
     Ok(Response {
         rate_limit_status,
@@ -207,6 +699,52 @@ pub async fn request_with_json_response<T: DeserializeOwned>(
     })
 }
 
+// n.b. this function is re-exported at the crate root and in the `raw` module - these docs are
+// public!
+/// Runs the given request until it completes or the given `cancel` future resolves, whichever
+/// happens first.
+///
+/// This can wrap any of the functions or builder methods in egg-mode that return a `Future`, as
+/// well as the request functions in the `raw` module. If `cancel` resolves first, the request is
+/// dropped (which closes its connection to Twitter) and this function returns
+/// `Error::Cancelled`. Whatever value `cancel` resolves to is ignored, so it can be a
+/// `oneshot::Receiver`, a timer, a server's "client disconnected" signal, or anything else.
+///
+/// Since the value is ignored, a `oneshot::Receiver` cancels the request as soon as its `Sender`
+/// is *dropped*, too, not just when something is sent with it: the receiver resolves with
+/// `Err(Canceled)` either way. Keep the sender alive for as long as the request should be allowed
+/// to run.
+///
+/// ```rust,no_run
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let token: egg_mode::Token = unimplemented!();
+/// use futures::channel::oneshot;
+///
+/// let (cancel, cancelled) = oneshot::channel::<()>();
+/// // hand `cancel` off to whatever should be able to abort the request, and keep it around until
+/// // the request is done - dropping it cancels the request
+/// # let _cancel = cancel;
+/// match egg_mode::with_cancel(egg_mode::tweet::show(20, &token), cancelled).await {
+///     Ok(tweet) => println!("{}", tweet.text),
+///     Err(egg_mode::error::Error::Cancelled) => println!("never mind, then"),
+///     Err(e) => println!("error: {}", e),
+/// }
+/// # }
+/// ```
+pub async fn with_cancel<F, T, C>(request: F, cancel: C) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+    C: Future,
+{
+    futures::pin_mut!(request);
+    futures::pin_mut!(cancel);
+    match futures::future::select(request, cancel).await {
+        futures::future::Either::Left((output, _)) => output,
+        futures::future::Either::Right(_) => Err(Cancelled),
+    }
+}
+
 // n.b. this type is exported at the crate root - these docs are public!
 /// Rate limit information returned with a `Response`.
 ///
@@ -245,3 +783,496 @@ impl TryFrom<&Headers> for RateLimit {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{html_error, read_body, redirect_request, send_request_with, ClientConfig};
+    use crate::error::Error;
+    use hyper::body::Bytes;
+    use hyper::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, LOCATION};
+    use hyper::{Body, Method, Request, StatusCode};
+
+    fn signed_request(method: Method, uri: &str) -> Request<Bytes> {
+        let mut request = Request::new(Bytes::from_static(b"status=hello"));
+        *request.method_mut() = method;
+        *request.uri_mut() = uri.parse().unwrap();
+        request.headers_mut().insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("OAuth oauth_signature=\"secret\""),
+        );
+        request
+    }
+
+    fn redirect_to(location: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(LOCATION, HeaderValue::from_static(location));
+        headers
+    }
+
+    #[test]
+    fn same_host_redirect() {
+        let request = signed_request(
+            Method::GET,
+            "https://api.twitter.com/1.1/old/path.json?id=1",
+        );
+
+        let next = redirect_request(
+            &request,
+            StatusCode::TEMPORARY_REDIRECT,
+            &redirect_to("/1.1/new/path.json?id=1"),
+        )
+        .unwrap();
+        assert_eq!(next.uri(), "https://api.twitter.com/1.1/new/path.json?id=1");
+        assert_eq!(next.method(), Method::GET);
+        assert!(next.headers().contains_key(AUTHORIZATION));
+
+        // a 303 turns a POST into a GET, without its body
+        let request = signed_request(Method::POST, "https://upload.twitter.com/1.1/media.json");
+        let next = redirect_request(
+            &request,
+            StatusCode::SEE_OTHER,
+            &redirect_to("https://upload.twitter.com/1.1/media/status.json"),
+        )
+        .unwrap();
+        assert_eq!(next.method(), Method::GET);
+        assert!(next.body().is_empty());
+        assert!(next.headers().contains_key(AUTHORIZATION));
+
+        // ...but a 307 repeats it as-is
+        let next = redirect_request(
+            &request,
+            StatusCode::TEMPORARY_REDIRECT,
+            &redirect_to("https://upload.twitter.com/1.1/media2.json"),
+        )
+        .unwrap();
+        assert_eq!(next.method(), Method::POST);
+        assert_eq!(next.body(), &Bytes::from_static(b"status=hello"));
+
+        // not every 3xx status is a redirect
+        assert!(redirect_request(&request, StatusCode::NOT_MODIFIED, &redirect_to("/")).is_none());
+        assert!(redirect_request(&request, StatusCode::OK, &redirect_to("/")).is_none());
+    }
+
+    #[test]
+    fn cross_host_redirect() {
+        let request = signed_request(Method::GET, "https://api.twitter.com/1.1/media/1234.jpg");
+
+        let next = redirect_request(
+            &request,
+            StatusCode::FOUND,
+            &redirect_to("https://pbs.twimg.com/media/1234.jpg"),
+        )
+        .unwrap();
+        assert_eq!(next.uri(), "https://pbs.twimg.com/media/1234.jpg");
+        assert!(!next.headers().contains_key(AUTHORIZATION));
+
+        // a change of scheme or port counts as a different host too
+        for &location in &[
+            "http://api.twitter.com/1.1/media/1234.jpg",
+            "https://api.twitter.com:8443/1.1/media/1234.jpg",
+        ] {
+            let next =
+                redirect_request(&request, StatusCode::FOUND, &redirect_to(location)).unwrap();
+            assert!(!next.headers().contains_key(AUTHORIZATION));
+        }
+    }
+
+    #[test]
+    fn html_error_page() {
+        let page = b"<!DOCTYPE html>\n<html>\n  <head><title>Twitter / Over capacity</title></head>\n  <body>Something is technically wrong.</body>\n</html>\n";
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        match html_error(StatusCode::SERVICE_UNAVAILABLE, &headers, page) {
+            Some(Error::UpstreamHtml { status, snippet }) => {
+                assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+                assert!(snippet.starts_with("<!DOCTYPE html> <html> <head><title>Twitter / Over"));
+            }
+            other => panic!("expected UpstreamHtml, got {:?}", other),
+        }
+
+        // without a content type, the body itself gives it away
+        let err = html_error(StatusCode::SERVICE_UNAVAILABLE, &HeaderMap::new(), page);
+        assert!(matches!(err, Some(Error::UpstreamHtml { .. })));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/json;charset=utf-8"),
+        );
+        let body = br#"{"errors":[{"code":130,"message":"Over capacity"}]}"#;
+        assert!(html_error(StatusCode::SERVICE_UNAVAILABLE, &headers, body).is_none());
+        assert!(html_error(StatusCode::SERVICE_UNAVAILABLE, &HeaderMap::new(), body).is_none());
+    }
+
+    #[test]
+    fn html_content_type_success() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+
+        // the OAuth token endpoints send urlencoded bodies labelled as HTML
+        let body = b"oauth_token=token&oauth_token_secret=secret&oauth_callback_confirmed=true";
+        assert!(html_error(StatusCode::OK, &headers, body).is_none());
+
+        // ...but an actual page is still caught, even with a successful status
+        let page = b"\n<html><body>Something is technically wrong.</body></html>";
+        let err = html_error(StatusCode::OK, &headers, page);
+        assert!(matches!(err, Some(Error::UpstreamHtml { .. })));
+    }
+
+    #[test]
+    fn html_content_type_unauthorized() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+
+        // the OAuth endpoints send their 401s labelled as HTML too, with plain-text or XML bodies
+        let text = b"Failed to validate oauth signature and token";
+        assert!(html_error(StatusCode::UNAUTHORIZED, &headers, text).is_none());
+        let xml = br#"<?xml version="1.0" encoding="UTF-8"?>
+<hash><error>Client application is not permitted to use xAuth.</error></hash>"#;
+        assert!(html_error(StatusCode::UNAUTHORIZED, &headers, xml).is_none());
+        assert!(html_error(StatusCode::UNAUTHORIZED, &headers, b"").is_none());
+    }
+
+    #[cfg(feature = "simd-json")]
+    #[test]
+    fn simd_json_matches_serde_json() {
+        use super::parse_json;
+        use crate::common::tests::load_file;
+        use serde::de::DeserializeOwned;
+
+        fn both<T: DeserializeOwned + std::fmt::Debug>(path: &str) {
+            let body = load_file(path);
+            let simd = parse_json::<T>(body.as_bytes()).unwrap();
+            let serde = serde_json::from_str::<T>(&body).unwrap();
+            assert_eq!(format!("{:?}", simd), format!("{:?}", serde), "{}", path);
+        }
+
+        both::<Vec<crate::tweet::Tweet>>("sample_payloads/tweet_array.json");
+        both::<crate::tweet::Tweet>("sample_payloads/sample-quote.json");
+        both::<Vec<crate::user::TwitterUser>>("sample_payloads/user_array.json");
+        both::<serde_json::Value>("sample_payloads/webhooks.json");
+
+        // parse errors still come back as `DeserializeError`
+        let err = parse_json::<crate::tweet::Tweet>(b"{\"id\": ").unwrap_err();
+        assert!(matches!(err, Error::DeserializeError(_)));
+    }
+
+    #[tokio::test]
+    async fn body_size_limit() {
+        // a streamed body doesn't announce its size, so it has to be caught while reading
+        let chunks = (0..4).map(|_| Ok::<_, std::io::Error>(vec![b'x'; 10]));
+        let body = Body::wrap_stream(futures::stream::iter(chunks));
+        let err = read_body(body, 25).await.unwrap_err();
+        assert!(matches!(err, Error::ResponseTooLarge { limit: 25 }));
+
+        // a body with a known length is rejected up front
+        let err = read_body(Body::from(vec![b'x'; 40]), 25).await.unwrap_err();
+        assert!(matches!(err, Error::ResponseTooLarge { limit: 25 }));
+
+        let chunks = (0..4).map(|_| Ok::<_, std::io::Error>(vec![b'x'; 10]));
+        let body = Body::wrap_stream(futures::stream::iter(chunks));
+        assert_eq!(read_body(body, 40).await.unwrap(), vec![b'x'; 40]);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn request_span_fields() {
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        type Fields = Arc<Mutex<Vec<(String, String)>>>;
+
+        struct Capture(Fields);
+
+        impl Visit for Capture {
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                let entry = (field.name().to_string(), format!("{:?}", value));
+                self.0.lock().unwrap().push(entry);
+            }
+        }
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+            fn new_span(&self, span: &Attributes) -> Id {
+                let name = span.metadata().name().to_string();
+                self.0.lock().unwrap().push(("span".to_string(), name));
+                span.record(&mut Capture(self.0.clone()));
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, values: &Record) {
+                values.record(&mut Capture(self.0.clone()));
+            }
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, _: &Event) {}
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let fields = Fields::default();
+        let request = signed_request(
+            Method::GET,
+            "https://api.twitter.com/1.1/statuses/show.json?id=20&secret=hunter2",
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Rate-Limit-Remaining", HeaderValue::from_static("899"));
+
+        tracing::subscriber::with_default(Capture(fields.clone()), || {
+            let span = super::request_span(request.method(), request.uri());
+            super::record_response(&span, StatusCode::OK, &headers);
+        });
+
+        let fields = fields.lock().unwrap();
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(field, _)| field == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(field("span"), Some("twitter_request"));
+        assert_eq!(field("method"), Some("GET"));
+        assert_eq!(
+            field("endpoint"),
+            Some("https://api.twitter.com/1.1/statuses/show.json")
+        );
+        assert_eq!(field("status"), Some("200"));
+        assert_eq!(field("rate_limit_remaining"), Some("899"));
+        assert!(fields
+            .iter()
+            .all(|(_, value)| !value.contains("hunter2") && !value.contains("OAuth")));
+    }
+
+    #[test]
+    fn http2_hosts() {
+        let api = "https://api.twitter.com/1.1/help/configuration.json";
+        let api = api.parse().unwrap();
+        let upload = "https://upload.twitter.com/1.1/media/upload.json";
+        let upload = upload.parse().unwrap();
+
+        let config = ClientConfig::new().http2_prior_knowledge("API.twitter.com");
+        assert!(config.uses_http2(&api));
+        assert!(!config.uses_http2(&upload));
+        assert!(!ClientConfig::new().uses_http2(&api));
+        assert!(ClientConfig::new().http2_only(true).uses_http2(&upload));
+    }
+
+    #[tokio::test]
+    async fn http2_only_request() {
+        use hyper::service::{make_service_fn, service_fn};
+
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        // a server that only speaks HTTP/2, and says which version each request came in with
+        static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+        let make_svc = make_service_fn(|_| {
+            CONNECTIONS.fetch_add(1, Ordering::SeqCst);
+            async {
+                Ok::<_, hyper::Error>(service_fn(|req: Request<Body>| async move {
+                    let body = format!("{{\"version\":\"{:?}\"}}", req.version());
+                    Ok::<_, hyper::Error>(hyper::Response::new(Body::from(body)))
+                }))
+            }
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into())
+            .http2_only(true)
+            .serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        let request = || {
+            Request::get(format!("http://{}/1.1/help/configuration.json", addr))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let clients = ClientConfig::new().http2_only(true).into();
+        for _ in 0..3 {
+            let (_, body) = send_request_with(&clients, request(), |_, _| None)
+                .await
+                .unwrap();
+            assert_eq!(body, br#"{"version":"HTTP/2.0"}"#.to_vec());
+        }
+        // the requests all went over the same connection
+        assert_eq!(CONNECTIONS.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn config_response_size() {
+        use hyper::service::{make_service_fn, service_fn};
+
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|_: Request<Body>| async move {
+                Ok::<_, hyper::Error>(hyper::Response::new(Body::from(vec![b' '; 100])))
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        let request = || {
+            Request::get(format!("http://{}/1.1/help/configuration.json", addr))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        // the limit comes from the config the request is sent with
+        let config = ClientConfig::new().max_response_size(50);
+        let err = send_request_with(&config.into(), request(), |_, _| None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::ResponseTooLarge { limit: 50 }));
+
+        let (_, body) = send_request_with(&ClientConfig::new().into(), request(), |_, _| None)
+            .await
+            .unwrap();
+        assert_eq!(body.len(), 100);
+    }
+
+    #[tokio::test]
+    async fn config_redirect_limit() {
+        use hyper::service::{make_service_fn, service_fn};
+
+        // a server that sends `/start` on to `/end`
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|req: Request<Body>| async move {
+                let resp = if req.uri().path() == "/start" {
+                    hyper::Response::builder()
+                        .status(StatusCode::FOUND)
+                        .header(LOCATION, "/end")
+                        .body(Body::empty())
+                } else {
+                    hyper::Response::builder().body(Body::from("{}"))
+                };
+                Ok::<_, hyper::Error>(resp.unwrap())
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        let request = || {
+            Request::get(format!("http://{}/start", addr))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let config = ClientConfig::new().max_redirects(0);
+        let err = send_request_with(&config.into(), request(), |_, _| None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BadStatus(StatusCode::FOUND)));
+
+        let (_, body) = send_request_with(&ClientConfig::new().into(), request(), |_, _| None)
+            .await
+            .unwrap();
+        assert_eq!(body, b"{}".to_vec());
+    }
+
+    #[tokio::test]
+    async fn urlencoded_token_response() {
+        use hyper::service::{make_service_fn, service_fn};
+
+        // a stand-in for `oauth/request_token`, which labels its urlencoded body as HTML
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|_: Request<Body>| async move {
+                let body = "oauth_token=token&oauth_token_secret=secret";
+                let resp = hyper::Response::builder()
+                    .header(CONTENT_TYPE, "text/html; charset=utf-8")
+                    .body(Body::from(body))
+                    .unwrap();
+                Ok::<_, hyper::Error>(resp)
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let request = Request::post(format!("http://{}/oauth/request_token", addr))
+            .body(Body::empty())
+            .unwrap();
+        let (_, body) = send_request_with(&ClientConfig::new().into(), request, |_, _| None)
+            .await
+            .unwrap();
+        assert_eq!(
+            body,
+            b"oauth_token=token&oauth_token_secret=secret".to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn unauthorized_token_response() {
+        use hyper::service::{make_service_fn, service_fn};
+
+        // a stand-in for `oauth/access_token` turning down a bad signature
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|_: Request<Body>| async move {
+                let resp = hyper::Response::builder()
+                    .status(StatusCode::UNAUTHORIZED)
+                    .header(CONTENT_TYPE, "text/html; charset=utf-8")
+                    .body(Body::from("Failed to validate oauth signature and token"))
+                    .unwrap();
+                Ok::<_, hyper::Error>(resp)
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let request = Request::post(format!("http://{}/oauth/access_token", addr))
+            .body(Body::empty())
+            .unwrap();
+        let err = send_request_with(&ClientConfig::new().into(), request, |_, _| None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BadStatus(StatusCode::UNAUTHORIZED)));
+    }
+
+    #[tokio::test]
+    async fn problem_response() {
+        use hyper::service::{make_service_fn, service_fn};
+
+        // a version 2 endpoint that explains its refusals in the body
+        let make_svc = make_service_fn(|_| async {
+            Ok::<_, hyper::Error>(service_fn(|_: Request<Body>| async move {
+                let resp = hyper::Response::builder()
+                    .status(StatusCode::FORBIDDEN)
+                    .header(CONTENT_TYPE, "application/problem+json")
+                    .body(Body::from(r#"{"title":"Forbidden","type":"about:blank"}"#))
+                    .unwrap();
+                Ok::<_, hyper::Error>(resp)
+            }))
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        let request = || {
+            Request::post(format!("http://{}/2/tweets", addr))
+                .body(Body::empty())
+                .unwrap()
+        };
+
+        let err = send_request_with(&ClientConfig::new().into(), request(), |status, body| {
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert!(body.starts_with(b"{\"title\":\"Forbidden\""));
+            Some(Error::SuperFollowsNotAllowed)
+        })
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::SuperFollowsNotAllowed));
+
+        let err = send_request_with(&ClientConfig::new().into(), request(), |_, _| None)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::BadStatus(StatusCode::FORBIDDEN)));
+    }
+}
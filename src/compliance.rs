@@ -0,0 +1,380 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Structs and methods for running batch compliance jobs.
+//!
+//! Apps that store tweets or users are required to keep them in line with what their authors have
+//! done since: deleting tweets that were deleted, hiding tweets that were made protected, and so
+//! on. Batch compliance jobs check a large list of tweet or user IDs at once, through version 2 of
+//! the Twitter API. Running one takes a few steps:
+//!
+//! 1. Create the job with `create_job`. This needs a Bearer token.
+//! 2. Upload the IDs to check with `ComplianceJob::upload`, one ID per line.
+//! 3. Poll the job with `show_job` until its `status` is `JobStatus::Complete`. This can take a
+//!    while, depending on how many IDs were uploaded.
+//! 4. Load the results with `ComplianceJob::download`, which yields a `ComplianceResult` for each
+//!    ID that needs action taken on it. IDs that don't need any action are left out.
+//!
+//! ```rust,no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let token: egg_mode::Token = unimplemented!();
+//! # let stored_ids: Vec<u64> = vec![];
+//! use egg_mode::compliance::{self, JobKind, JobStatus};
+//! use futures::TryStreamExt;
+//!
+//! let job = compliance::create_job(JobKind::Tweets, Some("nightly"), &token).await.unwrap();
+//! job.upload(stored_ids).await.unwrap();
+//!
+//! let job = loop {
+//!     let job = compliance::show_job(job.id, &token).await.unwrap().response;
+//!     match job.status {
+//!         JobStatus::Created | JobStatus::InProgress => {
+//!             tokio::time::delay_for(std::time::Duration::from_secs(60)).await
+//!         }
+//!         _ => break job,
+//!     }
+//! };
+//!
+//! let mut results = Box::pin(job.download());
+//! while let Some(result) = results.try_next().await.unwrap() {
+//!     println!("{} needs a {}: {}", result.id, result.action, result.reason);
+//! }
+//! # }
+//! ```
+//!
+//! The upload and download URLs are pre-signed by Twitter, so they don't need a token, but they
+//! expire after a while: the upload URL after 15 minutes, and the download URL after a week. The
+//! times are given in `upload_expires_at` and `download_expires_at`.
+
+use std::fmt::Write;
+
+use futures::stream::{self, Stream, StreamExt};
+use hyper::body::Bytes;
+use hyper::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::raw::RequestBuilder;
+use crate::common::*;
+use crate::error::{Error, Result};
+use crate::{auth, links};
+
+/// The kinds of IDs a compliance job can check.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum JobKind {
+    /// The job checks tweet IDs.
+    #[serde(rename = "tweets")]
+    Tweets,
+    /// The job checks user IDs.
+    #[serde(rename = "users")]
+    Users,
+}
+
+impl JobKind {
+    /// Returns the name Twitter uses for this kind of job in a request.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobKind::Tweets => "tweets",
+            JobKind::Users => "users",
+        }
+    }
+}
+
+/// How far along a compliance job is.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum JobStatus {
+    /// The job has been created, and is waiting for its IDs to be uploaded.
+    #[serde(rename = "created")]
+    Created,
+    /// The IDs have been uploaded, and the job is running.
+    #[serde(rename = "in_progress")]
+    InProgress,
+    /// The job is done, and its results can be downloaded.
+    #[serde(rename = "complete")]
+    Complete,
+    /// The job failed.
+    #[serde(rename = "failed")]
+    Failed,
+    /// The job was never started, or its results were never downloaded, before its URLs expired.
+    #[serde(rename = "expired")]
+    Expired,
+}
+
+impl JobStatus {
+    /// Returns the name Twitter uses for this status in a request.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JobStatus::Created => "created",
+            JobStatus::InProgress => "in_progress",
+            JobStatus::Complete => "complete",
+            JobStatus::Failed => "failed",
+            JobStatus::Expired => "expired",
+        }
+    }
+}
+
+/// Represents a single batch compliance job.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ComplianceJob {
+    /// Numeric ID for this job.
+    #[serde(with = "serde_via_string")]
+    pub id: u64,
+    /// Whether this job checks tweet IDs or user IDs.
+    #[serde(rename = "type")]
+    pub kind: JobKind,
+    /// The name given to this job when it was created, if any.
+    pub name: Option<String>,
+    /// Whether the upload URL accepts resumable uploads.
+    #[serde(default)]
+    pub resumable: bool,
+    /// How far along this job is.
+    pub status: JobStatus,
+    /// When this job was created.
+    #[serde(deserialize_with = "deserialize_iso8601")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// The pre-signed URL to upload this job's IDs to.
+    pub upload_url: String,
+    /// When `upload_url` stops working.
+    #[serde(deserialize_with = "deserialize_iso8601")]
+    pub upload_expires_at: chrono::DateTime<chrono::Utc>,
+    /// The pre-signed URL to download this job's results from.
+    pub download_url: String,
+    /// When `download_url` stops working.
+    #[serde(deserialize_with = "deserialize_iso8601")]
+    pub download_expires_at: chrono::DateTime<chrono::Utc>,
+    /// If this job failed, a description of what went wrong.
+    pub error: Option<String>,
+}
+
+impl ComplianceJob {
+    /// Uploads the given tweet or user IDs for this job to check.
+    ///
+    /// The IDs are sent as a file with one ID per line, as Twitter expects. Once the upload
+    /// finishes, the job starts on its own. A job can check up to 500,000 IDs per upload, and the
+    /// upload needs to happen before `upload_expires_at`.
+    ///
+    /// The whole file is put together in memory before it's sent, since the upload URL needs to
+    /// know its size up front. At up to 20 digits per ID, a full upload takes about 10MB. If
+    /// `ids` has more than 500,000 IDs, this returns `Error::TooManyComplianceIds` without
+    /// uploading anything, and stops reading `ids` once it's gone past the limit.
+    pub async fn upload<I: IntoIterator<Item = u64>>(&self, ids: I) -> Result<()> {
+        let file = ids_file(ids)?;
+        let req = RequestBuilder::new(Method::PUT, &self.upload_url)
+            .with_body(file, "text/plain")
+            .request_unauthenticated();
+        request_with_empty_response(req).await?;
+        Ok(())
+    }
+
+    /// Downloads the results of this job, as a `Stream` of the IDs that need action taken on
+    /// them.
+    ///
+    /// The results file is read a line at a time as it comes in, rather than loaded all at once,
+    /// so even the results of a large job don't need to fit in memory. If the download fails or a
+    /// line can't be parsed, the error is yielded; a failed download also ends the stream.
+    pub fn download(&self) -> impl Stream<Item = Result<ComplianceResult>> {
+        let req = RequestBuilder::new(Method::GET, &self.download_url).request_unauthenticated();
+        let body = async move {
+            let resp = get_response(req).await?;
+            if resp.status().is_success() {
+                Ok(resp.into_body())
+            } else {
+                Err(Error::BadStatus(resp.status()))
+            }
+        };
+        stream::once(body)
+            .map(|body| match body {
+                Ok(body) => json_lines(body).left_stream(),
+                Err(e) => stream::once(async { Err(e) }).right_stream(),
+            })
+            .flatten()
+    }
+}
+
+/// The most IDs Twitter accepts in one compliance job upload.
+const MAX_UPLOAD_IDS: usize = 500_000;
+
+/// Writes the given IDs out one per line, as long as there aren't more than `MAX_UPLOAD_IDS`.
+fn ids_file<I: IntoIterator<Item = u64>>(ids: I) -> Result<String> {
+    let mut file = String::new();
+    for (count, id) in ids.into_iter().enumerate() {
+        if count == MAX_UPLOAD_IDS {
+            return Err(Error::TooManyComplianceIds);
+        }
+        writeln!(file, "{}", id).unwrap();
+    }
+    Ok(file)
+}
+
+/// A tweet or user from a compliance job that needs action taken on it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ComplianceResult {
+    /// The ID of the tweet or user.
+    #[serde(with = "serde_via_string")]
+    pub id: u64,
+    /// What needs to be done with the stored tweet or user, like `delete` or `scrub_geo`.
+    pub action: String,
+    /// Why it needs to be done, like `deleted`, `protected`, or `suspended`.
+    pub reason: String,
+    /// When the tweet or user was created.
+    #[serde(deserialize_with = "deserialize_iso8601")]
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// When the tweet or user was changed or removed, if Twitter knows.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub redacted_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// The response from `POST` or `GET 2/compliance/jobs`.
+#[derive(Deserialize)]
+struct JobData<T> {
+    data: T,
+}
+
+/// Create a new batch compliance job, to check tweet or user IDs.
+///
+/// `token` must be a Bearer token. `name` is optional, and only there to tell jobs apart when
+/// listing them with `list_jobs`.
+pub async fn create_job(
+    kind: JobKind,
+    name: Option<&str>,
+    token: &auth::Token,
+) -> Result<Response<ComplianceJob>> {
+    let body = serde_json::json!({ "type": kind, "name": name });
+    let req = post_json(links::compliance::JOBS, token, body);
+    let resp = request_with_json_response::<JobData<ComplianceJob>>(req).await?;
+    Ok(Response::map(resp, |job| job.data))
+}
+
+/// Lookup a single batch compliance job by ID, to check its status.
+///
+/// `token` must be a Bearer token.
+pub async fn show_job(id: u64, token: &auth::Token) -> Result<Response<ComplianceJob>> {
+    let url = format!("{}/{}", links::compliance::JOBS, id);
+    let req = get(&url, token, None);
+    let resp = request_with_json_response::<JobData<ComplianceJob>>(req).await?;
+    Ok(Response::map(resp, |job| job.data))
+}
+
+/// Lookup the recent batch compliance jobs of the given kind, optionally only those with the
+/// given status.
+///
+/// `token` must be a Bearer token.
+pub async fn list_jobs(
+    kind: JobKind,
+    status: Option<JobStatus>,
+    token: &auth::Token,
+) -> Result<Response<Vec<ComplianceJob>>> {
+    let params = ParamList::new()
+        .add_param("type", kind.as_str())
+        .add_opt_param("status", status.map(JobStatus::as_str));
+    let req = get(links::compliance::JOBS, token, Some(&params));
+    let resp = request_with_json_response::<JobData<Option<Vec<ComplianceJob>>>>(req).await?;
+    Ok(Response::map(resp, |jobs| jobs.data.unwrap_or_default()))
+}
+
+/// Parses each line of the given body as a JSON value, as the body comes in.
+///
+/// Blank lines are skipped, and the last line doesn't need a line ending. If the body fails, the
+/// error is yielded and the stream ends.
+fn json_lines<T, S>(body: S) -> impl Stream<Item = Result<T>>
+where
+    T: serde::de::DeserializeOwned,
+    S: Stream<Item = std::result::Result<Bytes, hyper::Error>> + Unpin,
+{
+    let parse = |line: &[u8]| Ok(serde_json::from_slice(line)?);
+    let blank = |line: &[u8]| line.iter().all(u8::is_ascii_whitespace);
+
+    stream::unfold(Some((body, Vec::new())), move |state| async move {
+        let (mut body, mut buf) = state?;
+        loop {
+            if let Some(pos) = buf.iter().position(|&b| b == b'\n') {
+                let line = buf.drain(..=pos).collect::<Vec<_>>();
+                if !blank(&line) {
+                    return Some((parse(&line), Some((body, buf))));
+                }
+                continue;
+            }
+
+            match body.next().await {
+                Some(Ok(chunk)) => buf.extend_from_slice(&chunk),
+                Some(Err(e)) => return Some((Err(e.into()), None)),
+                None if blank(&buf) => return None,
+                None => return Some((parse(&buf), None)),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::load_file;
+
+    #[test]
+    fn parse_job() {
+        let sample = load_file("sample_payloads/v2-compliance-job.json");
+        let job: JobData<ComplianceJob> = serde_json::from_str(&sample).unwrap();
+        let job = job.data;
+
+        assert_eq!(job.id, 1392536840465268736);
+        assert_eq!(job.kind, JobKind::Tweets);
+        assert_eq!(job.name.as_deref(), Some("nightly"));
+        assert_eq!(job.status, JobStatus::Created);
+        assert!(!job.resumable);
+        assert!(job
+            .upload_url
+            .starts_with("https://storage.googleapis.com/"));
+        assert_eq!(job.upload_expires_at.timestamp(), 1620853233);
+        assert!(job.error.is_none());
+
+        let empty: JobData<Option<Vec<ComplianceJob>>> = serde_json::from_str("{}").unwrap();
+        assert!(empty.data.is_none());
+    }
+
+    #[test]
+    fn upload_file() {
+        assert_eq!(ids_file(vec![1, 20, 300]).unwrap(), "1\n20\n300\n");
+        assert_eq!(ids_file(None).unwrap(), "");
+
+        let full = ids_file(0..MAX_UPLOAD_IDS as u64).unwrap();
+        assert_eq!(full.lines().count(), MAX_UPLOAD_IDS);
+        // the IDs past the limit are never read
+        let too_many = (0..).inspect(|&id| assert!(id <= MAX_UPLOAD_IDS as u64));
+        let err = ids_file(too_many).unwrap_err();
+        assert!(matches!(err, Error::TooManyComplianceIds));
+    }
+
+    #[tokio::test]
+    async fn download_lines() {
+        use futures::TryStreamExt;
+
+        // split the file mid-line, and leave off the last line ending
+        let chunks = vec![
+            Ok(Bytes::from(
+                r#"{"id":"1392536753425838080","action":"delete","created_at":"2021-05-12T20:45:12.000Z","redacted_at":"2021-05-12T20:46:29.000Z","#,
+            )),
+            Ok(Bytes::from("\"reason\":\"deleted\"}\n\n")),
+            Ok(Bytes::from(
+                r#"{"id":"1392536753425838081","action":"scrub_geo","created_at":"2021-05-12T20:45:13.000Z","reason":"scrub_geo"}"#,
+            )),
+        ];
+        let results = json_lines::<ComplianceResult, _>(stream::iter(chunks))
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].id, 1392536753425838080);
+        assert_eq!(results[0].reason, "deleted");
+        assert!(results[0].redacted_at.is_some());
+        assert_eq!(results[1].action, "scrub_geo");
+        assert!(results[1].redacted_at.is_none());
+
+        let line = stream::iter(vec![Ok(Bytes::from("not json\n"))]);
+        let results = json_lines::<ComplianceResult, _>(line)
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(results[..], [Err(Error::DeserializeError(_))]));
+    }
+}
@@ -102,3 +102,33 @@ pub async fn indicate_typing(
     let req = post(links::direct::INDICATE_TYPING, token, Some(&params));
     request_with_empty_response(req).await
 }
+
+/// Send a batch of direct messages, one after another, reporting how each one went.
+///
+/// The returned `Stream` yields each recipient alongside the result of sending their message, in
+/// the order they were given. A failure for one recipient doesn't stop the batch: the stream moves
+/// on to the next message either way.
+///
+/// Messages are sent one at a time. If Twitter reports that the DM rate limit has been reached,
+/// this pauses until the limit resets and then tries the same message again, rather than
+/// reporting the rate limit as a failure for that recipient. It also pauses ahead of time if a
+/// response says there are no calls left in the current window. Retries are drawn from the
+/// budget set with [`set_retry_budget`]; once it runs out, the rate-limit error is reported for
+/// that recipient instead. Server errors are retried the same way, but a timeout or dropped
+/// connection is reported as a failure, since the message may have been sent anyway.
+///
+/// [`set_retry_budget`]: ../fn.set_retry_budget.html
+///
+/// If a recipient doesn't accept DMs from the authenticated user (for example, because they don't
+/// follow them), their result will be `Error::DirectMessageNotAllowed`, so that those recipients
+/// can be picked out from other failures.
+pub fn send_many(
+    messages: impl IntoIterator<Item = (UserID, String)>,
+    token: &auth::Token,
+) -> impl Stream<Item = (UserID, Result<DirectMessage, error::Error>)> {
+    let token = token.clone();
+    send_paced(messages.into_iter(), move |recipient, text| {
+        let token = token.clone();
+        async move { DraftMessage::new(text, recipient).send(&token).await }
+    })
+}
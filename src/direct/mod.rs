@@ -591,3 +591,107 @@ impl DraftMessage {
         Ok(Response::into(resp))
     }
 }
+
+/// Sends the given messages one by one with `send`, yielding each recipient with the result of
+/// sending their message.
+///
+/// Rate-limit errors hold a shared gate until the limit resets and retry the same message, and a
+/// successful response with no calls remaining holds the gate until its window resets. Server
+/// errors are retried too, but timeouts and dropped connections aren't, since the message may
+/// have been sent before the connection was lost. Twitter's error code 349 is reported as
+/// `Error::DirectMessageNotAllowed`; every other error is reported as-is for that recipient, and
+/// the next message is sent regardless.
+fn send_paced<I, T, F, Fut>(
+    messages: I,
+    send: F,
+) -> impl Stream<Item = (UserID, Result<T, error::Error>)>
+where
+    I: Iterator<Item = (UserID, String)>,
+    F: FnMut(UserID, String) -> Fut,
+    Fut: Future<Output = Result<Response<T>, error::Error>>,
+{
+    // sending a DM twice sends two DMs, so a lost connection can't be retried
+    let idempotent = is_idempotent(&hyper::Method::POST, links::direct::SEND);
+    let state = (messages, send, RateLimitGate::default());
+    stream::unfold(state, move |(mut messages, mut send, gate)| async move {
+        let (recipient, text) = messages.next()?;
+        let mut attempt = 0;
+        let result = loop {
+            gate.wait().await;
+            match send(recipient.clone(), text.clone()).await {
+                Ok(resp) => {
+                    if resp.rate_limit_status.remaining == 0 {
+                        gate.hold_for(rate_limit_backoff(resp.rate_limit_status.reset, 0));
+                    }
+                    break Ok(resp.response);
+                }
+                Err(error::Error::TwitterError(_, ref errors))
+                    if errors.errors.iter().any(|e| e.code == 349) =>
+                {
+                    break Err(error::Error::DirectMessageNotAllowed);
+                }
+                Err(e) => match retry_reset(&e, idempotent, attempt) {
+                    Some(reset) if gate.try_retry() => {
+                        gate.hold_for(rate_limit_backoff(reset, attempt));
+                        attempt += 1;
+                    }
+                    _ => break Err(e),
+                },
+            }
+        };
+        Some(((recipient, result), (messages, send, gate)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::error::{TwitterErrorCode, TwitterErrors};
+
+    #[tokio::test]
+    async fn send_many_reports_each_recipient() {
+        let rate_limit = RateLimit {
+            limit: 1000,
+            remaining: 900,
+            reset: 0,
+        };
+        let messages = vec![
+            (UserID::ID(1), "hello".to_string()),
+            (UserID::ID(2), "hello".to_string()),
+            (UserID::ScreenName("three".into()), "hello".to_string()),
+        ];
+
+        let results = send_paced(messages.into_iter(), |recipient, text| {
+            let result = match recipient {
+                UserID::ID(2) => Err(error::Error::TwitterError(
+                    hyper::HeaderMap::new(),
+                    TwitterErrors {
+                        errors: vec![TwitterErrorCode {
+                            message: "You cannot send messages to this user.".into(),
+                            code: 349,
+                        }],
+                    },
+                )),
+                _ => Ok(Response::new(rate_limit, text)),
+            };
+            async move { result }
+        })
+        .collect::<Vec<_>>()
+        .await;
+
+        assert_eq!(results.len(), 3);
+        match &results[0] {
+            (UserID::ID(1), Ok(text)) => assert_eq!(text, "hello"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match &results[1] {
+            (UserID::ID(2), Err(error::Error::DirectMessageNotAllowed)) => (),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match &results[2] {
+            (UserID::ScreenName(name), Ok(_)) => assert_eq!(name, "three"),
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -42,6 +42,9 @@
 //! - `display_url`: This is a truncated version of `expanded_url`, meant to be displayed inline
 //!   with the parent text. This is useful to show users where the link resolves to, without
 //!   potentially filling up a lot of space with the fullly expanded URL.
+
+use std::convert::TryFrom;
+
 use mime;
 use serde::{Serialize, Deserialize, Deserializer};
 
@@ -110,6 +113,41 @@ pub struct MediaEntity {
     pub ext_alt_text: Option<String>,
 }
 
+impl MediaEntity {
+    ///Returns the name and dimensions of the smallest size variant of this media that's at least
+    ///`min_width` pixels wide, or of the largest one if none of them are that wide. Returns `None`
+    ///if none of the size variants were given a width.
+    ///
+    ///The name can be given to `url_for_size` to get a link to that version of the media. Note that
+    ///the `thumb` size may be cropped, so it's only picked if it's the smallest size that's wide
+    ///enough.
+    pub fn best_size(&self, min_width: u32) -> Option<(&'static str, &MediaSize)> {
+        let width = |size: &MediaSize| u32::try_from(size.w).unwrap_or(0);
+        let sizes = [
+            ("thumb", &self.sizes.thumb),
+            ("small", &self.sizes.small),
+            ("medium", &self.sizes.medium),
+            ("large", &self.sizes.large),
+        ];
+        let sizes = sizes.iter().filter(|(_, size)| width(size) > 0);
+
+        sizes
+            .clone()
+            .filter(|(_, size)| width(size) >= min_width)
+            .min_by_key(|(_, size)| width(size))
+            .or_else(|| sizes.max_by_key(|(_, size)| width(size)))
+            .map(|&(name, size)| (name, size))
+    }
+
+    ///Returns a link to the given size variant of this media, like `"small"` or `"large"`.
+    ///
+    ///This appends the size to `media_url_https` as described above. The size isn't checked, so
+    ///make sure to use one of the names from `MediaSizes`, or one returned by `best_size`.
+    pub fn url_for_size(&self, size: &str) -> String {
+        format!("{}:{}", self.media_url_https, size)
+    }
+}
+
 ///Represents the types of media that can be attached to a tweet.
 #[derive(Debug, Copy, Clone, Deserialize, Serialize)]
 pub enum MediaType {
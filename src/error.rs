@@ -88,6 +88,11 @@ pub struct MediaError {
 }
 
 /// A set of errors that can occur when interacting with Twitter.
+///
+/// Variants that wrap an error from another library (or a `TwitterErrors` from Twitter itself)
+/// only describe what went wrong at a high level in their `Display` output. The wrapped error is
+/// available from `std::error::Error::source`, so error-reporting tools that walk the chain of
+/// sources will print both.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     ///A URL was passed to a shortcut function that didn't match the method being called.
@@ -111,9 +116,10 @@ pub enum Error {
     #[error("Future has already completed")]
     FutureAlreadyCompleted,
     ///The response from Twitter returned an error structure instead of the expected response. The
-    ///enclosed value was the response from Twitter.
-    #[error("Errors returned by Twitter: {_1}")]
-    TwitterError(Headers, TwitterErrors),
+    ///enclosed value was the response from Twitter, and is also available as the error's
+    ///`source()`.
+    #[error("Errors returned by Twitter")]
+    TwitterError(Headers, #[source] TwitterErrors),
     ///The response returned from Twitter contained an error indicating that the rate limit for
     ///that method has been reached. The enclosed value is the Unix timestamp in UTC when the next
     ///rate-limit window will open.
@@ -121,7 +127,7 @@ pub enum Error {
     RateLimit(i32),
     ///An attempt to upload a video or gif successfully uploaded the file, but failed in
     ///post-processing. The enclosed value contains the error message from Twitter.
-    #[error("Error processing media: {}", _0)]
+    #[error("Error processing media")]
     MediaError(#[from] MediaError),
     ///The response from Twitter gave a response code that indicated an error. The enclosed value
     ///was the response code.
@@ -132,42 +138,164 @@ pub enum Error {
     ///[TwitterErrors]: struct.TwitterErrors.html
     #[error("Error status received: {}", _0)]
     BadStatus(hyper::StatusCode),
+    ///Twitter responded with an HTML page instead of JSON, like the ones shown during maintenance
+    ///or by a proxy in front of Twitter's servers.
+    ///
+    ///This means something went wrong in the infrastructure between you and the API, rather than
+    ///with the request itself, so it's usually worth retrying the request later.
+    #[error("HTML page received with status {status}")]
+    UpstreamHtml {
+        ///The response code the page was sent with.
+        status: hyper::StatusCode,
+        ///The beginning of the page's text, with its whitespace collapsed, to help tell what the
+        ///page was.
+        snippet: String,
+    },
+    ///A call to `auth::xauth` was rejected with a `401 Unauthorized` status.
+    ///
+    ///Twitter only allows xAuth for apps that have been specifically granted access to it, and
+    ///returns this status for every xAuth request from any other app. It will also return this
+    ///status if the given username and password were incorrect. If your app has not been approved
+    ///for xAuth, use the PIN-based or web-based sign-in flows described in the `auth` module
+    ///instead.
+    #[error("xAuth request was rejected: the app may not be permitted to use xAuth, or the username and password were incorrect")]
+    XAuthUnauthorized,
+    ///The user declined to let the app access their account, when signing in through
+    ///`auth::LocalRedirect`.
+    #[error("The user declined to authorize the app")]
+    AuthorizationDenied,
+    ///A direct message sent with `direct::send_many` was rejected because the recipient doesn't
+    ///accept messages from the authenticated user, usually because they don't follow them.
+    ///
+    ///This corresponds to Twitter's error code 349. `send_many` reports it in place of the
+    ///`TwitterError` it would otherwise be, so that it can be told apart from other failures.
+    #[error("The recipient does not accept direct messages from this account")]
+    DirectMessageNotAllowed,
+    ///A tweet sent with `DraftTweet::for_super_followers` was rejected because the authenticated
+    ///account can't post tweets for Super Followers.
+    ///
+    ///Only accounts that have been accepted into Twitter's Super Follows program can post these
+    ///tweets. `DraftTweet::send` and `DraftTweetV2::send` report this in place of the `BadStatus`
+    ///it would otherwise be, when Twitter's response says this is why the tweet was rejected.
+    #[error("This account is not permitted to post tweets for Super Followers")]
+    SuperFollowsNotAllowed,
+    ///A draft tweet's poll was outside the limits Twitter places on polls, so it wasn't sent. The
+    ///enclosed value describes which limit was broken.
+    ///
+    ///Twitter allows two to four options of 1 to 25 characters each, open for between 5 minutes
+    ///and 7 days, and doesn't allow a poll on a tweet with media attached.
+    #[error("Invalid poll: {}", _0)]
+    InvalidPoll(&'static str),
+    ///A draft tweet tagged users in its media in a way Twitter doesn't allow, so it wasn't sent.
+    ///The enclosed value describes what was wrong.
+    ///
+    ///Up to 10 users can be tagged, and only in photos: the draft needs to have media attached, and
+    ///none of it can be a GIF or video.
+    #[error("Invalid media tags: {}", _0)]
+    InvalidMediaTags(&'static str),
+    ///`ComplianceJob::upload` was given more than the 500,000 IDs Twitter allows in one compliance
+    ///job, so nothing was uploaded.
+    #[error("Too many IDs for one compliance job upload")]
+    TooManyComplianceIds,
+    ///A request was wrapped with `with_cancel`, and its cancellation signal resolved before the
+    ///request finished.
+    #[error("Request was cancelled")]
+    Cancelled,
+    ///A v2 stream received no data, not even a keep-alive line, for the enclosed length of time,
+    ///so the connection was dropped. Open a new stream to reconnect.
+    #[error("No data received from the stream for {:?}", _0)]
+    StreamStalled(std::time::Duration),
+    ///The response from Twitter was larger than the limit set with
+    ///`ClientConfig::max_response_size`, so egg-mode stopped reading it. The enclosed value is the
+    ///limit that was in place, in bytes.
+    #[error("Response was larger than the limit of {limit} bytes")]
+    ResponseTooLarge {
+        ///The maximum response size that was in place when the response was read.
+        limit: usize,
+    },
     ///The web request experienced an error. The enclosed error was returned from hyper.
-    #[error("Network error: {}", _0)]
+    #[error("Network error")]
     NetError(#[from] hyper::error::Error),
     ///The `native_tls` implementation returned an error. The enclosed error was returned from
     ///`native_tls`.
     #[cfg(feature = "native_tls")]
-    #[error("TLS error: {}", _0)]
+    #[error("TLS error")]
     TlsError(#[from] native_tls::Error),
     ///An error was experienced while processing the response stream. The enclosed error was
     ///returned from libstd.
-    #[error("IO error: {}", _0)]
+    #[error("IO error")]
     IOError(#[from] std::io::Error),
     ///An error occurred while loading the JSON response. The enclosed error was returned from
     ///`serde_json`.
-    #[error("JSON deserialize error: {}", _0)]
+    #[error("JSON deserialize error")]
     DeserializeError(#[from] serde_json::Error),
     ///An error occurred when parsing a timestamp from Twitter. The enclosed error was returned
     ///from chrono.
-    #[error("Error parsing timestamp: {}", _0)]
+    #[error("Error parsing timestamp")]
     TimestampParseError(#[from] chrono::ParseError),
     ///The tokio `Timer` instance was shut down while waiting on a timer, for example while waiting
     ///for media to be processed by Twitter. The enclosed error was returned from `tokio`.
-    #[error("Timer runtime shutdown: {}", _0)]
+    #[error("Timer runtime shutdown")]
     TimerShutdownError(#[from] tokio::time::Error),
     ///An error occurred when reading the value from a response header. The enclused error was
     ///returned from hyper.
     ///
     ///This error should be considerably rare, but is included to ensure that egg-mode doesn't
     ///panic if it receives malformed headers or the like.
-    #[error("Error decoding headers: {}", _0)]
+    #[error("Error decoding headers")]
     HeaderParseError(#[from] hyper::header::ToStrError),
     ///An error occurred when converting a rate-limit header to an integer. The enclosed error was
     ///returned from the standard library.
     ///
     ///This error should be considerably rare, but is included to ensure that egg-mode doesn't
     ///panic if it receives malformed headers or the like.
-    #[error("Error converting headers: {}", _0)]
+    #[error("Error converting headers")]
     HeaderConvertError(#[from] std::num::ParseIntError),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Error;
+    use std::error::Error as StdError;
+
+    #[tokio::test]
+    async fn net_error_source() {
+        let body = hyper::Body::wrap_stream(futures::stream::once(async {
+            Err::<&'static str, _>(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                "reset",
+            ))
+        }));
+        let err: Error = hyper::body::to_bytes(body).await.unwrap_err().into();
+
+        assert_eq!(err.to_string(), "Network error");
+        let source = err.source().expect("NetError should have a source");
+        assert!(source.downcast_ref::<hyper::Error>().is_some());
+    }
+
+    #[test]
+    fn wrapped_error_sources() {
+        let json_err = serde_json::from_str::<u32>("nope").unwrap_err();
+        let err = Error::from(json_err);
+        assert_eq!(err.to_string(), "JSON deserialize error");
+        assert!(err
+            .source()
+            .unwrap()
+            .downcast_ref::<serde_json::Error>()
+            .is_some());
+
+        let errors = super::TwitterErrors {
+            errors: vec![super::TwitterErrorCode {
+                message: "Sorry, that page does not exist".to_string(),
+                code: 34,
+            }],
+        };
+        let err = Error::TwitterError(Default::default(), errors);
+        assert_eq!(
+            err.source().unwrap().to_string(),
+            "#34: Sorry, that page does not exist"
+        );
+
+        assert!(Error::RateLimit(0).source().is_none());
+    }
+}
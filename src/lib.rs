@@ -81,6 +81,33 @@
 //! compile error will result. If you need to use `rustls` or `rustls_webpki`, remember to set
 //! `default-features = false` in your Cargo.toml.
 //!
+//! Separately from the TLS features, the `tracing` feature (off by default) makes egg-mode emit a
+//! [`tracing`] span named `twitter_request` around each request it sends. The span records the
+//! request's `method` and `endpoint` (its URL without the query string), and once a response
+//! arrives, its `status` and, if Twitter sent one, its `rate_limit_remaining`. Query parameters,
+//! request bodies, and the `Authorization` header are never recorded.
+//!
+//! [`tracing`]: https://docs.rs/tracing
+//!
+//! The `simd-json` feature (also off by default) parses every response from Twitter with
+//! [`simd-json`] instead of `serde_json`, which can be faster when loading a lot of data. The
+//! same types come out either way, and parse errors are still returned as
+//! `Error::DeserializeError`.
+//!
+//! [`simd-json`]: https://docs.rs/simd-json
+//!
+//! The `local_redirect` feature (also off by default) adds `auth::sign_in_locally` and
+//! `auth::LocalRedirect`, which finish the web-based sign-in flow for desktop and CLI apps by
+//! starting a temporary web server on `127.0.0.1` to receive Twitter's callback.
+//!
+//! The `zeroize` feature (also off by default) wipes the secrets held in `KeyPair`, `Token`,
+//! `auth::oauth2::UserToken`, `auth::oauth2::Client`, and `auth::oauth2::PkceCode` from memory
+//! when they're dropped, using the [`zeroize`] crate. Those types never show their secrets in
+//! their `Debug` output, with or without this feature, and they implement `Drop` either way, so
+//! turning it on doesn't change what code compiles.
+//!
+//! [`zeroize`]: https://docs.rs/zeroize
+//!
 //! # Types and Functions
 //!
 //! All of the main content of egg-mode is in submodules, but there are a few things here in the
@@ -132,6 +159,8 @@
 //!   removing users, or loading the posts made by their members.
 //! * `media`: This module lets you upload images, GIFs, and videos to Twitter so you can attach
 //!   them to tweets.
+//! * `spaces`: This module lets you look up and search for Spaces, Twitter's live audio
+//!   conversations.
 //!
 //! ## Secondary actions
 //!
@@ -143,6 +172,13 @@
 //! * `service`: These are some miscellaneous methods that show information about the Twitter
 //!   service as a whole, like loading the maximum length of t.co URLs or loading the current Terms
 //!   of Service or Privacy Policy.
+//! * `account_activity`: This module lets you register the webhooks and subscriptions that the
+//!   Account Activity API uses to send you events about users, and answer the challenge Twitter
+//!   sends to those webhooks.
+//! * `text`: This module counts the length of tweet text the way Twitter does, so it can be
+//!   checked before being posted.
+//! * `compliance`: This module runs the batch compliance jobs that check whether stored tweets
+//!   and users have since been deleted, protected, or otherwise changed.
 //!
 //! ## Helper structs
 //!
@@ -163,7 +199,9 @@
 
 #[macro_use]
 mod common;
+pub mod account_activity;
 pub mod auth;
+pub mod compliance;
 pub mod cursor;
 pub mod direct;
 pub mod entities;
@@ -175,9 +213,16 @@ pub mod place;
 pub mod raw;
 pub mod search;
 pub mod service;
+pub mod spaces;
 pub mod stream;
+pub mod text;
 pub mod tweet;
 pub mod user;
 
 pub use crate::auth::{Token, KeyPair};
-pub use crate::common::{Response, ResponseIter, RateLimit};
+pub use crate::common::{Response, ResponseIter, RateLimit, with_cancel};
+pub use crate::common::Lookup;
+pub use crate::common::{max_redirects, set_max_redirects};
+pub use crate::common::{max_response_size, set_max_response_size};
+pub use crate::common::{set_retry_budget, RetryBudget};
+pub use crate::common::{set_client_config, ClientConfig};
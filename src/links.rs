@@ -2,6 +2,10 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 
+pub mod account_activity {
+    pub const ENV_STEM: &'static str = "https://api.twitter.com/1.1/account_activity/all";
+}
+
 pub mod auth {
     pub const REQUEST_TOKEN: &'static str = "https://api.twitter.com/oauth/request_token";
     pub const ACCESS_TOKEN: &'static str = "https://api.twitter.com/oauth/access_token";
@@ -11,6 +15,8 @@ pub mod auth {
     pub const AUTHENTICATE: &'static str = "https://api.twitter.com/oauth/authenticate";
     pub const VERIFY_CREDENTIALS: &'static str =
         "https://api.twitter.com/1.1/account/verify_credentials.json";
+    pub const OAUTH2_AUTHORIZE: &'static str = "https://twitter.com/i/oauth2/authorize";
+    pub const OAUTH2_TOKEN: &'static str = "https://api.twitter.com/2/oauth2/token";
 }
 
 pub mod users {
@@ -43,6 +49,11 @@ pub mod users {
     pub const REPORT_SPAM: &'static str = "https://api.twitter.com/1.1/users/report_spam.json";
     pub const MUTE: &'static str = "https://api.twitter.com/1.1/mutes/users/create.json";
     pub const UNMUTE: &'static str = "https://api.twitter.com/1.1/mutes/users/destroy.json";
+    pub const UPDATE_PROFILE: &'static str =
+        "https://api.twitter.com/1.1/account/update_profile.json";
+    pub const LOOKUP_V2: &'static str = "https://api.twitter.com/2/users";
+    pub const LOOKUP_BY_V2: &'static str = "https://api.twitter.com/2/users/by";
+    pub const SHOW_BY_USERNAME_V2: &'static str = "https://api.twitter.com/2/users/by/username";
 }
 
 pub mod statuses {
@@ -67,6 +78,11 @@ pub mod statuses {
     pub const UNLIKE: &'static str = "https://api.twitter.com/1.1/favorites/destroy.json";
     pub const UPDATE: &'static str = "https://api.twitter.com/1.1/statuses/update.json";
     pub const DELETE_STEM: &'static str = "https://api.twitter.com/1.1/statuses/destroy";
+    pub const OEMBED: &'static str = "https://publish.twitter.com/oembed";
+    pub const TWEETS_V2: &'static str = "https://api.twitter.com/2/tweets";
+    pub const COUNTS_RECENT_V2: &'static str = "https://api.twitter.com/2/tweets/counts/recent";
+    pub const SEARCH_ALL_V2: &'static str = "https://api.twitter.com/2/tweets/search/all";
+    pub const COUNTS_ALL_V2: &'static str = "https://api.twitter.com/2/tweets/counts/all";
 }
 
 pub mod media {
@@ -98,6 +114,7 @@ pub mod lists {
     pub const REMOVE_LIST: &'static str =
         "https://api.twitter.com/1.1/lists/members/destroy_all.json";
     pub const UPDATE: &'static str = "https://api.twitter.com/1.1/lists/update.json";
+    pub const LISTS_V2: &'static str = "https://api.twitter.com/2/lists";
 }
 
 pub mod place {
@@ -107,6 +124,10 @@ pub mod place {
     pub const SEARCH: &'static str = "https://api.twitter.com/1.1/geo/search.json";
 }
 
+pub mod compliance {
+    pub const JOBS: &'static str = "https://api.twitter.com/2/compliance/jobs";
+}
+
 pub mod direct {
     pub const SHOW: &'static str = "https://api.twitter.com/1.1/direct_messages/events/show.json";
     pub const LIST: &'static str = "https://api.twitter.com/1.1/direct_messages/events/list.json";
@@ -124,7 +145,16 @@ pub mod service {
         "https://api.twitter.com/1.1/application/rate_limit_status.json";
 }
 
+pub mod spaces {
+    pub const SPACES: &'static str = "https://api.twitter.com/2/spaces";
+    pub const BY_CREATOR_IDS: &'static str = "https://api.twitter.com/2/spaces/by/creator_ids";
+    pub const SEARCH: &'static str = "https://api.twitter.com/2/spaces/search";
+}
+
 pub mod stream {
     pub const SAMPLE: &'static str = "https://stream.twitter.com/1.1/statuses/sample.json";
     pub const FILTER: &'static str = "https://stream.twitter.com/1.1/statuses/filter.json";
+    pub const FILTER_V2: &'static str = "https://api.twitter.com/2/tweets/search/stream";
+    pub const SAMPLE_V2: &'static str = "https://api.twitter.com/2/tweets/sample/stream";
+    pub const RULES_V2: &'static str = "https://api.twitter.com/2/tweets/search/stream/rules";
 }
@@ -21,6 +21,21 @@ pub fn memberships<T: Into<UserID>>(user: T, token: &auth::Token) -> CursorIter<
     CursorIter::new(links::lists::MEMBERSHIPS, token, Some(params), Some(20))
 }
 
+///Look up every list the given user has been added to, with each list's owner.
+///
+///This works like `memberships`, but loads pages of 1000 lists at a time (the maximum) by
+///default. Each `List` includes the `TwitterUser` that created it in its `user` field. If
+///`filter_to_owned` is `true`, only the lists owned by the authenticated user are returned, which
+///can be used to see where the authenticated user has put someone.
+pub fn memberships_detailed<T: Into<UserID>>(
+    user: T,
+    filter_to_owned: bool,
+    token: &auth::Token,
+) -> CursorIter<ListCursor> {
+    let params = memberships_params(user.into(), filter_to_owned);
+    CursorIter::new(links::lists::MEMBERSHIPS, token, Some(params), Some(20)).with_page_size(1000)
+}
+
 ///Return up to 100 lists the given user is subscribed to, including those the user made
 ///themselves.
 ///
@@ -157,7 +172,11 @@ pub async fn is_member<'id, T: Into<UserID>>(
 ///Begin navigating the collection of tweets made by the users added to the given list.
 ///
 ///The interface for loading statuses from a list is exactly the same as loading from a personal
-///timeline. see the [`Timeline`] docs for details.
+///timeline. see the [`Timeline`] docs for details. Use `older` and `newer` on the returned
+///`Timeline` to page back with `max_id` or pick up new tweets with `since_id`. If `with_rts` is
+///false, retweets are left out of the results.
+///
+///This method has a default page size of 20 tweets, with a maximum of 200.
 ///
 ///[`Timeline`]: ../tweet/struct.Timeline.html
 pub fn statuses(list: ListID, with_rts: bool, token: &auth::Token) -> tweet::Timeline {
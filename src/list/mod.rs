@@ -61,11 +61,17 @@
 //!
 //! - `ownerships`/`subscriptions`/`list`: Note that `list` will only return the most recent 100
 //!   lists in the `ownerships`/`subscriptions` sets.
-//! - `memberships`
+//! - `memberships`/`memberships_detailed`
 //! - `members`/`is_member`
 //! - `subscribers`/`is_subscriber`
 //! - `show`
 //! - `statuses`
+//!
+//! The [`v2`][] submodule has version 2 equivalents of the basic actions and `show`, which refer
+//! to lists by numeric ID, along with the version 2-only follower and member counts and pinned
+//! lists.
+//!
+//! [`v2`]: v2/index.html
 
 use chrono;
 use serde::Deserialize;
@@ -74,6 +80,8 @@ use crate::common::*;
 use crate::{auth, links, user};
 
 mod fun;
+pub mod v2;
+
 pub use self::fun::*;
 
 /// Convenience enum to refer to a list via its owner and name or via numeric ID.
@@ -232,10 +240,18 @@ impl ListUpdate {
     }
 }
 
+///Assembles the parameters for a `lists/memberships` call about the given user.
+fn memberships_params(user: user::UserID, filter_to_owned: bool) -> ParamList {
+    ParamList::new()
+        .add_user_param(user)
+        .add_param("filter_to_owned_lists", filter_to_owned.to_string())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::List;
+    use super::{memberships_detailed, memberships_params, List};
     use crate::common::tests::load_file;
+    use crate::user::UserID;
 
     #[test]
     fn parse_list_sample() {
@@ -244,4 +260,25 @@ mod tests {
         assert_eq!(list.full_name, "@Scobleizer/all-people-in-spatial-2");
         assert_eq!(list.user.screen_name, "Scobleizer")
     }
+
+    #[test]
+    fn memberships_filter_param() {
+        let params = memberships_params(UserID::ScreenName("Scobleizer".into()), true);
+        let filter = params.get("filter_to_owned_lists").map(|v| v.as_ref());
+        assert_eq!(filter, Some("true"));
+        let name = params.get("screen_name").map(|v| v.as_ref());
+        assert_eq!(name, Some("Scobleizer"));
+
+        let params = memberships_params(UserID::ID(1), false);
+        let filter = params.get("filter_to_owned_lists").map(|v| v.as_ref());
+        assert_eq!(filter, Some("false"));
+    }
+
+    #[test]
+    fn memberships_detailed_page_size() {
+        let token = crate::Token::Bearer("bearer".to_string());
+        let lists = memberships_detailed("Scobleizer", true, &token);
+        assert_eq!(lists.page_size, Some(1000));
+        assert_eq!(lists.next_cursor, -1);
+    }
 }
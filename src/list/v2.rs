@@ -0,0 +1,505 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//!List management through version 2 of the Twitter API.
+//!
+//!These functions mirror the ones in the [`list`] module, with the same names where there's an
+//!equivalent, but refer to lists and users by numeric ID only. Version 2 also adds a few things
+//!version 1.1 can't do: loading a list's follower and member counts through `ListField`, and the
+//!lists a user has pinned to their profile with `pinned`, `pin`, and `unpin`.
+//!
+//!Like the rest of version 2, a `ListV2` only carries its ID and name by default; every other
+//!field, as well as the list's owner, needs to be asked for with a `Fields` builder. The actions
+//!that change a list need a token for the authenticated user: either an Access token, or an OAuth
+//!2.0 user token with the `list.write` scope.
+//!
+//![`list`]: ../index.html
+//!
+//!```rust,no_run
+//!# #[tokio::main]
+//!# async fn main() {
+//!# let token: egg_mode::Token = unimplemented!();
+//!use egg_mode::list::v2::{self, Fields, ListField};
+//!
+//!let fields = Fields::new()
+//!    .list_fields(vec![ListField::FollowerCount, ListField::MemberCount])
+//!    .owner(None);
+//!let lookup = v2::show(84839422, &fields, &token).await.unwrap();
+//!if let Some(list) = &lookup.data {
+//!    let owner = list.owner.as_ref().map(|u| &u.username);
+//!    println!("{} by {:?}: {:?} followers", list.name, owner, list.follower_count);
+//!}
+//!# }
+//!```
+
+use futures::stream::Stream;
+use hyper::Method;
+use serde::Deserialize;
+
+use crate::auth::raw::RequestBuilder;
+use crate::common::*;
+use crate::error::Result;
+use crate::tweet::v2::{paginate, Hydrate};
+use crate::user::v2::{UserField, UserV2};
+use crate::{auth, links};
+
+field_names! {
+    ///Optional fields of a list that can be requested with `Fields::list_fields`.
+    ListField {
+        ///`created_at`: When the list was created.
+        CreatedAt => "created_at",
+        ///`description`: The list's description.
+        Description => "description",
+        ///`follower_count`: The number of users who follow the list.
+        FollowerCount => "follower_count",
+        ///`member_count`: The number of users who have been added to the list.
+        MemberCount => "member_count",
+        ///`owner_id`: The ID of the user who owns the list.
+        OwnerId => "owner_id",
+        ///`private`: Whether the list is private.
+        Private => "private",
+    }
+}
+
+///The fields to ask for when loading lists.
+///
+///By default, Twitter only returns each list's `id` and `name`, and no `includes`. Each method
+///here adds to the set of fields requested, so they can be called more than once.
+#[derive(Debug, Clone, Default)]
+pub struct Fields {
+    list_fields: Vec<ListField>,
+    user_fields: Vec<UserField>,
+    owner: bool,
+}
+
+impl Fields {
+    ///Creates an empty set of fields, which loads each list's `id` and `name` only.
+    pub fn new() -> Fields {
+        Fields::default()
+    }
+
+    ///Asks for the given fields of each list.
+    pub fn list_fields(mut self, fields: impl IntoIterator<Item = ListField>) -> Fields {
+        self.list_fields.extend(fields);
+        self
+    }
+
+    ///Asks for each list's owner to be loaded into `ListV2::owner`, with the given fields.
+    ///
+    ///This also asks for `ListField::OwnerId`, so the owner can be matched up with its list.
+    pub fn owner(mut self, fields: impl IntoIterator<Item = UserField>) -> Fields {
+        self.owner = true;
+        self.list_fields.push(ListField::OwnerId);
+        self.user_fields.extend(fields);
+        self
+    }
+
+    ///Builds the query parameters for these fields.
+    fn params(&self) -> ParamList {
+        let list_fields = self.list_fields.iter().map(|f| f.as_str());
+        let user_fields = self.user_fields.iter().map(|f| f.as_str());
+        let expansions = if self.owner { Some("owner_id") } else { None };
+        ParamList::new()
+            .add_opt_param("list.fields", join_field_names(list_fields))
+            .add_opt_param("expansions", expansions)
+            .add_opt_param("user.fields", join_field_names(user_fields))
+    }
+}
+
+///Represents a single list, as returned by version 2 of the Twitter API.
+///
+///Only `id` and `name` are always present. The other fields are only filled in if they were asked
+///for with `Fields::list_fields`. `owner` is filled in from `includes` when it was asked for with
+///`Fields::owner`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListV2 {
+    ///Numeric ID for this list.
+    #[serde(with = "serde_via_string")]
+    pub id: u64,
+    ///The name of the list.
+    pub name: String,
+    ///When the list was created.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    ///The list's description.
+    pub description: Option<String>,
+    ///The number of users who follow the list.
+    pub follower_count: Option<u64>,
+    ///The number of users who have been added to the list.
+    pub member_count: Option<u64>,
+    ///Whether the list is private.
+    pub private: Option<bool>,
+    ///The ID of the user who owns the list.
+    #[serde(default, deserialize_with = "deserialize_opt_string_id")]
+    pub owner_id: Option<u64>,
+    ///The user who owns the list, if it was asked for with `Fields::owner`.
+    #[serde(skip)]
+    pub owner: Option<UserV2>,
+}
+
+impl Hydrate<ListIncludes> for ListV2 {
+    fn hydrate(&mut self, includes: &ListIncludes) {
+        self.owner = self.owner_id.and_then(|id| includes.user(id)).cloned();
+    }
+}
+
+///The related objects loaded alongside the lists in a `ListLookup`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ListIncludes {
+    ///The owners of the loaded lists, if they were asked for with `Fields::owner`.
+    #[serde(default)]
+    pub users: Vec<UserV2>,
+}
+
+impl ListIncludes {
+    ///Looks up the included user with the given ID.
+    pub fn user(&self, id: u64) -> Option<&UserV2> {
+        self.users.iter().find(|u| u.id == id)
+    }
+}
+
+///The response from a version 2 list lookup.
+///
+///`data` holds the lists that were loaded: a `Vec` from `pinned` or `subscriptions`, or an
+///`Option` from `show`. The lists are hydrated from `includes` as they're loaded.
+///
+///`includes` holds the owners asked for with `Fields::owner`.
+pub type ListLookup<T> = Lookup<T, ListIncludes>;
+
+///A page of lists from a call that loads lists a page at a time.
+pub type ListPage = Response<ListLookup<Vec<ListV2>>>;
+
+///Look up information for a single list, loading the given fields.
+///
+///If the list doesn't exist or is private, `data` will be `None`, and `errors` will say why.
+pub async fn show(
+    list_id: u64,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<ListLookup<Option<ListV2>>>> {
+    let url = format!("{}/{}", links::lists::LISTS_V2, list_id);
+    let req = get(&url, token, Some(&fields.params()));
+    request_with_json_response(req).await
+}
+
+///Look up the lists the given user has pinned to their profile, loading the given fields.
+///
+///`user_id` must be the ID of the authenticated user.
+pub async fn pinned(
+    user_id: u64,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<ListLookup<Vec<ListV2>>>> {
+    let url = format!("{}/{}/pinned_lists", links::users::LOOKUP_V2, user_id);
+    let req = get(&url, token, Some(&fields.params()));
+    request_with_json_response(req).await
+}
+
+///Look up the lists the given user follows, a page at a time, loading the given fields.
+///
+///Version 2 of the API calls these "followed lists"; they're the same lists as
+///`list::subscriptions` returns. Each item in the stream is a page of up to 100 lists; the next
+///page is loaded once the stream is polled again. If loading a page fails, the error is yielded
+///and the stream ends.
+pub fn subscriptions(
+    user_id: u64,
+    fields: &Fields,
+    token: &auth::Token,
+) -> impl Stream<Item = Result<ListPage>> {
+    let url = format!("{}/{}/followed_lists", links::users::LOOKUP_V2, user_id);
+    let params = fields.params().add_param("max_results", "100");
+    let token = token.clone();
+    paginate(move |next_token| {
+        let params = params.clone().add_opt_param("pagination_token", next_token);
+        let req = get(&url, &token, Some(&params));
+        request_with_json_response(req)
+    })
+}
+
+///Creates a list, with the given name, visibility, and description.
+///
+///The new list is owned by the authenticated user. Twitter only returns the new list's `id` and
+///`name`; use `show` to load any other fields.
+pub async fn create(
+    name: String,
+    public: bool,
+    desc: Option<String>,
+    token: &auth::Token,
+) -> Result<Response<ListV2>> {
+    let body = serde_json::json!({
+        "name": name,
+        "description": desc,
+        "private": !public,
+    });
+    let req = post_json(links::lists::LISTS_V2, token, body);
+    let resp = request_with_json_response::<ListData>(req).await?;
+    Ok(Response::map(resp, |list| list.data))
+}
+
+///Deletes the given list.
+///
+///The authenticated user must own the list. Upon success, the response says whether the list was
+///deleted.
+pub async fn delete(list_id: u64, token: &auth::Token) -> Result<Response<bool>> {
+    let url = format!("{}/{}", links::lists::LISTS_V2, list_id);
+    let resp = send_status(auth::raw::delete(&url, token, None)).await?;
+    Ok(Response::map(resp, |status| status.deleted))
+}
+
+///Begins updating a list's metadata.
+///
+///This works like `list::update`; see the [`ListUpdateV2`] docs for details.
+///
+///[`ListUpdateV2`]: struct.ListUpdateV2.html
+pub fn update(list_id: u64) -> ListUpdateV2 {
+    ListUpdateV2 {
+        list_id,
+        name: None,
+        public: None,
+        desc: None,
+    }
+}
+
+///Represents a pending update to a list's metadata, sent through version 2 of the API.
+///
+///Any fields that aren't set are left as they are.
+#[derive(Debug, Clone)]
+#[must_use = "ListUpdateV2 is lazy and won't do anything unless `send` is called"]
+pub struct ListUpdateV2 {
+    list_id: u64,
+    name: Option<String>,
+    public: Option<bool>,
+    desc: Option<String>,
+}
+
+impl ListUpdateV2 {
+    ///Updates the name of the list.
+    pub fn name(self, name: impl Into<String>) -> ListUpdateV2 {
+        ListUpdateV2 {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    ///Sets whether the list is public.
+    pub fn public(self, public: bool) -> ListUpdateV2 {
+        ListUpdateV2 {
+            public: Some(public),
+            ..self
+        }
+    }
+
+    ///Updates the description of the list.
+    pub fn desc(self, desc: impl Into<String>) -> ListUpdateV2 {
+        ListUpdateV2 {
+            desc: Some(desc.into()),
+            ..self
+        }
+    }
+
+    ///Builds the JSON body for `PUT 2/lists/:id`, leaving out the fields that aren't changing.
+    fn body(&self) -> serde_json::Value {
+        let mut body = serde_json::json!({});
+        if let Some(ref name) = self.name {
+            body["name"] = name.clone().into();
+        }
+        if let Some(ref desc) = self.desc {
+            body["description"] = desc.clone().into();
+        }
+        if let Some(public) = self.public {
+            body["private"] = (!public).into();
+        }
+        body
+    }
+
+    ///Sends the update request to Twitter. Upon success, the response says whether the list was
+    ///updated.
+    pub async fn send(self, token: &auth::Token) -> Result<Response<bool>> {
+        let url = format!("{}/{}", links::lists::LISTS_V2, self.list_id);
+        let req = RequestBuilder::new(Method::PUT, &url)
+            .with_body_json(self.body())
+            .request_token(token);
+        let resp = send_status(req).await?;
+        Ok(Response::map(resp, |status| status.updated))
+    }
+}
+
+///Adds the given user to the given list.
+///
+///The authenticated user must own the list. Upon success, the response says whether the user is
+///now a member of the list.
+pub async fn add_member(list_id: u64, user_id: u64, token: &auth::Token) -> Result<Response<bool>> {
+    let url = format!("{}/{}/members", links::lists::LISTS_V2, list_id);
+    let body = serde_json::json!({ "user_id": user_id.to_string() });
+    let resp = send_status(post_json(&url, token, body)).await?;
+    Ok(Response::map(resp, |status| status.is_member))
+}
+
+///Removes the given user from the given list.
+///
+///The authenticated user must own the list. Upon success, the response says whether the user is
+///still a member of the list.
+pub async fn remove_member(
+    list_id: u64,
+    user_id: u64,
+    token: &auth::Token,
+) -> Result<Response<bool>> {
+    let url = format!("{}/{}/members/{}", links::lists::LISTS_V2, list_id, user_id);
+    let resp = send_status(auth::raw::delete(&url, token, None)).await?;
+    Ok(Response::map(resp, |status| status.is_member))
+}
+
+///Follows the given list with the authenticated user.
+///
+///This is the version 2 equivalent of `list::subscribe`. `user_id` must be the ID of the
+///authenticated user. Upon success, the response says whether the user now follows the list.
+pub async fn subscribe(user_id: u64, list_id: u64, token: &auth::Token) -> Result<Response<bool>> {
+    let url = format!("{}/{}/followed_lists", links::users::LOOKUP_V2, user_id);
+    let body = serde_json::json!({ "list_id": list_id.to_string() });
+    let resp = send_status(post_json(&url, token, body)).await?;
+    Ok(Response::map(resp, |status| status.following))
+}
+
+///Unfollows the given list with the authenticated user.
+///
+///This is the version 2 equivalent of `list::unsubscribe`. `user_id` must be the ID of the
+///authenticated user. Upon success, the response says whether the user still follows the list.
+pub async fn unsubscribe(
+    user_id: u64,
+    list_id: u64,
+    token: &auth::Token,
+) -> Result<Response<bool>> {
+    let url = format!(
+        "{}/{}/followed_lists/{}",
+        links::users::LOOKUP_V2,
+        user_id,
+        list_id
+    );
+    let resp = send_status(auth::raw::delete(&url, token, None)).await?;
+    Ok(Response::map(resp, |status| status.following))
+}
+
+///Pins the given list to the authenticated user's profile.
+///
+///`user_id` must be the ID of the authenticated user. Upon success, the response says whether the
+///list is now pinned.
+pub async fn pin(user_id: u64, list_id: u64, token: &auth::Token) -> Result<Response<bool>> {
+    let url = format!("{}/{}/pinned_lists", links::users::LOOKUP_V2, user_id);
+    let body = serde_json::json!({ "list_id": list_id.to_string() });
+    let resp = send_status(post_json(&url, token, body)).await?;
+    Ok(Response::map(resp, |status| status.pinned))
+}
+
+///Unpins the given list from the authenticated user's profile.
+///
+///`user_id` must be the ID of the authenticated user. Upon success, the response says whether the
+///list is still pinned.
+pub async fn unpin(user_id: u64, list_id: u64, token: &auth::Token) -> Result<Response<bool>> {
+    let url = format!(
+        "{}/{}/pinned_lists/{}",
+        links::users::LOOKUP_V2,
+        user_id,
+        list_id
+    );
+    let resp = send_status(auth::raw::delete(&url, token, None)).await?;
+    Ok(Response::map(resp, |status| status.pinned))
+}
+
+///Sends one of the list actions, and parses the flag it responds with.
+async fn send_status(req: hyper::Request<hyper::Body>) -> Result<Response<ListFlags>> {
+    let resp = request_with_json_response::<ListStatus>(req).await?;
+    Ok(Response::map(resp, |status| status.data))
+}
+
+///The response from `POST 2/lists`.
+#[derive(Deserialize)]
+struct ListData {
+    data: ListV2,
+}
+
+///The response from the v2 list actions.
+#[derive(Deserialize)]
+struct ListStatus {
+    data: ListFlags,
+}
+
+///Each action only sends the flag it changes, so the rest default to `false`.
+#[derive(Deserialize)]
+struct ListFlags {
+    #[serde(default)]
+    updated: bool,
+    #[serde(default)]
+    deleted: bool,
+    #[serde(default)]
+    is_member: bool,
+    #[serde(default)]
+    following: bool,
+    #[serde(default)]
+    pinned: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::load_file;
+
+    #[test]
+    fn parse_lists() {
+        let sample = load_file("sample_payloads/v2-lists.json");
+        let lookup: ListLookup<Vec<ListV2>> = serde_json::from_str(&sample).unwrap();
+
+        assert_eq!(lookup.data.len(), 2);
+        assert_eq!(lookup.meta.next_token.as_deref(), Some("t6cdqiwemjgc"));
+        let list = &lookup.data[0];
+        assert_eq!(list.id, 1355797419175383040);
+        assert_eq!(list.name, "Twitter Comms");
+        assert_eq!(list.follower_count, Some(1012));
+        assert_eq!(list.member_count, Some(12));
+        assert_eq!(list.private, Some(false));
+        assert_eq!(list.created_at.unwrap().timestamp(), 1612148377);
+        assert_eq!(list.owner.as_ref().unwrap().username, "TwitterDev");
+        assert!(lookup.data[1].owner.is_none());
+
+        let status: ListStatus = serde_json::from_str(r#"{"data":{"pinned":true}}"#).unwrap();
+        assert!(status.data.pinned);
+        assert!(!status.data.following);
+        let status: ListStatus = serde_json::from_str(r#"{"data":{"is_member":false}}"#).unwrap();
+        assert!(!status.data.is_member);
+    }
+
+    #[test]
+    fn update_body() {
+        let update = update(1355797419175383040).name("Comms").public(false);
+        assert_eq!(
+            update.body(),
+            serde_json::json!({ "name": "Comms", "private": true })
+        );
+        assert_eq!(
+            super::update(1).desc("All of them").body(),
+            serde_json::json!({ "description": "All of them" })
+        );
+    }
+
+    #[test]
+    fn field_params() {
+        assert!(Fields::new().params().is_empty());
+
+        let params = Fields::new()
+            .list_fields(vec![ListField::FollowerCount])
+            .owner(vec![UserField::Verified])
+            .params();
+        assert_eq!(
+            params.get("list.fields").map(|v| v.as_ref()),
+            Some("follower_count,owner_id")
+        );
+        assert_eq!(
+            params.get("expansions").map(|v| v.as_ref()),
+            Some("owner_id")
+        );
+        assert_eq!(
+            params.get("user.fields").map(|v| v.as_ref()),
+            Some("verified")
+        );
+    }
+}
@@ -22,12 +22,18 @@
 //! let tweet = draft.send(&token).await?;
 //! # }
 //! ```
+//!
+//! `DraftTweet::attach_media_file` and `DraftTweet::attach_media_reader` do all of this in one
+//! call: they upload the media, wait for Twitter to finish processing it with
+//! [`wait_for_processing`], and add it to the draft.
 
+use std::future::Future;
 use std::time::{Duration, Instant};
 
 use base64;
 use serde::de::Error;
 use serde::{Deserialize, Deserializer};
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 use crate::common::*;
 use crate::{auth, error, links};
@@ -70,6 +76,20 @@ pub mod media_types {
     pub fn video_mp4() -> Mime {
         "video/mp4".parse().unwrap()
     }
+
+    /// Picks one of these media types from the extension of the given file path, or returns
+    /// `None` if the extension isn't one of them.
+    pub fn from_path(path: &std::path::Path) -> Option<Mime> {
+        let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "png" => Some(image_png()),
+            "jpg" | "jpeg" => Some(image_jpg()),
+            "webp" => Some(image_webp()),
+            "gif" => Some(image_gif()),
+            "mp4" => Some(video_mp4()),
+            _ => None,
+        }
+    }
 }
 
 /// Upload progress info.
@@ -144,9 +164,26 @@ struct RawMedia {
     progress: Option<ProgressInfo>,
 }
 
-#[derive(Debug, Clone, derive_more::From)]
+#[derive(Debug, Clone)]
 /// An opaque type representing a media id.
-pub struct MediaId(pub(crate) String);
+pub struct MediaId(pub(crate) String, pub(crate) Option<MediaCategory>);
+
+impl From<String> for MediaId {
+    fn from(id: String) -> MediaId {
+        MediaId(id, None)
+    }
+}
+
+impl MediaId {
+    /// Returns whether this media is known to be a GIF or video, from having been uploaded with
+    /// one of the upload functions in this module. Users can only be tagged in images.
+    pub(crate) fn is_gif_or_video(&self) -> bool {
+        matches!(
+            self.1,
+            Some(MediaCategory::Gif) | Some(MediaCategory::Video)
+        )
+    }
+}
 
 /// A handle representing uploaded media.
 #[derive(Debug, Clone)]
@@ -183,7 +220,7 @@ impl MediaHandle {
 /// Represents the kind of media that Twitter will accept.
 /// `.to_string()` will return a string suitable for use in API calls
 #[derive(Debug, Copy, Clone, PartialEq, Eq, derive_more::Display)]
-enum MediaCategory {
+pub(crate) enum MediaCategory {
     /// Static image. Four can be attached to a single tweet.
     #[display(fmt = "tweet_image")]
     Image,
@@ -239,7 +276,7 @@ pub async fn upload_media(
 
     let media = request_with_json_response::<RawMedia>(req).await?.response;
 
-    finish_upload(media, data, token).await
+    finish_upload(media, data, media_category, token).await
 }
 
 /// Upload media to the server, for use in a Direct Message.
@@ -276,34 +313,149 @@ pub async fn upload_media_for_dm(
 
     let media = request_with_json_response::<RawMedia>(req).await?.response;
 
-    finish_upload(media, data, token).await
+    finish_upload(media, data, media_category, token).await
+}
+
+/// Upload media to the server, reading it from the given `AsyncRead` instead of from memory.
+///
+/// This function works the same as [`upload_media`], but only holds one 1MB chunk of the media in
+/// memory at a time, which makes it better suited to uploading large videos straight from disk.
+/// Since Twitter needs to know the size of the upload before it starts, `total_size` must be the
+/// exact number of bytes that `reader` will produce. If the reader ends before that many bytes
+/// have been read, or still has data left afterward, the upload is abandoned and an `IOError` is
+/// returned.
+///
+/// As with `upload_media`, be sure to check the status of the uploaded media with [`get_status`]
+/// before attaching it to a tweet.
+pub async fn upload_reader(
+    reader: impl AsyncRead + Send,
+    total_size: u64,
+    media_type: &mime::Mime,
+    token: &auth::Token,
+) -> error::Result<MediaHandle> {
+    let media_category = MediaCategory::from(media_type);
+    let params = ParamList::new()
+        .add_param("command", "INIT")
+        .add_param("total_bytes", total_size.to_string())
+        .add_param("media_type", media_type.to_string())
+        .add_param("media_category", media_category.to_string());
+    let req = post(links::media::UPLOAD, token, Some(&params));
+
+    let media = request_with_json_response::<RawMedia>(req).await?.response;
+
+    let media_id = &media.id;
+    upload_chunks(reader, total_size, |ix, chunk| async move {
+        append_chunk(media_id, ix, &chunk, token).await
+    })
+    .await?;
+
+    finalize_upload(&media.id, media_category, token).await
+}
+
+/// Reads `reader` one chunk at a time, handing each chunk to `append` along with its segment
+/// index, and checks that it produced exactly `total_size` bytes.
+async fn upload_chunks<F, Fut>(
+    reader: impl AsyncRead,
+    total_size: u64,
+    mut append: F,
+) -> error::Result<()>
+where
+    F: FnMut(usize, Vec<u8>) -> Fut,
+    Fut: Future<Output = error::Result<()>>,
+{
+    let mut reader = Box::pin(reader);
+    let mut uploaded = 0u64;
+    for ix in 0.. {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let len = read_chunk(&mut reader, &mut chunk).await?;
+        if len == 0 {
+            break;
+        }
+        uploaded += len as u64;
+        if uploaded > total_size {
+            return Err(size_mismatch(
+                "media reader produced more than total_size bytes",
+            ));
+        }
+        chunk.truncate(len);
+        append(ix, chunk).await?;
+    }
+
+    if uploaded != total_size {
+        return Err(size_mismatch("media reader ended before total_size bytes"));
+    }
+    Ok(())
+}
+
+/// The size of each chunk sent with an APPEND command.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+fn size_mismatch(msg: &'static str) -> error::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg).into()
+}
+
+/// Reads from `reader` until `buf` is full or the reader is exhausted, returning the number of
+/// bytes read.
+async fn read_chunk<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]).await? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
 }
 
 async fn finish_upload(
     media: RawMedia,
     data: &[u8],
+    media_category: MediaCategory,
     token: &auth::Token
 ) -> error::Result<MediaHandle> {
     // divide into 1MB chunks
-    for (ix, chunk) in data.chunks(1024 * 1024).enumerate() {
-        let params = ParamList::new()
-            .add_param("command", "APPEND")
-            .add_param("media_id", media.id.clone())
-            .add_param("media_data", base64::encode(chunk))
-            .add_param("segment_index", ix.to_string());
-        let req = post(links::media::UPLOAD, token, Some(&params));
-        // This request has no response (upon success)
-        raw_request(req).await?;
+    for (ix, chunk) in data.chunks(CHUNK_SIZE).enumerate() {
+        append_chunk(&media.id, ix, chunk, token).await?;
     }
 
+    finalize_upload(&media.id, media_category, token).await
+}
+
+async fn append_chunk(
+    media_id: &str,
+    ix: usize,
+    chunk: &[u8],
+    token: &auth::Token,
+) -> error::Result<()> {
+    let params = ParamList::new()
+        .add_param("command", "APPEND")
+        .add_param("media_id", media_id.to_string())
+        .add_param("media_data", base64::encode(chunk))
+        .add_param("segment_index", ix.to_string());
+    let req = post(links::media::UPLOAD, token, Some(&params));
+    // This request has no response (upon success)
+    raw_request(req).await?;
+    Ok(())
+}
+
+async fn finalize_upload(
+    media_id: &str,
+    media_category: MediaCategory,
+    token: &auth::Token,
+) -> error::Result<MediaHandle> {
     let params = ParamList::new()
         .add_param("command", "FINALIZE")
-        .add_param("media_id", media.id.clone());
+        .add_param("media_id", media_id.to_string());
     let req = post(links::media::UPLOAD, token, Some(&params));
-    Ok(request_with_json_response::<RawMedia>(req)
+    let mut handle: MediaHandle = request_with_json_response::<RawMedia>(req)
         .await?
         .response
-        .into())
+        .into();
+    handle.id.1 = Some(media_category);
+    Ok(handle)
 }
 
 /// Check the status of uploaded media
@@ -312,10 +464,35 @@ pub async fn get_status(media_id: MediaId, token: &auth::Token) -> error::Result
         .add_param("command", "STATUS")
         .add_param("media_id", media_id.0);
     let req = get(links::media::UPLOAD, token, Some(&params));
-    Ok(request_with_json_response::<RawMedia>(req)
+    let mut handle: MediaHandle = request_with_json_response::<RawMedia>(req)
         .await?
         .response
-        .into())
+        .into();
+    // the status doesn't say what kind of media it's for, so keep what the ID already knew
+    handle.id.1 = media_id.1;
+    Ok(handle)
+}
+
+/// Wait for Twitter to finish processing the given upload, checking its status as often as
+/// Twitter asks.
+///
+/// GIFs and videos need to be processed before they can be attached to a tweet; images are ready
+/// as soon as they've been uploaded, in which case this returns the handle right away. If
+/// processing fails, the error Twitter gave is returned as `Error::MediaError`.
+pub async fn wait_for_processing(
+    handle: MediaHandle,
+    token: &auth::Token,
+) -> error::Result<MediaHandle> {
+    let mut handle = handle;
+    loop {
+        let wait = match handle.progress {
+            None | Some(ProgressInfo::Success) => return Ok(handle),
+            Some(ProgressInfo::Failed(err)) => return Err(err.into()),
+            Some(ProgressInfo::Pending(secs)) | Some(ProgressInfo::InProgress(secs)) => secs,
+        };
+        tokio::time::delay_for(Duration::from_secs(wait)).await;
+        handle = get_status(handle.id, token).await?;
+    }
 }
 
 /// Set metadata for a media upload. At the moment the only attribute that may
@@ -402,4 +579,82 @@ mod tests {
             other => assert!(false, format!("Unexpected value of progress={:?}", other)),
         }
     }
+
+    #[tokio::test]
+    async fn upload_chunks_from_reader() {
+        use super::{upload_chunks, CHUNK_SIZE};
+        use std::cell::RefCell;
+        use tokio::io::AsyncReadExt;
+
+        let data = (0..(5 * CHUNK_SIZE / 2))
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let total_size = data.len() as u64;
+        // split the reader up so that reads come back short partway through a chunk
+        let (head, tail) = data.split_at(300_000);
+
+        let segments = RefCell::new(Vec::new());
+        let uploaded = RefCell::new(Vec::new());
+        let append = |ix, chunk: Vec<u8>| {
+            segments.borrow_mut().push((ix, chunk.len()));
+            uploaded.borrow_mut().extend_from_slice(&chunk);
+            async { Ok(()) }
+        };
+        upload_chunks(head.chain(tail), total_size, append)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            segments.into_inner(),
+            vec![(0, CHUNK_SIZE), (1, CHUNK_SIZE), (2, CHUNK_SIZE / 2)]
+        );
+        assert_eq!(uploaded.into_inner(), data);
+
+        // a reader that doesn't match total_size stops the upload
+        let segments = RefCell::new(Vec::new());
+        let append = |ix, chunk: Vec<u8>| {
+            segments.borrow_mut().push((ix, chunk.len()));
+            async { Ok(()) }
+        };
+        let err = upload_chunks(&data[..], total_size - 1, append)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::error::Error::IOError(_)));
+        // the last chunk goes over, so it's never sent
+        assert_eq!(
+            segments.into_inner(),
+            vec![(0, CHUNK_SIZE), (1, CHUNK_SIZE)]
+        );
+
+        let err = upload_chunks(&data[..], total_size + 1, |_, _| async { Ok(()) })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, crate::error::Error::IOError(_)));
+    }
+
+    #[test]
+    fn media_type_from_path() {
+        use super::media_types::*;
+        use std::path::Path;
+
+        assert_eq!(from_path(Path::new("cat.png")), Some(image_png()));
+        assert_eq!(from_path(Path::new("photos/Cat.JPEG")), Some(image_jpg()));
+        assert_eq!(from_path(Path::new("clip.mp4")), Some(video_mp4()));
+        assert_eq!(from_path(Path::new("notes.txt")), None);
+        assert_eq!(from_path(Path::new("gif")), None);
+    }
+
+    #[tokio::test]
+    async fn wait_for_finished_upload() {
+        let token = crate::Token::Bearer("".to_string());
+        let handle = load_media("sample_payloads/media.json").into();
+        let handle = super::wait_for_processing(handle, &token).await.unwrap();
+        assert_eq!(handle.id.0, "710511363345354753");
+
+        let handle = load_media("sample_payloads/media_fail.json").into();
+        match super::wait_for_processing(handle, &token).await {
+            Err(crate::error::Error::MediaError(err)) => assert_eq!(err.code, 1),
+            other => panic!("unexpected result: {:?}", other.map(|h| h.id)),
+        }
+    }
 }
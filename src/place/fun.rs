@@ -4,6 +4,7 @@
 
 use crate::common::*;
 use crate::error::{Error, Result};
+use crate::user::TwitterUser;
 use crate::{auth, links};
 
 use super::PlaceQuery;
@@ -31,6 +32,11 @@ pub async fn show(id: &str, token: &auth::Token) -> Result<Response<Place>> {
 
 /// Begins building a reverse-geocode search with the given coordinate.
 ///
+/// The coordinate can be a (latitude, longitude) pair, or any other type that implements
+/// [`IntoCoordinate`].
+///
+/// [`IntoCoordinate`]: trait.IntoCoordinate.html
+///
 /// ## Examples
 ///
 /// ```rust,no_run
@@ -39,7 +45,7 @@ pub async fn show(id: &str, token: &auth::Token) -> Result<Response<Place>> {
 /// # async fn main() {
 /// # let token: Token = unimplemented!();
 /// use egg_mode::place::{self, PlaceType};
-/// let result = place::reverse_geocode(51.507222, -0.1275)
+/// let result = place::reverse_geocode((51.507222, -0.1275))
 ///     .granularity(PlaceType::City)
 ///     .call(&token)
 ///     .await
@@ -48,8 +54,8 @@ pub async fn show(id: &str, token: &auth::Token) -> Result<Response<Place>> {
 /// assert!(result.results.iter().any(|pl| pl.full_name == "London, England"));
 /// # }
 /// ```
-pub fn reverse_geocode(latitude: f64, longitude: f64) -> GeocodeBuilder {
-    GeocodeBuilder::new(latitude, longitude)
+pub fn reverse_geocode(coordinate: impl IntoCoordinate) -> GeocodeBuilder {
+    GeocodeBuilder::new(coordinate)
 }
 
 fn parse_url(base: &'static str, full: &str) -> Result<ParamList> {
@@ -89,6 +95,11 @@ pub async fn reverse_geocode_url(url: &str, token: &auth::Token) -> Result<Respo
 
 /// Begins building a location search via latitude/longitude.
 ///
+/// Like `reverse_geocode`, this accepts a (latitude, longitude) pair or any other type that
+/// implements [`IntoCoordinate`].
+///
+/// [`IntoCoordinate`]: trait.IntoCoordinate.html
+///
 /// ## Example
 ///
 /// ```rust,no_run
@@ -97,7 +108,7 @@ pub async fn reverse_geocode_url(url: &str, token: &auth::Token) -> Result<Respo
 /// # async fn main() {
 /// # let token: Token = unimplemented!();
 /// use egg_mode::place::{self, PlaceType};
-/// let result = place::search_point(51.507222, -0.1275)
+/// let result = place::search_point((51.507222, -0.1275))
 ///     .granularity(PlaceType::City)
 ///     .call(&token)
 ///     .await
@@ -106,7 +117,8 @@ pub async fn reverse_geocode_url(url: &str, token: &auth::Token) -> Result<Respo
 /// assert!(result.results.iter().any(|pl| pl.full_name == "London, England"));
 /// # }
 /// ```
-pub fn search_point(latitude: f64, longitude: f64) -> SearchBuilder {
+pub fn search_point(coordinate: impl IntoCoordinate) -> SearchBuilder {
+    let (latitude, longitude) = coordinate.lat_long();
     SearchBuilder::new(PlaceQuery::LatLon(latitude, longitude))
 }
 
@@ -138,6 +150,78 @@ pub fn search_ip(query: impl Into<CowStr>) -> SearchBuilder {
     SearchBuilder::new(PlaceQuery::IPAddress(query.into()))
 }
 
+/// Looks up a place matching the free-text `location` field of the given user's profile.
+///
+/// Before searching, the location is cleaned up by removing emoji and other symbols and
+/// collapsing extra whitespace, so a location like `"📍 Portland,   OR"` is searched for as
+/// `"Portland, OR"`. The first city in the search results is returned, falling back to the first
+/// administrative area (e.g. a state or province) if no cities were found.
+///
+/// If the user has no location set, or it's empty after cleaning it up, this returns `None`
+/// without calling Twitter. It also returns `None` if no cities or administrative areas matched.
+/// Since the `location` field can contain anything the user likes, a returned `Place` is a best
+/// guess and not necessarily where the user actually is.
+pub async fn resolve_user_location(
+    user: &TwitterUser,
+    token: &auth::Token,
+) -> Result<Option<Place>> {
+    let query = match user.location.as_deref().and_then(normalize_location) {
+        Some(query) => query,
+        None => return Ok(None),
+    };
+
+    let result = search_query(query).call(token).await?;
+    Ok(best_location_match(result.response.results))
+}
+
+/// Find the place that best describes the given coordinate, at the given granularity.
+///
+/// This runs `reverse_geocode` for the coordinate and picks one of the results: a place of the
+/// given type if there is one, or else the most specific broader place. When several places of
+/// that type come back, the one whose bounding box contains the coordinate is preferred, then the
+/// one whose center is closest to it. This returns `None` if Twitter found no places at least as
+/// broad as `granularity`.
+///
+/// ```rust,no_run
+/// # use egg_mode::Token;
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let token: Token = unimplemented!();
+/// use egg_mode::place::{self, PlaceType};
+///
+/// let city = place::place_at((45.5231, -122.6765), PlaceType::City, &token).await.unwrap();
+/// if let Some(city) = city {
+///     println!("tweeting from {}", city.full_name);
+/// }
+/// # }
+/// ```
+pub async fn place_at(
+    coordinate: impl IntoCoordinate,
+    granularity: PlaceType,
+    token: &auth::Token,
+) -> Result<Option<Place>> {
+    let coordinate = coordinate.lat_long();
+    let result = reverse_geocode(coordinate)
+        .granularity(granularity)
+        .call(token)
+        .await?;
+    Ok(best_geocode_match(
+        result.response.results,
+        coordinate,
+        granularity,
+    ))
+}
+
+/// Look up the Twitter account of the venue represented by the given place.
+///
+/// Places for venues like shops or restaurants sometimes have a `twitter` attribute with the
+/// venue's handle (see `Place::twitter_handle`). This looks up the user with that handle, and
+/// returns `None` without calling Twitter if the place doesn't have one. If the handle doesn't
+/// belong to an account anymore, the error from the lookup is returned.
+pub async fn venue_account(place: &Place, token: &auth::Token) -> Result<Option<TwitterUser>> {
+    venue_account_with(place, |handle| crate::user::show(handle, token)).await
+}
+
 ///From a URL given with the result of any `search_*` function, perform the same location search.
 ///
 ///## Errors
@@ -25,15 +25,27 @@
 //! Along with the list of place results, Twitter also returns the full search URL. egg-mode
 //! returns this URL as part of the result struct, allowing you to perform the same search using
 //! the `reverse_geocode_url` or `search_url` functions.
+//!
+//! To help work with the places Twitter returns, `build_tree` organizes search results by which
+//! places contain which others, and `haversine_distance_m` measures the distance between two
+//! coordinates. `score_results` combines that distance with how closely each place's name matches
+//! a query, to rank search results for a user picking a place.
+//!
+//! When you only need one place for a coordinate, `place_at` runs `reverse_geocode` and picks the
+//! result that best fits the coordinate at the granularity you ask for.
+//! `DraftTweet::auto_place` uses it to attach the place to a tweet.
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
+use std::future::Future;
 
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json;
 
 use crate::common::*;
+use crate::user::TwitterUser;
 use crate::{auth, error, links};
 
 mod fun;
@@ -42,6 +54,9 @@ pub use self::fun::*;
 
 // https://developer.twitter.com/en/docs/tweets/data-dictionary/overview/geo-objects#place
 ///Represents a named location.
+///
+///Two `Place`s are equal if they have the same `id`, even if the rest of their fields differ, e.g.
+///because they were loaded at different times.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Place {
     ///Alphanumeric ID of the location.
@@ -68,8 +83,83 @@ pub struct Place {
     pub contained_within: Option<Vec<Place>>,
 }
 
+impl Place {
+    ///Returns the center of this place's bounding box, as a (latitude, longitude) pair, or `None`
+    ///if the bounding box is empty.
+    ///
+    ///This is the average of the bounding box's corners, which is close enough to the middle of the
+    ///place for most places, but may be off for very large ones.
+    pub fn centroid(&self) -> Option<(f64, f64)> {
+        if self.bounding_box.is_empty() {
+            return None;
+        }
+
+        let count = self.bounding_box.len() as f64;
+        let (long, lat) = self
+            .bounding_box
+            .iter()
+            .fold((0.0, 0.0), |(long, lat), corner| {
+                (long + corner.0, lat + corner.1)
+            });
+        Some((lat / count, long / count))
+    }
+
+    ///Returns a simplified copy of this place's bounding box, dropping any corners that are less
+    ///than `tolerance_m` meters from the outline formed by the others.
+    ///
+    ///This uses the Douglas-Peucker algorithm, measuring distances in meters so that the same
+    ///tolerance works at any latitude. The first and last corners are always kept, so a closed
+    ///outline stays closed. Like `bounding_box`, the corners are (longitude, latitude) pairs. This
+    ///is meant for drawing outlines at a small scale, where corners that barely change the shape
+    ///don't matter.
+    pub fn simplify_bounding_box(&self, tolerance_m: f64) -> Vec<(f64, f64)> {
+        simplify_outline(&self.bounding_box, tolerance_m)
+    }
+
+    ///Returns the Twitter handle of the venue this place represents, without a leading `@`, if
+    ///its `twitter` attribute is set.
+    ///
+    ///Use `venue_account` to look up the account itself.
+    pub fn twitter_handle(&self) -> Option<&str> {
+        self.attributes
+            .get("twitter")
+            .map(|handle| handle.trim().trim_start_matches('@'))
+            .filter(|handle| !handle.is_empty())
+    }
+
+    ///Returns the name of this place in the given locale, if Twitter provided one, or its
+    ///`full_name` if not.
+    ///
+    ///Localized names are read from `attributes`, under keys of the form `name:<locale>`, like
+    ///`name:fr` or `name:pt-BR`. The names are checked in this order:
+    ///
+    ///1. The name for the exact locale given, like `name:pt-BR` for `"pt-BR"`.
+    ///2. The name for just the language of that locale, like `name:pt` for `"pt-BR"`. Locales
+    ///   written with an underscore, like `"pt_BR"`, are split the same way.
+    ///3. The `full_name` of the place.
+    pub fn localized_name(&self, locale: &str) -> &str {
+        let language = locale.split(&['-', '_'][..]).next().unwrap_or(locale);
+        self.attributes
+            .get(&format!("name:{}", locale))
+            .or_else(|| self.attributes.get(&format!("name:{}", language)))
+            .map(|name| name.as_str())
+            .unwrap_or(&self.full_name)
+    }
+}
+
+impl PartialEq for Place {
+    fn eq(&self, other: &Place) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Place {}
+
 ///Represents the type of region represented by a given place.
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+///
+///Place types are ordered from most to least specific, so a `PointOfInterest` sorts before a
+///`Neighborhood`, which sorts before a `City`, and so on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum PlaceType {
     ///A coordinate with no area.
     #[serde(rename = "poi")]
@@ -97,7 +187,53 @@ pub enum Accuracy {
     Feet(f64),
 }
 
+///A type that can be used as a coordinate for a location search.
+///
+///`reverse_geocode` and `search_point` take any type that implements this trait, so points from
+///other libraries can be handed to them directly. egg-mode implements it for (latitude, longitude)
+///pairs, and for references to anything that implements it. To use your own point type, implement
+///`lat_long` to return its coordinate:
+///
+///```rust
+///use egg_mode::place::IntoCoordinate;
+///
+///struct Landmark {
+///    lat: f64,
+///    lon: f64,
+///}
+///
+///impl IntoCoordinate for Landmark {
+///    fn lat_long(&self) -> (f64, f64) {
+///        (self.lat, self.lon)
+///    }
+///}
+///
+///let trafalgar = Landmark { lat: 51.508, lon: -0.128 };
+///let builder = egg_mode::place::reverse_geocode(&trafalgar);
+///```
+pub trait IntoCoordinate {
+    ///Returns this point as a (latitude, longitude) pair, in degrees.
+    fn lat_long(&self) -> (f64, f64);
+}
+
+impl IntoCoordinate for (f64, f64) {
+    fn lat_long(&self) -> (f64, f64) {
+        *self
+    }
+}
+
+impl<T: IntoCoordinate + ?Sized> IntoCoordinate for &T {
+    fn lat_long(&self) -> (f64, f64) {
+        (**self).lat_long()
+    }
+}
+
 ///Represents the result of a location search, either via `reverse_geocode` or `search`.
+///
+///Twitter's location searches aren't paginated: every place that matched the search is in
+///`results`, up to the `max_results` given to the search, and there's no cursor to load more. To
+///get more places, run the search again with a higher `max_results` (Twitter may still return
+///fewer), or with a different `granularity`, and combine the results with `SearchResult::merge`.
 pub struct SearchResult {
     ///The full URL used to pull the result list. This can be fed to the `_url` version of your
     ///original call to avoid having to fill out the argument list again.
@@ -126,6 +262,26 @@ impl<'de> Deserialize<'de> for SearchResult {
     }
 }
 
+impl SearchResult {
+    ///Combines the places from several searches into one list, with each place appearing once,
+    ///sorted from most to least specific `place_type`.
+    ///
+    ///This is meant for combining several `reverse_geocode` calls for the same point made with
+    ///different `granularity` settings. Places are compared by ID, and when the same place appears
+    ///in more than one result, the first copy is kept. Places with the same `place_type` keep the
+    ///order they were given in.
+    pub fn merge(results: impl IntoIterator<Item = SearchResult>) -> Vec<Place> {
+        let mut merged: Vec<Place> = Vec::new();
+        for place in results.into_iter().flat_map(|result| result.results) {
+            if !merged.contains(&place) {
+                merged.push(place);
+            }
+        }
+        merged.sort_by_key(|place| place.place_type);
+        merged
+    }
+}
+
 ///Represents a `reverse_geocode` query before it is sent.
 ///
 ///The available methods on this builder struct allow you to specify optional parameters to the
@@ -143,9 +299,9 @@ pub struct GeocodeBuilder {
 
 impl GeocodeBuilder {
     ///Begins building a reverse-geocode query with the given coordinate.
-    fn new(latitude: f64, longitude: f64) -> Self {
+    fn new(coordinate: impl IntoCoordinate) -> Self {
         GeocodeBuilder {
-            coordinate: (latitude, longitude),
+            coordinate: coordinate.lat_long(),
             accuracy: None,
             granularity: None,
             max_results: None,
@@ -229,7 +385,7 @@ pub struct SearchBuilder {
     granularity: Option<PlaceType>,
     max_results: Option<u32>,
     contained_within: Option<String>,
-    attributes: Option<HashMap<String, String>>,
+    attributes: Option<HashMap<String, Vec<String>>>,
 }
 
 impl SearchBuilder {
@@ -299,9 +455,37 @@ impl SearchBuilder {
     ///
     ///For example, `.attribute("street_address", "123 Main St")` searches for places with the
     ///given street address.
+    ///
+    ///Calling this with an `attribute_key` that was already given replaces any values that were
+    ///set for it, including ones set with `attribute_any`.
     pub fn attribute(self, attribute_key: String, attribute_value: String) -> Self {
         let mut attrs = self.attributes.unwrap_or_default();
-        attrs.insert(attribute_key, attribute_value);
+        attrs.insert(attribute_key, vec![attribute_value]);
+
+        SearchBuilder {
+            attributes: Some(attrs),
+            ..self
+        }
+    }
+
+    ///Restricts results to those where the given attribute matches any of the given values.
+    ///
+    ///This sends the `attribute:{attribute_key}` parameter once for each value, which Twitter
+    ///treats as an OR: a place matches if its attribute is equal to any one of them. This is
+    ///useful for things like a phone number that may be written in a few different formats.
+    ///
+    ///Values given here are added to any that were already set for the same key, whether by
+    ///`attribute` or by an earlier call to `attribute_any`. Different keys are still combined the
+    ///same way as with `attribute`.
+    ///
+    ///For example, `.attribute_any("phone".to_string(), &["+1 555 123 4567", "555-123-4567"])`
+    ///searches for places with either phone number.
+    pub fn attribute_any(self, attribute_key: String, attribute_values: &[&str]) -> Self {
+        let mut attrs = self.attributes.unwrap_or_default();
+        attrs
+            .entry(attribute_key)
+            .or_default()
+            .extend(attribute_values.iter().map(|v| v.to_string()));
 
         SearchBuilder {
             attributes: Some(attrs),
@@ -311,6 +495,12 @@ impl SearchBuilder {
 
     ///Finalize the search parameters and return the results collection.
     pub async fn call(&self, token: &auth::Token) -> Result<Response<SearchResult>, error::Error> {
+        let req = get(links::place::SEARCH, token, Some(&self.params()));
+        request_with_json_response(req).await
+    }
+
+    ///Assembles the parameters for this search.
+    fn params(&self) -> ParamList {
         let mut params = match &self.query {
             PlaceQuery::LatLon(lat, long) => ParamList::new()
                 .add_param("lat", lat.to_string())
@@ -324,13 +514,14 @@ impl SearchBuilder {
         .add_opt_param("contained_within", self.contained_within.map_string());
 
         if let Some(ref attrs) = self.attributes {
-            for (k, v) in attrs {
-                params.add_param_ref(format!("attribute:{}", k), v.clone());
+            for (k, vals) in attrs {
+                for v in vals {
+                    params.push_param(format!("attribute:{}", k), v.clone());
+                }
             }
         }
 
-        let req = get(links::place::SEARCH, token, Some(&params));
-        request_with_json_response(req).await
+        params
     }
 }
 
@@ -355,6 +546,397 @@ impl fmt::Display for Accuracy {
     }
 }
 
+///A place and the places contained within it, as assembled by `build_tree`.
+#[derive(Debug, Clone)]
+pub struct PlaceNode {
+    ///The place represented by this node.
+    pub place: Place,
+    ///The places that list this one in their `contained_within`, in the order they were given.
+    pub children: Vec<PlaceNode>,
+}
+
+///Organizes a set of places into a hierarchy, using their `contained_within` fields.
+///
+///Each place is nested under the first place in its `contained_within` list that also appears in
+///`results`. Places whose parents aren't in `results` (or that don't list any parents) are
+///returned as the roots of the hierarchy. Both the roots and the children of each node are kept in
+///the same order they were given in.
+///
+///This is meant to be used with the results of a location search, which can return places of
+///several different granularities at once. If the same place ID appears more than once, only the
+///first one is used as a parent. If the places' `contained_within` fields form a loop, it's broken
+///up so that every place is still returned exactly once.
+pub fn build_tree(results: Vec<Place>) -> Vec<PlaceNode> {
+    let mut ids = HashMap::new();
+    for (ix, place) in results.iter().enumerate() {
+        ids.entry(place.id.as_str()).or_insert(ix);
+    }
+
+    let mut parents = results
+        .iter()
+        .enumerate()
+        .map(|(ix, place)| {
+            place
+                .contained_within
+                .iter()
+                .flatten()
+                .filter_map(|parent| ids.get(parent.id.as_str()).cloned())
+                .find(|&parent| parent != ix)
+        })
+        .collect::<Vec<_>>();
+
+    // walking up from any place should reach a root within `len` steps; if it doesn't, we've
+    // landed in a loop, so cut it where we are and try again
+    for ix in 0..parents.len() {
+        loop {
+            let mut current = ix;
+            let mut steps = 0;
+            while let Some(parent) = parents[current] {
+                if steps == parents.len() {
+                    break;
+                }
+                current = parent;
+                steps += 1;
+            }
+            if parents[current].is_none() {
+                break;
+            }
+            parents[current] = None;
+        }
+    }
+
+    let mut children = vec![Vec::new(); results.len()];
+    let mut roots = Vec::new();
+    for (ix, parent) in parents.iter().enumerate() {
+        match parent {
+            Some(parent) => children[*parent].push(ix),
+            None => roots.push(ix),
+        }
+    }
+
+    fn assemble(ix: usize, places: &mut [Option<Place>], children: &[Vec<usize>]) -> PlaceNode {
+        PlaceNode {
+            place: places[ix].take().expect("place added to tree twice"),
+            children: children[ix]
+                .iter()
+                .map(|&child| assemble(child, places, children))
+                .collect(),
+        }
+    }
+
+    let mut places = results.into_iter().map(Some).collect::<Vec<_>>();
+    roots
+        .into_iter()
+        .map(|root| assemble(root, &mut places, &children))
+        .collect()
+}
+
+///The mean radius of the Earth, in meters.
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+///Returns the great-circle distance between two points on the Earth, in meters.
+///
+///The points are given as (latitude, longitude) pairs in degrees, the same as the `coordinates`
+///field on a `Tweet`. This uses the haversine formula with the Earth's mean radius, which is
+///accurate to within about half a percent anywhere on the globe. Note that a place's
+///`bounding_box` lists its corners as (longitude, latitude) instead, so swap those coordinates
+///before handing them to this function.
+pub fn haversine_distance_m(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lon1) = (from.0.to_radians(), from.1.to_radians());
+    let (lat2, lon2) = (to.0.to_radians(), to.1.to_radians());
+
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    // rounding can push `a` just past 1 for (nearly) antipodal points
+    2.0 * EARTH_RADIUS_M * a.sqrt().min(1.0).asin()
+}
+
+///Runs Douglas-Peucker over the given (longitude, latitude) outline, for
+///`Place::simplify_bounding_box`.
+fn simplify_outline(corners: &[(f64, f64)], tolerance_m: f64) -> Vec<(f64, f64)> {
+    if corners.len() < 3 {
+        return corners.to_vec();
+    }
+
+    // project the corners onto a flat plane in meters, scaling longitude by the outline's average
+    // latitude. bounding boxes are small enough that this is close to the true distances
+    let mean_lat = corners.iter().map(|c| c.1).sum::<f64>() / corners.len() as f64;
+    let scale = EARTH_RADIUS_M * std::f64::consts::PI / 180.0;
+    let points = corners
+        .iter()
+        .map(|&(long, lat)| (long * scale * mean_lat.to_radians().cos(), lat * scale))
+        .collect::<Vec<_>>();
+
+    let mut keep = vec![false; corners.len()];
+    keep[0] = true;
+    keep[corners.len() - 1] = true;
+    let mut spans = vec![(0, corners.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let distance = |idx| segment_distance(points[idx], points[first], points[last]);
+        let farthest = (first + 1..last)
+            .map(|idx| (idx, distance(idx)))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+        if let Some((idx, dist)) = farthest {
+            if dist > tolerance_m {
+                keep[idx] = true;
+                spans.push((first, idx));
+                spans.push((idx, last));
+            }
+        }
+    }
+
+    corners
+        .iter()
+        .zip(keep)
+        .filter(|&(_, keep)| keep)
+        .map(|(&corner, _)| corner)
+        .collect()
+}
+
+///Returns the distance from `point` to the line segment from `start` to `end`, on a flat plane.
+fn segment_distance(point: (f64, f64), start: (f64, f64), end: (f64, f64)) -> f64 {
+    let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+    let length_sq = dx * dx + dy * dy;
+    let t = if length_sq > 0.0 {
+        (((point.0 - start.0) * dx + (point.1 - start.1) * dy) / length_sq).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let (x, y) = (start.0 + t * dx, start.1 + t * dy);
+    ((point.0 - x).powi(2) + (point.1 - y).powi(2)).sqrt()
+}
+
+///How much each part of a place's score counts for in `score_results_with`.
+///
+///The weights don't need to add up to 1; a place's score is the weighted average of its parts,
+///so only their proportions matter. The default counts name similarity for 60% of the score and
+///distance for 40%.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ScoreWeights {
+    ///How much the similarity between the query and the place's name counts for.
+    pub similarity: f64,
+    ///How much the distance between the place and the given point counts for.
+    pub distance: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> ScoreWeights {
+        ScoreWeights {
+            similarity: 0.6,
+            distance: 0.4,
+        }
+    }
+}
+
+///The distance, in meters, at which a place's distance score drops to 1/e, or about 0.37.
+const DISTANCE_SCALE_M: f64 = 10_000.0;
+
+///Scores the given search results by how well they match the given query and how close they are
+///to the given point, and sorts them from best to worst.
+///
+///This uses the default `ScoreWeights`; see `score_results_with` for details of how the scores
+///are calculated.
+pub fn score_results(
+    results: Vec<Place>,
+    query: &str,
+    near: Option<(f64, f64)>,
+) -> Vec<(Place, f64)> {
+    score_results_with(results, query, near, ScoreWeights::default())
+}
+
+///Scores the given search results by how well they match the given query and how close they are
+///to the given point, with the given weights, and sorts them from best to worst.
+///
+///Every score is between 0 and 1, and is made of two parts:
+///
+///* The similarity between `query` and the place's `name` or `full_name`, whichever is closer.
+///  This compares the pairs of letters in each word, ignoring case and punctuation, so that
+///  `"portland"` is a perfect match for a place named "Portland", and a close match for
+///  "Portland, OR" or "Portland Heights".
+///* The distance from `near` to the place's `centroid`, which counts for 1 at the centroid itself
+///  and falls off exponentially from there, to about 0.37 at 10 km away. A place with no bounding
+///  box gets no credit for distance.
+///
+///If `near` is `None`, places are scored on similarity alone. Places with the same score are left
+///in the order they were given.
+pub fn score_results_with(
+    results: Vec<Place>,
+    query: &str,
+    near: Option<(f64, f64)>,
+    weights: ScoreWeights,
+) -> Vec<(Place, f64)> {
+    let query = bigrams(query);
+    let mut scored = results
+        .into_iter()
+        .map(|place| {
+            let similarity = f64::max(
+                bigram_similarity(&query, &bigrams(&place.name)),
+                bigram_similarity(&query, &bigrams(&place.full_name)),
+            );
+            let score = match near {
+                Some(near) => {
+                    let distance = place
+                        .centroid()
+                        .map(|center| {
+                            (-haversine_distance_m(near, center) / DISTANCE_SCALE_M).exp()
+                        })
+                        .unwrap_or(0.0);
+                    let total = weights.similarity + weights.distance;
+                    if total > 0.0 {
+                        (weights.similarity * similarity + weights.distance * distance) / total
+                    } else {
+                        0.0
+                    }
+                }
+                None => similarity,
+            };
+            (place, score)
+        })
+        .collect::<Vec<_>>();
+
+    scored.sort_by(|(_, left), (_, right)| right.partial_cmp(left).unwrap_or(Ordering::Equal));
+    scored
+}
+
+///Splits the given text into lowercase words, and returns every pair of adjacent letters in them.
+fn bigrams(text: &str) -> Vec<(char, char)> {
+    let mut pairs = Vec::new();
+    for word in text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+    {
+        let chars = word.to_lowercase().chars().collect::<Vec<_>>();
+        if chars.len() == 1 {
+            pairs.push((chars[0], ' '));
+        }
+        pairs.extend(chars.windows(2).map(|pair| (pair[0], pair[1])));
+    }
+    pairs
+}
+
+///Returns the Sørensen–Dice coefficient of the given sets of letter pairs: 1 if they're the
+///same, 0 if they have nothing in common.
+fn bigram_similarity(left: &[(char, char)], right: &[(char, char)]) -> f64 {
+    if left.is_empty() || right.is_empty() {
+        return 0.0;
+    }
+
+    let mut unmatched = right.to_vec();
+    let mut shared = 0;
+    for pair in left {
+        if let Some(idx) = unmatched.iter().position(|other| other == pair) {
+            unmatched.swap_remove(idx);
+            shared += 1;
+        }
+    }
+    (2 * shared) as f64 / (left.len() + right.len()) as f64
+}
+
+///Cleans up a user-entered location to use as a search query, returning `None` if nothing
+///usable is left.
+fn normalize_location(location: &str) -> Option<String> {
+    let cleaned = location
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ',' | '.' | '\'' | '-') {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect::<String>();
+
+    let query = cleaned
+        .split(',')
+        .map(|part| part.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    if query.is_empty() {
+        None
+    } else {
+        Some(query)
+    }
+}
+
+///Picks the first city from the given search results, or the first administrative area if there
+///are no cities.
+fn best_location_match(results: Vec<Place>) -> Option<Place> {
+    let mut admin = None;
+
+    for place in results {
+        match place.place_type {
+            PlaceType::City => return Some(place),
+            PlaceType::Admin if admin.is_none() => admin = Some(place),
+            _ => (),
+        }
+    }
+
+    admin
+}
+
+///Picks the place from a reverse geocode that best matches the given coordinate at the given
+///granularity.
+///
+///Places of the requested type are preferred, then the next broadest type, and so on; places
+///more specific than requested are never picked. Among places of the same type, ones whose
+///bounding box contains the coordinate come first, then the ones whose centers are closest to it.
+fn best_geocode_match(
+    results: Vec<Place>,
+    coordinate: (f64, f64),
+    granularity: PlaceType,
+) -> Option<Place> {
+    let rank = |place: &Place| {
+        let contains = bounding_box_contains(place, coordinate);
+        let distance = place.centroid().map_or(f64::INFINITY, |center| {
+            haversine_distance_m(coordinate, center)
+        });
+        (place.place_type, !contains, distance)
+    };
+
+    results
+        .into_iter()
+        .filter(|place| place.place_type >= granularity)
+        .map(|place| (rank(&place), place))
+        .min_by(|(left, _), (right, _)| left.partial_cmp(right).unwrap_or(Ordering::Equal))
+        .map(|(_, place)| place)
+}
+
+///Returns whether the corners of the given place's bounding box surround the given (latitude,
+///longitude) coordinate.
+fn bounding_box_contains(place: &Place, (lat, long): (f64, f64)) -> bool {
+    if place.bounding_box.is_empty() {
+        return false;
+    }
+
+    let (mut min_long, mut min_lat) = (f64::INFINITY, f64::INFINITY);
+    let (mut max_long, mut max_lat) = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+    for &(corner_long, corner_lat) in &place.bounding_box {
+        min_long = min_long.min(corner_long);
+        max_long = max_long.max(corner_long);
+        min_lat = min_lat.min(corner_lat);
+        max_lat = max_lat.max(corner_lat);
+    }
+    (min_lat..=max_lat).contains(&lat) && (min_long..=max_long).contains(&long)
+}
+
+///Helper function for `venue_account` that looks up the place's Twitter account with the given
+///function, if it has one.
+async fn venue_account_with<F, Fut>(
+    place: &Place,
+    lookup: F,
+) -> Result<Option<TwitterUser>, error::Error>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<Response<TwitterUser>, error::Error>>,
+{
+    match place.twitter_handle() {
+        Some(handle) => Ok(Some(lookup(handle.to_string()).await?.response)),
+        None => Ok(None),
+    }
+}
+
 mod serde_bounding_box {
     use serde::{Serialize, Deserialize, Serializer, Deserializer};
     use serde::de::Error;
@@ -378,7 +960,8 @@ mod serde_bounding_box {
     {
         #[derive(Serialize)]
         struct SerBox {
-            coordinates: Vec<(f64, f64)>,
+            // bounding boxes are given as a list of polygons, of which Twitter only uses the first
+            coordinates: Vec<Vec<(f64, f64)>>,
             #[serde(rename = "type")]
             box_type: BoxType,
         }
@@ -398,7 +981,7 @@ mod serde_bounding_box {
                 };
 
                 SerBox {
-                    coordinates: src.clone(),
+                    coordinates: vec![src.clone()],
                     box_type,
                 }
             }
@@ -408,3 +991,456 @@ mod serde_bounding_box {
         out.serialize(ser)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::load_file;
+
+    fn place(name: &str, place_type: PlaceType) -> Place {
+        Place {
+            id: name.to_lowercase(),
+            attributes: HashMap::new(),
+            bounding_box: vec![],
+            country: "United States".to_string(),
+            country_code: "US".to_string(),
+            full_name: name.to_string(),
+            name: name.to_string(),
+            place_type,
+            contained_within: None,
+        }
+    }
+
+    fn place_within(name: &str, place_type: PlaceType, parent: &Place) -> Place {
+        Place {
+            contained_within: Some(vec![parent.clone()]),
+            ..place(name, place_type)
+        }
+    }
+
+    #[test]
+    fn simplify_bounding_box() {
+        // a closed square about 1km on a side, with extra corners along two of its edges that are
+        // less than a meter out of line
+        let mut square = place("Square", PlaceType::Neighborhood);
+        square.bounding_box = vec![
+            (-0.1300, 51.5000),
+            (-0.1250, 51.500004),
+            (-0.1200, 51.5000),
+            (-0.1200, 51.5045),
+            (-0.1200, 51.5090),
+            (-0.1250, 51.509003),
+            (-0.1300, 51.5090),
+            (-0.130005, 51.5045),
+            (-0.1300, 51.5000),
+        ];
+
+        assert_eq!(
+            square.simplify_bounding_box(5.0),
+            vec![
+                (-0.1300, 51.5000),
+                (-0.1200, 51.5000),
+                (-0.1200, 51.5090),
+                (-0.1300, 51.5090),
+                (-0.1300, 51.5000),
+            ]
+        );
+        // with a tiny tolerance, only the corner exactly in line with its neighbors is dropped
+        let mut corners = square.bounding_box.clone();
+        corners.remove(3);
+        assert_eq!(square.simplify_bounding_box(0.01), corners);
+
+        // with a huge tolerance, only the ends of the ring are left
+        assert_eq!(
+            square.simplify_bounding_box(1_000_000.0),
+            vec![(-0.1300, 51.5000), (-0.1300, 51.5000)]
+        );
+    }
+
+    #[test]
+    fn bounding_box_round_trip() {
+        // `deserialize` reads the first polygon out of the nested list Twitter sends, so
+        // `serialize` has to write the same nesting for a saved `Place` (or a tweet with one) to
+        // load again
+        let mut square = place("Square", PlaceType::Neighborhood);
+        square.bounding_box = vec![
+            (-0.1300, 51.5000),
+            (-0.1200, 51.5000),
+            (-0.1200, 51.5090),
+            (-0.1300, 51.5090),
+        ];
+
+        let json = serde_json::to_value(&square).unwrap();
+        assert_eq!(json["bounding_box"]["type"], "Polygon");
+        assert_eq!(json["bounding_box"]["coordinates"][0][2][1], 51.5090);
+        let loaded: Place = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.bounding_box, square.bounding_box);
+    }
+
+    #[test]
+    fn custom_coordinates() {
+        struct Pin {
+            lat: f64,
+            lon: f64,
+        }
+
+        impl IntoCoordinate for Pin {
+            fn lat_long(&self) -> (f64, f64) {
+                (self.lat, self.lon)
+            }
+        }
+
+        let pin = Pin {
+            lat: 51.507222,
+            lon: -0.1275,
+        };
+        assert_eq!(reverse_geocode(&pin).coordinate, (51.507222, -0.1275));
+        assert_eq!(reverse_geocode(pin).coordinate, (51.507222, -0.1275));
+        assert_eq!(
+            reverse_geocode((40.7128, -74.006)).coordinate,
+            (40.7128, -74.006)
+        );
+        match search_point((40.7128, -74.006)).query {
+            PlaceQuery::LatLon(lat, lon) => assert_eq!((lat, lon), (40.7128, -74.006)),
+            _ => panic!("search_point should search by coordinate"),
+        }
+    }
+
+    #[test]
+    fn merge_search_results() {
+        let portland = place("Portland", PlaceType::City);
+        let oregon = place("Oregon", PlaceType::Admin);
+        let pearl = place("Pearl", PlaceType::Neighborhood);
+        let city_search = SearchResult {
+            url: "city".to_string(),
+            results: vec![oregon.clone(), portland.clone()],
+        };
+        let neighborhood_search = SearchResult {
+            url: "neighborhood".to_string(),
+            results: vec![
+                Place {
+                    full_name: "Portland, OR".to_string(),
+                    ..portland.clone()
+                },
+                pearl.clone(),
+                oregon.clone(),
+            ],
+        };
+
+        let merged = SearchResult::merge(vec![city_search, neighborhood_search]);
+
+        assert_eq!(merged, vec![pearl, portland, oregon]);
+        // the first copy of a duplicated place is the one that's kept
+        assert_eq!(merged[1].full_name, "Portland");
+    }
+
+    #[test]
+    fn score_by_name_and_distance() {
+        // a place with a small bounding box around the given (latitude, longitude) point
+        let around = |name: &str, full_name: &str, place_type, (lat, long): (f64, f64)| Place {
+            bounding_box: vec![
+                (long - 0.01, lat - 0.01),
+                (long + 0.01, lat - 0.01),
+                (long + 0.01, lat + 0.01),
+                (long - 0.01, lat + 0.01),
+            ],
+            full_name: full_name.to_string(),
+            ..place(name, place_type)
+        };
+        let results = vec![
+            around("Salem", "Salem, OR", PlaceType::City, (44.94, -123.04)),
+            around("Portland", "Portland, ME", PlaceType::City, (43.66, -70.26)),
+            around(
+                "Portland Heights",
+                "Portland Heights, Portland",
+                PlaceType::Neighborhood,
+                (45.51, -122.70),
+            ),
+            around(
+                "Portland",
+                "Portland, OR",
+                PlaceType::City,
+                (45.52, -122.68),
+            ),
+        ];
+        let names = |scored: &[(Place, f64)]| {
+            scored
+                .iter()
+                .map(|(place, _)| place.full_name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let scored = score_results(results.clone(), "portland", Some((45.52, -122.68)));
+        assert_eq!(
+            names(&scored),
+            vec![
+                "Portland, OR",
+                "Portland Heights, Portland",
+                "Portland, ME",
+                "Salem, OR"
+            ]
+        );
+        assert!((scored[0].1 - 1.0).abs() < 1e-9, "{}", scored[0].1);
+        for &(_, score) in &scored {
+            assert!((0.0..=1.0).contains(&score), "{}", score);
+        }
+
+        // without a location, both cities named Portland are perfect matches, so they stay in
+        // their original order
+        let scored = score_results(results.clone(), "Portland", None);
+        assert_eq!(
+            names(&scored),
+            vec![
+                "Portland, ME",
+                "Portland, OR",
+                "Portland Heights, Portland",
+                "Salem, OR"
+            ]
+        );
+        // "Salem, OR" only has the "or" in common with "portland"
+        assert!(scored[3].1 < 0.2, "{}", scored[3].1);
+
+        // counting only distance puts Portland Heights and Salem ahead of Maine
+        let weights = ScoreWeights {
+            similarity: 0.0,
+            distance: 1.0,
+        };
+        let scored = score_results_with(results, "portland", Some((45.52, -122.68)), weights);
+        assert_eq!(
+            names(&scored),
+            vec![
+                "Portland, OR",
+                "Portland Heights, Portland",
+                "Salem, OR",
+                "Portland, ME"
+            ]
+        );
+    }
+
+    #[test]
+    fn localized_names() {
+        let mut attributes = HashMap::new();
+        attributes.insert("name:fr".to_string(), "Tour Eiffel".to_string());
+        attributes.insert("name:pt-BR".to_string(), "Torre Eiffel".to_string());
+        attributes.insert("street_address".to_string(), "Champ de Mars".to_string());
+        let tower = Place {
+            attributes,
+            full_name: "Eiffel Tower".to_string(),
+            ..place("Eiffel Tower", PlaceType::PointOfInterest)
+        };
+
+        assert_eq!(tower.localized_name("pt-BR"), "Torre Eiffel");
+        assert_eq!(tower.localized_name("fr"), "Tour Eiffel");
+        assert_eq!(tower.localized_name("fr-CA"), "Tour Eiffel");
+        assert_eq!(tower.localized_name("fr_CA"), "Tour Eiffel");
+        // there's a name for Brazilian Portuguese, but not for Portuguese in general
+        assert_eq!(tower.localized_name("pt"), "Eiffel Tower");
+        assert_eq!(tower.localized_name("en"), "Eiffel Tower");
+    }
+
+    #[test]
+    fn place_tree() {
+        let oregon = place("Oregon", PlaceType::Admin);
+        let portland = place_within("Portland", PlaceType::City, &oregon);
+        let salem = place_within("Salem", PlaceType::City, &oregon);
+        let pearl = place_within("Pearl District", PlaceType::Neighborhood, &portland);
+        let hawthorne = place_within("Hawthorne", PlaceType::Neighborhood, &portland);
+        let seattle = place("Seattle", PlaceType::City);
+        let belltown = place_within("Belltown", PlaceType::Neighborhood, &seattle);
+
+        // results aren't necessarily ordered by granularity
+        let results = vec![pearl, portland, belltown, oregon, hawthorne, salem];
+        let tree = build_tree(results);
+
+        // Belltown's parent wasn't in the results, so it's a root alongside Oregon
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].place.name, "Belltown");
+        assert!(tree[0].children.is_empty());
+
+        let oregon = &tree[1];
+        assert_eq!(oregon.place.name, "Oregon");
+        let cities = oregon
+            .children
+            .iter()
+            .map(|n| n.place.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(cities, vec!["Portland", "Salem"]);
+
+        let portland = &oregon.children[0];
+        let hoods = portland
+            .children
+            .iter()
+            .map(|n| n.place.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(hoods, vec!["Pearl District", "Hawthorne"]);
+        assert!(portland.children.iter().all(|n| n.children.is_empty()));
+        assert!(oregon.children[1].children.is_empty());
+    }
+
+    #[test]
+    fn place_tree_loop() {
+        let a = place("A", PlaceType::City);
+        let b = place_within("B", PlaceType::City, &a);
+        let a = place_within("A", PlaceType::City, &b);
+        let c = place_within("C", PlaceType::Neighborhood, &a);
+
+        let tree = build_tree(vec![c, a, b]);
+        fn count(nodes: &[PlaceNode]) -> usize {
+            nodes.iter().map(|n| 1 + count(&n.children)).sum()
+        }
+        assert_eq!(count(&tree), 3);
+        assert_eq!(tree.len(), 1);
+    }
+
+    #[test]
+    fn haversine() {
+        let paris = (48.8566, 2.3522);
+        let london = (51.5074, -0.1278);
+        let distance = haversine_distance_m(paris, london);
+        assert!((distance - 343_560.0).abs() < 1_000.0, "{}", distance);
+        assert_eq!(distance, haversine_distance_m(london, paris));
+
+        assert_eq!(haversine_distance_m(paris, paris), 0.0);
+
+        let half_way = std::f64::consts::PI * EARTH_RADIUS_M;
+        let antipodal = haversine_distance_m((0.0, 0.0), (0.0, 180.0));
+        assert!((antipodal - half_way).abs() < 1.0);
+    }
+
+    #[tokio::test]
+    async fn venue_accounts() {
+        let sample = load_file("sample_payloads/user_array.json");
+        let user = serde_json::from_str::<Vec<TwitterUser>>(&sample)
+            .unwrap()
+            .remove(0);
+        let rate_limit = RateLimit {
+            limit: 900,
+            remaining: 900,
+            reset: 0,
+        };
+
+        let mut venue = place("Powell's City of Books", PlaceType::PointOfInterest);
+        venue
+            .attributes
+            .insert("twitter".to_string(), "@Powells".to_string());
+        assert_eq!(venue.twitter_handle(), Some("Powells"));
+
+        let account = venue_account_with(&venue, |handle| {
+            assert_eq!(handle, "Powells");
+            futures::future::ready(Ok(Response::new(rate_limit, user.clone())))
+        })
+        .await
+        .unwrap();
+        assert_eq!(account.map(|u| u.id), Some(user.id));
+
+        let city = place("Portland", PlaceType::City);
+        assert_eq!(city.twitter_handle(), None);
+        let account = venue_account_with(&city, |_| async {
+            panic!("looked up a user for a place without a handle")
+        })
+        .await
+        .unwrap();
+        assert!(account.is_none());
+    }
+
+    #[test]
+    fn user_location_match() {
+        let sample = load_file("sample_payloads/user_array.json");
+        let mut user = serde_json::from_str::<Vec<TwitterUser>>(&sample)
+            .unwrap()
+            .remove(0);
+        user.location = Some("Portland, OR".to_string());
+
+        let query = user.location.as_deref().and_then(normalize_location);
+        assert_eq!(query.as_deref(), Some("Portland, OR"));
+        assert_eq!(
+            normalize_location(" 🌲 Portland ,  OR 🌧 ").as_deref(),
+            Some("Portland, OR")
+        );
+        assert_eq!(normalize_location(" ✨🌈✨ "), None);
+        assert_eq!(normalize_location(""), None);
+
+        let results = vec![
+            place("Portland Pizza Co.", PlaceType::PointOfInterest),
+            place("Pearl District", PlaceType::Neighborhood),
+            place("Oregon, USA", PlaceType::Admin),
+            place("Portland, OR", PlaceType::City),
+            place("Portland, ME", PlaceType::City),
+        ];
+        let best = best_location_match(results).unwrap();
+        assert_eq!(best.full_name, "Portland, OR");
+
+        let results = vec![
+            place("Portland Pizza Co.", PlaceType::PointOfInterest),
+            place("Oregon, USA", PlaceType::Admin),
+            place("United States", PlaceType::Country),
+        ];
+        let best = best_location_match(results).unwrap();
+        assert_eq!(best.full_name, "Oregon, USA");
+
+        let results = vec![place("Portland Pizza Co.", PlaceType::PointOfInterest)];
+        assert!(best_location_match(results).is_none());
+    }
+
+    #[test]
+    fn geocode_match() {
+        // (longitude, latitude) corners of a box around the given (latitude, longitude) center
+        fn around(name: &str, place_type: PlaceType, (lat, long): (f64, f64), size: f64) -> Place {
+            Place {
+                bounding_box: vec![
+                    (long - size, lat - size),
+                    (long + size, lat - size),
+                    (long + size, lat + size),
+                    (long - size, lat + size),
+                ],
+                ..place(name, place_type)
+            }
+        }
+        let here = (45.5231, -122.6765);
+
+        let results = vec![
+            around("Pioneer Square", PlaceType::PointOfInterest, here, 0.001),
+            around("Beaverton, OR", PlaceType::City, (45.4871, -122.8037), 0.05),
+            around("Portland, OR", PlaceType::City, (45.54, -122.65), 0.1),
+            around("Oregon, USA", PlaceType::Admin, (44.0, -120.5), 3.0),
+        ];
+        let best = best_geocode_match(results.clone(), here, PlaceType::City).unwrap();
+        assert_eq!(best.full_name, "Portland, OR");
+        let best = best_geocode_match(results.clone(), here, PlaceType::PointOfInterest).unwrap();
+        assert_eq!(best.full_name, "Pioneer Square");
+
+        // without a place of the requested type, the next broadest one is picked
+        let best = best_geocode_match(results[3..].to_vec(), here, PlaceType::City).unwrap();
+        assert_eq!(best.full_name, "Oregon, USA");
+        assert!(best_geocode_match(results[..1].to_vec(), here, PlaceType::City).is_none());
+
+        // neither city contains the point, so the closest one is picked
+        let elsewhere = (45.40, -122.90);
+        let best = best_geocode_match(results, elsewhere, PlaceType::City).unwrap();
+        assert_eq!(best.full_name, "Beaverton, OR");
+    }
+
+    #[test]
+    fn attribute_any_params() {
+        let search = search_query("coffee")
+            .attribute_any("phone".to_string(), &["+1 555 123 4567", "555-123-4567"])
+            .attribute("street_address".to_string(), "123 Main St".to_string())
+            .attribute_any("phone".to_string(), &["5551234567"]);
+
+        let query = search.params().to_urlencoded();
+        let phones = query
+            .split('&')
+            .filter(|p| p.starts_with("attribute%3Aphone="))
+            .collect::<Vec<_>>();
+        assert_eq!(phones.len(), 3);
+        assert!(phones.contains(&"attribute%3Aphone=%2B1%20555%20123%204567"));
+        assert!(phones.contains(&"attribute%3Aphone=555-123-4567"));
+        assert!(phones.contains(&"attribute%3Aphone=5551234567"));
+        assert!(query.contains("attribute%3Astreet_address=123%20Main%20St"));
+
+        // a single-value attribute replaces what was there before
+        let search = search.attribute("phone".to_string(), "555-000-0000".to_string());
+        let query = search.params().to_urlencoded();
+        assert_eq!(query.matches("attribute%3Aphone=").count(), 1);
+    }
+}
@@ -65,10 +65,16 @@
 //!
 //! If you need the ability to assemble a request in a way that `request_get`, `request_post`, or
 //! `request_post_json` don't allow, the `RequestBuilder` type available in the `auth` submodule
-//! provides the lowest-level control over how a request is built and signed. For more information,
-//! see the [`auth`] module.
+//! provides the lowest-level control over how a request is built and signed. If you're sending the
+//! request yourself, `sign_request` in the same module computes just its `Authorization` header.
+//! For more information, see the [`auth`] module.
 //!
 //! [`auth`]: auth/index.html
+//!
+//! If you're deserializing responses into your own types, `deserialize_twitter_timestamp` and
+//! `deserialize_iso8601` can be given to `#[serde(deserialize_with = "...")]` to load the
+//! timestamps from the v1.1 and v2 APIs, respectively, into a `DateTime<Utc>`. For optional v2
+//! timestamps, use `deserialize_opt_iso8601` along with `#[serde(default)]`.
 
 use hyper::{Body, Request};
 
@@ -153,6 +159,10 @@ pub use crate::common::get_response as response_future;
 pub use crate::common::raw_request as response_raw_bytes;
 pub use crate::common::request_with_json_response as response_json;
 pub use crate::common::request_with_empty_response as response_empty;
+pub use crate::common::with_cancel;
+pub use crate::common::{max_redirects, set_max_redirects};
+pub use crate::common::{max_response_size, set_max_response_size};
+pub use crate::common::{set_client_config, ClientConfig};
 
 /// Converts the given request into a `TwitterStream`.
 ///
@@ -169,6 +179,8 @@ pub fn response_as_stream(req: Request<Body>) -> TwitterStream {
 
 pub use crate::common::RoundTrip;
 
+pub use crate::common::{deserialize_iso8601, deserialize_opt_iso8601, deserialize_twitter_timestamp};
+
 /// Facilities to manually assemble signed requests.
 ///
 /// In case you need to do things that aren't available in the `raw` module, the `RequestBuilder`
@@ -222,6 +234,10 @@ pub use crate::common::RoundTrip;
 /// For more information, see the functions available on `RequestBuilder`.
 pub mod auth {
     pub use crate::auth::raw::RequestBuilder;
+    #[doc(no_inline)]
+    pub use crate::auth::sign_request;
+    #[doc(no_inline)]
+    pub use crate::auth::SignatureMethod;
 
     #[doc(no_inline)]
     pub use hyper::Method;
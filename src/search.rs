@@ -41,15 +41,27 @@
 //! page][search-place]. A future version of egg-mode might break these options into further
 //! methods on `SearchBuilder`.
 //!
+//! If you'd rather have every result at once, `SearchBuilder::stream_all` returns a `Stream` of
+//! tweets that loads older pages as needed, waits out rate limits, and stops at the end of
+//! Twitter's 7-day search window. For a fixed number of results, `SearchBuilder::collect` loads
+//! pages until it has that many tweets, and returns them all at once.
+//!
+//! For searches beyond the last 7 days, `full_archive` searches every tweet through version 2 of
+//! the Twitter API, for projects that have access to it. Its results are loaded as a `Stream` of
+//! pages, which follows the `next_token` of each page.
+//!
 //! [search-doc]: https://developer.twitter.com/en/docs/tweets/search/api-reference/get-search-tweets
 //! [search-place]: https://developer.twitter.com/en/docs/tweets/search/guides/tweets-by-place
 
+use std::collections::HashSet;
 use std::fmt;
+use std::future::Future;
 
+use futures::stream::{self, Stream, StreamExt};
 use serde::{Deserialize, Deserializer};
 
 use crate::common::*;
-use crate::tweet::Tweet;
+use crate::tweet::{self, v2, Tweet};
 use crate::{auth, error, links};
 
 ///Begin setting up a tweet search with the given query.
@@ -57,6 +69,7 @@ pub fn search<S: Into<CowStr>>(query: S) -> SearchBuilder {
     SearchBuilder {
         query: query.into(),
         lang: None,
+        strict_lang: false,
         result_type: None,
         count: None,
         until: None,
@@ -102,6 +115,7 @@ pub struct SearchBuilder {
     ///The text to search for.
     query: CowStr,
     lang: Option<CowStr>,
+    strict_lang: bool,
     result_type: Option<ResultType>,
     count: Option<u32>,
     until: Option<(u32, u32, u32)>,
@@ -120,6 +134,27 @@ impl SearchBuilder {
         }
     }
 
+    ///Sets whether to double-check the language of the returned tweets, after Twitter has
+    ///filtered them with `lang`.
+    ///
+    ///Twitter's language filtering isn't perfect, and searches restricted with `lang` can still
+    ///return tweets that Twitter itself has marked as being in another language. If this is set to
+    ///`true`, any tweet whose `lang` field doesn't match the language given to `lang` is removed
+    ///from the results before they're returned, including from later pages loaded with
+    ///`SearchResult::older` or `newer`. This has no effect if `lang` isn't set. By default, the
+    ///results are returned as Twitter sent them.
+    ///
+    ///Note that since the removed tweets still count against the page size requested with
+    ///`count`, pages may come back with fewer tweets than requested, or even empty. `collect` and
+    ///`stream_all` keep loading pages as usual, but will need to load more of them to gather the
+    ///same number of tweets.
+    pub fn strict_lang(self, strict: bool) -> Self {
+        SearchBuilder {
+            strict_lang: strict,
+            ..self
+        }
+    }
+
     ///Specify the type of search results to include. The default is `Recent`.
     pub fn result_type(self, result_type: ResultType) -> Self {
         SearchBuilder {
@@ -174,12 +209,16 @@ impl SearchBuilder {
         }
     }
 
-    ///Finalize the search terms and return the first page of responses.
-    pub async fn call(self, token: &auth::Token) -> Result<Response<SearchResult>, error::Error> {
-        let params = ParamList::new()
+    ///Returns the language to check the results against, if `strict_lang` is set.
+    fn strict_lang_code(&self) -> Option<CowStr> {
+        self.lang.clone().filter(|_| self.strict_lang)
+    }
+
+    fn params(&self) -> ParamList {
+        ParamList::new()
             .extended_tweets()
-            .add_param("q", self.query)
-            .add_opt_param("lang", self.lang)
+            .add_param("q", self.query.clone())
+            .add_opt_param("lang", self.lang.clone())
             .add_opt_param("result_type", self.result_type.map_string())
             .add_opt_param("count", self.count.map_string())
             .add_opt_param("since_id", self.since_id.map_string())
@@ -191,18 +230,228 @@ impl SearchBuilder {
             )
             .add_opt_param(
                 "geocode",
-                self.geocode.map(|(lat, lon, radius)| match radius {
-                    Distance::Miles(r) => format!("{:.6},{:.6},{}mi", lat, lon, r),
-                    Distance::Kilometers(r) => format!("{:.6},{:.6},{}km", lat, lon, r),
-                }),
-            );
+                self.geocode
+                    .as_ref()
+                    .map(|(lat, lon, radius)| match radius {
+                        Distance::Miles(r) => format!("{:.6},{:.6},{}mi", lat, lon, r),
+                        Distance::Kilometers(r) => format!("{:.6},{:.6},{}km", lat, lon, r),
+                    }),
+            )
+    }
 
+    ///Finalize the search terms and return the first page of responses.
+    pub async fn call(self, token: &auth::Token) -> Result<Response<SearchResult>, error::Error> {
+        let params = self.params();
+        let strict_lang = self.strict_lang_code();
         let req = get(links::statuses::SEARCH, token, Some(&params));
         let mut resp = request_with_json_response::<SearchResult>(req).await?;
 
         resp.response.params = Some(params);
+        resp.response.retain_lang(strict_lang);
         Ok(resp)
     }
+
+    ///Finalize the search terms and load up to `limit` matching tweets, across as many pages as
+    ///needed.
+    ///
+    ///Pages are loaded starting with the most recent tweets and working backward, until `limit`
+    ///tweets have been gathered or Twitter runs out of results. Any tweet that shows up on more
+    ///than one page is only returned once. If `count` hasn't been set, pages of 100 tweets are
+    ///requested, to make as few calls as possible.
+    ///
+    ///The rate-limit information in the returned `Response` is from the last page that was
+    ///loaded. If loading any page fails, including by hitting the rate limit, that error is
+    ///returned and the tweets loaded so far are discarded; use `stream_all` if you'd rather wait
+    ///out the rate limit.
+    pub async fn collect(
+        self,
+        limit: usize,
+        token: &auth::Token,
+    ) -> Result<Response<Vec<Tweet>>, error::Error> {
+        let strict_lang = self.strict_lang_code();
+        let params = SearchBuilder {
+            count: self.count.or(Some(100)),
+            ..self
+        }
+        .params();
+
+        collect_pages(limit, params, strict_lang, |params| {
+            let req = get(links::statuses::SEARCH, token, Some(&params));
+            request_with_json_response::<SearchResult>(req)
+        })
+        .await
+    }
+
+    ///Finalize the search terms and return a `Stream` of every matching tweet.
+    ///
+    ///The returned stream loads pages of results as needed, starting with the most recent tweets
+    ///and working backward. Any tweet that shows up on more than one page is only returned once.
+    ///If Twitter says the rate limit has been reached, the stream waits for the rate limit to
+    ///reset, and then tries again, as long as the process-wide [`RetryBudget`] allows it. The
+    ///stream ends when Twitter returns an empty page, or when it reaches tweets older than the
+    ///7-day window that standard search covers.
+    ///
+    ///If the stream encounters any other error, the error is returned, and the stream ends.
+    ///
+    ///[`RetryBudget`]: ../struct.RetryBudget.html
+    ///
+    ///```rust,no_run
+    ///# use egg_mode::Token;
+    ///# #[tokio::main]
+    ///# async fn main() {
+    ///# let token: Token = unimplemented!();
+    ///use egg_mode::search;
+    ///use futures::{StreamExt, TryStreamExt};
+    ///
+    ///let tweets = search::search("rustlang")
+    ///    .count(100)
+    ///    .stream_all(&token)
+    ///    .try_collect::<Vec<_>>()
+    ///    .await
+    ///    .unwrap();
+    ///# }
+    ///```
+    pub fn stream_all(
+        self,
+        token: &auth::Token,
+    ) -> impl Stream<Item = Result<Tweet, error::Error>> {
+        let params = self.params();
+        let strict_lang = self.strict_lang_code();
+        let token = token.clone();
+        let horizon = tweet::timestamp_to_id(chrono::Utc::now() - chrono::Duration::days(7));
+
+        stream_pages(horizon, params, strict_lang, move |params| {
+            let req = get(links::statuses::SEARCH, &token, Some(&params));
+            request_with_json_response::<SearchResult>(req)
+        })
+    }
+}
+
+///Helper function for `collect` that pages through search results with the given loader until
+///`limit` tweets have been gathered.
+///
+///The loader is called with the parameters for each page, starting with `params`; see
+///`SearchResult::older_params` for how the following pages are requested. At least one page is
+///always loaded, so that there's rate-limit information to return. If `lang` is given, tweets in
+///other languages are skipped.
+async fn collect_pages<F, Fut>(
+    limit: usize,
+    mut params: ParamList,
+    lang: Option<CowStr>,
+    mut load: F,
+) -> Result<Response<Vec<Tweet>>, error::Error>
+where
+    F: FnMut(ParamList) -> Fut,
+    Fut: Future<Output = Result<Response<SearchResult>, error::Error>>,
+{
+    let mut seen = HashSet::new();
+    let mut tweets = Vec::new();
+
+    loop {
+        let page = load(params.clone()).await?;
+        let rate_limit_status = page.rate_limit_status;
+        let next_params = page.response.older_params(&params);
+        let statuses = page.response.statuses;
+
+        let before = seen.len();
+        let min_id = statuses.iter().map(|t| t.id).min();
+        for tweet in statuses {
+            if seen.insert(tweet.id) && tweets.len() < limit && in_lang(&tweet, lang.as_deref()) {
+                tweets.push(tweet);
+            }
+        }
+
+        // stop when we have enough, or when a page gives us nothing new
+        match min_id {
+            Some(_) if tweets.len() < limit && seen.len() > before => params = next_params,
+            _ => return Ok(Response::new(rate_limit_status, tweets)),
+        }
+    }
+}
+
+///Helper function for `stream_all` that pages through search results with the given loader.
+///
+///The loader is called with the parameters for each page, starting with `params`; see
+///`SearchResult::older_params` for how the following pages are requested. Tweets with IDs below
+///`horizon` are dropped, and the stream ends once a page reaches them. If `lang` is given, tweets
+///in other languages are dropped as well.
+fn stream_pages<F, Fut>(
+    horizon: u64,
+    params: ParamList,
+    lang: Option<CowStr>,
+    load: F,
+) -> impl Stream<Item = Result<Tweet, error::Error>>
+where
+    F: FnMut(ParamList) -> Fut,
+    Fut: Future<Output = Result<Response<SearchResult>, error::Error>>,
+{
+    let state = (load, params, HashSet::new(), lang, false);
+    stream::unfold(
+        state,
+        move |(mut load, params, mut seen, lang, done)| async move {
+            if done {
+                return None;
+            }
+
+            let mut attempt = 0;
+            let page = loop {
+                // searches are loaded with GET, so every failure worth retrying is safe to retry
+                let reset = match load(params.clone()).await {
+                    Ok(page) => break page.response,
+                    Err(e) => match retry_reset(&e, true, attempt) {
+                        Some(reset) if retry_budget().try_acquire() => reset,
+                        _ => return Some((vec![Err(e)], (load, params, seen, lang, true))),
+                    },
+                };
+                tokio::time::delay_for(rate_limit_backoff(reset, attempt)).await;
+                attempt += 1;
+            };
+
+            let params = page.older_params(&params);
+            let min_id = page.statuses.iter().map(|t| t.id).min()?;
+            let tweets = page
+                .statuses
+                .into_iter()
+                .filter(|t| t.id >= horizon && seen.insert(t.id))
+                .collect::<Vec<_>>();
+            // if a page gives us nothing new, asking for the next one won't either
+            if tweets.is_empty() {
+                return None;
+            }
+            let tweets = tweets
+                .into_iter()
+                .filter(|t| in_lang(t, lang.as_deref()))
+                .map(Ok)
+                .collect::<Vec<_>>();
+
+            let done = min_id <= horizon;
+            Some((tweets, (load, params, seen, lang, done)))
+        },
+    )
+    .flat_map(stream::iter)
+}
+
+///Parses the parameters out of the `next_results` query string from a page of search results,
+///returning `None` if there aren't any.
+fn parse_next_results(next_results: &str) -> Option<ParamList> {
+    let query = next_results.trim_start_matches('?');
+    let params = url::form_urlencoded::parse(query.as_bytes())
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect::<ParamList>();
+    if params.is_empty() {
+        None
+    } else {
+        Some(params)
+    }
+}
+
+///Returns whether Twitter marked the given tweet as being in the given language, or `true` if no
+///language was given.
+fn in_lang(tweet: &Tweet, lang: Option<&str>) -> bool {
+    match lang {
+        Some(lang) => matches!(tweet.lang.as_deref(), Some(l) if l.eq_ignore_ascii_case(lang)),
+        None => true,
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -236,6 +485,8 @@ impl<'de> Deserialize<'de> for SearchResult {
             max_id: raw.search_metadata.max_id,
             since_id: raw.search_metadata.since_id,
             params: None,
+            strict_lang: None,
+            next_results: raw.search_metadata.next_results,
         })
     }
 }
@@ -253,26 +504,55 @@ pub struct SearchResult {
     ///First tweet id in this page of results. This id can be used in `SearchBuilder::since_tweet`
     pub since_id: u64,
     params: Option<ParamList>,
+    strict_lang: Option<CowStr>,
+    ///The query string Twitter gave to load the next page of results, if there is one.
+    next_results: Option<String>,
 }
 
 impl SearchResult {
-    ///Load the next page of search results for the same query.
-    pub async fn older(&self, token: &auth::Token) -> Result<Response<SearchResult>, error::Error> {
-        let mut params =
-            ParamList::from(self.params.as_ref().cloned().unwrap_or_default()).extended_tweets();
+    ///Removes tweets that aren't in the given language, if one was given, and remembers it for
+    ///later pages.
+    fn retain_lang(&mut self, lang: Option<CowStr>) {
+        self.statuses.retain(|t| in_lang(t, lang.as_deref()));
+        self.strict_lang = lang;
+    }
 
+    ///Returns the parameters to load the page of results after this one, which was loaded with
+    ///the given parameters.
+    ///
+    ///If Twitter gave a `next_results` query string with this page, its parameters are used as-is
+    ///(with `tweet_mode=extended`, so the tweets can be parsed). Otherwise, the parameters for
+    ///this page are reused with `max_id` set to just below the oldest tweet on it.
+    fn older_params(&self, params: &ParamList) -> ParamList {
+        if let Some(next) = self.next_results.as_deref().and_then(parse_next_results) {
+            return next.extended_tweets();
+        }
+
+        let mut params = params.clone().extended_tweets();
         params.remove("since_id");
 
         if let Some(min_id) = self.statuses.iter().map(|t| t.id).min() {
-            params.add_param_ref("max_id", (min_id - 1).to_string());
+            params.add_param_ref("max_id", min_id.saturating_sub(1).to_string());
         } else {
             params.remove("max_id");
         }
 
+        params
+    }
+
+    ///Load the next page of search results for the same query.
+    ///
+    ///If Twitter said where the next page starts, with the `next_results` field of the search
+    ///metadata, that's what gets loaded. Otherwise, this loads the tweets older than the oldest
+    ///one on this page.
+    pub async fn older(&self, token: &auth::Token) -> Result<Response<SearchResult>, error::Error> {
+        let params = self.older_params(self.params.as_ref().unwrap_or(&ParamList::new()));
+
         let req = get(links::statuses::SEARCH, token, Some(&params));
         let mut resp = request_with_json_response::<SearchResult>(req).await?;
 
         resp.response.params = Some(params);
+        resp.response.retain_lang(self.strict_lang.clone());
         Ok(resp)
     }
 
@@ -292,6 +572,483 @@ impl SearchResult {
         let mut resp = request_with_json_response::<SearchResult>(req).await?;
 
         resp.response.params = Some(params);
+        resp.response.retain_lang(self.strict_lang.clone());
         Ok(resp)
     }
 }
+
+///Begin setting up a search of the full archive of tweets with the given query.
+///
+///This uses `2/tweets/search/all` from version 2 of the Twitter API, which needs a Bearer token
+///for a project with access to the full archive. The query uses the version 2 search operators,
+///which differ slightly from the ones `search` takes; see [Twitter's documentation][query] for
+///details.
+///
+///[query]: https://developer.twitter.com/en/docs/twitter-api/tweets/search/integrate/build-a-query
+pub fn full_archive<S: Into<CowStr>>(query: S) -> FullArchiveBuilder {
+    FullArchiveBuilder {
+        query: query.into(),
+        fields: v2::Fields::new(),
+        start_time: None,
+        end_time: None,
+        since_id: None,
+        until_id: None,
+        sort_order: None,
+        max_results: None,
+    }
+}
+
+///Represents the order full-archive search results are returned in.
+#[derive(Debug, Copy, Clone)]
+pub enum SortOrder {
+    ///Return the most recent tweets first. This is the default.
+    Recency,
+    ///Return the most relevant tweets first.
+    Relevancy,
+}
+
+///Display impl that turns the variants into strings that can be used as search parameters.
+impl fmt::Display for SortOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SortOrder::Recency => write!(f, "recency"),
+            SortOrder::Relevancy => write!(f, "relevancy"),
+        }
+    }
+}
+
+///Represents a full-archive tweet search before being sent.
+#[derive(Debug, Clone)]
+#[must_use = "FullArchiveBuilder is lazy and won't do anything unless `pages` is called"]
+pub struct FullArchiveBuilder {
+    ///The text to search for.
+    query: CowStr,
+    fields: v2::Fields,
+    start_time: Option<chrono::DateTime<chrono::Utc>>,
+    end_time: Option<chrono::DateTime<chrono::Utc>>,
+    since_id: Option<u64>,
+    until_id: Option<u64>,
+    sort_order: Option<SortOrder>,
+    max_results: Option<u32>,
+}
+
+impl FullArchiveBuilder {
+    ///Sets the fields and expansions to load for each tweet. By default, only each tweet's `id`
+    ///and `text` are loaded.
+    pub fn fields(self, fields: v2::Fields) -> Self {
+        FullArchiveBuilder { fields, ..self }
+    }
+
+    ///Restricts results to tweets posted at or after the given time. By default, Twitter only
+    ///returns tweets from the last 30 days.
+    pub fn start_time(self, start_time: chrono::DateTime<chrono::Utc>) -> Self {
+        FullArchiveBuilder {
+            start_time: Some(start_time),
+            ..self
+        }
+    }
+
+    ///Restricts results to tweets posted before the given time.
+    pub fn end_time(self, end_time: chrono::DateTime<chrono::Utc>) -> Self {
+        FullArchiveBuilder {
+            end_time: Some(end_time),
+            ..self
+        }
+    }
+
+    ///Restricts results to those with higher IDs than (i.e. that were posted after) the given
+    ///tweet ID.
+    pub fn since_tweet(self, since_id: u64) -> Self {
+        FullArchiveBuilder {
+            since_id: Some(since_id),
+            ..self
+        }
+    }
+
+    ///Restricts results to those with lower IDs than (i.e. that were posted before) the given
+    ///tweet ID.
+    pub fn until_tweet(self, until_id: u64) -> Self {
+        FullArchiveBuilder {
+            until_id: Some(until_id),
+            ..self
+        }
+    }
+
+    ///Specify the order results are returned in. The default is `Recency`.
+    pub fn sort_order(self, sort_order: SortOrder) -> Self {
+        FullArchiveBuilder {
+            sort_order: Some(sort_order),
+            ..self
+        }
+    }
+
+    ///Set the number of tweets to return per-page, between 10 and 500. The default is 10.
+    pub fn max_results(self, max_results: u32) -> Self {
+        FullArchiveBuilder {
+            max_results: Some(max_results),
+            ..self
+        }
+    }
+
+    fn params(&self) -> ParamList {
+        self.fields
+            .params()
+            .add_param("query", self.query.clone())
+            .add_opt_param("start_time", self.start_time.as_ref().map(v2::time_param))
+            .add_opt_param("end_time", self.end_time.as_ref().map(v2::time_param))
+            .add_opt_param("since_id", self.since_id.map_string())
+            .add_opt_param("until_id", self.until_id.map_string())
+            .add_opt_param("sort_order", self.sort_order.map_string())
+            .add_opt_param("max_results", self.max_results.map_string())
+    }
+
+    ///Finalize the search terms and return a `Stream` of every page of matching tweets.
+    ///
+    ///Each item in the stream is a page of tweets, along with the `includes` for that page; the
+    ///next page is loaded with the page's `next_token` once the stream is polled again. If
+    ///loading a page fails, the error is yielded and the stream ends.
+    ///
+    ///```rust,no_run
+    ///# use egg_mode::Token;
+    ///# #[tokio::main]
+    ///# async fn main() {
+    ///# let token: Token = unimplemented!();
+    ///use egg_mode::search;
+    ///use futures::TryStreamExt;
+    ///
+    ///let start = "2010-01-01T00:00:00Z".parse().unwrap();
+    ///let mut pages = Box::pin(
+    ///    search::full_archive("from:rustlang")
+    ///        .start_time(start)
+    ///        .max_results(500)
+    ///        .pages(&token),
+    ///);
+    ///while let Some(page) = pages.try_next().await.unwrap() {
+    ///    for tweet in &page.data {
+    ///        println!("{}", tweet.text);
+    ///    }
+    ///}
+    ///# }
+    ///```
+    pub fn pages(
+        self,
+        token: &auth::Token,
+    ) -> impl Stream<Item = Result<v2::TweetPage, error::Error>> {
+        let params = self.params();
+        let token = token.clone();
+        v2::paginate(move |next_token| {
+            let params = params.clone().add_opt_param("next_token", next_token);
+            let req = get(links::statuses::SEARCH_ALL_V2, &token, Some(&params));
+            request_with_json_response(req)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_pages, full_archive, search, stream_pages, SearchResult, SortOrder};
+    use crate::common::tests::load_file;
+    use crate::common::{ParamList, RateLimit, Response};
+    use crate::error::Error;
+    use crate::tweet::Tweet;
+
+    use futures::{StreamExt, TryStreamExt};
+
+    fn max_id(params: &ParamList) -> Option<u64> {
+        params.get("max_id").map(|id| id.parse().unwrap())
+    }
+
+    #[tokio::test]
+    async fn collect_up_to_limit() {
+        let sample: Tweet =
+            serde_json::from_str(&load_file("sample_payloads/sample-extended-onepic.json"))
+                .unwrap();
+        let page = |top: u64, remaining: i32| {
+            // pages of 100 tweets, counting down from `top`
+            let statuses = (0..100)
+                .map(|offset| Tweet {
+                    id: top - offset,
+                    ..sample.clone()
+                })
+                .collect();
+            let rate_limit = RateLimit {
+                limit: 180,
+                remaining,
+                reset: 0,
+            };
+            Ok(Response::new(
+                rate_limit,
+                SearchResult {
+                    statuses,
+                    query: "rustlang".to_string(),
+                    max_id: 0,
+                    since_id: 0,
+                    params: None,
+                    strict_lang: None,
+                    next_results: None,
+                },
+            ))
+        };
+
+        let mut calls = Vec::new();
+        let tweets = collect_pages(150, ParamList::new(), None, |params| {
+            let max_id = max_id(&params);
+            calls.push(max_id);
+            let resp = match max_id {
+                None => page(1000, 179),
+                // the second page starts with the last tweet of the first page again
+                Some(900) => page(901, 178),
+                _ => panic!("loaded too many pages"),
+            };
+            futures::future::ready(resp)
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(calls, vec![None, Some(900)]);
+        assert_eq!(tweets.rate_limit_status.remaining, 178);
+        let ids = tweets.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids, (851..=1000).rev().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn stream_all_pages() {
+        let sample: Tweet =
+            serde_json::from_str(&load_file("sample_payloads/sample-extended-onepic.json"))
+                .unwrap();
+        let rate_limit = RateLimit {
+            limit: 180,
+            remaining: 180,
+            reset: 0,
+        };
+        let page = |ids: &[u64]| {
+            let statuses = ids
+                .iter()
+                .rev()
+                .map(|&id| Tweet {
+                    id,
+                    ..sample.clone()
+                })
+                .collect();
+            Ok(Response::new(
+                rate_limit,
+                SearchResult {
+                    statuses,
+                    query: "rustlang".to_string(),
+                    max_id: 0,
+                    since_id: 0,
+                    params: None,
+                    strict_lang: None,
+                    next_results: None,
+                },
+            ))
+        };
+
+        let calls = std::cell::RefCell::new(Vec::new());
+        let limited = std::cell::Cell::new(false);
+        let load = |params: ParamList| {
+            let max_id = max_id(&params);
+            calls.borrow_mut().push(max_id);
+            let resp = match max_id {
+                None => page(&[91, 92, 93, 94, 95, 96, 97, 98, 99, 100]),
+                // the rate limit hits partway through, and the boundary tweet shows up again
+                Some(90) if !limited.get() => {
+                    limited.set(true);
+                    Err(Error::RateLimit(0))
+                }
+                Some(90) => page(&[81, 82, 83, 84, 85, 86, 87, 88, 89, 90, 91]),
+                Some(80) => page(&[71, 72, 73, 74, 75, 76, 77, 78, 79, 80]),
+                _ => page(&[]),
+            };
+            futures::future::ready(resp)
+        };
+
+        let tweets = stream_pages(0, ParamList::new(), None, &load)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let ids = tweets.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids, (71..=100).rev().collect::<Vec<_>>());
+        assert_eq!(
+            calls.replace(Vec::new()),
+            vec![None, Some(90), Some(90), Some(80), Some(70)]
+        );
+
+        // stop once the stream reaches the end of the search window
+        let tweets = stream_pages(85, ParamList::new(), None, &load)
+            .map(|t| t.unwrap().id)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(tweets, (85..=100).rev().collect::<Vec<_>>());
+        assert_eq!(calls.into_inner(), vec![None, Some(90)]);
+    }
+
+    #[tokio::test]
+    async fn next_results_params() {
+        let sample = load_file("sample_payloads/search-next-results.json");
+        let first: SearchResult = serde_json::from_str(&sample).unwrap();
+        let rate_limit = RateLimit {
+            limit: 180,
+            remaining: 180,
+            reset: 0,
+        };
+
+        // the page after this one is requested with exactly what `next_results` says
+        let expected = vec![
+            ("count", "1"),
+            ("include_entities", "1"),
+            ("lang", "en"),
+            ("max_id", "782349500404862975"),
+            ("q", "#rustlang egg-mode"),
+            ("result_type", "recent"),
+            ("tweet_mode", "extended"),
+        ];
+        let sorted = |params: &ParamList| {
+            let mut pairs = params
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<Vec<_>>();
+            pairs.sort();
+            pairs
+        };
+        let expected = expected
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>();
+        let params = search("#rustlang egg-mode").count(100).params();
+        assert_eq!(sorted(&first.older_params(&params)), expected);
+
+        let mut calls = Vec::new();
+        let mut pages = vec![first];
+        let tweets = collect_pages(10, params, None, |params| {
+            calls.push(sorted(&params));
+            let page = pages.pop().unwrap_or(SearchResult {
+                statuses: vec![],
+                query: "#rustlang egg-mode".to_string(),
+                max_id: 0,
+                since_id: 0,
+                params: None,
+                strict_lang: None,
+                next_results: None,
+            });
+            futures::future::ready(Ok(Response::new(rate_limit, page)))
+        })
+        .await
+        .unwrap();
+        assert_eq!(tweets.len(), 1);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[1], expected);
+    }
+
+    #[test]
+    fn lang_params() {
+        let params = search("rustlang").lang("es").params();
+        assert_eq!(params.get("lang").map(|lang| lang.as_ref()), Some("es"));
+
+        let params = search("rustlang").params();
+        assert!(params.get("lang").is_none());
+
+        assert_eq!(search("rustlang").lang("es").strict_lang_code(), None);
+        assert_eq!(
+            search("rustlang")
+                .lang("es")
+                .strict_lang(true)
+                .strict_lang_code()
+                .as_deref(),
+            Some("es")
+        );
+        assert_eq!(
+            search("rustlang").strict_lang(true).strict_lang_code(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn strict_lang_filter() {
+        let sample: Tweet =
+            serde_json::from_str(&load_file("sample_payloads/sample-extended-onepic.json"))
+                .unwrap();
+        let rate_limit = RateLimit {
+            limit: 180,
+            remaining: 180,
+            reset: 0,
+        };
+        // every third tweet was marked as being in English instead
+        let page = |top: u64| {
+            let statuses = (0..10)
+                .map(|offset| Tweet {
+                    id: top - offset,
+                    lang: Some(match (top - offset) % 3 {
+                        0 => "en".to_string(),
+                        _ => "es".to_string(),
+                    }),
+                    ..sample.clone()
+                })
+                .collect();
+            futures::future::ready(Ok(Response::new(
+                rate_limit,
+                SearchResult {
+                    statuses,
+                    query: "rustlang".to_string(),
+                    max_id: 0,
+                    since_id: 0,
+                    params: None,
+                    strict_lang: None,
+                    next_results: None,
+                },
+            )))
+        };
+
+        let tweets = collect_pages(10, ParamList::new(), Some("es".into()), |params| {
+            page(max_id(&params).unwrap_or(100))
+        })
+        .await
+        .unwrap();
+        let ids = tweets.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![100, 98, 97, 95, 94, 92, 91, 89, 88, 86]);
+
+        let tweets = stream_pages(81, ParamList::new(), Some("ES".into()), |params| {
+            page(max_id(&params).unwrap_or(100))
+        })
+        .map(|t| t.unwrap().id)
+        .collect::<Vec<_>>()
+        .await;
+        assert_eq!(
+            tweets,
+            vec![100, 98, 97, 95, 94, 92, 91, 89, 88, 86, 85, 83, 82]
+        );
+    }
+
+    #[test]
+    fn full_archive_params() {
+        use crate::tweet::v2::{Fields, TweetField};
+
+        let start = "2010-01-01T00:00:00.000Z".parse().unwrap();
+        let params = full_archive("from:rustlang")
+            .fields(Fields::new().tweet_fields(vec![TweetField::CreatedAt]))
+            .start_time(start)
+            .sort_order(SortOrder::Relevancy)
+            .max_results(500)
+            .params();
+
+        assert_eq!(
+            params.get("query").map(|v| v.as_ref()),
+            Some("from:rustlang")
+        );
+        assert_eq!(
+            params.get("start_time").map(|v| v.as_ref()),
+            Some("2010-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            params.get("sort_order").map(|v| v.as_ref()),
+            Some("relevancy")
+        );
+        assert_eq!(params.get("max_results").map(|v| v.as_ref()), Some("500"));
+        assert_eq!(
+            params.get("tweet.fields").map(|v| v.as_ref()),
+            Some("created_at")
+        );
+        assert!(params.get("end_time").is_none());
+        assert!(params.get("next_token").is_none());
+    }
+}
@@ -0,0 +1,393 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Structs and methods for looking up and searching for Spaces.
+//!
+//! Spaces are live audio conversations hosted on Twitter. They're only available through version
+//! 2 of the Twitter API, so like [`tweet::v2`], a `Space` only carries its ID and state by
+//! default; every other field, as well as the users hosting or speaking in it, needs to be asked
+//! for with a `Fields` builder. These functions work with Bearer tokens and OAuth 2.0 user tokens,
+//! as well as Access tokens.
+//!
+//! [`tweet::v2`]: ../tweet/v2/index.html
+//!
+//! ```rust,no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let token: egg_mode::Token = unimplemented!();
+//! use egg_mode::spaces::{self, Expansion, Fields, SearchState, SpaceField};
+//!
+//! let fields = Fields::new()
+//!     .space_fields(vec![SpaceField::Title, SpaceField::ParticipantCount])
+//!     .expansions(vec![Expansion::HostIds]);
+//! let found = spaces::search("rustlang", SearchState::Live, &fields, &token)
+//!     .await
+//!     .unwrap();
+//! for space in &found.data {
+//!     let hosts = space.hosts.iter().map(|u| u.username.as_str()).collect::<Vec<_>>();
+//!     let listening = space.participant_count.unwrap_or(0);
+//!     println!("{:?} ({} listening), hosted by {:?}", space.title, listening, hosts);
+//! }
+//! # }
+//! ```
+//!
+//! Spaces that couldn't be loaded don't make the whole call fail. Instead, they're left out of
+//! `data`, and a `Problem` describing each one is given in `errors`.
+
+use serde::Deserialize;
+
+use crate::common::*;
+use crate::error::Result;
+use crate::tweet::v2::Hydrate;
+use crate::user::v2::{UserField, UserV2};
+use crate::{auth, links};
+
+field_names! {
+    /// Optional fields of a Space that can be requested with `Fields::space_fields`.
+    SpaceField {
+        /// `created_at`: When the Space was created.
+        CreatedAt => "created_at",
+        /// `creator_id`: The ID of the user who created the Space.
+        CreatorId => "creator_id",
+        /// `ended_at`: When the Space ended.
+        EndedAt => "ended_at",
+        /// `host_ids`: The IDs of the users hosting the Space.
+        HostIds => "host_ids",
+        /// `invited_user_ids`: The IDs of the users invited to speak in the Space.
+        InvitedUserIds => "invited_user_ids",
+        /// `is_ticketed`: Whether the Space needs a ticket to join.
+        IsTicketed => "is_ticketed",
+        /// `lang`: The language of the Space, if given by its creator.
+        Lang => "lang",
+        /// `participant_count`: The number of users in the Space.
+        ParticipantCount => "participant_count",
+        /// `scheduled_start`: When a scheduled Space is set to start.
+        ScheduledStart => "scheduled_start",
+        /// `speaker_ids`: The IDs of the users who are speaking, or have spoken, in the Space.
+        SpeakerIds => "speaker_ids",
+        /// `started_at`: When the Space started.
+        StartedAt => "started_at",
+        /// `subscriber_count`: The number of users who have asked to be told when a scheduled
+        /// Space starts.
+        SubscriberCount => "subscriber_count",
+        /// `title`: The title of the Space.
+        Title => "title",
+        /// `updated_at`: When the Space was last updated.
+        UpdatedAt => "updated_at",
+    }
+}
+
+field_names! {
+    /// Users related to a Space that can be loaded into `SpaceIncludes` with `Fields::expansions`.
+    Expansion {
+        /// `creator_id`: The user who created the Space.
+        CreatorId => "creator_id",
+        /// `host_ids`: The users hosting the Space.
+        HostIds => "host_ids",
+        /// `invited_user_ids`: The users invited to speak in the Space.
+        InvitedUserIds => "invited_user_ids",
+        /// `speaker_ids`: The users who are speaking, or have spoken, in the Space.
+        SpeakerIds => "speaker_ids",
+    }
+}
+
+field_names! {
+    /// The states of Spaces to return from `search`.
+    SearchState {
+        /// `all`: Return both live and scheduled Spaces.
+        All => "all",
+        /// `live`: Only return Spaces that are live now.
+        Live => "live",
+        /// `scheduled`: Only return Spaces that are scheduled to start later.
+        Scheduled => "scheduled",
+    }
+}
+
+/// The fields and expansions to ask for when loading Spaces.
+///
+/// By default, Twitter only returns each Space's `id` and `state`, and no `includes`. Each method
+/// here adds to the set of fields requested, so they can be called more than once.
+#[derive(Debug, Clone, Default)]
+pub struct Fields {
+    space_fields: Vec<SpaceField>,
+    expansions: Vec<Expansion>,
+    user_fields: Vec<UserField>,
+}
+
+impl Fields {
+    /// Creates an empty set of fields, which loads each Space's `id` and `state` only.
+    pub fn new() -> Fields {
+        Fields::default()
+    }
+
+    /// Asks for the given fields of each Space.
+    pub fn space_fields(mut self, fields: impl IntoIterator<Item = SpaceField>) -> Fields {
+        self.space_fields.extend(fields);
+        self
+    }
+
+    /// Asks for the given users to be loaded into `includes`.
+    pub fn expansions(mut self, expansions: impl IntoIterator<Item = Expansion>) -> Fields {
+        self.expansions.extend(expansions);
+        self
+    }
+
+    /// Asks for the given fields of each included user. These only have an effect along with
+    /// `expansions`.
+    pub fn user_fields(mut self, fields: impl IntoIterator<Item = UserField>) -> Fields {
+        self.user_fields.extend(fields);
+        self
+    }
+
+    /// Builds the query parameters for these fields.
+    fn params(&self) -> ParamList {
+        let space_fields = self.space_fields.iter().map(|f| f.as_str());
+        let expansions = self.expansions.iter().map(|e| e.as_str());
+        let user_fields = self.user_fields.iter().map(|f| f.as_str());
+        ParamList::new()
+            .add_opt_param("space.fields", join_field_names(space_fields))
+            .add_opt_param("expansions", join_field_names(expansions))
+            .add_opt_param("user.fields", join_field_names(user_fields))
+    }
+}
+
+/// Whether a Space is scheduled, live, or over.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum SpaceState {
+    /// The Space is scheduled to start later.
+    #[serde(rename = "scheduled")]
+    Scheduled,
+    /// The Space is live now.
+    #[serde(rename = "live")]
+    Live,
+    /// The Space has ended.
+    #[serde(rename = "ended")]
+    Ended,
+}
+
+/// Represents a single Space.
+///
+/// Only `id` and `state` are always present. The other fields are only filled in if they were
+/// asked for with `Fields::space_fields`, and if Twitter has a value for them on this Space. The
+/// lists of user IDs are empty if they weren't asked for. The users themselves are filled in from
+/// `includes` when a `SpaceLookup` is loaded, for the expansions asked for with
+/// `Fields::expansions`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Space {
+    /// The ID of this Space, like `1DXxyRYNejbKM`.
+    pub id: String,
+    /// Whether this Space is scheduled, live, or over.
+    pub state: SpaceState,
+    /// The title of this Space.
+    pub title: Option<String>,
+    /// The ID of the user who created this Space.
+    #[serde(default, deserialize_with = "deserialize_opt_string_id")]
+    pub creator_id: Option<u64>,
+    /// The IDs of the users hosting this Space.
+    #[serde(default, deserialize_with = "deserialize_string_ids")]
+    pub host_ids: Vec<u64>,
+    /// The IDs of the users who are speaking, or have spoken, in this Space.
+    #[serde(default, deserialize_with = "deserialize_string_ids")]
+    pub speaker_ids: Vec<u64>,
+    /// The IDs of the users invited to speak in this Space.
+    #[serde(default, deserialize_with = "deserialize_string_ids")]
+    pub invited_user_ids: Vec<u64>,
+    /// The number of users in this Space.
+    pub participant_count: Option<u64>,
+    /// For scheduled Spaces, the number of users who have asked to be told when it starts.
+    pub subscriber_count: Option<u64>,
+    /// Whether this Space needs a ticket to join.
+    pub is_ticketed: Option<bool>,
+    /// The language of this Space, if given by its creator.
+    pub lang: Option<String>,
+    /// When this Space was created.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// For scheduled Spaces, when it's set to start.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub scheduled_start: Option<chrono::DateTime<chrono::Utc>>,
+    /// When this Space started.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// When this Space ended.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub ended_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// When this Space was last updated.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// The user who created this Space, from `Expansion::CreatorId`.
+    #[serde(skip)]
+    pub creator: Option<UserV2>,
+    /// The users hosting this Space, from `Expansion::HostIds`.
+    #[serde(skip)]
+    pub hosts: Vec<UserV2>,
+    /// The users who are speaking, or have spoken, in this Space, from `Expansion::SpeakerIds`.
+    #[serde(skip)]
+    pub speakers: Vec<UserV2>,
+    /// The users invited to speak in this Space, from `Expansion::InvitedUserIds`.
+    #[serde(skip)]
+    pub invited_users: Vec<UserV2>,
+}
+
+impl Hydrate<SpaceIncludes> for Space {
+    fn hydrate(&mut self, includes: &SpaceIncludes) {
+        let users = |ids: &[u64]| {
+            ids.iter()
+                .filter_map(|&id| includes.user(id))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        self.creator = self.creator_id.and_then(|id| includes.user(id)).cloned();
+        self.hosts = users(&self.host_ids);
+        self.speakers = users(&self.speaker_ids);
+        self.invited_users = users(&self.invited_user_ids);
+    }
+}
+
+/// The users loaded alongside the Spaces in a `SpaceLookup`.
+///
+/// These are only filled in for the expansions asked for with `Fields::expansions`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SpaceIncludes {
+    /// Users who created, host, speak in, or were invited to the loaded Spaces.
+    #[serde(default)]
+    pub users: Vec<UserV2>,
+}
+
+impl SpaceIncludes {
+    /// Looks up the included user with the given ID.
+    pub fn user(&self, id: u64) -> Option<&UserV2> {
+        self.users.iter().find(|u| u.id == id)
+    }
+}
+
+/// The response from a Spaces lookup or search.
+///
+/// `data` holds the Spaces that were loaded: a `Vec` from `by_creators` or `search`, or an
+/// `Option` from `show`. The Spaces are hydrated from `includes` as they're loaded.
+///
+/// For `by_creators` and `search`, `meta` holds the number of Spaces that were found.
+pub type SpaceLookup<T> = Lookup<T, SpaceIncludes>;
+
+/// Lookup a single Space by ID, loading the given fields.
+///
+/// If the Space doesn't exist, `data` will be `None`, and `errors` will say why.
+pub async fn show(
+    id: &str,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<SpaceLookup<Option<Space>>>> {
+    let url = format!("{}/{}", links::spaces::SPACES, id);
+    let req = get(&url, token, Some(&fields.params()));
+    request_with_json_response(req).await
+}
+
+/// Lookup the live and scheduled Spaces created by the given users, loading the given fields.
+///
+/// Twitter accepts up to 100 user IDs per call.
+pub async fn by_creators<I: IntoIterator<Item = u64>>(
+    user_ids: I,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<SpaceLookup<Vec<Space>>>> {
+    let ids = user_ids
+        .into_iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<_>>();
+    let params = fields.params().add_param("user_ids", ids.join(","));
+    let req = get(links::spaces::BY_CREATOR_IDS, token, Some(&params));
+    request_with_json_response(req).await
+}
+
+/// Search for Spaces by their title, loading the given fields.
+///
+/// Only Spaces in the given `state` are returned; Spaces that have already ended can't be
+/// searched for.
+pub async fn search(
+    query: &str,
+    state: SearchState,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<SpaceLookup<Vec<Space>>>> {
+    let params = fields
+        .params()
+        .add_param("query", query.to_string())
+        .add_param("state", state.as_str());
+    let req = get(links::spaces::SEARCH, token, Some(&params));
+    request_with_json_response(req).await
+}
+
+/// Deserializes a list of numeric IDs that the v2 API sends as strings.
+fn deserialize_string_ids<'de, D>(ser: D) -> std::result::Result<Vec<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    let ids = Vec::<String>::deserialize(ser)?;
+    ids.iter()
+        .map(|id| id.parse().map_err(D::Error::custom))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::load_file;
+
+    #[test]
+    fn parse_spaces() {
+        let sample = load_file("sample_payloads/v2-spaces.json");
+        let lookup: SpaceLookup<Vec<Space>> = serde_json::from_str(&sample).unwrap();
+
+        assert_eq!(lookup.data.len(), 2);
+        assert_eq!(lookup.meta.result_count, 2);
+
+        let live = &lookup.data[0];
+        assert_eq!(live.id, "1DXxyRYNejbKM");
+        assert_eq!(live.state, SpaceState::Live);
+        assert_eq!(live.title.as_deref(), Some("Rust hangout"));
+        assert_eq!(live.host_ids, vec![2244994945]);
+        assert_eq!(live.speaker_ids, vec![2244994945, 783214]);
+        assert_eq!(live.participant_count, Some(42));
+        assert_eq!(live.started_at.unwrap().timestamp(), 1626889936);
+        let host = lookup.includes.user(live.host_ids[0]).unwrap();
+        assert_eq!(host.username, "TwitterDev");
+        assert_eq!(live.hosts.len(), 1);
+        assert_eq!(live.hosts[0].username, "TwitterDev");
+        let speakers = live.speakers.iter().map(|u| u.id).collect::<Vec<_>>();
+        assert_eq!(speakers, live.speaker_ids);
+        assert_eq!(live.creator.as_ref().map(|u| u.id), Some(2244994945));
+
+        let scheduled = &lookup.data[1];
+        assert_eq!(scheduled.state, SpaceState::Scheduled);
+        assert_eq!(scheduled.subscriber_count, Some(7));
+        assert!(scheduled.speaker_ids.is_empty());
+        assert!(scheduled.scheduled_start.is_some());
+        assert!(scheduled.started_at.is_none());
+    }
+
+    #[test]
+    fn field_params() {
+        assert!(Fields::new().params().is_empty());
+
+        let fields = Fields::new()
+            .space_fields(vec![SpaceField::HostIds, SpaceField::Title])
+            .expansions(vec![Expansion::HostIds, Expansion::SpeakerIds])
+            .user_fields(vec![UserField::Verified]);
+        let params = fields.params();
+        assert_eq!(
+            params.get("space.fields").map(|v| v.as_ref()),
+            Some("host_ids,title")
+        );
+        assert_eq!(
+            params.get("expansions").map(|v| v.as_ref()),
+            Some("host_ids,speaker_ids")
+        );
+        assert_eq!(
+            params.get("user.fields").map(|v| v.as_ref()),
+            Some("verified")
+        );
+    }
+}
@@ -9,6 +9,11 @@
 //! 400 keywords, 5,000 user ids and 25 locations.
 //! See the [official documentation](https://developer.twitter.com/en/docs/tweets/filter-realtime/overview) for more details.
 //!
+//! The v1.1 filter stream is no longer available to most apps. Its replacement in version 2 of the
+//! API, which filters tweets with rules stored ahead of time, is in the [`v2`] submodule.
+//!
+//! [`v2`]: v2/index.html
+//!
 //! ### Example
 //! ```rust,no_run
 //! # #[tokio::main]
@@ -61,6 +66,8 @@ use crate::common::*;
 use crate::tweet::Tweet;
 use crate::{error, links};
 
+pub mod v2;
+
 // TODO rewrite this
 // https://developer.twitter.com/en/docs/tweets/filter-realtime/guides/streaming-message-types
 /// Represents the kinds of messages that can be sent over Twitter's Streaming API.
@@ -224,10 +231,15 @@ impl TwitterStream {
     }
 }
 
-impl Stream for TwitterStream {
-    type Item = Result<StreamMessage, error::Error>;
-
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+impl TwitterStream {
+    /// Polls for the next line of the stream, including its line ending.
+    ///
+    /// This is shared by `TwitterStream` and the `v2` streams, which only differ in how they parse
+    /// each line.
+    pub(crate) fn poll_line(
+        &mut self,
+        cx: &mut Context,
+    ) -> Poll<Option<Result<String, error::Error>>> {
         if let Some(req) = self.request.take() {
             self.response = Some(get_response(req));
         }
@@ -253,6 +265,23 @@ impl Stream for TwitterStream {
 
         if let Some(mut body) = self.body.take() {
             loop {
+                // a single chunk can carry several lines, so hand out any that are left over first
+                if let Some(pos) = self.buf.windows(2).position(|w| w == b"\r\n") {
+                    self.body = Some(body);
+                    let pos = pos + 2;
+                    let line = match std::str::from_utf8(&self.buf[..pos]) {
+                        Ok(line) => Ok(line.to_string()),
+                        Err(_) => Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "stream did not contain valid UTF-8",
+                        )
+                        .into()),
+                    };
+
+                    self.buf.drain(..pos);
+                    return Poll::Ready(Some(line));
+                }
+
                 match Pin::new(&mut body).poll_next(cx) {
                     Poll::Pending => {
                         self.body = Some(body);
@@ -267,23 +296,6 @@ impl Stream for TwitterStream {
                     }
                     Poll::Ready(Some(Ok(chunk))) => {
                         self.buf.extend(&*chunk);
-
-                        if let Some(pos) = self.buf.windows(2).position(|w| w == b"\r\n") {
-                            self.body = Some(body);
-                            let pos = pos + 2;
-                            let resp = if let Ok(msg_str) = std::str::from_utf8(&self.buf[..pos]) {
-                                StreamMessage::from_str(msg_str)
-                            } else {
-                                Err(io::Error::new(
-                                    io::ErrorKind::InvalidData,
-                                    "stream did not contain valid UTF-8",
-                                )
-                                .into())
-                            };
-
-                            self.buf.drain(..pos);
-                            return Poll::Ready(Some(Ok(resp?)));
-                        }
                     }
                 }
             }
@@ -293,6 +305,15 @@ impl Stream for TwitterStream {
     }
 }
 
+impl Stream for TwitterStream {
+    type Item = Result<StreamMessage, error::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let line = futures::ready!(self.poll_line(cx));
+        Poll::Ready(line.map(|line| line.and_then(|line| StreamMessage::from_str(&line))))
+    }
+}
+
 /// Represents the amount of filtering that can be done to streams on Twitter's side.
 ///
 /// According to Twitter's documentation, "When displaying a stream of Tweets to end users
@@ -545,6 +566,21 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn lines_split_within_chunk() {
+        use futures::StreamExt;
+
+        let mut stream = TwitterStream {
+            buf: vec![],
+            request: None,
+            response: None,
+            body: Some(Body::from("\r\n\r\n")),
+        };
+        assert!(matches!(stream.next().await, Some(Ok(StreamMessage::Ping))));
+        assert!(matches!(stream.next().await, Some(Ok(StreamMessage::Ping))));
+        assert!(stream.next().await.is_none());
+    }
+
     #[test]
     fn parse_empty_stream() {
         let msg = StreamMessage::from_str("").unwrap();
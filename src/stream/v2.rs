@@ -0,0 +1,482 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Access to the filtered and sampled streams in version 2 of the Twitter API.
+//!
+//! Rather than taking its filters when it connects, the v2 filtered stream delivers the tweets
+//! that match a set of rules stored with your app. Rules are added with [`add_rules`], listed with
+//! [`rules`], and removed with [`delete_rules`]; the stream picks up any changes right away,
+//! without needing to reconnect. Each tweet from the stream comes with the rules it matched, as
+//! well as any expansions asked for by the `tweet::v2::Fields` given to [`filter`].
+//!
+//! [`add_rules`]: fn.add_rules.html
+//! [`rules`]: fn.rules.html
+//! [`delete_rules`]: fn.delete_rules.html
+//! [`filter`]: fn.filter.html
+//!
+//! The [`sample`] stream delivers a random sample of about 1% of all public tweets instead, with
+//! the same fields and expansions, and without needing any rules.
+//!
+//! [`sample`]: fn.sample.html
+//!
+//! These endpoints can only be called with a Bearer token. See the [official documentation][docs]
+//! for the rule syntax and how many rules your app can have.
+//!
+//! [docs]: https://developer.twitter.com/en/docs/twitter-api/tweets/filtered-stream/introduction
+//!
+//! ### Example
+//! ```rust,no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let token: egg_mode::Token = unimplemented!();
+//! use egg_mode::stream::v2::{self, DraftRule, StreamEvent};
+//! use egg_mode::tweet::v2::{Expansion, Fields};
+//! use futures::TryStreamExt;
+//!
+//! let rule = DraftRule::new("rustlang -is:retweet").tag("rust");
+//! v2::add_rules(vec![rule], false, &token).await.unwrap();
+//!
+//! let fields = Fields::new().expansions(vec![Expansion::AuthorId]);
+//! v2::filter(&fields, &token).try_for_each(|event| {
+//!     if let StreamEvent::Tweet(tweet) = event {
+//!         let author = tweet.data.author_id.and_then(|id| tweet.includes.user(id));
+//!         println!("{:?}: {}", author.map(|u| &u.username), tweet.data.text);
+//!     }
+//!     futures::future::ok(())
+//! }).await.expect("Stream error");
+//! # }
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::Stream;
+use hyper::{Body, Method, Request};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::raw::RequestBuilder;
+use crate::auth::Token;
+use crate::common::*;
+use crate::tweet::v2::{Fields, Hydrate, Includes, Problem, TweetV2};
+use crate::{error, links};
+
+use super::TwitterStream;
+
+/// A message from the v2 filtered or sampled stream.
+#[derive(Debug, Clone)]
+pub enum StreamEvent {
+    /// A tweet that matched one or more of the stream's rules.
+    Tweet(Box<MatchedTweet>),
+    /// An empty line sent to keep the connection open while no tweets are coming in. Twitter sends
+    /// one every 20 seconds, so if none arrive for a while, the connection has stalled.
+    Ping,
+    /// Twitter is about to close the stream, for the given reasons.
+    Disconnect(Vec<Problem>),
+}
+
+impl FromStr for StreamEvent {
+    type Err = error::Error;
+    fn from_str(input: &str) -> Result<Self, error::Error> {
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(StreamEvent::Ping);
+        }
+
+        let raw: RawStreamEvent = serde_json::from_str(input)?;
+        match raw.data {
+            Some(mut data) => {
+                data.hydrate(&raw.includes);
+                Ok(StreamEvent::Tweet(Box::new(MatchedTweet {
+                    data,
+                    includes: raw.includes,
+                    matching_rules: raw.matching_rules,
+                })))
+            }
+            None if !raw.errors.is_empty() => Ok(StreamEvent::Disconnect(raw.errors)),
+            None => Err(error::Error::InvalidResponse(
+                "stream message had no tweet or errors",
+                Some(input.to_string()),
+            )),
+        }
+    }
+}
+
+/// A line of the v2 filtered stream, before sorting out which kind of message it is.
+#[derive(Deserialize)]
+struct RawStreamEvent {
+    data: Option<TweetV2>,
+    #[serde(default)]
+    includes: Includes,
+    #[serde(default)]
+    matching_rules: Vec<MatchingRule>,
+    #[serde(default)]
+    errors: Vec<Problem>,
+}
+
+/// A tweet delivered by the v2 filtered or sampled stream.
+#[derive(Debug, Clone)]
+pub struct MatchedTweet {
+    /// The tweet itself, with the fields given to `filter`, hydrated from `includes`.
+    pub data: TweetV2,
+    /// The related objects asked for with `Fields::expansions`.
+    pub includes: Includes,
+    /// The rules this tweet matched.
+    pub matching_rules: Vec<MatchingRule>,
+}
+
+/// One of the rules a streamed tweet matched.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MatchingRule {
+    /// The ID of the rule.
+    #[serde(with = "serde_via_string")]
+    pub id: u64,
+    /// The tag given to the rule when it was added, if any.
+    pub tag: Option<String>,
+}
+
+/// How long a `TweetStream` waits for data before deciding the connection has stalled, by default.
+///
+/// Twitter sends a keep-alive line every 20 seconds while no tweets are coming in, so this allows
+/// for one to arrive late.
+const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A `Stream` that represents a connection to the v2 filtered or sampled stream.
+///
+/// Since Twitter sends a keep-alive line every 20 seconds, a stream that goes quiet for longer
+/// than that has stalled, even if the connection looks open. If nothing at all arrives for 30
+/// seconds, the stream yields `Error::StreamStalled` and then ends, so you know to reconnect.
+/// This timeout can be changed or turned off with `stall_timeout`.
+#[must_use = "Streams are lazy and do nothing unless polled"]
+pub struct TweetStream {
+    inner: Option<TwitterStream>,
+    stall_timeout: Option<Duration>,
+    stall: Option<tokio::time::Delay>,
+}
+
+impl TweetStream {
+    fn new(request: Request<Body>) -> TweetStream {
+        TweetStream {
+            inner: Some(TwitterStream::new(request)),
+            stall_timeout: Some(DEFAULT_STALL_TIMEOUT),
+            stall: None,
+        }
+    }
+
+    /// Sets how long to wait for data (including keep-alive lines) before giving up on the
+    /// connection, or turns off the check if `timeout` is `None`. The default is 30 seconds.
+    pub fn stall_timeout(self, timeout: Option<Duration>) -> TweetStream {
+        TweetStream {
+            stall_timeout: timeout,
+            stall: None,
+            ..self
+        }
+    }
+}
+
+impl Stream for TweetStream {
+    type Item = Result<StreamEvent, error::Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        let inner = match this.inner.as_mut() {
+            Some(inner) => inner,
+            None => return Poll::Ready(None),
+        };
+
+        if let Poll::Ready(line) = inner.poll_line(cx) {
+            // the timer starts over with every line, keep-alives included
+            this.stall = None;
+            return Poll::Ready(
+                line.map(|line| line.and_then(|line| StreamEvent::from_str(&line))),
+            );
+        }
+
+        if let Some(timeout) = this.stall_timeout {
+            let stall = this
+                .stall
+                .get_or_insert_with(|| tokio::time::delay_for(timeout));
+            if Pin::new(stall).poll(cx).is_ready() {
+                this.inner = None;
+                return Poll::Ready(Some(Err(error::Error::StreamStalled(timeout))));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+/// Opens a connection to the v2 filtered stream, which delivers the tweets matching your app's
+/// rules with the given fields.
+pub fn filter(fields: &Fields, token: &Token) -> TweetStream {
+    let req = get(links::stream::FILTER_V2, token, Some(&fields.params()));
+    TweetStream::new(req)
+}
+
+/// Opens a connection to the v2 sampled stream, which delivers a random sample of about 1% of all
+/// public tweets with the given fields.
+///
+/// Tweets from this stream don't match any rules, so their `matching_rules` are always empty.
+pub fn sample(fields: &Fields, token: &Token) -> TweetStream {
+    let req = get(links::stream::SAMPLE_V2, token, Some(&fields.params()));
+    TweetStream::new(req)
+}
+
+/// A rule stored for the v2 filtered stream.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+    /// The ID of the rule, for use with `delete_rules`.
+    #[serde(with = "serde_via_string")]
+    pub id: u64,
+    /// The rule itself, like `"cat has:images"`.
+    pub value: String,
+    /// The tag given to the rule, if any.
+    pub tag: Option<String>,
+}
+
+/// A new rule to add to the v2 filtered stream with `add_rules`.
+#[derive(Debug, Clone, Serialize)]
+pub struct DraftRule {
+    value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<String>,
+}
+
+impl DraftRule {
+    /// Creates a new rule with the given value, like `"cat has:images"`.
+    pub fn new(value: impl Into<String>) -> DraftRule {
+        DraftRule {
+            value: value.into(),
+            tag: None,
+        }
+    }
+
+    /// Gives the rule a tag, which is sent along with each tweet that matches it.
+    pub fn tag(self, tag: impl Into<String>) -> DraftRule {
+        DraftRule {
+            tag: Some(tag.into()),
+            ..self
+        }
+    }
+}
+
+/// Counts of the rules changed by `add_rules` or `delete_rules`.
+///
+/// Only the counts that apply to the call that was made are filled in; the rest are zero.
+#[derive(Debug, Copy, Clone, Default, Deserialize)]
+pub struct RuleSummary {
+    /// The number of rules that were added.
+    #[serde(default)]
+    pub created: u32,
+    /// The number of rules that weren't added, because they were invalid or already existed.
+    #[serde(default)]
+    pub not_created: u32,
+    /// The number of valid rules given.
+    #[serde(default)]
+    pub valid: u32,
+    /// The number of invalid rules given.
+    #[serde(default)]
+    pub invalid: u32,
+    /// The number of rules that were deleted.
+    #[serde(default)]
+    pub deleted: u32,
+    /// The number of rules that weren't deleted, because they didn't exist.
+    #[serde(default)]
+    pub not_deleted: u32,
+}
+
+/// The result of a call to `add_rules` or `delete_rules`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(from = "RawRuleUpdate")]
+pub struct RuleUpdate {
+    /// The rules that were added. This is empty for `delete_rules`.
+    pub rules: Vec<Rule>,
+    /// Counts of the rules changed.
+    pub summary: RuleSummary,
+    /// The rules that couldn't be changed, and why.
+    pub errors: Vec<Problem>,
+}
+
+/// The response from `POST tweets/search/stream/rules`.
+#[derive(Deserialize)]
+struct RawRuleUpdate {
+    #[serde(default)]
+    data: Vec<Rule>,
+    meta: RawRuleMeta,
+    #[serde(default)]
+    errors: Vec<Problem>,
+}
+
+#[derive(Deserialize)]
+struct RawRuleMeta {
+    #[serde(default)]
+    summary: RuleSummary,
+}
+
+impl From<RawRuleUpdate> for RuleUpdate {
+    fn from(raw: RawRuleUpdate) -> RuleUpdate {
+        RuleUpdate {
+            rules: raw.data,
+            summary: raw.meta.summary,
+            errors: raw.errors,
+        }
+    }
+}
+
+/// The response from `GET tweets/search/stream/rules`.
+#[derive(Deserialize)]
+struct RawRules {
+    #[serde(default)]
+    data: Vec<Rule>,
+}
+
+/// Loads the rules currently stored for the v2 filtered stream.
+pub async fn rules(token: &Token) -> Result<Response<Vec<Rule>>, error::Error> {
+    let req = get(links::stream::RULES_V2, token, None);
+    let resp = request_with_json_response::<RawRules>(req).await?;
+    Ok(Response::map(resp, |rules| rules.data))
+}
+
+/// Adds the given rules to the v2 filtered stream.
+///
+/// If `dry_run` is true, Twitter only checks whether the rules are valid, without adding them.
+/// Rules that are invalid or already exist aren't added, and are listed in the `errors` of the
+/// returned `RuleUpdate`, alongside the rules that were.
+pub async fn add_rules<I: IntoIterator<Item = DraftRule>>(
+    rules: I,
+    dry_run: bool,
+    token: &Token,
+) -> Result<Response<RuleUpdate>, error::Error> {
+    let rules = rules.into_iter().collect::<Vec<_>>();
+    update_rules(serde_json::json!({ "add": rules }), dry_run, token).await
+}
+
+/// Deletes the rules with the given IDs from the v2 filtered stream.
+///
+/// If `dry_run` is true, Twitter only checks whether the rules exist, without deleting them.
+pub async fn delete_rules<I: IntoIterator<Item = u64>>(
+    ids: I,
+    dry_run: bool,
+    token: &Token,
+) -> Result<Response<RuleUpdate>, error::Error> {
+    let ids = ids.into_iter().map(|id| id.to_string()).collect::<Vec<_>>();
+    update_rules(
+        serde_json::json!({ "delete": { "ids": ids } }),
+        dry_run,
+        token,
+    )
+    .await
+}
+
+/// Sends the given changes to the v2 filtered stream's rules.
+async fn update_rules(
+    body: serde_json::Value,
+    dry_run: bool,
+    token: &Token,
+) -> Result<Response<RuleUpdate>, error::Error> {
+    let params =
+        ParamList::new().add_opt_param("dry_run", if dry_run { Some("true") } else { None });
+    let req = RequestBuilder::new(Method::POST, links::stream::RULES_V2)
+        .with_query_params(&params)
+        .with_body_json(body)
+        .request_token(token);
+    request_with_json_response(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::load_file;
+
+    #[test]
+    fn parse_stream_events() {
+        let sample = load_file("sample_payloads/v2-stream-tweet.json");
+        let tweet = match StreamEvent::from_str(&sample).unwrap() {
+            StreamEvent::Tweet(tweet) => tweet,
+            other => panic!("not a tweet: {:?}", other),
+        };
+        assert_eq!(tweet.data.id, 1293595870563381249);
+        assert_eq!(
+            tweet.includes.user(2244994945).unwrap().username,
+            "TwitterDev"
+        );
+        assert_eq!(tweet.data.author.as_ref().unwrap().username, "TwitterDev");
+        assert_eq!(tweet.matching_rules.len(), 2);
+        assert_eq!(tweet.matching_rules[0].id, 1292867705050251266);
+        assert_eq!(
+            tweet.matching_rules[0].tag.as_deref(),
+            Some("twitterdev announcements")
+        );
+        assert_eq!(tweet.matching_rules[1].tag, None);
+
+        assert!(matches!(
+            StreamEvent::from_str("\r\n"),
+            Ok(StreamEvent::Ping)
+        ));
+
+        let disconnect = r#"{"errors":[{"title":"operational-disconnect","disconnect_type":"UpstreamOperationalDisconnect","detail":"This stream has been disconnected upstream for operational reasons.","type":"https://api.twitter.com/2/problems/operational-disconnect"}]}"#;
+        match StreamEvent::from_str(disconnect).unwrap() {
+            StreamEvent::Disconnect(errors) => {
+                assert_eq!(errors[0].title, "operational-disconnect")
+            }
+            other => panic!("not a disconnect: {:?}", other),
+        }
+
+        assert!(StreamEvent::from_str("{}").is_err());
+    }
+
+    #[tokio::test]
+    async fn stalled_stream_ends() {
+        use futures::StreamExt;
+
+        let (mut sender, body) = Body::channel();
+        let mut inner = TwitterStream::new(Request::new(Body::empty()));
+        inner.request = None;
+        inner.body = Some(body);
+        let mut stream = TweetStream {
+            inner: Some(inner),
+            stall_timeout: None,
+            stall: None,
+        }
+        .stall_timeout(Some(Duration::from_millis(50)));
+
+        sender.send_data("\r\n".into()).await.unwrap();
+        assert!(matches!(stream.next().await, Some(Ok(StreamEvent::Ping))));
+        let stalled = stream.next().await;
+        assert!(matches!(stalled, Some(Err(error::Error::StreamStalled(_)))));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn parse_rule_update() {
+        let sample = load_file("sample_payloads/v2-rules-added.json");
+        let update: RuleUpdate = serde_json::from_str(&sample).unwrap();
+        assert_eq!(update.rules.len(), 1);
+        assert_eq!(update.rules[0].id, 1292867705050251266);
+        assert_eq!(update.rules[0].value, "from:TwitterDev has:links");
+        assert_eq!(update.summary.created, 1);
+        assert_eq!(update.summary.invalid, 1);
+        assert_eq!(update.summary.deleted, 0);
+        assert_eq!(update.errors[0].title, "Invalid Rule");
+
+        let deleted = r#"{"meta":{"sent":"2020-08-10T19:34:04.727Z","summary":{"deleted":2,"not_deleted":0}}}"#;
+        let update: RuleUpdate = serde_json::from_str(deleted).unwrap();
+        assert!(update.rules.is_empty());
+        assert_eq!(update.summary.deleted, 2);
+    }
+
+    #[test]
+    fn draft_rule_json() {
+        let rules = vec![
+            DraftRule::new("cat has:images").tag("cats"),
+            DraftRule::new("dog"),
+        ];
+        let body = serde_json::to_string(&serde_json::json!({ "add": rules })).unwrap();
+        assert_eq!(
+            body,
+            r#"{"add":[{"tag":"cats","value":"cat has:images"},{"value":"dog"}]}"#
+        );
+    }
+}
@@ -0,0 +1,234 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Helpers for checking the length of tweet text before posting it.
+//!
+//! Twitter doesn't count the length of a tweet by its characters, but by a weighted count, and only
+//! accepts tweets whose weighted count is at most `MAX_WEIGHTED_LENGTH`. Sending a longer tweet
+//! gets the request rejected with error code 186. The rules for that count are:
+//!
+//! * Links count as `URL_LENGTH` characters, however long they are, since that's how long they
+//!   are once Twitter has shortened them with t.co. Words starting with `http://`, `https://`, or
+//!   `www.` are counted as links. Punctuation at the end of a link isn't counted as part of it.
+//! * Characters from Latin scripts and common punctuation count as one character each. This
+//!   covers the Unicode ranges U+0000 to U+10FF, U+2000 to U+200D, U+2010 to U+201F, and U+2032 to
+//!   U+2037.
+//! * All other characters, such as CJK text, count as two.
+//! * An emoji counts as two characters, including emoji built out of several code points, like
+//!   flags, keycaps, emoji with skin tone modifiers, and sequences joined with zero-width joiners.
+//!
+//! Twitter also normalizes the text to Unicode Normalization Form C before counting it. This module
+//! doesn't, so text with decomposed accents may count as longer here than it will on Twitter.
+//!
+//! ```rust
+//! use egg_mode::text;
+//!
+//! assert_eq!(text::character_count("hello, world!"), 13);
+//! assert_eq!(text::character_count("read more at https://example.com/a/long/path"), 36);
+//! assert_eq!(text::character_count("こんにちは 👋🏽"), 13);
+//! assert!(text::is_valid("hello, world!"));
+//! ```
+
+/// The longest weighted character count Twitter allows in a tweet.
+pub const MAX_WEIGHTED_LENGTH: usize = 280;
+
+/// How many characters a link counts as, regardless of how long it is.
+///
+/// This is the length of a link once Twitter has shortened it with t.co. The current value is also
+/// available from `service::config`, in `short_url_length_https`.
+pub const URL_LENGTH: usize = 23;
+
+/// Counts the given text the way Twitter does when checking that a tweet isn't too long.
+///
+/// See the [module docs](index.html) for the rules this follows.
+pub fn character_count(text: &str) -> usize {
+    segments(text).map(|seg| seg.weight).sum()
+}
+
+/// Returns how many more characters could be added to the given text before it's too long to be
+/// tweeted. This is negative if the text is already too long.
+pub fn remaining_chars(text: &str) -> i32 {
+    MAX_WEIGHTED_LENGTH as i32 - character_count(text) as i32
+}
+
+/// Returns whether the given text can be posted as a tweet: whether it has any text, and isn't
+/// longer than `MAX_WEIGHTED_LENGTH`.
+pub fn is_valid(text: &str) -> bool {
+    !text.trim().is_empty() && character_count(text) <= MAX_WEIGHTED_LENGTH
+}
+
+/// Returns the length in bytes of the longest start of the given text whose weighted count is at
+/// most `limit`, without breaking up any links or emoji.
+pub(crate) fn fit(text: &str, limit: usize) -> usize {
+    let mut weight = 0;
+    for seg in segments(text) {
+        weight += seg.weight;
+        if weight > limit {
+            return seg.start;
+        }
+    }
+    text.len()
+}
+
+/// Returns whether the given word would be counted as a link.
+pub(crate) fn is_url(word: &str) -> bool {
+    let prefix = |p: &str| {
+        let word = word.as_bytes();
+        word.len() >= p.len() && word[..p.len()].eq_ignore_ascii_case(p.as_bytes())
+    };
+    prefix("http://") || prefix("https://") || prefix("www.")
+}
+
+/// A piece of text that's counted as a unit: a link, an emoji, or a single character.
+struct Segment {
+    start: usize,
+    weight: usize,
+}
+
+/// Splits the given text into the pieces that are counted separately.
+fn segments(text: &str) -> impl Iterator<Item = Segment> + '_ {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let rest = &text[pos..];
+        let c = rest.chars().next()?;
+        let start = pos;
+
+        let after_word =
+            matches!(text[..start].chars().next_back(), Some(prev) if prev.is_alphanumeric());
+        if !after_word && is_url(rest) {
+            pos += url_len(rest);
+            Some(Segment {
+                start,
+                weight: URL_LENGTH,
+            })
+        } else if let Some(len) = emoji_len(rest) {
+            pos += len;
+            Some(Segment { start, weight: 2 })
+        } else {
+            pos += c.len_utf8();
+            Some(Segment {
+                start,
+                weight: char_weight(c),
+            })
+        }
+    })
+}
+
+/// Returns the length of the link at the start of the given text, leaving off any punctuation at
+/// its end.
+fn url_len(text: &str) -> usize {
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    let url = text[..end].trim_end_matches(&['.', ',', ';', ':', '!', '?', ')', '"', '\''][..]);
+    url.len()
+}
+
+/// If the given text starts with an emoji, returns its length, including any modifiers and joined
+/// emoji that make up the rest of it.
+fn emoji_len(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+    let (_, first) = chars.next()?;
+    let mut end = first.len_utf8();
+
+    if is_regional_indicator(first) {
+        // flags are made from two regional indicators
+        if let Some(&(idx, c)) = chars.peek() {
+            if is_regional_indicator(c) {
+                end = idx + c.len_utf8();
+            }
+        }
+        return Some(end);
+    }
+
+    let rest = &text[end..];
+    let keycap = first.is_ascii_digit() || first == '#' || first == '*';
+    if keycap {
+        // keycaps are only emoji if they finish with the combining keycap character
+        if !(rest.starts_with('\u{20E3}') || rest.starts_with("\u{FE0F}\u{20E3}")) {
+            return None;
+        }
+    } else if !is_emoji(first) && !rest.starts_with('\u{FE0F}') {
+        // characters like © are only emoji when followed by the emoji variation selector
+        return None;
+    }
+
+    while let Some(&(idx, c)) = chars.peek() {
+        if is_emoji_modifier(c) {
+            chars.next();
+            end = idx + c.len_utf8();
+        } else if c == '\u{200D}' {
+            // a zero-width joiner takes the next character into the emoji with it
+            chars.next();
+            end = idx + c.len_utf8();
+            if let Some((idx, c)) = chars.next() {
+                end = idx + c.len_utf8();
+            }
+        } else {
+            break;
+        }
+    }
+
+    Some(end)
+}
+
+fn is_emoji(c: char) -> bool {
+    matches!(c as u32, 0x1F000..=0x1FAFF | 0x2300..=0x23FF | 0x2600..=0x27BF | 0x2B05..=0x2B55)
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+/// Variation selectors, the keycap character, skin tone modifiers, and tag characters, which all
+/// change the emoji before them instead of standing on their own.
+fn is_emoji_modifier(c: char) -> bool {
+    matches!(c as u32, 0xFE0E | 0xFE0F | 0x20E3 | 0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F)
+}
+
+fn char_weight(c: char) -> usize {
+    match c as u32 {
+        0x0000..=0x10FF | 0x2000..=0x200D | 0x2010..=0x201F | 0x2032..=0x2037 => 1,
+        _ => 2,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_counts() {
+        assert_eq!(character_count(""), 0);
+        assert_eq!(character_count("hello world"), 11);
+        assert_eq!(character_count("café — “quoted”"), 15);
+        assert_eq!(character_count("こんにちは"), 10);
+
+        // links
+        assert_eq!(character_count("https://example.com/a/very/long/path"), 23);
+        assert_eq!(character_count("see www.example.com."), 28);
+        assert_eq!(character_count("(HTTPS://example.com)"), 25);
+        assert_eq!(character_count("nothttps://example.com"), 22);
+
+        // emoji
+        assert_eq!(character_count("👋"), 2);
+        assert_eq!(character_count("👋🏽"), 2);
+        assert_eq!(character_count("👩‍👩‍👧‍👦"), 2);
+        assert_eq!(character_count("🇨🇦🇯🇵"), 4);
+        assert_eq!(character_count("1️⃣ 2"), 4);
+        assert_eq!(character_count("❤️"), 2);
+        assert_eq!(character_count("©️ ©"), 4);
+    }
+
+    #[test]
+    fn limits() {
+        assert_eq!(remaining_chars("hello"), 275);
+        assert_eq!(remaining_chars(&"あ".repeat(141)), -2);
+        assert!(is_valid(&"a".repeat(280)));
+        assert!(!is_valid(&"a".repeat(281)));
+        assert!(!is_valid("   "));
+
+        assert_eq!(fit("hello world", 5), 5);
+        assert_eq!(fit("ab👋🏽", 3), 2);
+        assert_eq!(fit("short", 280), 5);
+    }
+}
@@ -23,6 +23,24 @@ pub async fn show(id: u64, token: &auth::Token) -> Result<Response<Tweet>> {
     request_with_json_response(req).await
 }
 
+///Check whether the tweet with the given ID still exists.
+///
+///This loads the tweet the same way `show` does, but if Twitter says the tweet doesn't exist
+///(with error code 144 or 34), this returns `false` instead of an error. This can be used to
+///confirm that a tweet was actually deleted, or to check whether tweets in a saved dataset are
+///still available. Any other error, including a tweet being hidden because its author is
+///protected or suspended, is returned as-is.
+///
+///Since a missing tweet is reported as an error, the rate-limit information in the returned
+///`Response` is read from the error response's headers in that case.
+pub async fn exists(id: u64, token: &auth::Token) -> Result<Response<bool>> {
+    let params = ParamList::new()
+        .extended_tweets()
+        .add_param("id", id.to_string());
+    let req = get(links::statuses::SHOW, token, Some(&params));
+    tweet_exists(request_with_json_response::<Tweet>(req).await)
+}
+
 ///Lookup the most recent 100 (or fewer) retweets of the given tweet.
 ///
 ///Use the `count` parameter to indicate how many retweets you would like to retrieve. If `count`
@@ -54,6 +72,42 @@ pub fn retweeters_of(id: u64, token: &auth::Token) -> cursor::CursorIter<cursor:
     cursor::CursorIter::new(links::statuses::RETWEETERS_OF, token, Some(params), None)
 }
 
+///Lookup the users that have retweeted the given tweet, with their full profiles.
+///
+///This pages through the IDs from `retweeters_of`, and loads each page of them with
+///`user::lookup` as it comes in, yielding the users one at a time, most recent retweet first.
+///Users that couldn't be loaded, such as suspended accounts, are left out. Each user is returned
+///with the rate-limit information from the `user::lookup` call that loaded it; note that loading
+///the IDs draws from a separate rate limit.
+///
+///If loading either the IDs or the users fails, the error is yielded, and the stream ends.
+///
+///```rust,no_run
+///# use egg_mode::Token;
+///use egg_mode::tweet;
+///use futures::TryStreamExt;
+///# #[tokio::main]
+///# async fn main() {
+///# let token: Token = unimplemented!();
+///let users = tweet::retweeting_users(1261253754969640960, &token)
+///    .map_ok(|user| user.response.screen_name)
+///    .try_collect::<Vec<_>>()
+///    .await
+///    .unwrap();
+///# }
+///```
+pub fn retweeting_users(
+    id: u64,
+    token: &auth::Token,
+) -> impl Stream<Item = Result<Response<user::TwitterUser>>> {
+    let token = token.clone();
+    let ids = retweeters_of(id, &token);
+    hydrate_users(ids, move |chunk| {
+        let token = token.clone();
+        async move { user::lookup(chunk, &token).await }
+    })
+}
+
 ///Lookup tweet information for the given list of tweet IDs.
 ///
 ///This function differs from `lookup_map` in how it handles protected or nonexistent tweets.
@@ -78,6 +132,27 @@ pub async fn lookup<I: IntoIterator<Item = u64>>(
     request_with_json_response(req).await
 }
 
+///Lookup tweet information for any number of tweet IDs, loading them 100 at a time.
+///
+///`lookup` can only load 100 tweets in one call. This function splits the given IDs into chunks
+///of 100 and loads each of them with `lookup`, running up to `concurrency` calls at once. Like
+///`lookup`, any tweets that couldn't be loaded are left out. The tweets from every chunk are
+///returned together, in the same order as the chunks.
+///
+///Since all of these calls draw from the same rate limit, if any of them is rate-limited, all of
+///them wait for the limit to reset before trying again. Any other error ends the lookup and is
+///returned. The rate-limit information in the returned `Response` is from the last chunk.
+pub async fn lookup_all<I: IntoIterator<Item = u64>>(
+    ids: I,
+    concurrency: usize,
+    token: &auth::Token,
+) -> Result<Response<Vec<Tweet>>> {
+    let ids = ids.into_iter().collect::<Vec<_>>();
+    let idempotent = is_idempotent(&hyper::Method::POST, links::statuses::LOOKUP);
+    let load = |chunk| lookup(chunk, token);
+    lookup_chunked(ids, 100, concurrency, idempotent, load).await
+}
+
 ///Lookup tweet information for the given list of tweet IDs, and return a map indicating which IDs
 ///couldn't be found.
 ///
@@ -85,6 +160,10 @@ pub async fn lookup<I: IntoIterator<Item = u64>>(
 ///`lookup_map` gives a map containing every ID in the input slice; tweets that don't exist or
 ///can't be read by the authenticated user store `None` in the map, whereas tweets that could be
 ///loaded store `Some` and the requested status.
+///
+///Version 1.1 of the API doesn't say why a tweet couldn't be loaded. To tell apart tweets that
+///were deleted from ones whose authors are suspended or protected, use `v2::lookup_map`, which
+///gives an `Absence` for each tweet that's missing.
 pub async fn lookup_map<I: IntoIterator<Item = u64>>(
     ids: I,
     token: &auth::Token,
@@ -132,12 +211,135 @@ pub async fn lookup_map<I: IntoIterator<Item = u64>>(
     Ok(Response::map(parsed, |_| map))
 }
 
+///Load the tweets for a stream of tweet IDs, like the ones in a "dehydrated" dataset of tweets.
+///
+///The IDs are gathered into groups of 100 and loaded with `lookup_map`, so the returned stream
+///yields each ID in the same order it came in, paired with its tweet, or `None` if the tweet
+///couldn't be loaded (for example, if it's been deleted or its author's account is protected).
+///
+///If Twitter says the rate limit has been reached, this waits until it resets and tries again. Any
+///other error ends the stream.
+///
+///```rust,no_run
+///# use egg_mode::Token;
+///use egg_mode::tweet;
+///use futures::{stream, TryStreamExt};
+///# #[tokio::main]
+///# async fn main() {
+///# let token: Token = unimplemented!();
+///let ids = vec![1261253754969640960, 1261253755518988288];
+///let tweets = tweet::rehydrate(stream::iter(ids), &token)
+///    .try_collect::<Vec<_>>()
+///    .await
+///    .unwrap();
+///# }
+///```
+pub fn rehydrate<S>(ids: S, token: &auth::Token) -> impl Stream<Item = Result<(u64, Option<Tweet>)>>
+where
+    S: Stream<Item = u64>,
+{
+    let token = token.clone();
+    rehydrate_chunks(ids, move |chunk| {
+        let token = token.clone();
+        async move { lookup_map(chunk, &token).await }
+    })
+}
+
+///Load every version of the given tweet, from the original tweet to its most recent edit.
+///
+///The given ID can be the ID of any version of the tweet. This function loads it with `show`, then
+///loads the rest of the versions listed in its `edit_history_ids` with `lookup`. The returned
+///tweets are sorted oldest to newest. If the tweet has never been edited, this returns just the
+///tweet itself. Any versions that couldn't be loaded (for example, if they've since been deleted)
+///are left out.
+///
+///Since this makes two calls to Twitter (one if the tweet hasn't been edited), the rate-limit
+///information in the returned `Response` is from the last call that was made.
+pub async fn edit_history(id: u64, token: &auth::Token) -> Result<Response<Vec<Tweet>>> {
+    let tweet = show(id, token).await?;
+    let history = match tweet.edit_history_ids {
+        Some(ref ids) if tweet.is_edited() => ids.clone(),
+        _ => return Ok(Response::map(tweet, |tweet| vec![tweet])),
+    };
+
+    let versions = lookup(history.iter().cloned(), token).await?;
+    Ok(Response::map(versions, |versions| {
+        order_edit_history(&history, versions)
+    }))
+}
+
+///Load the thread the given tweet is part of: the chain of replies its author posted to their own
+///tweets, from the first tweet to the last.
+///
+///This works in two directions. Going up, it follows `in_reply_to_status_id` with `show` for as
+///long as the author was replying to themselves, which finds the start of the thread. Going down,
+///it searches for the author's replies to themselves posted after that, and follows each tweet to
+///the earliest reply it got from the author. If the author replied to the same tweet more than
+///once, only the first reply is followed, so the returned thread is always one straight line.
+///
+///Since this uses the standard search API to find the later tweets, only replies from the last 7
+///days can be found, and at most 500 of them are checked. Tweets in the thread that have been
+///deleted or can't be seen end the thread there. The rate-limit information in the returned
+///`Response` is from the search.
+///
+///```rust,no_run
+///# use egg_mode::Token;
+///# #[tokio::main]
+///# async fn main() {
+///# let token: Token = unimplemented!();
+///let thread = egg_mode::tweet::thread_of(1261061777017569281, &token).await.unwrap();
+///for tweet in thread.iter() {
+///    println!("{}", tweet.text);
+///}
+///# }
+///```
+pub async fn thread_of(id: u64, token: &auth::Token) -> Result<Response<Vec<Tweet>>> {
+    let tweet = show(id, token).await?.response;
+    let author = match tweet.user {
+        Some(ref user) => (user.id, user.screen_name.clone()),
+        None => return Err(InvalidResponse("tweet was loaded without its author", None)),
+    };
+
+    let mut thread = vec![tweet];
+    loop {
+        let first = &thread[0];
+        let parent = match (first.in_reply_to_status_id, first.in_reply_to_user_id) {
+            (Some(parent), Some(user)) if user == author.0 => parent,
+            _ => break,
+        };
+        match show(parent, token).await {
+            Ok(parent) => thread.insert(0, parent.response),
+            Err(error::Error::TwitterError(_, ref errors))
+                if errors
+                    .errors
+                    .iter()
+                    .any(|e| [34, 144, 179].contains(&e.code)) =>
+            {
+                break
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let query = format!("from:{} to:{}", author.1, author.1);
+    let replies = crate::search::search(query)
+        .result_type(crate::search::ResultType::Recent)
+        .since_tweet(thread[thread.len() - 1].id)
+        .collect(500, token)
+        .await?;
+    Ok(Response::map(replies, |replies| {
+        continue_thread(thread, replies)
+    }))
+}
+
 ///Make a `Timeline` struct for navigating the collection of tweets posted by the authenticated
 ///user and the users they follow.
 ///
 ///This method has a default page size of 20 tweets, with a maximum of 200.
 ///
-///Twitter will only return the most recent 800 tweets by navigating this method.
+///Twitter will only return the most recent 800 tweets by navigating this method. To leave out
+///replies, or the authors' profiles, use `exclude_replies` or `trim_user` on the returned
+///`Timeline`.
 pub fn home_timeline(token: &auth::Token) -> Timeline {
     Timeline::new(links::statuses::HOME_TIMELINE, None, token)
 }
@@ -179,6 +381,58 @@ pub fn user_timeline<T: Into<UserID>>(
     Timeline::new(links::statuses::USER_TIMELINE, Some(params), token)
 }
 
+///Make a `Timeline` struct for navigating the tweets posted by the given user that have photos,
+///videos, or GIFs attached.
+///
+///The v1.1 API this uses has no way to ask for only tweets with media, so this loads the user's
+///timeline (including replies and retweets) and leaves out every tweet without any
+///`extended_entities.media`. This means pages will often have far fewer tweets than the requested
+///page size, and can even be empty when there are older tweets left to load. Paging with `older`
+///and `newer` still picks up where the last full page left off; `collect_up_to` keeps loading
+///pages until it has collected enough tweets with media.
+///
+///Like `user_timeline`, this has a default page size of 20 tweets (before filtering), with a
+///maximum of 200, and Twitter will only load the most recent 3,200 tweets.
+pub fn media_timeline<T: Into<UserID>>(acct: T, token: &auth::Token) -> Timeline {
+    let params = ParamList::new()
+        .extended_tweets()
+        .add_user_param(acct.into())
+        .add_param("include_entities", "true")
+        .add_param("exclude_replies", "false")
+        .add_param("include_rts", "true");
+
+    Timeline::new(links::statuses::USER_TIMELINE, Some(params), token).media_only()
+}
+
+///Collect the most recent tweets from several users' timelines at once.
+///
+///This loads up to `per_user_limit` tweets from each user's timeline, as with
+///[`Timeline::collect_up_to`][]. Each user's timeline is loaded one page at a time, but up to
+///`concurrency` users are loaded in parallel. The returned `Stream` yields each user's tweets (or
+///the error encountered while loading them) once they've all been loaded, in the same order as
+///`users`.
+///
+///[`Timeline::collect_up_to`]: struct.Timeline.html#method.collect_up_to
+///
+///Since all of these timelines are loaded with the same token, they all draw from the same rate
+///limit. If any of them is rate-limited, all of them will wait for the limit to reset before
+///requesting any more pages.
+///
+///Timelines are loaded with replies and retweets included, 200 tweets at a time. Twitter will only
+///load the most recent 3,200 tweets of each user.
+pub fn crawl_many(
+    users: &[UserID],
+    per_user_limit: usize,
+    concurrency: usize,
+    token: &auth::Token,
+) -> impl Stream<Item = (UserID, Result<Vec<Tweet>>)> {
+    let token = token.clone();
+    crawl_users(users.to_vec(), concurrency, move |user, gate| {
+        let timeline = user_timeline(user, true, true, &token).with_page_size(200);
+        async move { timeline.collect_with_gate(per_user_limit, &gate).await }
+    })
+}
+
 ///Make a `Timeline` struct for navigating the collection of tweets posted by the authenticated
 ///user that have been retweeted by others.
 ///
@@ -197,6 +451,34 @@ pub fn liked_by<T: Into<UserID>>(acct: T, token: &auth::Token) -> Timeline {
     Timeline::new(links::statuses::LIKES_OF, Some(params), token)
 }
 
+///Load every tweet liked by the given user, newest like first, one tweet at a time.
+///
+///This pages through `liked_by` 200 tweets at a time, with the same retries as
+///`Timeline::into_stream`, and ends once Twitter has no older likes to return. If loading a page
+///fails for good, the error is yielded and the stream ends.
+///
+///```rust,no_run
+///# use egg_mode::Token;
+///# #[tokio::main]
+///# async fn main() -> egg_mode::error::Result<()> {
+///# let token: Token = unimplemented!();
+///use egg_mode::tweet;
+///use futures::TryStreamExt;
+///
+///let mut likes = Box::pin(tweet::likes_of("rustlang", &token));
+///while let Some(tweet) = likes.try_next().await? {
+///    println!("{}", tweet.text);
+///}
+///# Ok(())
+///# }
+///```
+pub fn likes_of<T: Into<UserID>>(
+    acct: T,
+    token: &auth::Token,
+) -> impl Stream<Item = Result<Tweet>> {
+    liked_by(acct, token).with_page_size(200).into_stream(None)
+}
+
 ///Retweet the given status as the authenticated user.
 ///
 ///On success, the future returned by this function yields the retweet, with the original status
@@ -19,6 +19,8 @@
 //! - `DraftTweet`: This is what you use to post a new tweet. At present, not all available options
 //!   are supported, but basics like marking the tweet as a reply and attaching a location
 //!   coordinate are available.
+//! - `DraftTweetV2`: This posts a new tweet through version 2 of the API, which supports
+//!   attaching a poll and quoting a tweet by its ID.
 //! - `Timeline`: Returned by several functions in this module, this is how you cursor through a
 //!   collection of tweets. See the struct-level documentation for details.
 //!
@@ -29,9 +31,12 @@
 //! These functions perform actions on their given tweets. They require write access to the
 //! authenticated user's account.
 //!
-//! - `delete` (for creating a tweet, see `DraftTweet`)
+//! - `delete` (for creating a tweet, see `DraftTweet`, or `DraftTweetV2` to attach a poll)
 //! - `like`/`unlike`
 //! - `retweet`/`unretweet`
+//! - `bookmark`/`unbookmark` (these use version 2 of the API, and need an OAuth 2.0 user token)
+//! - `hide_reply` (this uses version 2 of the API, and only works on replies to the authenticated
+//!   user's tweets)
 //!
 //! ### Metadata lookup
 //!
@@ -41,8 +46,23 @@
 //! - `show`
 //! - `lookup`/`lookup_map` (for the differences between these functions, see their respective
 //!   documentations.)
-//! - `retweeters_of`
+//! - `lookup_all` (this loads any number of tweets, 100 at a time)
+//! - `retweeters_of` (`retweeting_users` loads the retweeters' full profiles)
 //! - `retweets_of`
+//! - `quotes_of` (this uses version 2 of the API, and yields pages of `v2::TweetV2`s)
+//! - `metrics` (this uses version 2 of the API to load the impression and click counts of the
+//!   authenticated user's own tweets)
+//! - `thread_of` (this loads the whole thread a tweet is part of, as posted by its author)
+//! - `oembed` (this loads the HTML snippet for embedding a tweet on a web page, and doesn't need a
+//!   `Token`)
+//!
+//! To display a tweet, `render` turns its text into HTML with its hashtags, mentions, and links
+//! linked, and `spans` splits the text into `TweetSpan`s for rendering it some other way.
+//!
+//! The [`v2`][] submodule also has `show` and `lookup` functions, which load tweets through
+//! version 2 of the Twitter API instead, with fields like `conversation_id` and `public_metrics`.
+//!
+//! [`v2`]: v2/index.html
 //!
 //! ### `Timeline` cursors
 //!
@@ -51,16 +71,27 @@
 //! user's own view of Twitter, or with feeds you might see attached to a user's profile page.
 //!
 //! - `home_timeline`/`mentions_timeline`/`retweets_of_me`
-//! - `user_timeline`/`liked_by`
+//! - `user_timeline`/`media_timeline`/`liked_by`
+//!
+//! A `Timeline` can also be turned into a `Stream` of tweets with `into_stream`, which loads older
+//! pages as they're needed; `likes_of` does this for everything a user has liked. To pick a
+//! timeline back up after a restart, save its position with `Timeline::state`, and recreate it
+//! with `Timeline::restore`.
+//!
+//! The authenticated user's bookmarks can be loaded with `bookmarks_of`, which pages through them
+//! with version 2 of the API, and yields `v2::TweetV2`s rather than `Tweet`s.
 
 use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::future::Future;
 use std::pin::Pin;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::task::{Context, Poll};
 
 use chrono;
+use futures::stream::{self, Stream, StreamExt};
 use hyper::{Body, Request};
 use regex::Regex;
 use serde::{Serialize, Deserialize, Deserializer};
@@ -68,12 +99,22 @@ use serde::{Serialize, Deserialize, Deserializer};
 use crate::common::*;
 use crate::error::{Error::InvalidResponse, Result};
 use crate::stream::FilterLevel;
+use crate::text::{character_count, fit, is_url, MAX_WEIGHTED_LENGTH};
+use crate::user::UserID;
 use crate::{auth, entities, error, links, media, place, user};
 
 mod fun;
+mod oembed;
 mod raw;
+mod render;
+pub mod v2;
 
 pub use self::fun::*;
+pub use self::oembed::*;
+pub use self::render::{render, spans, TweetSpan};
+pub use self::v2::{
+    bookmark, bookmarks_of, hide_reply, metrics, quotes_of, unbookmark, DraftTweetV2, TweetMetrics,
+};
 
 round_trip! { raw::RawTweet,
     ///Represents a single status update.
@@ -88,7 +129,7 @@ round_trip! { raw::RawTweet,
     ///* `text`
     ///* `id`
     ///* `created_at`
-    ///* `user`
+    ///* `user`/`user_id`
     ///* `source`
     ///* `favorite_count`/`retweet_count`
     ///* `lang`, though third-party clients usually don't surface this at a user-interface level.
@@ -125,6 +166,20 @@ round_trip! { raw::RawTweet,
     ///* `retweeted_status`
     ///* `quoted_status`/`quoted_status_id`
     ///
+    ///## Enterprise streams
+    ///
+    ///Tweets delivered by enterprise streams like PowerTrack say which of the stream's rules they
+    ///matched, so they can be routed without having to check them again.
+    ///
+    ///* `matching_rules`
+    ///
+    ///## Edits
+    ///
+    ///If Twitter includes the edit history of a tweet, this field lists the IDs of every version of
+    ///it, whether or not it has actually been edited. See also `is_edited` and `edit_history`.
+    ///
+    ///* `edit_history_ids`
+    ///
     ///## Media
     ///
     ///As a tweet can attach an image, GIF, or video, these fields allow you to access information
@@ -160,6 +215,13 @@ round_trip! { raw::RawTweet,
         ///If this tweet is an extended tweet with "hidden" metadata and entities, contains the byte
         ///offsets between which the "displayable" tweet text is.
         pub display_text_range: Option<(usize, usize)>,
+        ///If present, the IDs of every version of this tweet, from the original tweet to the most
+        ///recent edit.
+        ///
+        ///Edited tweets are posted with a new ID, so this list will include this tweet's own ID,
+        ///along with any edits made before or after it. Use `edit_history` to load all of them at
+        ///once.
+        pub edit_history_ids: Option<Vec<u64>>,
         ///Link, hashtag, and user mention information extracted from the tweet text.
         pub entities: TweetEntities,
         ///Extended media information attached to the tweet, if media is available.
@@ -187,6 +249,9 @@ round_trip! { raw::RawTweet,
         ///Can contain a language ID indicating the machine-detected language of the text, or "und" if
         ///no language could be detected.
         pub lang: Option<String>,
+        ///If this tweet was delivered by an enterprise stream like PowerTrack, the rules that
+        ///matched it.
+        pub matching_rules: Option<Vec<MatchingRule>>,
         ///When present, the `Place` that this tweet is associated with (but not necessarily where it
         ///originated from).
         pub place: Option<place::Place>,
@@ -196,7 +261,16 @@ round_trip! { raw::RawTweet,
         ///If this tweet is quoting another by link, contains the ID of the quoted tweet.
         pub quoted_status_id: Option<u64>,
         ///If this tweet is quoting another by link, contains the quoted tweet.
+        ///
+        ///Twitter includes the full quoted tweet when it's available, so there's no need to load it
+        ///separately with `show`. This is `None` if the quoted tweet has been deleted or can't be
+        ///viewed by the authenticated user, even if `quoted_status_id` is present.
         pub quoted_status: Option<Box<Tweet>>,
+        ///If present, indicates who is allowed to reply to this tweet.
+        ///
+        ///Twitter only includes this for some tweets, so if this is `None`, that doesn't
+        ///necessarily mean that everyone can reply.
+        pub reply_settings: Option<ReplySettings>,
         //"A set of key-value pairs indicating the intended contextual delivery of the containing
         //Tweet. Currently used by Twitter’s Promoted Products."
         //pub scopes: Option<Scopes>,
@@ -220,8 +294,11 @@ round_trip! { raw::RawTweet,
         ///full text is longer than 280 characters.
         pub truncated: bool,
         ///The user who posted this tweet. This field will be absent on tweets included as part of a
-        ///`TwitterUser`.
+        ///`TwitterUser`, and on tweets loaded from a `Timeline` with `trim_user` set.
         pub user: Option<Box<user::TwitterUser>>,
+        ///The ID of the user who posted this tweet. Unlike `user`, this is still filled in for
+        ///tweets loaded from a `Timeline` with `trim_user` set.
+        pub user_id: Option<u64>,
         ///If present and `true`, indicates that this tweet has been withheld due to a DMCA complaint.
         pub withheld_copyright: bool,
         ///If present, contains two-letter country codes indicating where this tweet is being withheld.
@@ -246,6 +323,40 @@ impl TryFrom<raw::RawTweet> for Tweet {
             .or(raw.text)
             .ok_or_else(|| error::Error::MissingValue("text"))?;
         let current_user_retweet = raw.current_user_retweet.map(|cur| cur.id);
+        let edit_history_ids = match raw
+            .edit_history
+            .map(|history| history.edit_tweet_ids)
+            .or(raw.edit_history_tweet_ids)
+        {
+            Some(ids) => Some(
+                ids.iter()
+                    .map(|id| {
+                        id.parse::<u64>().or(Err(InvalidResponse(
+                            "could not parse edit history id as integer",
+                            Some(id.clone()),
+                        )))
+                    })
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            None => raw.edit_history_ids,
+        };
+        let reply_settings = raw
+            .reply_settings
+            .as_deref()
+            .and_then(ReplySettings::from_setting)
+            .or(raw
+                .conversation_control
+                .as_ref()
+                .and_then(|control| ReplySettings::from_policy(&control.policy)));
+
+        let (user, user_id) = match raw.user {
+            Some(raw::RawTweetUser::Full(user)) => {
+                let id = user.id;
+                (Some(user), Some(id))
+            }
+            Some(raw::RawTweetUser::Trimmed { id }) => (None, Some(id)),
+            None => (None, None),
+        };
 
         if let Some(ref mut range) = raw.display_text_range {
             codepoints_to_bytes(range, &text);
@@ -277,6 +388,7 @@ impl TryFrom<raw::RawTweet> for Tweet {
             coordinates: raw.coordinates.map(|coords| coords.coordinates),
             created_at: raw.created_at,
             display_text_range: raw.display_text_range,
+            edit_history_ids,
             entities: raw.entities,
             extended_entities: raw.extended_entities,
             favorite_count: raw.favorite_count,
@@ -287,16 +399,19 @@ impl TryFrom<raw::RawTweet> for Tweet {
             in_reply_to_screen_name: raw.in_reply_to_screen_name,
             in_reply_to_status_id: raw.in_reply_to_status_id,
             lang: raw.lang,
+            matching_rules: raw.matching_rules,
             place: raw.place,
             possibly_sensitive: raw.possibly_sensitive,
             quoted_status_id: raw.quoted_status_id,
             quoted_status: raw.quoted_status,
+            reply_settings,
             retweet_count: raw.retweet_count,
             retweeted: raw.retweeted,
             retweeted_status: raw.retweeted_status,
             source: raw.source,
             truncated: raw.truncated,
-            user: raw.user,
+            user_id,
+            user,
             withheld_copyright: raw.withheld_copyright,
             withheld_in_countries: raw.withheld_in_countries,
             withheld_scope: raw.withheld_scope,
@@ -306,6 +421,102 @@ impl TryFrom<raw::RawTweet> for Tweet {
     }
 }
 
+impl Tweet {
+    ///Returns whether this tweet is part of a chain of edits.
+    ///
+    ///This is true for both the original tweet and any of its edits, as long as Twitter returned
+    ///its edit history.
+    pub fn is_edited(&self) -> bool {
+        matches!(self.edit_history_ids, Some(ref ids) if ids.len() > 1)
+    }
+
+    ///Determines whether this tweet can be shown to a viewer in the given country, and whether its
+    ///media should be hidden behind a content warning.
+    ///
+    ///`viewer_country` is a two-letter country code, compared case-insensitively against
+    ///`withheld_in_countries`. If it's `None`, only tweets that are withheld everywhere (including
+    ///because of a DMCA complaint) are marked as not visible. Separately, tweets marked as
+    ///`possibly_sensitive` are marked with `blur_media`.
+    ///
+    ///The returned `reason` describes why the tweet was hidden or blurred, using `withheld_scope`
+    ///to say whether it was the tweet itself or the user who posted it that was withheld. If the
+    ///tweet is both withheld and sensitive, the reason describes the withholding.
+    pub fn safe_render(&self, viewer_country: Option<&str>) -> SafeRender {
+        let countries = self.withheld_in_countries.as_deref().unwrap_or(&[]);
+        let subject = match self.withheld_scope.as_deref() {
+            Some("user") => "The user who posted this tweet",
+            _ => "This tweet",
+        };
+
+        let withheld = if self.withheld_copyright || countries.iter().any(|c| c == "XY") {
+            Some(format!(
+                "{} has been withheld in response to a DMCA complaint",
+                subject
+            ))
+        } else if countries.iter().any(|c| c == "XX") {
+            Some(format!("{} has been withheld in all countries", subject))
+        } else {
+            viewer_country
+                .filter(|v| countries.iter().any(|c| c.eq_ignore_ascii_case(v)))
+                .map(|v| {
+                    format!(
+                        "{} has been withheld in {}",
+                        subject,
+                        v.to_ascii_uppercase()
+                    )
+                })
+        };
+
+        let blur_media = self.possibly_sensitive == Some(true);
+
+        SafeRender {
+            visible: withheld.is_none(),
+            blur_media,
+            reason: withheld.or_else(|| {
+                if blur_media {
+                    Some("This tweet may contain sensitive content".to_string())
+                } else {
+                    None
+                }
+            }),
+        }
+    }
+
+    ///Buckets the app this tweet was posted from into a broad kind of client, based on its
+    ///`source`.
+    ///
+    ///If Twitter didn't say which app posted the tweet, this returns `ClientCategory::Unknown`. See
+    ///`TweetSource::category` for how apps are classified.
+    pub fn client_category(&self) -> ClientCategory {
+        self.source
+            .as_ref()
+            .map_or(ClientCategory::Unknown, TweetSource::category)
+    }
+
+    ///Returns the best guess at where this tweet was posted from, as a (latitude, longitude) pair.
+    ///
+    ///If the tweet has precise `coordinates` attached, those are returned. Otherwise, this falls
+    ///back to the centroid of the tweet's `place`, if it has one (see `Place::centroid`). If the
+    ///tweet isn't geotagged at all, this returns `None`.
+    pub fn best_coordinates(&self) -> Option<(f64, f64)> {
+        self.coordinates
+            .or_else(|| self.place.as_ref().and_then(place::Place::centroid))
+    }
+}
+
+///Guidance on how to display a tweet, as returned by `Tweet::safe_render`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafeRender {
+    ///Whether the tweet can be shown to the viewer at all. This is `false` if the tweet is
+    ///withheld in the viewer's country.
+    pub visible: bool,
+    ///Whether the tweet's media should be hidden behind a content warning, because it was marked
+    ///as possibly sensitive.
+    pub blur_media: bool,
+    ///A human-readable explanation of why the tweet was hidden or blurred, if it was.
+    pub reason: Option<String>,
+}
+
 ///Represents the app from which a specific tweet was posted.
 ///
 ///This struct is parsed out of the HTML anchor tag that Twitter returns as part of each tweet.
@@ -361,6 +572,52 @@ impl FromStr for TweetSource {
     }
 }
 
+impl TweetSource {
+    ///Buckets this app into a broad kind of client, based on its name.
+    ///
+    ///Apps are classified using a short list of well-known names:
+    ///
+    ///* `OfficialMobile`: "Twitter for iPhone", "Twitter for iPad", "Twitter for Android", and
+    ///  "Twitter for Android Tablets".
+    ///* `OfficialWeb`: "Twitter Web App", "Twitter Web Client", "Mobile Web (M2)", and "TweetDeck".
+    ///* `Automation`: "IFTTT", "Zapier.com", "dlvr.it", and "Buffer", which post on a user's
+    ///  behalf when something happens elsewhere or at a scheduled time.
+    ///
+    ///Any other app with a name is `ThirdParty`, and an app with an empty name is `Unknown`. Since
+    ///app names are chosen by their developers, this is only a rough guide: a bot built on the API
+    ///directly will show up as a `ThirdParty` app.
+    pub fn category(&self) -> ClientCategory {
+        match self.name.as_str() {
+            "Twitter for iPhone"
+            | "Twitter for iPad"
+            | "Twitter for Android"
+            | "Twitter for Android Tablets" => ClientCategory::OfficialMobile,
+            "Twitter Web App" | "Twitter Web Client" | "Mobile Web (M2)" | "TweetDeck" => {
+                ClientCategory::OfficialWeb
+            }
+            "IFTTT" | "Zapier.com" | "dlvr.it" | "Buffer" => ClientCategory::Automation,
+            "" => ClientCategory::Unknown,
+            _ => ClientCategory::ThirdParty,
+        }
+    }
+}
+
+///Represents the broad kind of app a tweet was posted from, as returned by
+///`Tweet::client_category`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ClientCategory {
+    ///One of Twitter's own apps for phones and tablets.
+    OfficialMobile,
+    ///Twitter's website, or one of Twitter's own web-based clients.
+    OfficialWeb,
+    ///An app made by someone other than Twitter.
+    ThirdParty,
+    ///A service that posts tweets automatically, like a cross-posting or scheduling service.
+    Automation,
+    ///The tweet didn't say which app it was posted from.
+    Unknown,
+}
+
 fn deserialize_tweet_source<'de, D>(ser: D) -> std::result::Result<Option<TweetSource>, D::Error>
 where
     D: Deserializer<'de>,
@@ -403,6 +660,91 @@ pub struct ExtendedTweetEntities {
     pub media: Vec<entities::MediaEntity>,
 }
 
+///Represents a rule from an enterprise stream filter that matched a tweet.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct MatchingRule {
+    ///The tag given to the rule when it was added, if any.
+    pub tag: Option<String>,
+    ///Numeric ID for the rule.
+    pub id: u64,
+}
+
+///Represents the set of users who are allowed to reply to a tweet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ReplySettings {
+    ///Anyone can reply.
+    #[serde(rename = "everyone")]
+    Everyone,
+    ///Only users followed by the tweet's author (and users mentioned in the tweet) can reply.
+    #[serde(rename = "following")]
+    Following,
+    ///Only users mentioned in the tweet can reply.
+    #[serde(rename = "mentionedUsers")]
+    MentionedUsers,
+}
+
+impl ReplySettings {
+    ///Parses the `reply_settings` field used by Twitter's v2 API, returning `None` for settings
+    ///this library doesn't know about.
+    fn from_setting(setting: &str) -> Option<ReplySettings> {
+        match setting {
+            "everyone" => Some(ReplySettings::Everyone),
+            "following" => Some(ReplySettings::Following),
+            "mentionedUsers" => Some(ReplySettings::MentionedUsers),
+            _ => None,
+        }
+    }
+
+    ///Parses the `policy` of a v1.1 `conversation_control` object.
+    fn from_policy(policy: &str) -> Option<ReplySettings> {
+        match policy.to_ascii_lowercase().replace('_', "").as_str() {
+            "community" => Some(ReplySettings::Following),
+            "byinvitation" => Some(ReplySettings::MentionedUsers),
+            _ => None,
+        }
+    }
+}
+
+///Sorts the versions of an edited tweet into the order given by its edit history, leaving out any
+///tweets that aren't part of that history.
+fn order_edit_history(history: &[u64], versions: Vec<Tweet>) -> Vec<Tweet> {
+    let mut versions = versions
+        .into_iter()
+        .filter_map(|tweet| {
+            history
+                .iter()
+                .position(|&id| id == tweet.id)
+                .map(|pos| (pos, tweet))
+        })
+        .collect::<Vec<_>>();
+    versions.sort_by_key(|&(pos, _)| pos);
+    versions.dedup_by_key(|&mut (pos, _)| pos);
+    versions.into_iter().map(|(_, tweet)| tweet).collect()
+}
+
+///The Twitter "epoch" used in tweet IDs: Thu Nov 04 01:42:54.657 UTC 2010, in milliseconds since
+///the Unix epoch.
+const TWITTER_EPOCH_MS: i64 = 1_288_834_974_657;
+
+///Returns the smallest tweet ID that could have been posted at or after the given time.
+///
+///Tweet IDs are "snowflake" IDs, which carry the time the tweet was posted in their upper bits.
+///This means that the ID returned from this function can be compared against other tweet IDs to
+///see whether they were posted before or after the given time, without needing to load the tweets
+///themselves. Since the lower bits of the ID are not based on time, the ID given here is not
+///likely to be an actual tweet.
+///
+///Tweets posted before November 4, 2010 don't use snowflake IDs, so any time before then will
+///return zero.
+pub fn timestamp_to_id(timestamp: chrono::DateTime<chrono::Utc>) -> u64 {
+    let offset = timestamp.timestamp_millis() - TWITTER_EPOCH_MS;
+    if offset <= 0 {
+        0
+    } else {
+        (offset as u64) << 22
+    }
+}
+
 /// Helper struct to navigate collections of tweets by requesting tweets older or newer than certain
 /// IDs.
 ///
@@ -484,9 +826,13 @@ pub struct ExtendedTweetEntities {
 /// If you want to manually pull tweets between certain IDs, the baseline `call` function can do
 /// that for you. Keep in mind, though, that `call` doesn't update the `min_id` or `max_id` fields,
 /// so you'll have to set those yourself if you want to follow up with `older` or `newer`.
+///
+/// To only load tweets from a certain span of time, use `since_date` and `until_date`. These set
+/// bounds that apply to every call made with the `Timeline` in addition to the IDs given above,
+/// and are not cleared by `reset`.
 pub struct Timeline {
     ///The URL to request tweets from.
-    link: &'static str,
+    link: CowStr,
     ///The token to authorize requests with.
     token: auth::Token,
     ///Optional set of params to include prior to adding timeline navigation parameters.
@@ -499,6 +845,13 @@ pub struct Timeline {
     pub max_id: Option<u64>,
     ///The smallest/oldest tweet ID returned in the last call to `start`, `older`, or `newer`.
     pub min_id: Option<u64>,
+    ///The `since_id` set by `since_date`, applied to every request.
+    since_bound: Option<u64>,
+    ///The `max_id` set by `until_date`, applied to every request.
+    until_bound: Option<u64>,
+    ///Whether to leave out tweets without attached media from each page, as `media_timeline`
+    ///does.
+    media_only: bool,
 }
 
 impl Timeline {
@@ -551,11 +904,143 @@ impl Timeline {
         since_id: Option<u64>,
         max_id: Option<u64>,
     ) -> Result<Response<Vec<Tweet>>> {
-        request_with_json_response(self.request(since_id, max_id)).await
+        let mut resp: Response<Vec<Tweet>> =
+            request_with_json_response(self.request(since_id, max_id)).await?;
+        resp.response.retain(|tweet| self.keeps(tweet));
+        Ok(resp)
+    }
+
+    ///Page backward through this timeline from the most recent tweet, collecting up to `limit`
+    ///unique tweets.
+    ///
+    ///This repeatedly loads pages of `self.count` tweets (see `with_page_size`), stopping once
+    ///`limit` tweets have been collected or Twitter stops returning new tweets. Any tweet that
+    ///shows up on more than one page is only returned once. If Twitter responds with a rate-limit
+    ///error, this waits until the rate limit resets and tries the same page again, backing off
+    ///exponentially if the reset time has already passed. Server errors, timeouts, and dropped
+    ///connections are retried the same way, up to three times in a row. Any other error, or one of
+    ///these when the [`RetryBudget`] has run out, is returned immediately, discarding the tweets
+    ///loaded so far.
+    ///
+    ///[`RetryBudget`]: ../struct.RetryBudget.html
+    ///
+    ///Note that this ignores any IDs currently saved in the timeline, and always starts from the
+    ///most recent tweet.
+    pub async fn collect_up_to(self, limit: usize) -> Result<Vec<Tweet>> {
+        self.collect_with_gate(limit, &RateLimitGate::default())
+            .await
+    }
+
+    ///Helper function for `collect_up_to` that shares the given rate-limit state with other
+    ///timelines.
+    pub(crate) async fn collect_with_gate(
+        self,
+        limit: usize,
+        gate: &RateLimitGate,
+    ) -> Result<Vec<Tweet>> {
+        let load = |max_id| request_with_json_response(self.request(None, max_id));
+        collect_pages(limit, gate, |tweet| self.keeps(tweet), load).await
+    }
+
+    ///Turn this timeline into a `Stream` of its tweets, loading older pages as needed.
+    ///
+    ///The stream starts just below the oldest tweet this timeline has already loaded (or from the
+    ///most recent tweet, if it hasn't loaded any), and loads pages of `self.count` tweets the same
+    ///way `older` does, yielding their tweets one at a time. If `since_id` is given, only tweets
+    ///newer than it are loaded. The stream ends when Twitter stops returning new tweets, which
+    ///happens once it reaches `since_id` or the oldest tweet Twitter will load for this timeline
+    ///(3,200 tweets back for a user's timeline, and 800 for the home timeline). Any tweet that
+    ///shows up on more than one page is only returned once.
+    ///
+    ///If Twitter says the rate limit has been reached, the stream waits for the rate limit to
+    ///reset before loading the page again, as long as the process-wide [`RetryBudget`] allows it.
+    ///Server errors, timeouts, and dropped connections are retried the same way, up to three
+    ///times in a row. Any other error is returned, and the stream ends.
+    ///
+    ///[`RetryBudget`]: ../struct.RetryBudget.html
+    ///
+    ///```rust,no_run
+    ///# use egg_mode::Token;
+    ///# #[tokio::main]
+    ///# async fn main() {
+    ///# let token: Token = unimplemented!();
+    ///use egg_mode::tweet;
+    ///use futures::TryStreamExt;
+    ///
+    ///tweet::user_timeline("rustlang", true, true, &token)
+    ///    .with_page_size(200)
+    ///    .into_stream(None)
+    ///    .try_for_each(|tweet| async move {
+    ///        println!("{}", tweet.text);
+    ///        Ok(())
+    ///    })
+    ///    .await
+    ///    .unwrap();
+    ///# }
+    ///```
+    pub fn into_stream(self, since_id: Option<u64>) -> impl Stream<Item = Result<Tweet>> {
+        let start = self.min_id.map(|id| id.saturating_sub(1));
+        let media_only = self.media_only;
+        let keep = move |tweet: &Tweet| !media_only || has_media(tweet);
+        let load = move |max_id| request_with_json_response(self.request(since_id, max_id));
+        stream_pages(start, keep, load)
+    }
+
+    ///Loads every tweet in this timeline that's newer than `since_id` and older than
+    ///`newest_seen`.
+    ///
+    ///This is meant for polling a timeline with `since_id`: if more tweets were posted since the
+    ///last poll than fit in one page, Twitter returns only the newest of them, leaving a gap
+    ///between `since_id` and the oldest tweet that was returned. Give that tweet's ID as
+    ///`newest_seen` to load the rest. See [`fill_gap`] for details.
+    ///
+    ///[`fill_gap`]: fn.fill_gap.html
+    ///
+    ///This ignores any IDs currently saved in the timeline, and doesn't update them.
+    pub async fn fill_gap(&self, since_id: u64, newest_seen: u64) -> Result<Vec<Tweet>> {
+        let mut tweets = fill_gap(
+            |since_id, max_id| {
+                request_with_json_response(self.request(Some(since_id), Some(max_id)))
+            },
+            since_id,
+            newest_seen,
+        )
+        .await?;
+        tweets.retain(|tweet| self.keeps(tweet));
+        Ok(tweets)
+    }
+
+    ///Restricts this timeline to tweets posted at or after the given time.
+    ///
+    ///This converts the given time into a tweet ID with `timestamp_to_id`, and sends it as the
+    ///`since_id` of every request made with this timeline. If a call is also given a `since_id`,
+    ///the more recent of the two is used.
+    pub fn since_date(self, date: chrono::DateTime<chrono::Utc>) -> Self {
+        Timeline {
+            since_bound: Some(timestamp_to_id(date).saturating_sub(1)),
+            ..self
+        }
+    }
+
+    ///Restricts this timeline to tweets posted before the given time.
+    ///
+    ///This converts the given time into a tweet ID with `timestamp_to_id`, and sends it as the
+    ///`max_id` of every request made with this timeline. If a call is also given a `max_id`, the
+    ///older of the two is used.
+    pub fn until_date(self, date: chrono::DateTime<chrono::Utc>) -> Self {
+        Timeline {
+            until_bound: Some(timestamp_to_id(date).saturating_sub(1)),
+            ..self
+        }
     }
 
     ///Helper function to construct a `Request` from the current state.
     fn request(&self, since_id: Option<u64>, max_id: Option<u64>) -> Request<Body> {
+        let since_id = std::cmp::max(since_id, self.since_bound);
+        let max_id = match (max_id, self.until_bound) {
+            (Some(id), Some(bound)) => Some(std::cmp::min(id, bound)),
+            (id, bound) => id.or(bound),
+        };
         let params = ParamList::from(self.params_base.as_ref().cloned().unwrap_or_default())
             .add_param("count", self.count.to_string())
             .add_param("tweet_mode", "extended")
@@ -563,7 +1048,73 @@ impl Timeline {
             .add_opt_param("since_id", since_id.map(|v| v.to_string()))
             .add_opt_param("max_id", max_id.map(|v| v.to_string()));
 
-        get(self.link, &self.token, Some(&params))
+        get(&self.link, &self.token, Some(&params))
+    }
+
+    ///Save the position of this timeline, so it can be picked up again later with `restore`.
+    ///
+    ///The returned `TimelineState` holds everything needed to recreate this timeline except for
+    ///its token: which timeline it loads, its page size, the IDs saved from the last call to
+    ///`start`, `older`, or `newer`, and any bounds set with `since_date` or `until_date`. It can be
+    ///serialized with serde, so that (for example) a bot can save where it left off before it
+    ///shuts down, and call `newer` on the restored timeline when it starts back up to load only
+    ///the tweets posted since then.
+    ///
+    ///```rust,no_run
+    ///# use egg_mode::Token;
+    ///# #[tokio::main]
+    ///# async fn main() {
+    ///# let token: Token = unimplemented!();
+    ///use egg_mode::tweet::{self, Timeline, TimelineState};
+    ///
+    ///let (timeline, _feed) = tweet::home_timeline(&token).start().await.unwrap();
+    ///let saved = serde_json::to_string(&timeline.state()).unwrap();
+    ///
+    ///// ...later...
+    ///let state: TimelineState = serde_json::from_str(&saved).unwrap();
+    ///let (timeline, _new_posts) = Timeline::restore(state, &token).newer(None).await.unwrap();
+    ///# }
+    ///```
+    pub fn state(&self) -> TimelineState {
+        TimelineState {
+            link: self.link.clone(),
+            params: self
+                .params_base
+                .as_ref()
+                .map(|params| params.iter().map(|(k, v)| (k.clone(), v.clone())).collect()),
+            count: self.count,
+            max_id: self.max_id,
+            min_id: self.min_id,
+            since_bound: self.since_bound,
+            until_bound: self.until_bound,
+            media_only: self.media_only,
+        }
+    }
+
+    ///Recreate a timeline from a position saved with `state`, using the given token to make its
+    ///requests.
+    ///
+    ///The restored timeline loads the same tweets, and picks up from the same place, as the one
+    ///the state was saved from. The request is sent to whatever URL is in the state, so only
+    ///restore states that came from `state` in the first place.
+    pub fn restore(state: TimelineState, token: &auth::Token) -> Timeline {
+        Timeline {
+            link: state.link,
+            token: token.clone(),
+            params_base: state.params.map(|params| {
+                let mut params_base = ParamList::new();
+                for (k, v) in params {
+                    params_base.push_param(k, v);
+                }
+                params_base
+            }),
+            count: state.count,
+            max_id: state.max_id,
+            min_id: state.min_id,
+            since_bound: state.since_bound,
+            until_bound: state.until_bound,
+            media_only: state.media_only,
+        }
     }
 
     ///Helper builder function to set the page size.
@@ -574,12 +1125,62 @@ impl Timeline {
         }
     }
 
+    ///Sets whether to leave replies out of this timeline.
+    ///
+    ///This works with the home timeline and user timelines. Note that Twitter loads a page of
+    ///tweets before removing the replies from it, so pages may have fewer tweets than the
+    ///requested page size.
+    pub fn exclude_replies(self, exclude: bool) -> Self {
+        self.with_param("exclude_replies", exclude.to_string())
+    }
+
+    ///Sets whether to include retweets in this timeline.
+    ///
+    ///This works with user timelines and list timelines. As with `exclude_replies`, pages may
+    ///have fewer tweets than the requested page size when retweets are left out.
+    pub fn include_rts(self, include: bool) -> Self {
+        self.with_param("include_rts", include.to_string())
+    }
+
+    ///Sets whether to leave the author's profile out of each tweet in this timeline.
+    ///
+    ///With this set, the tweets in this timeline will have `user` set to `None`, and only carry the
+    ///author's ID in `user_id`. This makes the pages of tweets much smaller, which is useful when
+    ///the authors are already known, like for a single user's timeline. This works with the home,
+    ///mentions, user, and list timelines, as well as `retweets_of_me`.
+    pub fn trim_user(self, trim: bool) -> Self {
+        self.with_param("trim_user", trim.to_string())
+    }
+
+    ///Adds the given parameter to every request made with this timeline, replacing any value
+    ///already set for it.
+    fn with_param(self, key: &'static str, value: String) -> Self {
+        let params = self.params_base.unwrap_or_default().add_param(key, value);
+        Timeline {
+            params_base: Some(params),
+            ..self
+        }
+    }
+
     ///With the returned slice of Tweets, set the min_id and max_id on self.
     fn map_ids(&mut self, resp: &[Tweet]) {
         self.max_id = resp.first().map(|status| status.id);
         self.min_id = resp.last().map(|status| status.id);
     }
 
+    ///Returns whether the given tweet belongs in the pages returned from this timeline.
+    fn keeps(&self, tweet: &Tweet) -> bool {
+        !self.media_only || has_media(tweet)
+    }
+
+    ///Leaves out tweets without attached media from the pages returned from this timeline.
+    pub(crate) fn media_only(self) -> Self {
+        Timeline {
+            media_only: true,
+            ..self
+        }
+    }
+
     ///Create an instance of `Timeline` with the given link and tokens.
     pub(crate) fn new(
         link: &'static str,
@@ -587,14 +1188,380 @@ impl Timeline {
         token: &auth::Token,
     ) -> Self {
         Timeline {
-            link: link,
+            link: link.into(),
             token: token.clone(),
             params_base: params_base,
             count: 20,
             max_id: None,
             min_id: None,
+            since_bound: None,
+            until_bound: None,
+            media_only: false,
+        }
+    }
+}
+
+///The saved position of a `Timeline`, which can be serialized and later turned back into a
+///`Timeline` with `Timeline::restore`.
+///
+///This is returned by `Timeline::state`; see that method for details. Its contents are private,
+///so that the `Timeline` can keep track of what it needs to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimelineState {
+    link: CowStr,
+    params: Option<Vec<(CowStr, CowStr)>>,
+    count: i32,
+    max_id: Option<u64>,
+    min_id: Option<u64>,
+    since_bound: Option<u64>,
+    until_bound: Option<u64>,
+    #[serde(default)]
+    media_only: bool,
+}
+
+///Returns whether the given tweet has any photos, videos, or GIFs attached to it.
+fn has_media(tweet: &Tweet) -> bool {
+    match tweet.extended_entities {
+        Some(ref entities) => !entities.media.is_empty(),
+        None => false,
+    }
+}
+
+///Helper function for `Timeline::collect_up_to` that pages backward using the given loader.
+///
+///The loader is called with the `max_id` to request, starting from `None`. If it returns a
+///rate-limit error, the given `gate` is closed until the limit resets. Only tweets that `keep`
+///returns `true` for are collected, but paging continues until a page has no unseen tweets at all.
+async fn collect_pages<K, F, Fut>(
+    limit: usize,
+    gate: &RateLimitGate,
+    keep: K,
+    mut load: F,
+) -> Result<Vec<Tweet>>
+where
+    K: Fn(&Tweet) -> bool,
+    F: FnMut(Option<u64>) -> Fut,
+    Fut: Future<Output = Result<Response<Vec<Tweet>>>>,
+{
+    let mut seen = HashSet::new();
+    let mut tweets = Vec::new();
+    let mut max_id = None;
+    let mut attempt = 0;
+
+    while tweets.len() < limit {
+        gate.wait().await;
+        let page = match load(max_id).await {
+            Ok(resp) => resp.response,
+            // timelines are loaded with GET, so every failure worth retrying is safe to retry
+            Err(e) => match retry_reset(&e, true, attempt) {
+                Some(reset) if gate.try_retry() => {
+                    gate.hold_for(rate_limit_backoff(reset, attempt));
+                    attempt += 1;
+                    continue;
+                }
+                _ => return Err(e),
+            },
+        };
+        attempt = 0;
+
+        let before = seen.len();
+        for tweet in page {
+            max_id = Some(std::cmp::min(
+                max_id.unwrap_or(u64::MAX),
+                tweet.id.saturating_sub(1),
+            ));
+            if tweets.len() < limit && seen.insert(tweet.id) && keep(&tweet) {
+                tweets.push(tweet);
+            }
+        }
+
+        if seen.len() == before {
+            break;
+        }
+    }
+
+    Ok(tweets)
+}
+
+///Helper function for `Timeline::into_stream` that pages backward using the given loader.
+///
+///The loader is called with the `max_id` to request, starting from `start`. Only tweets that `keep`
+///returns `true` for are yielded, but paging continues until a page has no unseen tweets at all.
+fn stream_pages<K, F, Fut>(
+    start: Option<u64>,
+    keep: K,
+    load: F,
+) -> impl Stream<Item = Result<Tweet>>
+where
+    K: Fn(&Tweet) -> bool,
+    F: FnMut(Option<u64>) -> Fut,
+    Fut: Future<Output = Result<Response<Vec<Tweet>>>>,
+{
+    let state = (load, keep, start, HashSet::new(), false);
+    stream::unfold(
+        state,
+        |(mut load, keep, max_id, mut seen, done)| async move {
+            if done {
+                return None;
+            }
+
+            let mut attempt = 0;
+            let page = loop {
+                // timelines are loaded with GET, so every failure worth retrying is safe to retry
+                let reset = match load(max_id).await {
+                    Ok(resp) => break resp.response,
+                    Err(e) => match retry_reset(&e, true, attempt) {
+                        Some(reset) if retry_budget().try_acquire() => reset,
+                        _ => return Some((vec![Err(e)], (load, keep, max_id, seen, true))),
+                    },
+                };
+                tokio::time::delay_for(rate_limit_backoff(reset, attempt)).await;
+                attempt += 1;
+            };
+
+            let next_max_id = page
+                .iter()
+                .map(|tweet| tweet.id.saturating_sub(1))
+                .min()
+                .map(|id| std::cmp::min(id, max_id.unwrap_or(u64::MAX)));
+            let tweets = page
+                .into_iter()
+                .filter(|tweet| seen.insert(tweet.id))
+                .collect::<Vec<_>>();
+            // if a page gives us nothing new, asking for the next one won't either
+            if tweets.is_empty() {
+                return None;
+            }
+            let tweets = tweets
+                .into_iter()
+                .filter(|tweet| keep(tweet))
+                .map(Ok)
+                .collect::<Vec<_>>();
+
+            Some((tweets, (load, keep, next_max_id, seen, false)))
+        },
+    )
+    .flat_map(stream::iter)
+}
+
+///Loads every tweet newer than `since_id` and older than `newest_seen`, using the given function to
+///load each page.
+///
+///`fetch` is called with a `since_id` and `max_id` to request, and should return the matching
+///page of tweets, newest first, like `Timeline::call` does. The first page is requested with a
+///`max_id` just below `newest_seen`, and each page after that picks up just below the oldest
+///tweet on the previous one, until a page reaches `since_id` or comes back with no new tweets.
+///
+///The tweets are returned newest first, with each tweet appearing once, even if it showed up on
+///more than one page. Neither the tweet given by `since_id` nor the one given by `newest_seen` is
+///included. If Twitter responds with a rate-limit error, this waits until the rate limit resets
+///and tries the same page again, as long as the process-wide [`RetryBudget`] allows it. Any other
+///error is returned immediately, discarding the tweets loaded so far.
+///
+///[`RetryBudget`]: ../struct.RetryBudget.html
+///
+///`Timeline::fill_gap` calls this with a `Timeline`, but this can be used with any endpoint that
+///pages through tweets by ID.
+pub async fn fill_gap<F, Fut>(mut fetch: F, since_id: u64, newest_seen: u64) -> Result<Vec<Tweet>>
+where
+    F: FnMut(u64, u64) -> Fut,
+    Fut: Future<Output = Result<Response<Vec<Tweet>>>>,
+{
+    let mut seen = HashSet::new();
+    let mut tweets = Vec::new();
+    let mut max_id = newest_seen.saturating_sub(1);
+    let mut attempt = 0;
+
+    while max_id > since_id {
+        let page = match fetch(since_id, max_id).await {
+            Ok(resp) => resp.response,
+            Err(e) => match retry_reset(&e, true, attempt) {
+                Some(reset) if retry_budget().try_acquire() => {
+                    tokio::time::delay_for(rate_limit_backoff(reset, attempt)).await;
+                    attempt += 1;
+                    continue;
+                }
+                _ => return Err(e),
+            },
+        };
+        attempt = 0;
+
+        let before = tweets.len();
+        for tweet in page {
+            if tweet.id > since_id && tweet.id <= max_id && seen.insert(tweet.id) {
+                tweets.push(tweet);
+            }
+        }
+
+        match tweets[before..].iter().map(|t| t.id).min() {
+            Some(oldest) => max_id = oldest - 1,
+            None => break,
+        }
+    }
+
+    tweets.sort_by_key(|t| std::cmp::Reverse(t.id));
+    Ok(tweets)
+}
+
+///Helper function for `thread_of` that extends the given thread with the replies that continue
+///it, following each tweet to the earliest reply to it.
+fn continue_thread(mut thread: Vec<Tweet>, mut replies: Vec<Tweet>) -> Vec<Tweet> {
+    replies.sort_by_key(|tweet| tweet.id);
+    for reply in replies {
+        let last = thread[thread.len() - 1].id;
+        if reply.in_reply_to_status_id == Some(last) {
+            thread.push(reply);
         }
     }
+    thread
+}
+
+///Helper function for `exists` that turns a "no status found" error from loading a tweet into
+///`false`.
+fn tweet_exists(loaded: Result<Response<Tweet>>) -> Result<Response<bool>> {
+    match loaded {
+        Ok(resp) => Ok(Response::map(resp, |_| true)),
+        Err(error::Error::TwitterError(headers, errors))
+            if errors.errors.iter().any(|e| e.code == 144 || e.code == 34) =>
+        {
+            Ok(Response::new(RateLimit::try_from(&headers)?, false))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+///Helper function for `crawl_many` that runs the given crawl function for each user, sharing one
+///`RateLimitGate` between them all.
+///
+///At most `concurrency` crawls are run at once, and their results are returned in the same order
+///as `users`.
+fn crawl_users<F, Fut>(
+    users: Vec<UserID>,
+    concurrency: usize,
+    mut crawl: F,
+) -> impl Stream<Item = (UserID, Result<Vec<Tweet>>)>
+where
+    F: FnMut(UserID, Arc<RateLimitGate>) -> Fut,
+    Fut: Future<Output = Result<Vec<Tweet>>>,
+{
+    let gate = Arc::new(RateLimitGate::default());
+    stream::iter(users)
+        .map(move |user| {
+            let crawled = crawl(user.clone(), gate.clone());
+            async move { (user, crawled.await) }
+        })
+        .buffered(std::cmp::max(concurrency, 1))
+}
+
+///Helper function for `rehydrate` that loads the given IDs in chunks of 100 using the given loader.
+///
+///The loader is called with each chunk of IDs, and is called again with the same chunk if it
+///returns a rate-limit error, once the limit has reset.
+fn rehydrate_chunks<S, F, Fut>(ids: S, load: F) -> impl Stream<Item = Result<(u64, Option<Tweet>)>>
+where
+    S: Stream<Item = u64>,
+    F: FnMut(Vec<u64>) -> Fut,
+    Fut: Future<Output = Result<Response<HashMap<u64, Option<Tweet>>>>>,
+{
+    let state = (
+        Box::pin(ids.chunks(100)),
+        load,
+        RateLimitGate::default(),
+        false,
+    );
+    stream::unfold(state, |(mut chunks, mut load, gate, done)| async move {
+        if done {
+            return None;
+        }
+
+        let chunk = chunks.next().await?;
+        let idempotent = is_idempotent(&hyper::Method::POST, links::statuses::LOOKUP);
+        let mut attempt = 0;
+        let found = loop {
+            gate.wait().await;
+            match load(chunk.clone()).await {
+                Ok(resp) => break resp.response,
+                Err(e) => match retry_reset(&e, idempotent, attempt) {
+                    Some(reset) if gate.try_retry() => {
+                        gate.hold_for(rate_limit_backoff(reset, attempt));
+                        attempt += 1;
+                    }
+                    _ => return Some((vec![Err(e)], (chunks, load, gate, true))),
+                },
+            }
+        };
+
+        let pairs = chunk
+            .into_iter()
+            .map(|id| Ok((id, found.get(&id).cloned().flatten())))
+            .collect::<Vec<_>>();
+        Some((pairs, (chunks, load, gate, false)))
+    })
+    .flat_map(stream::iter)
+}
+
+///Helper function for `retweeting_users` that loads the users with the given IDs in chunks of up
+///to 100 using the given loader.
+///
+///IDs are gathered into chunks as they come in, so each page of IDs is loaded as soon as it
+///arrives. The users are yielded in the same order as their IDs. The first error, whether from
+///`ids` or from the loader, is yielded and ends the stream.
+fn hydrate_users<S, F, Fut>(
+    ids: S,
+    load: F,
+) -> impl Stream<Item = Result<Response<user::TwitterUser>>>
+where
+    S: Stream<Item = Result<Response<u64>>>,
+    F: FnMut(Vec<u64>) -> Fut,
+    Fut: Future<Output = Result<Response<Vec<user::TwitterUser>>>>,
+{
+    // stop after the first error, rather than letting the cursor try the same page again
+    let ids = ids.scan(false, |failed, id| {
+        let next = if *failed { None } else { Some(id) };
+        *failed = matches!(next, Some(Err(_)));
+        futures::future::ready(next)
+    });
+    let state = (Box::pin(ids.ready_chunks(100)), load, false);
+    stream::unfold(state, |(mut chunks, mut load, done)| async move {
+        if done {
+            return None;
+        }
+
+        let chunk = chunks.next().await?;
+        let mut ids = Vec::new();
+        let mut error = None;
+        for id in chunk {
+            match id {
+                Ok(id) => ids.push(id.response),
+                Err(e) => error = Some(e),
+            }
+        }
+
+        let mut users = Vec::new();
+        if !ids.is_empty() {
+            match load(ids.clone()).await {
+                Ok(resp) => {
+                    let rate_limit = resp.rate_limit_status;
+                    let mut found = resp
+                        .response
+                        .into_iter()
+                        .map(|user| (user.id, user))
+                        .collect::<HashMap<_, _>>();
+                    users.extend(
+                        ids.iter()
+                            .filter_map(|id| found.remove(id))
+                            .map(|user| Ok(Response::new(rate_limit, user))),
+                    );
+                }
+                Err(e) => return Some((vec![Err(e)], (chunks, load, true))),
+            }
+        }
+
+        let done = error.is_some();
+        users.extend(error.map(Err));
+        Some((users, (chunks, load, done)))
+    })
+    .flat_map(stream::iter)
 }
 
 /// `Future` which represents loading from a `Timeline`.
@@ -615,9 +1582,12 @@ impl Future for TimelineFuture {
         match Pin::new(&mut self.loader).poll(cx) {
             Poll::Pending => Poll::Pending,
             Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
-            Poll::Ready(Ok(resp)) => {
+            Poll::Ready(Ok(mut resp)) => {
                 if let Some(mut timeline) = self.timeline.take() {
+                    // save the IDs from the whole page, so that paging doesn't revisit tweets that
+                    // were filtered out
                     timeline.map_ids(&resp.response);
+                    resp.response.retain(|tweet| timeline.keeps(tweet));
                     Poll::Ready(Ok((timeline, resp)))
                 } else {
                     Poll::Ready(Err(error::Error::FutureAlreadyCompleted))
@@ -715,6 +1685,15 @@ pub struct DraftTweet {
     ///States whether the media attached with `media_ids` should be labeled as "possibly
     ///sensitive", to mask the media by default.
     pub possibly_sensitive: Option<bool>,
+    ///If present, restricts who is allowed to reply to the tweet.
+    pub reply_settings: Option<ReplySettings>,
+    ///If present and `true`, only the authenticated user's Super Followers can see the tweet.
+    pub for_super_followers: Option<bool>,
+    ///If present, the options of a poll to attach to the draft, and how many minutes it's open
+    ///for.
+    pub poll: Option<(Vec<CowStr>, u32)>,
+    ///The users to tag in the photos attached with `media_ids`.
+    pub media_tagged_users: Vec<UserID>,
 }
 
 impl DraftTweet {
@@ -731,9 +1710,23 @@ impl DraftTweet {
             place_id: None,
             media_ids: Vec::new(),
             possibly_sensitive: None,
+            reply_settings: None,
+            for_super_followers: None,
+            poll: None,
+            media_tagged_users: Vec::new(),
         }
     }
 
+    ///Returns how many more characters could be added to this draft's text before it's too long
+    ///to be sent, as counted by `text::character_count`. This is negative if the text is already
+    ///too long, in which case `send` would fail with error code 186.
+    ///
+    ///Links in the text count as 23 characters each. A link given to `attachment_url` doesn't
+    ///count against the text at all.
+    pub fn remaining_chars(&self) -> i32 {
+        crate::text::remaining_chars(&self.text)
+    }
+
     ///Marks this draft tweet as replying to the given status ID.
     ///
     ///Note that this will only properly take effect if the user who posted the given status is
@@ -815,6 +1808,79 @@ impl DraftTweet {
         }
     }
 
+    ///Attach the given Place to this tweet, such as one picked from the results of a
+    ///`place::search_query`.
+    ///
+    ///This sets `place_id` to the place's ID. To also attach a coordinate for the place, for
+    ///example its `centroid`, call `coordinates` as well.
+    ///
+    ///```rust,no_run
+    ///# use egg_mode::Token;
+    ///# #[tokio::main]
+    ///# async fn main() {
+    ///# let token: Token = unimplemented!();
+    ///use egg_mode::place;
+    ///use egg_mode::tweet::DraftTweet;
+    ///
+    ///let results = place::search_query("portland").call(&token).await.unwrap();
+    ///let place = &results.results[0];
+    ///
+    ///let mut draft = DraftTweet::new("hello from portland").place(place);
+    ///if let Some((lat, long)) = place.centroid() {
+    ///    draft = draft.coordinates(lat, long, false);
+    ///}
+    ///# }
+    ///```
+    ///
+    ///Location fields will be ignored unless the user has enabled geolocation from their profile.
+    pub fn place(self, place: &place::Place) -> Self {
+        self.place_id(place.id.clone())
+    }
+
+    ///Looks up the place at the given coordinate with `place::place_at`, and attaches it to this
+    ///tweet.
+    ///
+    ///`granularity` says what kind of place to look for, such as a `PlaceType::City` for "tweeting
+    ///from Portland". If `display_coordinates` is given, the coordinate is attached as well, as
+    ///with `coordinates`, and the flag says whether a pin is shown on it. If no place was found,
+    ///the draft is returned as it was, aside from those coordinates.
+    ///
+    ///```rust,no_run
+    ///# use egg_mode::Token;
+    ///# #[tokio::main]
+    ///# async fn main() {
+    ///# let token: Token = unimplemented!();
+    ///use egg_mode::place::PlaceType;
+    ///use egg_mode::tweet::DraftTweet;
+    ///
+    ///let draft = DraftTweet::new("hello from here")
+    ///    .auto_place(45.5231, -122.6765, PlaceType::Neighborhood, None, &token)
+    ///    .await
+    ///    .unwrap();
+    ///draft.send(&token).await.unwrap();
+    ///# }
+    ///```
+    ///
+    ///Location fields will be ignored unless the user has enabled geolocation from their profile.
+    pub async fn auto_place(
+        self,
+        latitude: f64,
+        longitude: f64,
+        granularity: place::PlaceType,
+        display_coordinates: Option<bool>,
+        token: &auth::Token,
+    ) -> Result<Self> {
+        let found = place::place_at((latitude, longitude), granularity, token).await?;
+        let draft = match display_coordinates {
+            Some(display) => self.coordinates(latitude, longitude, display),
+            None => self,
+        };
+        Ok(match found {
+            Some(place) => draft.place(&place),
+            None => draft,
+        })
+    }
+
     ///Attaches the given media ID(s) to this tweet. If more than four IDs are in this slice, only
     ///the first four will be attached. Note that Twitter will only allow one GIF, one video, or up
     ///to four images to be attached to a single tweet.
@@ -827,17 +1893,218 @@ impl DraftTweet {
         self.media_ids.push(media_id);
     }
 
-    ///Marks the media attached with `media_ids` as being sensitive, so it can be hidden by
-    ///default.
-    pub fn possibly_sensitive(self, sensitive: bool) -> Self {
-        DraftTweet {
-            possibly_sensitive: Some(sensitive),
-            ..self
-        }
-    }
+    ///Uploads the given file and attaches it to this tweet, returning its media ID.
+    ///
+    ///The file's media type is picked from its extension with `media_types::from_path`; a file
+    ///with any other extension can't be attached this way, and returns an `IOError`. Otherwise,
+    ///this works like `attach_media_reader`, reading the file a chunk at a time.
+    ///
+    ///```rust,no_run
+    ///# use egg_mode::Token;
+    ///# #[tokio::main]
+    ///# async fn main() {
+    ///# let token: Token = unimplemented!();
+    ///use egg_mode::tweet::DraftTweet;
+    ///
+    ///let mut draft = DraftTweet::new("Hey, check out this cute cat!");
+    ///draft.attach_media_file("cat.png", &token).await.unwrap();
+    ///let tweet = draft.send(&token).await.unwrap();
+    ///# }
+    ///```
+    pub async fn attach_media_file(
+        &mut self,
+        path: impl AsRef<std::path::Path>,
+        token: &auth::Token,
+    ) -> Result<media::MediaId> {
+        let path = path.as_ref();
+        let media_type = media::media_types::from_path(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "file extension isn't a media type Twitter accepts",
+            )
+        })?;
+        let file = tokio::fs::File::open(path).await?;
+        let size = file.metadata().await?.len();
+        self.attach_media_reader(file, size, &media_type, token)
+            .await
+    }
 
-    ///Send the assembled tweet as the authenticated user.
-    pub async fn send(&self, token: &auth::Token) -> Result<Response<Tweet>> {
+    ///Uploads the media read from `reader` and attaches it to this tweet, returning its media ID.
+    ///
+    ///This uploads the media with `media::upload_reader`, so `total_size` must be the exact number
+    ///of bytes `reader` will produce. It then waits for Twitter to finish processing the upload
+    ///with `media::wait_for_processing`, which can take a while for videos, and adds the media to
+    ///the draft with `add_media`. The returned ID can be used to add alt text with
+    ///`media::set_metadata` before the draft is sent.
+    pub async fn attach_media_reader(
+        &mut self,
+        reader: impl tokio::io::AsyncRead + Send,
+        total_size: u64,
+        media_type: &mime::Mime,
+        token: &auth::Token,
+    ) -> Result<media::MediaId> {
+        let handle = media::upload_reader(reader, total_size, media_type, token).await?;
+        let handle = media::wait_for_processing(handle, token).await?;
+        self.add_media(handle.id.clone());
+        Ok(handle.id)
+    }
+
+    ///Marks the media attached with `media_ids` as being sensitive, so it can be hidden by
+    ///default.
+    pub fn possibly_sensitive(self, sensitive: bool) -> Self {
+        DraftTweet {
+            possibly_sensitive: Some(sensitive),
+            ..self
+        }
+    }
+
+    ///Restricts who can reply to the tweet.
+    ///
+    ///By default, anyone can reply. Note that users mentioned in the tweet can always reply to it.
+    ///
+    ///Version 1.1 of the API can't restrict replies, so a draft with any setting other than
+    ///`ReplySettings::Everyone` is sent through version 2 instead, the same way as a draft with a
+    ///poll. (See `poll` for the settings that don't apply when that happens.) The setting comes
+    ///back in the posted tweet's `reply_settings`.
+    pub fn reply_settings(self, settings: ReplySettings) -> Self {
+        DraftTweet {
+            reply_settings: Some(settings),
+            ..self
+        }
+    }
+
+    ///Marks whether the tweet should only be visible to the authenticated user's Super Followers.
+    ///
+    ///Only accounts in Twitter's Super Follows program can post these tweets. If the account
+    ///can't, `send` will return `Error::SuperFollowsNotAllowed`.
+    ///
+    ///Version 1.1 of the API can't post these tweets, so a draft marked for Super Followers is
+    ///sent through version 2 instead, the same way as a draft with a poll. (See `poll` for the
+    ///settings that don't apply when that happens.)
+    pub fn for_super_followers(self, super_followers: bool) -> Self {
+        DraftTweet {
+            for_super_followers: Some(super_followers),
+            ..self
+        }
+    }
+
+    ///Attaches a poll with the given options to the tweet, open for the given number of minutes.
+    ///
+    ///Version 1.1 of the API can't post polls, so a draft with a poll is sent through version 2
+    ///instead, the same way as a `DraftTweetV2`, and the new tweet is then loaded with `show`.
+    ///This means `coordinates`, `place_id`, `attachment_url`, `auto_populate_reply_metadata`, and
+    ///`possibly_sensitive` don't apply to a draft with a poll. Replies always have their mentions
+    ///filled in, and `exclude_reply_user_ids` still applies.
+    ///
+    ///Twitter accepts two to four options of up to 25 characters each, and a duration between 5
+    ///minutes and 7 days (10080 minutes). A tweet with a poll can't have media attached as well.
+    ///`send` checks these limits before contacting Twitter, and returns `Error::InvalidPoll` if
+    ///the poll is outside them.
+    ///
+    ///```rust,no_run
+    ///# use egg_mode::Token;
+    ///# #[tokio::main]
+    ///# async fn main() {
+    ///# let token: Token = unimplemented!();
+    ///use egg_mode::tweet::DraftTweet;
+    ///
+    ///let draft = DraftTweet::new("Tabs or spaces?").poll(vec!["Tabs", "Spaces"], 24 * 60);
+    ///let tweet = draft.send(&token).await.unwrap();
+    ///# }
+    ///```
+    pub fn poll<I, S>(self, options: I, duration_minutes: u32) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<CowStr>,
+    {
+        let options = options.into_iter().map(Into::into).collect();
+        DraftTweet {
+            poll: Some((options, duration_minutes)),
+            ..self
+        }
+    }
+
+    ///Tags the given users in the photos attached to this tweet.
+    ///
+    ///Up to 10 users can be tagged, and only in photos. `send` checks this before contacting
+    ///Twitter, and returns `Error::InvalidMediaTags` if no media is attached, if more than 10
+    ///users are tagged, or if any of the media was uploaded as a GIF or video. (Media IDs that
+    ///didn't come from the functions in the `media` module are assumed to be photos.)
+    ///
+    ///Like a poll, tags can only be sent through version 2 of the API, so a draft with tagged
+    ///users is sent the same way as one with a poll; see `poll` for what that means for the rest
+    ///of the draft. Version 2 only accepts tagged users by ID, so any users given by screen name
+    ///are looked up with `user::lookup` first.
+    pub fn tag_users<T: Into<UserID>>(self, users: impl IntoIterator<Item = T>) -> Self {
+        DraftTweet {
+            media_tagged_users: users.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    ///Loads the IDs of the users in `media_tagged_users`, looking up the ones given by screen
+    ///name.
+    async fn tagged_user_ids(&self, token: &auth::Token) -> Result<Vec<u64>> {
+        let names = self
+            .media_tagged_users
+            .iter()
+            .filter_map(|user| match user {
+                UserID::ScreenName(name) => Some(UserID::ScreenName(name.clone())),
+                UserID::ID(_) => None,
+            })
+            .collect::<Vec<_>>();
+        let found = if names.is_empty() {
+            Vec::new()
+        } else {
+            user::lookup(names, token).await?.response
+        };
+
+        self.media_tagged_users
+            .iter()
+            .map(|user| match user {
+                UserID::ID(id) => Ok(*id),
+                UserID::ScreenName(name) => found
+                    .iter()
+                    .find(|u| u.screen_name.eq_ignore_ascii_case(name))
+                    .map(|u| u.id)
+                    .ok_or(error::Error::InvalidMediaTags(
+                        "a tagged user couldn't be found",
+                    )),
+            })
+            .collect()
+    }
+
+    ///Converts this draft into a `DraftTweetV2`, for drafts that need to be sent through version 2.
+    ///
+    ///The tagged users are left out, since the ones given by screen name need to be looked up.
+    fn to_v2(&self) -> DraftTweetV2 {
+        DraftTweetV2 {
+            text: self.text.clone(),
+            in_reply_to: self.in_reply_to,
+            exclude_reply_user_ids: self
+                .exclude_reply_user_ids
+                .as_ref()
+                .map(|ids| ids.to_vec())
+                .unwrap_or_default(),
+            quote_tweet_id: None,
+            poll: self.poll.clone(),
+            reply_settings: self.reply_settings,
+            media_ids: self.media_ids.clone(),
+            tagged_user_ids: Vec::new(),
+            for_super_followers: self.for_super_followers.unwrap_or(false),
+        }
+    }
+
+    ///Returns whether this draft has to be sent through version 2 of the API, because it uses
+    ///something version 1.1 can't post.
+    fn needs_v2(&self) -> bool {
+        self.poll.is_some()
+            || !self.media_tagged_users.is_empty()
+            || self.for_super_followers == Some(true)
+            || !matches!(self.reply_settings, None | Some(ReplySettings::Everyone))
+    }
+
+    fn params(&self) -> ParamList {
         let mut params = ParamList::new()
             .add_param("status", self.text.clone())
             .add_opt_param("in_reply_to_status_id", self.in_reply_to.map_string())
@@ -880,15 +2147,212 @@ impl DraftTweet {
             params.add_param_ref("media_ids", media);
         }
 
+        params
+    }
+
+    ///Send the assembled tweet as the authenticated user.
+    ///
+    ///If a poll was attached with `poll`, users were tagged with `tag_users`, replies were
+    ///restricted with `reply_settings`, or the draft is `for_super_followers`, this posts the
+    ///tweet through version 2 of the API, so `token` needs to be an Access token or an OAuth 2.0
+    ///user token with the `tweet.write`, `tweet.read`, and `users.read` scopes.
+    pub async fn send(&self, token: &auth::Token) -> Result<Response<Tweet>> {
+        if !self.media_tagged_users.is_empty() {
+            v2::check_media_tags(self.media_tagged_users.len(), &self.media_ids)?;
+        }
+        if self.needs_v2() {
+            let mut draft = self.to_v2();
+            draft.tagged_user_ids = self.tagged_user_ids(token).await?;
+            let created = draft.send(token).await?;
+            return show(created.id, token).await;
+        }
+
+        let params = self.params();
         let req = post(links::statuses::UPDATE, token, Some(&params));
         request_with_json_response(req).await
     }
+
+    ///Send the assembled tweet as a thread, splitting its text into as many tweets as it needs.
+    ///
+    ///If the text is too long for one tweet, it's split on whitespace, preferring to end each
+    ///tweet at the end of a sentence when that doesn't make it much shorter than it could be.
+    ///Links are never split apart, and count as 23 characters each, the length Twitter gives
+    ///them after shortening them. Only a single word too long for a tweet on its own is broken up.
+    ///
+    ///The first tweet is sent with all of this draft's settings. Each tweet after it is posted as
+    ///a reply to the one before, keeping the draft's location, `reply_settings`, and
+    ///`for_super_followers`, but not its media, tagged users, poll, or `attachment_url`. The
+    ///posted tweets are returned in order, along with the rate-limit information from the last
+    ///one. If a tweet can't be posted, the rest of the thread isn't sent, and a `ThreadError` is
+    ///returned with the error and the tweets that were already posted, which stay posted. To
+    ///finish the thread, send the rest of its text as a reply to the last of those tweets.
+    ///
+    ///```rust,no_run
+    ///# use egg_mode::Token;
+    ///# #[tokio::main]
+    ///# async fn main() {
+    ///# let token: Token = unimplemented!();
+    ///use egg_mode::tweet::DraftTweet;
+    ///
+    ///let update = std::fs::read_to_string("release-notes.txt").unwrap();
+    ///let thread = DraftTweet::new(update).send_as_thread(&token).await.unwrap();
+    ///println!("posted {} tweets", thread.len());
+    ///# }
+    ///```
+    pub async fn send_as_thread(
+        &self,
+        token: &auth::Token,
+    ) -> std::result::Result<Response<Vec<Tweet>>, ThreadError> {
+        post_thread(self, |draft| async move { draft.send(token).await }).await
+    }
+}
+
+///An error from `DraftTweet::send_as_thread`, along with the tweets it had already posted.
+///
+///This converts into the `Error` it holds, so `?` can still be used on the result of
+///`send_as_thread` when the posted tweets don't matter.
+#[derive(Debug, thiserror::Error)]
+#[error("thread failed after {} tweets were posted: {}", .posted.len(), .error)]
+pub struct ThreadError {
+    ///The tweets that were posted before the error, in order.
+    pub posted: Vec<Tweet>,
+    ///The error from posting the next tweet.
+    #[source]
+    pub error: error::Error,
+}
+
+impl From<ThreadError> for error::Error {
+    fn from(err: ThreadError) -> error::Error {
+        err.error
+    }
+}
+
+///Posts the given draft as a thread for `DraftTweet::send_as_thread`, sending each tweet with
+///`send`.
+async fn post_thread<F, Fut>(
+    draft: &DraftTweet,
+    mut send: F,
+) -> std::result::Result<Response<Vec<Tweet>>, ThreadError>
+where
+    F: FnMut(DraftTweet) -> Fut,
+    Fut: Future<Output = Result<Response<Tweet>>>,
+{
+    let mut pieces = split_thread(&draft.text).into_iter();
+    let first = pieces.next().unwrap_or_default();
+    let first = DraftTweet {
+        text: first.into(),
+        ..draft.clone()
+    };
+    let mut resp = match send(first).await {
+        Ok(resp) => resp,
+        Err(error) => {
+            return Err(ThreadError {
+                posted: Vec::new(),
+                error,
+            })
+        }
+    };
+    let mut tweets = Vec::new();
+
+    for piece in pieces {
+        let reply = DraftTweet {
+            text: piece.into(),
+            in_reply_to: Some(resp.id),
+            auto_populate_reply_metadata: None,
+            exclude_reply_user_ids: None,
+            attachment_url: None,
+            media_ids: Vec::new(),
+            possibly_sensitive: None,
+            poll: None,
+            media_tagged_users: Vec::new(),
+            ..draft.clone()
+        };
+        let next = match send(reply).await {
+            Ok(next) => next,
+            Err(error) => {
+                tweets.push(resp.response);
+                return Err(ThreadError {
+                    posted: tweets,
+                    error,
+                });
+            }
+        };
+        tweets.push(std::mem::replace(&mut resp, next).response);
+    }
+
+    Ok(Response::map(resp, |last| {
+        tweets.push(last);
+        tweets
+    }))
+}
+
+///Splits the given text into pieces that each fit in a tweet, for `DraftTweet::send_as_thread`.
+fn split_thread(text: &str) -> Vec<String> {
+    // find the position of each word, so the whitespace inside each piece can be kept as it was
+    let mut spans = Vec::new();
+    let mut pos = 0;
+    while let Some(offset) = text[pos..].find(|c: char| !c.is_whitespace()) {
+        let mut start = pos + offset;
+        let end = text[start..]
+            .find(char::is_whitespace)
+            .map_or(text.len(), |len| start + len);
+
+        // the only words that are split apart are the ones too long to fit into a tweet at all
+        while character_count(&text[start..end]) > MAX_WEIGHTED_LENGTH {
+            let split = start + fit(&text[start..end], MAX_WEIGHTED_LENGTH);
+            spans.push((start, split));
+            start = split;
+        }
+        spans.push((start, end));
+        pos = end;
+    }
+
+    let mut pieces = Vec::new();
+    let mut first = 0;
+    while first < spans.len() {
+        let start = spans[first].0;
+        let mut last = first;
+        let mut sentence_end = None;
+        while last + 1 < spans.len()
+            && character_count(&text[start..spans[last + 1].1]) <= MAX_WEIGHTED_LENGTH
+        {
+            if ends_sentence(&text[spans[last].0..spans[last].1])
+                && character_count(&text[start..spans[last].1]) >= MAX_WEIGHTED_LENGTH / 2
+            {
+                sentence_end = Some(last);
+            }
+            last += 1;
+        }
+        if last + 1 < spans.len() && !ends_sentence(&text[spans[last].0..spans[last].1]) {
+            last = sentence_end.unwrap_or(last);
+        }
+        pieces.push(text[start..spans[last].1].to_string());
+        first = last + 1;
+    }
+
+    if pieces.is_empty() {
+        pieces.push(text.to_string());
+    }
+    pieces
+}
+
+///Returns whether the given word ends a sentence.
+fn ends_sentence(word: &str) -> bool {
+    let word = word.trim_end_matches(&['"', '\'', ')', '”', '’'][..]);
+    !is_url(word) && word.ends_with(&['.', '!', '?', '…'][..])
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Tweet;
+    use super::{
+        character_count, collect_pages, continue_thread, crawl_users, fill_gap, has_media,
+        hydrate_users, media_timeline, order_edit_history, post_thread, rehydrate_chunks,
+        split_thread, stream_pages, tweet_exists, ClientCategory, DraftTweet, FilterLevel,
+        MatchingRule, ReplySettings, Tweet, TweetSource,
+    };
     use crate::common::tests::load_file;
+    use crate::common::{RateLimit, RateLimitGate, Response};
+    use crate::user::UserID;
 
     use chrono::{Datelike, Timelike, Weekday};
 
@@ -897,6 +2361,21 @@ mod tests {
         ::serde_json::from_str(&sample).unwrap()
     }
 
+    #[test]
+    fn best_coordinates() {
+        let mut precise = load_tweet("sample_payloads/sample-extended-onepic.json");
+        assert_eq!(precise.best_coordinates(), None);
+        precise.coordinates = Some((43.65, -79.38));
+        assert_eq!(precise.best_coordinates(), Some((43.65, -79.38)));
+
+        let sample = load_tweet("sample_payloads/sample-retweet.json");
+        let place_only = sample.retweeted_status.unwrap();
+        assert!(place_only.coordinates.is_none());
+        let (lat, long) = place_only.best_coordinates().unwrap();
+        assert!((lat - 43.629311).abs() < 1e-6);
+        assert!((long - -79.2725695).abs() < 1e-6);
+    }
+
     #[test]
     fn parse_basic() {
         let sample = load_tweet("sample_payloads/sample-extended-onepic.json");
@@ -973,7 +2452,10 @@ mod tests {
 
         assert_eq!(sample.quoted_status_id, Some(783004145485840384));
         assert!(sample.quoted_status.is_some());
-        assert_eq!(sample.quoted_status.unwrap().text,
+        let quoted = sample.quoted_status.unwrap();
+        assert_eq!(quoted.id, 783004145485840384);
+        assert!(quoted.user.is_some());
+        assert_eq!(quoted.text,
                    "@chalkboardsband hot damn i should call up my friends in austin, i might actually be able to make one of these now :D");
     }
 
@@ -982,10 +2464,437 @@ mod tests {
         let sample = load_tweet("sample_payloads/sample-retweet.json");
 
         assert!(sample.retweeted_status.is_some());
-        assert_eq!(sample.retweeted_status.unwrap().text,
+        let retweeted = sample.retweeted_status.unwrap();
+        assert!(retweeted.user.is_some());
+        assert!(retweeted.retweeted_status.is_none());
+        assert_eq!(retweeted.text,
                    "it's working: follow @andrewhuangbot for a random lyric of mine every hour. we'll call this version 0.1.0. wanna get line breaks in there");
     }
 
+    #[test]
+    fn parse_edited() {
+        let sample = load_tweet("sample_payloads/sample-edited.json");
+
+        assert_eq!(sample.id, 1583925017839816704);
+        assert_eq!(
+            sample.edit_history_ids,
+            Some(vec![1583924814849720321, 1583925017839816704])
+        );
+        assert!(sample.is_edited());
+
+        let json = serde_json::to_string(&sample).unwrap();
+        let roundtrip: Tweet = serde_json::from_str(&json).unwrap();
+        assert_eq!(roundtrip.edit_history_ids, sample.edit_history_ids);
+
+        // v2-style payloads list the history under a different name
+        let mut v2: serde_json::Value =
+            serde_json::from_str(&load_file("sample_payloads/sample-edited.json")).unwrap();
+        let history = v2.as_object_mut().unwrap().remove("edit_history").unwrap();
+        v2["edit_history_tweet_ids"] = history["edit_tweet_ids"].clone();
+        let v2: Tweet = serde_json::from_value(v2).unwrap();
+        assert_eq!(v2.edit_history_ids, sample.edit_history_ids);
+
+        let unedited = load_tweet("sample_payloads/sample-reply.json");
+        assert_eq!(unedited.edit_history_ids, None);
+        assert!(!unedited.is_edited());
+
+        let single = Tweet {
+            edit_history_ids: Some(vec![unedited.id]),
+            ..unedited
+        };
+        assert!(!single.is_edited());
+    }
+
+    #[test]
+    fn media_best_size() {
+        let sample = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let media = &sample.extended_entities.unwrap().media[0];
+
+        // thumb is 150 wide, small is 628, and medium and large are both 814
+        let best = |min_width| media.best_size(min_width).unwrap().0;
+        assert_eq!(best(0), "thumb");
+        assert_eq!(best(150), "thumb");
+        assert_eq!(best(151), "small");
+        assert_eq!(best(628), "small");
+        assert_eq!(best(700), "medium");
+        assert_eq!(media.best_size(700).unwrap().1.w, 814);
+        // nothing is wide enough, so take the biggest one
+        assert_eq!(best(2048), "large");
+
+        assert_eq!(
+            media.url_for_size(best(600)),
+            "https://pbs.twimg.com/media/Ctt2uHyWAAAgpxd.jpg:small"
+        );
+
+        // sizes without a width are never picked
+        let mut blank = media.clone();
+        blank.sizes.large.w = 0;
+        assert_eq!(blank.best_size(2048).unwrap().0, "medium");
+        blank.sizes.thumb.w = 0;
+        blank.sizes.small.w = 0;
+        blank.sizes.medium.w = 0;
+        assert!(blank.best_size(0).is_none());
+    }
+
+    #[test]
+    fn client_categories() {
+        let source = |name: &str| TweetSource {
+            name: name.to_string(),
+            url: "https://example.com".to_string(),
+        };
+
+        assert_eq!(
+            source("Twitter for Android").category(),
+            ClientCategory::OfficialMobile
+        );
+        assert_eq!(source("TweetDeck").category(), ClientCategory::OfficialWeb);
+        assert_eq!(source("IFTTT").category(), ClientCategory::Automation);
+        assert_eq!(source("").category(), ClientCategory::Unknown);
+
+        let web = load_tweet("sample_payloads/compatibilityplus_extended_13997.json");
+        assert_eq!(web.client_category(), ClientCategory::OfficialWeb);
+
+        let mut tweetbot = load_tweet("sample_payloads/sample-extended-onepic.json");
+        assert_eq!(tweetbot.client_category(), ClientCategory::ThirdParty);
+
+        tweetbot.source = None;
+        assert_eq!(tweetbot.client_category(), ClientCategory::Unknown);
+    }
+
+    #[test]
+    fn parse_matching_rules() {
+        let sample = load_tweet("sample_payloads/sample-enterprise.json");
+
+        assert!(matches!(sample.filter_level, Some(FilterLevel::Low)));
+        assert_eq!(
+            sample.matching_rules,
+            Some(vec![
+                MatchingRule {
+                    tag: Some("rust".to_string()),
+                    id: 1166916266197536768,
+                },
+                MatchingRule {
+                    tag: None,
+                    id: 1166916266197536769,
+                },
+            ])
+        );
+
+        let roundtrip: Tweet =
+            serde_json::from_str(&serde_json::to_string(&sample).unwrap()).unwrap();
+        assert_eq!(roundtrip.matching_rules, sample.matching_rules);
+
+        let plain = load_tweet("sample_payloads/sample-reply.json");
+        assert_eq!(plain.matching_rules, None);
+    }
+
+    #[test]
+    fn parse_reply_settings() {
+        let sample: serde_json::Value =
+            serde_json::from_str(&load_file("sample_payloads/sample-reply.json")).unwrap();
+        let with_field = |key: &str, value: serde_json::Value| {
+            let mut json = sample.clone();
+            json[key] = value;
+            serde_json::from_value::<Tweet>(json).unwrap()
+        };
+
+        assert_eq!(
+            serde_json::from_value::<Tweet>(sample.clone())
+                .unwrap()
+                .reply_settings,
+            None
+        );
+
+        for &(setting, expected) in &[
+            ("everyone", ReplySettings::Everyone),
+            ("following", ReplySettings::Following),
+            ("mentionedUsers", ReplySettings::MentionedUsers),
+        ] {
+            let tweet = with_field("reply_settings", setting.into());
+            assert_eq!(tweet.reply_settings, Some(expected));
+
+            let json = serde_json::to_string(&tweet).unwrap();
+            let roundtrip: Tweet = serde_json::from_str(&json).unwrap();
+            assert_eq!(roundtrip.reply_settings, Some(expected));
+        }
+
+        // settings we don't know about are skipped instead of failing the whole tweet
+        let tweet = with_field("reply_settings", "subscribers".into());
+        assert_eq!(tweet.reply_settings, None);
+
+        for &(policy, expected) in &[
+            ("community", ReplySettings::Following),
+            ("by_invitation", ReplySettings::MentionedUsers),
+            ("ByInvitation", ReplySettings::MentionedUsers),
+        ] {
+            let tweet = with_field(
+                "conversation_control",
+                serde_json::json!({ "policy": policy }),
+            );
+            assert_eq!(tweet.reply_settings, Some(expected));
+        }
+    }
+
+    #[test]
+    fn draft_reply_settings() {
+        let draft = DraftTweet::new("anyone can reply");
+        assert!(!draft.needs_v2());
+        let draft = draft.reply_settings(ReplySettings::Everyone);
+        assert!(!draft.needs_v2());
+        assert!(draft.params().get("conversation_control").is_none());
+
+        // restricting replies takes the v2 endpoint, which gets the setting as `reply_settings`
+        for &settings in &[ReplySettings::Following, ReplySettings::MentionedUsers] {
+            let draft = DraftTweet::new("who can reply?").reply_settings(settings);
+            assert!(draft.needs_v2());
+            let body = draft.to_v2().body();
+            assert_eq!(
+                body["reply_settings"],
+                serde_json::to_value(settings).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn draft_super_followers() {
+        let draft = DraftTweet::new("just for you")
+            .reply_settings(ReplySettings::Following)
+            .for_super_followers(true);
+        assert!(draft.needs_v2());
+        let v2 = draft.to_v2();
+        assert!(v2.for_super_followers);
+        assert_eq!(v2.reply_settings, Some(ReplySettings::Following));
+
+        let draft = DraftTweet::new("for everyone").for_super_followers(false);
+        assert!(!draft.needs_v2());
+        assert!(draft.params().get("for_super_followers_only").is_none());
+    }
+
+    #[test]
+    fn draft_place() {
+        let sample = load_tweet("sample_payloads/sample-retweet.json");
+        let place = sample.retweeted_status.unwrap().place.unwrap();
+
+        let params = DraftTweet::new("hello from toronto").place(&place).params();
+        assert_eq!(
+            params.get("place_id").map(|v| v.as_ref()),
+            Some("3797791ff9c0e4c6")
+        );
+        assert!(params.get("lat").is_none());
+
+        let (lat, long) = place.centroid().unwrap();
+        assert!((lat - 43.629311).abs() < 1e-6, "{}", lat);
+        assert!((long - -79.2725695).abs() < 1e-6, "{}", long);
+    }
+
+    #[test]
+    fn draft_poll() {
+        let draft = DraftTweet::new("Tabs or spaces?")
+            .in_reply_to(1212092628029698048)
+            .exclude_reply_user_ids(vec![783214])
+            .poll(vec!["Tabs", "Spaces"], 60);
+        let v2 = draft.to_v2();
+        assert_eq!(v2.text, "Tabs or spaces?");
+        assert_eq!(v2.in_reply_to, Some(1212092628029698048));
+        assert_eq!(v2.exclude_reply_user_ids, vec![783214]);
+        assert_eq!(v2.poll, Some((vec!["Tabs".into(), "Spaces".into()], 60)));
+        assert!(!v2.for_super_followers);
+    }
+
+    #[test]
+    fn thread_continuation() {
+        let sample = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let tweet = |id: u64, reply_to: Option<u64>| Tweet {
+            id,
+            in_reply_to_status_id: reply_to,
+            ..sample.clone()
+        };
+
+        let thread = vec![tweet(10, None), tweet(20, Some(10))];
+        let replies = vec![
+            tweet(50, Some(40)),
+            tweet(30, Some(20)),
+            // a second reply to the same tweet is a branch, not the rest of the thread
+            tweet(35, Some(20)),
+            tweet(40, Some(30)),
+            tweet(45, Some(99)),
+        ];
+        let ids = continue_thread(thread, replies)
+            .iter()
+            .map(|t| t.id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![10, 20, 30, 40, 50]);
+
+        let thread = continue_thread(vec![tweet(10, None)], vec![]);
+        assert_eq!(thread.len(), 1);
+    }
+
+    #[test]
+    fn draft_tag_users() {
+        let draft = DraftTweet::new("look who it is")
+            .tag_users(vec![UserID::from("rustlang"), UserID::from(783214)]);
+        assert_eq!(draft.media_tagged_users.len(), 2);
+        assert!(draft.to_v2().tagged_user_ids.is_empty());
+    }
+
+    #[test]
+    fn draft_remaining_chars() {
+        assert_eq!(DraftTweet::new("hello").remaining_chars(), 275);
+        let draft = DraftTweet::new("read this: https://example.com/a/long/path")
+            .attachment_url("https://twitter.com/rustlang/status/1");
+        assert_eq!(draft.remaining_chars(), 246);
+        assert_eq!(DraftTweet::new("🦀".repeat(141)).remaining_chars(), -2);
+    }
+
+    #[test]
+    fn thread_splitting() {
+        assert_eq!(character_count("hello world"), 11);
+        assert_eq!(
+            character_count("see https://example.com/a/very/long/path/to/something"),
+            27
+        );
+        assert_eq!(character_count("こんにちは"), 10);
+
+        assert_eq!(split_thread("short and sweet"), vec!["short and sweet"]);
+        assert_eq!(split_thread(""), vec![""]);
+
+        // a sentence break is preferred once the tweet is at least half full
+        let first = "This sentence is long enough to fill up more than half of a tweet, which \
+                     takes quite a lot of words when you stop and think about it, doesn't it? \
+                     Yes, it does.";
+        let second = "The next one spills over the edge, and would have to be split somewhere in \
+                      the middle if it weren't moved into the next tweet.";
+        let text = format!("{} {}", first, second);
+        assert!(character_count(&text) > 280);
+        assert_eq!(split_thread(&text), vec![first, second]);
+
+        // without any sentences, it breaks at the last word that fits, keeping links whole
+        let link = "https://example.com/this/link/is/far/longer/than/twenty/three/characters";
+        let text = format!("{}{}", "word ".repeat(60), link);
+        let pieces = split_thread(&text);
+        assert_eq!(pieces.len(), 2);
+        assert_eq!(pieces[1], format!("word word word word {}", link));
+        assert!(pieces.iter().all(|p| character_count(p) <= 280));
+
+        // whitespace inside a piece is kept
+        assert_eq!(
+            split_thread("line one\n\nline two "),
+            vec!["line one\n\nline two"]
+        );
+
+        // a single word that can't fit is the only thing broken up
+        let long = "a".repeat(300);
+        assert_eq!(split_thread(&long), vec!["a".repeat(280), "a".repeat(20)]);
+    }
+
+    #[tokio::test]
+    async fn thread_keeps_posted_tweets() {
+        use std::cell::RefCell;
+
+        let sample = load_tweet("sample_payloads/sample-reply.json");
+        let sent = RefCell::new(Vec::new());
+        // posts each tweet with the next ID, until the `fail_at`th one
+        let sender = |fail_at: u64| {
+            let (sample, sent) = (&sample, &sent);
+            move |draft: DraftTweet| {
+                sent.borrow_mut().push(draft.in_reply_to);
+                let id = sent.borrow().len() as u64;
+                let result = if id == fail_at {
+                    Err(crate::error::Error::BadStatus(
+                        hyper::StatusCode::SERVICE_UNAVAILABLE,
+                    ))
+                } else {
+                    let rate = RateLimit {
+                        limit: 300,
+                        remaining: 300 - id as i32,
+                        reset: 0,
+                    };
+                    let tweet = Tweet {
+                        id,
+                        ..sample.clone()
+                    };
+                    Ok(Response::new(rate, tweet))
+                };
+                async move { result }
+            }
+        };
+        let text = format!("{} {} {}", "a".repeat(280), "b".repeat(280), "c".repeat(10));
+
+        // the thread stops at the tweet that failed, and reports the one before it as posted
+        let err = post_thread(&DraftTweet::new(text.clone()), sender(2))
+            .await
+            .unwrap_err();
+        assert!(matches!(err.error, crate::error::Error::BadStatus(_)));
+        assert_eq!(err.posted.iter().map(|t| t.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(*sent.borrow(), vec![None, Some(1)]);
+
+        sent.borrow_mut().clear();
+        let err = post_thread(&DraftTweet::new(text.clone()), sender(1))
+            .await
+            .unwrap_err();
+        assert!(err.posted.is_empty());
+
+        sent.borrow_mut().clear();
+        let thread = post_thread(&DraftTweet::new(text), sender(0))
+            .await
+            .unwrap();
+        assert_eq!(
+            thread.iter().map(|t| t.id).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(*sent.borrow(), vec![None, Some(1), Some(2)]);
+        assert_eq!(thread.rate_limit_status.remaining, 297);
+    }
+
+    #[test]
+    fn edit_history_order() {
+        let sample = load_tweet("sample_payloads/sample-edited.json");
+        let version = |id| Tweet {
+            id,
+            ..sample.clone()
+        };
+        let history = vec![30, 10, 20];
+
+        // lookup can return tweets in any order, and may include things we didn't ask for
+        let versions = vec![
+            version(20),
+            version(99),
+            version(30),
+            version(10),
+            version(20),
+        ];
+        let ordered = order_edit_history(&history, versions);
+        let ids = ordered.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![30, 10, 20]);
+
+        // versions that couldn't be loaded are skipped
+        let ordered = order_edit_history(&history, vec![version(20), version(30)]);
+        let ids = ordered.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![30, 20]);
+    }
+
+    #[test]
+    fn roundtrip_embedded_statuses() {
+        for path in &[
+            "sample_payloads/sample-quote.json",
+            "sample_payloads/sample-retweet.json",
+        ] {
+            let sample = load_tweet(path);
+            let json1 = serde_json::to_value(&sample).unwrap();
+            let roundtrip: Tweet = serde_json::from_value(json1.clone()).unwrap();
+
+            assert_eq!(
+                sample.quoted_status.as_ref().map(|t| t.id),
+                roundtrip.quoted_status.as_ref().map(|t| t.id)
+            );
+            assert_eq!(
+                sample.retweeted_status.as_ref().map(|t| t.id),
+                roundtrip.retweeted_status.as_ref().map(|t| t.id)
+            );
+            assert_eq!(json1, serde_json::to_value(&roundtrip).unwrap());
+        }
+    }
+
     #[test]
     fn parse_image_alt_text() {
         let sample = load_tweet("sample_payloads/sample-image-alt-text.json");
@@ -1007,4 +2916,553 @@ mod tests {
 
         assert_eq!(json1, json2);
     }
+
+    #[tokio::test]
+    async fn hydrate_retweeters() {
+        use crate::error::Error;
+        use crate::user::TwitterUser;
+        use futures::{stream, StreamExt};
+
+        let rate_limit = RateLimit {
+            limit: 900,
+            remaining: 900,
+            reset: 0,
+        };
+        let users: Vec<TwitterUser> =
+            serde_json::from_str(&load_file("sample_payloads/user_array.json")).unwrap();
+
+        // 404 has no user to load, and the IDs stop with an error
+        let ids = vec![
+            Ok(2244994945),
+            Ok(404),
+            Ok(783214),
+            Err(Error::BadUrl),
+            Ok(6253282),
+        ];
+        let ids = stream::iter(
+            ids.into_iter()
+                .map(|id| id.map(|id| Response::new(rate_limit, id))),
+        );
+        let mut calls = Vec::new();
+        let load = |chunk: Vec<u64>| {
+            calls.push(chunk.clone());
+            let mut found = users
+                .iter()
+                .filter(|user| chunk.contains(&user.id))
+                .cloned()
+                .collect::<Vec<_>>();
+            // user::lookup doesn't keep the order the IDs were given in
+            found.reverse();
+            async move { Ok(Response::new(rate_limit, found)) }
+        };
+        let loaded = hydrate_users(ids, load).collect::<Vec<_>>().await;
+
+        assert_eq!(calls, vec![vec![2244994945, 404, 783214]]);
+        assert_eq!(loaded.len(), 3);
+        assert_eq!(loaded[0].as_ref().unwrap().response.id, 2244994945);
+        assert_eq!(loaded[1].as_ref().unwrap().response.id, 783214);
+        assert!(matches!(loaded[2], Err(Error::BadUrl)));
+    }
+
+    #[test]
+    fn timeline_options() {
+        let token = crate::Token::Bearer("bearer".to_string());
+        let timeline = super::home_timeline(&token)
+            .exclude_replies(true)
+            .trim_user(true);
+        let params = timeline.params_base.as_ref().unwrap();
+        assert_eq!(
+            params.get("exclude_replies").map(|v| v.as_ref()),
+            Some("true")
+        );
+        assert_eq!(params.get("trim_user").map(|v| v.as_ref()), Some("true"));
+        assert!(params.get("include_rts").is_none());
+
+        // the builder methods override what the timeline was created with
+        let timeline = super::user_timeline("rustlang", true, true, &token).include_rts(false);
+        let params = timeline.params_base.as_ref().unwrap();
+        assert_eq!(params.get("include_rts").map(|v| v.as_ref()), Some("false"));
+        assert_eq!(
+            params.get("exclude_replies").map(|v| v.as_ref()),
+            Some("false")
+        );
+    }
+
+    #[test]
+    fn trimmed_user() {
+        let full = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let author_id = full.user.as_ref().unwrap().id;
+        assert_eq!(full.user_id, Some(author_id));
+
+        let mut json: serde_json::Value =
+            serde_json::from_str(&load_file("sample_payloads/sample-extended-onepic.json"))
+                .unwrap();
+        json["user"] = serde_json::json!({ "id": author_id, "id_str": author_id.to_string() });
+        let trimmed: Tweet = serde_json::from_value(json).unwrap();
+        assert!(trimmed.user.is_none());
+        assert_eq!(trimmed.user_id, Some(author_id));
+        assert_eq!(trimmed.id, full.id);
+    }
+
+    #[test]
+    fn timeline_state_round_trip() {
+        use super::{Timeline, TimelineState};
+
+        let token = crate::Token::Bearer("bearer".to_string());
+        let mut timeline = media_timeline("rustlang", &token)
+            .with_page_size(50)
+            .since_date(chrono::Utc::now() - chrono::Duration::days(1));
+        timeline.max_id = Some(1265628900081700865);
+        timeline.min_id = Some(1265628849343168513);
+        let base = timeline.params_base.as_mut().unwrap();
+        base.push_param("expansions", "author_id");
+        base.push_param("expansions", "attachments.media_keys");
+
+        let state = timeline.state();
+        let json = serde_json::to_string(&state).unwrap();
+        let restored_state: TimelineState = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored_state, state);
+
+        let restored = Timeline::restore(restored_state, &token);
+        assert_eq!(restored.link, timeline.link);
+        assert_eq!(restored.count, 50);
+        assert_eq!(restored.max_id, Some(1265628900081700865));
+        assert_eq!(restored.min_id, Some(1265628849343168513));
+        assert_eq!(restored.since_bound, timeline.since_bound);
+        assert!(restored.media_only);
+        let params = restored.params_base.unwrap();
+        assert_eq!(
+            params.get("screen_name").map(|v| v.as_ref()),
+            Some("rustlang")
+        );
+        assert_eq!(
+            params.get_all("expansions"),
+            ["author_id", "attachments.media_keys"]
+        );
+        assert_eq!(params.len(), timeline.params_base.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn media_only_pages() {
+        let token = crate::Token::Bearer("bearer".to_string());
+        let timeline = media_timeline("rustlang", &token);
+        let sample = load_file("sample_payloads/tweet_array.json");
+        let page: Vec<Tweet> = serde_json::from_str(&sample).unwrap();
+
+        // only one tweet on this page has media attached
+        let mut filtered = page.clone();
+        filtered.retain(|tweet| timeline.keeps(tweet));
+        let ids = filtered.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids, vec![1265628900081700865]);
+
+        let mut unfiltered = page.clone();
+        let all = super::user_timeline("rustlang", true, true, &token);
+        unfiltered.retain(|tweet| all.keeps(tweet));
+        assert_eq!(unfiltered.len(), 20);
+
+        // a page with nothing worth keeping shouldn't stop collection early
+        let rate_limit = RateLimit {
+            limit: 900,
+            remaining: 900,
+            reset: 0,
+        };
+        let gate = RateLimitGate::default();
+        let mut calls = 0;
+        let load = |max_id: Option<u64>| {
+            calls += 1;
+            let page = page
+                .iter()
+                .filter(|t| t.id <= max_id.unwrap_or(u64::MAX))
+                .take(10)
+                .cloned()
+                .collect::<Vec<_>>();
+            async move { Ok(Response::new(rate_limit, page)) }
+        };
+        let tweets = collect_pages(5, &gate, has_media, load).await.unwrap();
+        assert_eq!(calls, 3);
+        assert_eq!(tweets.len(), 1);
+        assert_eq!(tweets[0].id, 1265628900081700865);
+    }
+
+    #[tokio::test]
+    async fn collect_pages_dedup() {
+        let sample = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let rate_limit = RateLimit {
+            limit: 900,
+            remaining: 900,
+            reset: 0,
+        };
+        let mut calls = 0;
+
+        // hand out pages of 100 tweets with IDs counting down from 1000, repeating the last tweet
+        // of the previous page at the top of each one
+        let gate = RateLimitGate::default();
+        let load = |max_id: Option<u64>| {
+            calls += 1;
+            let top = max_id.map_or(1000, |id| id + 1);
+            let page = (0..100)
+                .map(|offset| Tweet {
+                    id: top - offset,
+                    ..sample.clone()
+                })
+                .collect::<Vec<_>>();
+            async move { Ok(Response::new(rate_limit, page)) }
+        };
+        let tweets = collect_pages(350, &gate, |_| true, load).await.unwrap();
+
+        assert_eq!(calls, 4);
+        assert_eq!(tweets.len(), 350);
+        let mut ids = tweets.iter().map(|t| t.id).collect::<Vec<_>>();
+        ids.dedup();
+        assert_eq!(ids.len(), 350);
+        assert_eq!(ids.first(), Some(&1000));
+        assert_eq!(ids.last(), Some(&651));
+    }
+
+    #[tokio::test]
+    async fn stream_pages_until_exhausted() {
+        use crate::error::Error;
+        use futures::StreamExt;
+
+        let sample = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let rate_limit = RateLimit {
+            limit: 900,
+            remaining: 900,
+            reset: 0,
+        };
+
+        // hand out pages of up to 100 tweets with IDs counting down to 751, repeating the last
+        // tweet of the previous page at the top of each one
+        let mut requested = Vec::new();
+        let load = |max_id: Option<u64>| {
+            requested.push(max_id);
+            let top = max_id.map_or(1000, |id| id + 1);
+            let page = (0..100)
+                .map(|offset| top - offset)
+                .filter(|&id| id > 750)
+                .map(|id| Tweet {
+                    id,
+                    ..sample.clone()
+                })
+                .collect::<Vec<_>>();
+            async move { Ok(Response::new(rate_limit, page)) }
+        };
+        let tweets = stream_pages(None, |tweet| tweet.id % 2 == 0, load)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(requested, vec![None, Some(900), Some(801), Some(750)]);
+        assert_eq!(tweets.len(), 125);
+        let ids = tweets
+            .iter()
+            .map(|t| t.as_ref().unwrap().id)
+            .collect::<Vec<_>>();
+        assert_eq!(ids.first(), Some(&1000));
+        assert_eq!(ids.last(), Some(&752));
+
+        // picking up from a saved position, and ending on an error
+        let mut requested = Vec::new();
+        let load = |max_id: Option<u64>| {
+            requested.push(max_id);
+            let page = vec![Tweet {
+                id: max_id.unwrap(),
+                ..sample.clone()
+            }];
+            async move {
+                match page[0].id {
+                    id if id > 498 => Ok(Response::new(rate_limit, page)),
+                    _ => Err(Error::BadUrl),
+                }
+            }
+        };
+        let tweets = stream_pages(Some(499), |_| true, load)
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(requested, vec![Some(499), Some(498)]);
+        assert_eq!(tweets.len(), 2);
+        assert_eq!(tweets[0].as_ref().unwrap().id, 499);
+        assert!(matches!(tweets[1], Err(Error::BadUrl)));
+    }
+
+    #[tokio::test]
+    async fn collect_pages_retry_budget() {
+        use crate::common::RetryBudget;
+        use crate::error::Error;
+
+        // with only one retry to spend, the second rate-limit error is handed back as-is
+        let gate =
+            RateLimitGate::with_budget(RetryBudget::new(1, std::time::Duration::from_secs(3600)));
+        let mut calls = 0;
+        let load = |_| {
+            calls += 1;
+            async { Err(Error::RateLimit(0)) }
+        };
+        let result = collect_pages(10, &gate, |_| true, load).await;
+
+        assert_eq!(calls, 2);
+        assert!(matches!(result, Err(Error::RateLimit(0))));
+    }
+
+    #[test]
+    fn exists_from_show() {
+        use crate::error::{Error, TwitterErrorCode, TwitterErrors};
+
+        let sample = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let rate_limit = RateLimit {
+            limit: 900,
+            remaining: 899,
+            reset: 0,
+        };
+        let found = tweet_exists(Ok(Response::new(rate_limit, sample))).unwrap();
+        assert!(found.response);
+        assert_eq!(found.rate_limit_status.remaining, 899);
+
+        let not_found = |code| {
+            Err(Error::TwitterError(
+                hyper::HeaderMap::new(),
+                TwitterErrors {
+                    errors: vec![TwitterErrorCode {
+                        message: "No status found with that ID.".to_string(),
+                        code,
+                    }],
+                },
+            ))
+        };
+        assert!(!tweet_exists(not_found(144)).unwrap().response);
+        assert!(!tweet_exists(not_found(34)).unwrap().response);
+        assert!(matches!(
+            tweet_exists(not_found(179)),
+            Err(Error::TwitterError(..))
+        ));
+    }
+
+    #[tokio::test]
+    async fn fill_gap_pages() {
+        let sample = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let rate_limit = RateLimit {
+            limit: 900,
+            remaining: 900,
+            reset: 0,
+        };
+        let mut calls = Vec::new();
+
+        // a poll with since_id 100 returned tweets 300 down to 250, leaving 101..=249 to load in
+        // pages of up to 60 tweets, each of which repeats the boundary tweet from the page before
+        let tweets = fill_gap(
+            |since_id: u64, max_id: u64| {
+                calls.push((since_id, max_id));
+                let top = if max_id == 249 { max_id } else { max_id + 1 };
+                let page = (since_id + 1..=top)
+                    .rev()
+                    .take(60)
+                    .map(|id| Tweet {
+                        id,
+                        ..sample.clone()
+                    })
+                    .collect::<Vec<_>>();
+                async move { Ok(Response::new(rate_limit, page)) }
+            },
+            100,
+            250,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(calls, vec![(100, 249), (100, 189), (100, 130)]);
+        let ids = tweets.iter().map(|t| t.id).collect::<Vec<_>>();
+        assert_eq!(ids, (101..=249).rev().collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn rehydrate_in_chunks() {
+        use futures::{stream, TryStreamExt};
+        use std::cell::RefCell;
+        use std::collections::HashMap;
+
+        let sample = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let rate_limit = RateLimit {
+            limit: 900,
+            remaining: 900,
+            reset: 0,
+        };
+        let calls = RefCell::new(Vec::new());
+
+        // every third tweet has been deleted
+        let pairs = rehydrate_chunks(stream::iter(1..=250), |chunk: Vec<u64>| {
+            calls.borrow_mut().push(chunk.len());
+            let found = chunk
+                .into_iter()
+                .map(|id| {
+                    let tweet = if id % 3 == 0 {
+                        None
+                    } else {
+                        Some(Tweet {
+                            id,
+                            ..sample.clone()
+                        })
+                    };
+                    (id, tweet)
+                })
+                .collect::<HashMap<_, _>>();
+            async move { Ok(Response::new(rate_limit, found)) }
+        })
+        .try_collect::<Vec<_>>()
+        .await
+        .unwrap();
+
+        assert_eq!(*calls.borrow(), vec![100, 100, 50]);
+        assert_eq!(pairs.len(), 250);
+        for (idx, (id, tweet)) in pairs.into_iter().enumerate() {
+            assert_eq!(id, idx as u64 + 1);
+            assert_eq!(tweet.is_none(), id % 3 == 0);
+            if let Some(tweet) = tweet {
+                assert_eq!(tweet.id, id);
+            }
+        }
+    }
+
+    #[test]
+    fn safe_render() {
+        let clean = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let render = clean.safe_render(Some("de"));
+        assert!(render.visible);
+        assert!(!render.blur_media);
+        assert_eq!(render.reason, None);
+
+        let sensitive = Tweet {
+            possibly_sensitive: Some(true),
+            ..clean.clone()
+        };
+        let render = sensitive.safe_render(None);
+        assert!(render.visible);
+        assert!(render.blur_media);
+        assert!(render.reason.is_some());
+
+        let withheld = Tweet {
+            withheld_in_countries: Some(vec!["DE".to_string(), "FR".to_string()]),
+            withheld_scope: Some("status".to_string()),
+            ..clean.clone()
+        };
+        let render = withheld.safe_render(Some("de"));
+        assert!(!render.visible);
+        assert!(!render.blur_media);
+        assert_eq!(
+            render.reason.as_deref(),
+            Some("This tweet has been withheld in DE")
+        );
+        assert!(withheld.safe_render(Some("US")).visible);
+        assert!(withheld.safe_render(None).visible);
+
+        let everywhere = Tweet {
+            withheld_in_countries: Some(vec!["XX".to_string()]),
+            withheld_scope: Some("user".to_string()),
+            ..clean
+        };
+        let render = everywhere.safe_render(None);
+        assert!(!render.visible);
+        assert_eq!(
+            render.reason.as_deref(),
+            Some("The user who posted this tweet has been withheld in all countries")
+        );
+    }
+
+    #[test]
+    fn timeline_date_bounds() {
+        fn date(s: &str) -> chrono::DateTime<chrono::Utc> {
+            chrono::DateTime::parse_from_rfc3339(s)
+                .unwrap()
+                .with_timezone(&chrono::Utc)
+        }
+
+        let token = crate::auth::Token::Bearer("bearer".to_string());
+        let since = date("2020-06-01T00:00:00Z");
+        let until = date("2020-06-08T00:00:00Z");
+
+        // 2020-06-01T00:00:00Z is 1590969600000ms since the Unix epoch
+        let since_id = ((1_590_969_600_000u64 - 1_288_834_974_657) << 22) - 1;
+        let until_id = ((1_591_574_400_000u64 - 1_288_834_974_657) << 22) - 1;
+        assert_eq!(super::timestamp_to_id(since), since_id + 1);
+
+        let timeline = super::user_timeline("rustlang", true, true, &token)
+            .since_date(since)
+            .until_date(until);
+
+        let req = timeline.request(None, None);
+        let query = req.uri().query().unwrap();
+        assert!(query.contains(&format!("since_id={}", since_id)));
+        assert!(query.contains(&format!("max_id={}", until_id)));
+
+        // explicit IDs only narrow the range further
+        let req = timeline.request(Some(since_id + 10), Some(until_id + 10));
+        let query = req.uri().query().unwrap();
+        assert!(query.contains(&format!("since_id={}", since_id + 10)));
+        assert!(query.contains(&format!("max_id={}", until_id)));
+
+        assert_eq!(super::timestamp_to_id(date("2009-01-01T00:00:00Z")), 0);
+    }
+
+    #[tokio::test]
+    async fn crawl_users_concurrency() {
+        use futures::StreamExt;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let sample = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let rate_limit = RateLimit {
+            limit: 900,
+            remaining: 900,
+            reset: 0,
+        };
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let users = vec![UserID::ID(1), UserID::ID(2), UserID::ID(3)];
+        let results = crawl_users(users, 2, |user, gate| {
+            let sample = sample.clone();
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            let base = match user {
+                UserID::ID(id) => id * 10_000,
+                UserID::ScreenName(_) => unreachable!(),
+            };
+            async move {
+                let load = |max_id: Option<u64>| {
+                    let sample = sample.clone();
+                    let in_flight = in_flight.clone();
+                    let max_in_flight = max_in_flight.clone();
+                    async move {
+                        let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                        max_in_flight.fetch_max(now, Ordering::SeqCst);
+                        tokio::time::delay_for(std::time::Duration::from_millis(5)).await;
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                        let top = max_id.unwrap_or(base + 999);
+                        let page = (0..20)
+                            .map(|offset| Tweet {
+                                id: top - offset,
+                                ..sample.clone()
+                            })
+                            .collect::<Vec<_>>();
+                        Ok(Response::new(rate_limit, page))
+                    }
+                };
+                collect_pages(50, &gate, |_| true, load).await
+            }
+        })
+        .collect::<Vec<_>>()
+        .await;
+
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+        assert_eq!(results.len(), 3);
+        for (ix, (user, tweets)) in results.into_iter().enumerate() {
+            let id = ix as u64 + 1;
+            assert!(matches!(user, UserID::ID(u) if u == id));
+            let tweets = tweets.unwrap();
+            assert_eq!(tweets.len(), 50);
+            assert!(tweets.iter().all(|t| t.id / 10_000 == id));
+            assert_eq!(tweets.first().unwrap().id, id * 10_000 + 999);
+            assert_eq!(tweets.last().unwrap().id, id * 10_000 + 950);
+        }
+    }
 }
@@ -0,0 +1,329 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::fmt;
+
+use hyper::Method;
+use serde::Deserialize;
+
+use crate::auth::raw::RequestBuilder;
+use crate::common::*;
+use crate::error::Result;
+use crate::links;
+
+///Load the HTML snippet used to embed the given tweet on a web page.
+///
+///The tweet can be given either by its numeric ID or by the URL of its page on twitter.com. The
+///returned `OEmbedBuilder` can be used to set how the embedded tweet looks, and `call` sends the
+///request.
+///
+///This uses the `statuses/oembed` endpoint, which is served from `publish.twitter.com` and
+///doesn't need to be authenticated, so unlike most functions in egg-mode, this doesn't take a
+///`Token`. The `Response` carries no rate-limit information, since Twitter doesn't send any for
+///this endpoint.
+///
+///```rust,no_run
+///# #[tokio::main]
+///# async fn main() {
+///use egg_mode::tweet::{self, OEmbedTheme};
+///
+///let embed = tweet::oembed(1261253754969640960)
+///    .theme(OEmbedTheme::Dark)
+///    .omit_script(true)
+///    .call()
+///    .await
+///    .unwrap();
+///println!("{}", embed.html);
+///# }
+///```
+pub fn oembed<T: Into<OEmbedTarget>>(tweet: T) -> OEmbedBuilder {
+    OEmbedBuilder {
+        url: tweet.into().into_url(),
+        theme: None,
+        hide_media: None,
+        hide_thread: None,
+        max_width: None,
+        omit_script: None,
+        align: None,
+        lang: None,
+        dnt: None,
+    }
+}
+
+///The tweet to load an embed snippet for, either by its ID or by its URL.
+///
+///This is used by `oembed`, which can be called with a `u64` tweet ID or a string with the URL of
+///the tweet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OEmbedTarget {
+    ///Referring to the tweet by its numeric ID.
+    ID(u64),
+    ///Referring to the tweet by its URL, like `https://twitter.com/rustlang/status/123`.
+    Url(CowStr),
+}
+
+impl OEmbedTarget {
+    fn into_url(self) -> CowStr {
+        match self {
+            // twitter redirects this to the tweet under its author's screen name
+            OEmbedTarget::ID(id) => format!("https://twitter.com/i/status/{}", id).into(),
+            OEmbedTarget::Url(url) => url,
+        }
+    }
+}
+
+impl From<u64> for OEmbedTarget {
+    fn from(id: u64) -> OEmbedTarget {
+        OEmbedTarget::ID(id)
+    }
+}
+
+impl From<&'static str> for OEmbedTarget {
+    fn from(url: &'static str) -> OEmbedTarget {
+        OEmbedTarget::Url(url.into())
+    }
+}
+
+impl From<String> for OEmbedTarget {
+    fn from(url: String) -> OEmbedTarget {
+        OEmbedTarget::Url(url.into())
+    }
+}
+
+///The color scheme to use for an embedded tweet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OEmbedTheme {
+    ///Dark text on a light background. This is the default.
+    Light,
+    ///Light text on a dark background.
+    Dark,
+}
+
+///Display impl that turns the variants into strings that can be used as oEmbed parameters.
+impl fmt::Display for OEmbedTheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OEmbedTheme::Light => write!(f, "light"),
+            OEmbedTheme::Dark => write!(f, "dark"),
+        }
+    }
+}
+
+///How to float an embedded tweet within the surrounding page.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OEmbedAlign {
+    ///Float the tweet to the left of the page's text.
+    Left,
+    ///Float the tweet to the right of the page's text.
+    Right,
+    ///Center the tweet on the page.
+    Center,
+}
+
+///Display impl that turns the variants into strings that can be used as oEmbed parameters.
+impl fmt::Display for OEmbedAlign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OEmbedAlign::Left => write!(f, "left"),
+            OEmbedAlign::Right => write!(f, "right"),
+            OEmbedAlign::Center => write!(f, "center"),
+        }
+    }
+}
+
+///Represents a request for a tweet's embed snippet before being sent.
+///
+///This is returned by `oembed`; see that function for details.
+#[must_use = "OEmbedBuilder is lazy and won't do anything unless `call`ed"]
+#[derive(Debug, Clone)]
+pub struct OEmbedBuilder {
+    url: CowStr,
+    theme: Option<OEmbedTheme>,
+    hide_media: Option<bool>,
+    hide_thread: Option<bool>,
+    max_width: Option<u32>,
+    omit_script: Option<bool>,
+    align: Option<OEmbedAlign>,
+    lang: Option<CowStr>,
+    dnt: Option<bool>,
+}
+
+impl OEmbedBuilder {
+    ///Sets the color scheme of the embedded tweet. The default is `Light`.
+    pub fn theme(self, theme: OEmbedTheme) -> Self {
+        OEmbedBuilder {
+            theme: Some(theme),
+            ..self
+        }
+    }
+
+    ///Sets whether to leave out the photos, videos, and link previews attached to the tweet. By
+    ///default, these are shown.
+    pub fn hide_media(self, hide: bool) -> Self {
+        OEmbedBuilder {
+            hide_media: Some(hide),
+            ..self
+        }
+    }
+
+    ///Sets whether to leave out the tweet this one is replying to, if it's a reply. By default,
+    ///the parent tweet is shown above it.
+    pub fn hide_thread(self, hide: bool) -> Self {
+        OEmbedBuilder {
+            hide_thread: Some(hide),
+            ..self
+        }
+    }
+
+    ///Sets the widest the embedded tweet can be, in pixels. Twitter only accepts widths from 220
+    ///to 550 pixels, and uses the nearest of those for anything outside that range. The default
+    ///is 550.
+    pub fn max_width(self, width: u32) -> Self {
+        OEmbedBuilder {
+            max_width: Some(width),
+            ..self
+        }
+    }
+
+    ///Sets whether to leave the `<script>` tag that loads Twitter's `widgets.js` out of the
+    ///returned HTML. This is useful when embedding several tweets on the same page, since the
+    ///script only needs to be loaded once. By default, the script tag is included.
+    pub fn omit_script(self, omit: bool) -> Self {
+        OEmbedBuilder {
+            omit_script: Some(omit),
+            ..self
+        }
+    }
+
+    ///Sets how to float the embedded tweet within the surrounding page. By default, it isn't
+    ///floated.
+    pub fn align(self, align: OEmbedAlign) -> Self {
+        OEmbedBuilder {
+            align: Some(align),
+            ..self
+        }
+    }
+
+    ///Sets the language of the text Twitter adds around the tweet, like the timestamp and the
+    ///"follow" button, as a language code like `"en"` or `"ja"`. By default, this is English.
+    pub fn lang<S: Into<CowStr>>(self, lang: S) -> Self {
+        OEmbedBuilder {
+            lang: Some(lang.into()),
+            ..self
+        }
+    }
+
+    ///Sets whether to ask Twitter not to use the embedded tweet to personalize content and ads
+    ///for the people who view it. By default, Twitter may do so.
+    pub fn do_not_track(self, dnt: bool) -> Self {
+        OEmbedBuilder {
+            dnt: Some(dnt),
+            ..self
+        }
+    }
+
+    fn params(&self) -> ParamList {
+        ParamList::new()
+            .add_param("url", self.url.clone())
+            .add_opt_param("theme", self.theme.map_string())
+            .add_opt_param("hide_media", self.hide_media.map_string())
+            .add_opt_param("hide_thread", self.hide_thread.map_string())
+            .add_opt_param("maxwidth", self.max_width.map_string())
+            .add_opt_param("omit_script", self.omit_script.map_string())
+            .add_opt_param("align", self.align.map_string())
+            .add_opt_param("lang", self.lang.clone())
+            .add_opt_param("dnt", self.dnt.map_string())
+    }
+
+    ///Sends the request, and returns the HTML snippet along with information about the tweet.
+    pub async fn call(self) -> Result<Response<OEmbed>> {
+        let req = RequestBuilder::new(Method::GET, links::statuses::OEMBED)
+            .with_query_params(&self.params())
+            .request_unauthenticated();
+        request_with_json_response(req).await
+    }
+}
+
+///The HTML snippet for embedding a tweet, along with information about it.
+///
+///This is returned by `oembed`, and follows the [oEmbed][] format.
+///
+///[oEmbed]: https://oembed.com/
+#[derive(Debug, Clone, Deserialize)]
+pub struct OEmbed {
+    ///The HTML to put on a page to show the tweet. Unless `omit_script` was set, this includes a
+    ///`<script>` tag that loads Twitter's `widgets.js`, which turns the snippet into a rendered
+    ///tweet.
+    pub html: String,
+    ///The URL of the tweet.
+    pub url: String,
+    ///The display name of the tweet's author.
+    pub author_name: String,
+    ///The URL of the author's profile.
+    pub author_url: String,
+    ///The width of the embedded tweet, in pixels, if Twitter gave one.
+    pub width: Option<u32>,
+    ///The height of the embedded tweet, in pixels, if Twitter gave one. Twitter doesn't know how
+    ///tall the tweet will be until it's rendered, so this is usually missing.
+    pub height: Option<u32>,
+    ///How long the snippet can be cached for, in seconds.
+    #[serde(with = "serde_via_string")]
+    pub cache_age: u64,
+    ///The oEmbed type of the snippet. For tweets this is always `"rich"`.
+    #[serde(rename = "type")]
+    pub embed_type: String,
+    ///The name of the site the tweet is from, `"Twitter"`.
+    pub provider_name: String,
+    ///The URL of the site the tweet is from.
+    pub provider_url: String,
+    ///The version of the oEmbed format the response follows.
+    pub version: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::load_file;
+
+    #[test]
+    fn oembed_params() {
+        let builder = oembed(1261253754969640960u64)
+            .theme(OEmbedTheme::Dark)
+            .max_width(325)
+            .omit_script(true)
+            .align(OEmbedAlign::Center);
+        let params = builder.params();
+
+        assert_eq!(
+            params.get("url").map(|v| v.as_ref()),
+            Some("https://twitter.com/i/status/1261253754969640960")
+        );
+        assert_eq!(params.get("theme").map(|v| v.as_ref()), Some("dark"));
+        assert_eq!(params.get("maxwidth").map(|v| v.as_ref()), Some("325"));
+        assert_eq!(params.get("omit_script").map(|v| v.as_ref()), Some("true"));
+        assert_eq!(params.get("align").map(|v| v.as_ref()), Some("center"));
+        assert!(params.get("hide_media").is_none());
+
+        let params = oembed("https://twitter.com/rustlang/status/1261253754969640960").params();
+        assert_eq!(
+            params.get("url").map(|v| v.as_ref()),
+            Some("https://twitter.com/rustlang/status/1261253754969640960")
+        );
+    }
+
+    #[test]
+    fn parse_oembed() {
+        let content = load_file("sample_payloads/oembed.json");
+        let embed: OEmbed = serde_json::from_str(&content).unwrap();
+
+        assert!(embed
+            .html
+            .starts_with("<blockquote class=\"twitter-tweet\">"));
+        assert_eq!(embed.author_name, "Rust Language");
+        assert_eq!(embed.width, Some(550));
+        assert_eq!(embed.height, None);
+        assert_eq!(embed.cache_age, 3153600000);
+        assert_eq!(embed.embed_type, "rich");
+    }
+}
@@ -5,17 +5,21 @@ use serde::Deserialize;
 use crate::common::serde_datetime;
 
 use super::{
-    deserialize_tweet_source, ExtendedTweetEntities, FilterLevel, Tweet,
+    deserialize_tweet_source, ExtendedTweetEntities, FilterLevel, MatchingRule, Tweet,
     TweetEntities, TweetSource,
 };
 
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct RawTweet {
+    pub conversation_control: Option<RawConversationControl>,
     pub coordinates: Option<RawCoordinates>,
     #[serde(with = "serde_datetime")]
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub current_user_retweet: Option<CurrentUserRetweet>,
     pub display_text_range: Option<(usize, usize)>,
+    pub edit_history: Option<RawEditHistory>,
+    pub edit_history_ids: Option<Vec<u64>>,
+    pub edit_history_tweet_ids: Option<Vec<String>>,
     pub entities: TweetEntities,
     pub extended_entities: Option<ExtendedTweetEntities>,
     pub extended_tweet: Option<RawExtendedTweet>,
@@ -27,10 +31,12 @@ pub(crate) struct RawTweet {
     pub in_reply_to_screen_name: Option<String>,
     pub in_reply_to_status_id: Option<u64>,
     pub lang: Option<String>,
+    pub matching_rules: Option<Vec<MatchingRule>>,
     pub place: Option<place::Place>,
     pub possibly_sensitive: Option<bool>,
     pub quoted_status_id: Option<u64>,
     pub quoted_status: Option<Box<Tweet>>,
+    pub reply_settings: Option<String>,
     pub retweet_count: i32,
     pub retweeted: Option<bool>,
     pub retweeted_status: Option<Box<Tweet>>,
@@ -39,7 +45,7 @@ pub(crate) struct RawTweet {
     pub text: Option<String>,
     pub full_text: Option<String>,
     pub truncated: bool,
-    pub user: Option<Box<user::TwitterUser>>,
+    pub user: Option<RawTweetUser>,
     #[serde(default)]
     pub withheld_copyright: bool,
     pub withheld_in_countries: Option<Vec<String>>,
@@ -54,6 +60,24 @@ pub(crate) struct RawExtendedTweet {
     pub extended_entities: Option<ExtendedTweetEntities>,
 }
 
+///The author of a tweet. Tweets loaded with `trim_user` only include the author's ID.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum RawTweetUser {
+    Full(Box<user::TwitterUser>),
+    Trimmed { id: u64 },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct RawConversationControl {
+    pub policy: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct RawEditHistory {
+    pub edit_tweet_ids: Vec<String>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub(crate) struct RawCoordinates {
     #[serde(rename = "type")]
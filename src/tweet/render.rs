@@ -0,0 +1,286 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+use std::borrow::Cow;
+
+use crate::common::percent_encode;
+use crate::entities::{HashtagEntity, MediaEntity, MentionEntity, UrlEntity};
+
+use super::Tweet;
+
+///A piece of a tweet's text, as split up by `tweet::spans`.
+///
+///Each variant other than `Text` holds the entity that covers that piece of the text. The text
+///the entity covers can be sliced out of the tweet's `text` with the entity's `range`.
+#[derive(Debug, Clone)]
+pub enum TweetSpan<'a> {
+    ///Plain text between entities. Twitter sends tweet text with `&`, `<`, and `>` escaped as
+    ///HTML entities; these are turned back into regular characters here.
+    Text(Cow<'a, str>),
+    ///A hashtag, like `#rustlang`.
+    Hashtag(&'a HashtagEntity),
+    ///A financial symbol, or "cashtag", like `$TWTR`.
+    Symbol(&'a HashtagEntity),
+    ///A mention of another user, like `@rustlang`.
+    Mention(&'a MentionEntity),
+    ///A link, shortened to a `t.co` URL.
+    Url(&'a UrlEntity),
+    ///A link to a photo, video, or GIF attached to the tweet.
+    Media(&'a MediaEntity),
+}
+
+///Splits the text of the given tweet into plain text and the entities within it.
+///
+///Only the part of the text within the tweet's `display_text_range` is included, so the
+///mentions at the start of a reply and the link to attached media at the end of a tweet are left
+///out, the same way Twitter's own clients leave them out. Entities are placed using their
+///`range`s, which egg-mode has already converted from Twitter's character offsets into byte
+///offsets. Entities that overlap an earlier one or don't fall on a character boundary are treated
+///as plain text.
+///
+///Note that a retweet's own text is the truncated `RT @user:` text; to show the original tweet,
+///use the spans of its `retweeted_status` instead.
+pub fn spans(tweet: &Tweet) -> Vec<TweetSpan<'_>> {
+    let text = &tweet.text;
+    let (start, end) = display_range(tweet);
+
+    let entities = &tweet.entities;
+    let mut found = Vec::new();
+    found.extend(
+        entities
+            .hashtags
+            .iter()
+            .map(|e| (e.range, TweetSpan::Hashtag(e))),
+    );
+    found.extend(
+        entities
+            .symbols
+            .iter()
+            .map(|e| (e.range, TweetSpan::Symbol(e))),
+    );
+    found.extend(
+        entities
+            .user_mentions
+            .iter()
+            .map(|e| (e.range, TweetSpan::Mention(e))),
+    );
+    found.extend(entities.urls.iter().map(|e| (e.range, TweetSpan::Url(e))));
+    found.extend(
+        entities
+            .media
+            .iter()
+            .flatten()
+            .map(|e| (e.range, TweetSpan::Media(e))),
+    );
+    found.retain(|&((from, to), _)| {
+        start <= from
+            && from < to
+            && to <= end
+            && text.is_char_boundary(from)
+            && text.is_char_boundary(to)
+    });
+    found.sort_by_key(|&((from, _), _)| from);
+
+    let mut spans = Vec::new();
+    let mut pos = start;
+    for ((from, to), span) in found {
+        if from < pos {
+            continue;
+        }
+        if from > pos {
+            spans.push(TweetSpan::Text(unescape(&text[pos..from])));
+        }
+        spans.push(span);
+        pos = to;
+    }
+    if pos < end {
+        spans.push(TweetSpan::Text(unescape(&text[pos..end])));
+    }
+
+    spans
+}
+
+///Renders the text of the given tweet as HTML, with its hashtags, symbols, mentions, links, and
+///media linked.
+///
+///The text is split up the same way as `spans`, so only the part within the tweet's
+///`display_text_range` is rendered. Each entity becomes an `<a>` tag:
+///
+///* hashtags link to their page on Twitter, and symbols to a search for them
+///* mentions link to the mentioned user's profile
+///* links and media show their `display_url`, link to their `t.co` URL as Twitter asks, and have
+///  their `expanded_url` as a `title`
+///
+///All the text is escaped, so the result can be put into a page as-is. Newlines are kept as they
+///are, so the element holding the text should be styled with `white-space: pre-wrap` (or the
+///newlines replaced with `<br>`) to show line breaks.
+///
+///```rust,no_run
+///# use egg_mode::Token;
+///# #[tokio::main]
+///# async fn main() {
+///# let token: Token = unimplemented!();
+///let tweet = egg_mode::tweet::show(1261253754969640960, &token).await.unwrap();
+///println!("<p class=\"tweet\">{}</p>", egg_mode::tweet::render(&tweet));
+///# }
+///```
+pub fn render(tweet: &Tweet) -> String {
+    let text = &tweet.text;
+    let mut html = String::new();
+
+    for span in spans(tweet) {
+        match span {
+            TweetSpan::Text(t) => html.push_str(&escape(&t)),
+            TweetSpan::Hashtag(e) => html.push_str(&format!(
+                "<a href=\"https://twitter.com/hashtag/{}\">{}</a>",
+                percent_encode(&e.text),
+                escape(&text[e.range.0..e.range.1]),
+            )),
+            TweetSpan::Symbol(e) => html.push_str(&format!(
+                "<a href=\"https://twitter.com/search?q=%24{}\">{}</a>",
+                percent_encode(&e.text),
+                escape(&text[e.range.0..e.range.1]),
+            )),
+            TweetSpan::Mention(e) => html.push_str(&format!(
+                "<a href=\"https://twitter.com/{}\">{}</a>",
+                percent_encode(&e.screen_name),
+                escape(&text[e.range.0..e.range.1]),
+            )),
+            TweetSpan::Url(e) => {
+                html.push_str(&link(&e.url, e.expanded_url.as_deref(), &e.display_url))
+            }
+            TweetSpan::Media(e) => {
+                html.push_str(&link(&e.url, Some(&e.expanded_url), &e.display_url))
+            }
+        }
+    }
+
+    html
+}
+
+///Returns the byte range of the tweet's text that should be displayed, making sure it's valid to
+///slice the text with.
+fn display_range(tweet: &Tweet) -> (usize, usize) {
+    let text = &tweet.text;
+    match tweet.display_text_range {
+        Some((start, end))
+            if start <= end
+                && end <= text.len()
+                && text.is_char_boundary(start)
+                && text.is_char_boundary(end) =>
+        {
+            (start, end)
+        }
+        _ => (0, text.len()),
+    }
+}
+
+fn link(url: &str, expanded_url: Option<&str>, display_url: &str) -> String {
+    match expanded_url {
+        Some(expanded) => format!(
+            "<a href=\"{}\" title=\"{}\">{}</a>",
+            escape(url),
+            escape(expanded),
+            escape(display_url)
+        ),
+        None => format!("<a href=\"{}\">{}</a>", escape(url), escape(display_url)),
+    }
+}
+
+///Undoes the HTML escaping Twitter applies to tweet text.
+fn unescape(text: &str) -> Cow<'_, str> {
+    if text.contains('&') {
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&amp;", "&")
+            .into()
+    } else {
+        text.into()
+    }
+}
+
+fn escape(text: &str) -> Cow<'_, str> {
+    if text.contains(&['&', '<', '>', '"', '\''][..]) {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '"' => escaped.push_str("&quot;"),
+                '\'' => escaped.push_str("&#39;"),
+                c => escaped.push(c),
+            }
+        }
+        escaped.into()
+    } else {
+        text.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::load_file;
+
+    fn load_tweet(path: &str) -> Tweet {
+        let sample = load_file(path);
+        ::serde_json::from_str(&sample).unwrap()
+    }
+
+    #[test]
+    fn display_range_spans() {
+        let tweet = load_tweet("sample_payloads/sample-extended-onepic.json");
+        let spans = spans(&tweet);
+
+        assert_eq!(spans.len(), 3);
+        assert!(matches!(spans[0], TweetSpan::Text(ref text) if text == "."));
+        match spans[1] {
+            TweetSpan::Mention(mention) => assert_eq!(mention.screen_name, "Serrayak"),
+            ref other => panic!("expected a mention, got {:?}", other),
+        }
+        // the link to the attached photo is outside the display range
+        match spans[2] {
+            TweetSpan::Text(ref text) => {
+                assert!(text.starts_with(" said he’d use"));
+                assert!(text.ends_with("he said that"));
+            }
+            ref other => panic!("expected text, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn render_html() {
+        let tweets: Vec<Tweet> =
+            ::serde_json::from_str(&load_file("sample_payloads/tweet_array.json")).unwrap();
+        let tweet = tweets[19].retweeted_status.as_ref().unwrap();
+        let html = render(tweet);
+
+        assert!(html.starts_with(
+            "If you have an interest in \
+             <a href=\"https://twitter.com/rustlang\">@rustlang</a>, then"
+        ));
+        assert!(
+            html.contains("(feat. <a href=\"https://twitter.com/TrueLayer\">@TrueLayer</a> &amp; ")
+        );
+        assert!(html.ends_with(
+            "Register here: <a href=\"https://t.co/wAWbf7NClJ\" \
+             title=\"https://smrkts.co/3c1gJfm\">smrkts.co/3c1gJfm</a>"
+        ));
+
+        let spans = spans(tweet);
+        assert!(spans
+            .iter()
+            .any(|span| matches!(span, TweetSpan::Text(text) if text == " & ")));
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(unescape("a &amp;lt; b &lt; c"), "a &lt; b < c");
+        assert_eq!(
+            escape("<a href='x'>&</a>"),
+            "&lt;a href=&#39;x&#39;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,1822 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//!Tweet lookup through version 2 of the Twitter API.
+//!
+//!Version 2 of the API returns tweets in a different shape than the rest of egg-mode loads. A
+//!`TweetV2` only carries its ID and text by default; every other field needs to be asked for by
+//!name, as do any related objects (like the tweet's author or attached media) that should be
+//!"expanded" into the `includes` section of the response. The `Fields` builder collects these
+//!requests, and can be reused across calls:
+//!
+//!```rust,no_run
+//!# #[tokio::main]
+//!# async fn main() {
+//!# let token: egg_mode::Token = unimplemented!();
+//!use egg_mode::tweet::v2::{self, Expansion, Fields, TweetField, UserField};
+//!
+//!let fields = Fields::new()
+//!    .tweet_fields(vec![TweetField::CreatedAt, TweetField::PublicMetrics])
+//!    .expansions(vec![Expansion::AuthorId])
+//!    .user_fields(vec![UserField::Verified]);
+//!let lookup = v2::lookup(vec![1212092628029698048, 1293595870563381249], &fields, &token)
+//!    .await
+//!    .unwrap();
+//!for tweet in &lookup.data {
+//!    let author = tweet.author.as_ref().map(|u| &u.username);
+//!    println!("{:?}: {}", author, tweet.text);
+//!}
+//!# }
+//!```
+//!
+//!The objects in `includes` are also joined back into the tweets that refer to them, so the
+//!author of each tweet above is also in `tweet.author`, the tweets it references are in
+//!`tweet.referenced`, and so on. See `Hydrate` for details.
+//!
+//!Tweets that couldn't be loaded don't make the whole call fail. Instead, they're left out of
+//!`data`, and a `Problem` describing each one is given in `errors`. `lookup_map` goes a step
+//!further, and returns a map from each ID to its tweet or an `Absence` saying why it's missing.
+//!
+//!To see how many tweets match a search query over time without loading the tweets themselves,
+//!use `counts`, which counts them by the minute, hour, or day.
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use futures::stream::{self, Stream};
+use hyper::Method;
+use serde::Deserialize;
+
+use crate::auth::raw::RequestBuilder;
+use crate::common::*;
+use crate::entities::MediaType;
+use crate::error::{Error, Result};
+use crate::{auth, links, media, user};
+
+use super::ReplySettings;
+
+pub use crate::user::v2::{UserField, UserV2};
+
+field_names! {
+    ///Optional fields of a tweet that can be requested with `Fields::tweet_fields`.
+    TweetField {
+        ///`attachments`: The media keys and poll IDs attached to the tweet.
+        Attachments => "attachments",
+        ///`author_id`: The ID of the user who posted the tweet.
+        AuthorId => "author_id",
+        ///`conversation_id`: The ID of the tweet that started the conversation this tweet is in.
+        ConversationId => "conversation_id",
+        ///`created_at`: When the tweet was posted.
+        CreatedAt => "created_at",
+        ///`geo`: The place tagged in the tweet.
+        Geo => "geo",
+        ///`in_reply_to_user_id`: The ID of the user this tweet replies to.
+        InReplyToUserId => "in_reply_to_user_id",
+        ///`lang`: The language Twitter detected in the tweet.
+        Lang => "lang",
+        ///`non_public_metrics`: The tweet's impression and click counts. These are only available
+        ///for the authenticated user's own tweets.
+        NonPublicMetrics => "non_public_metrics",
+        ///`organic_metrics`: The tweet's engagement counts, not counting engagement from when it
+        ///was promoted. These are only available for the authenticated user's own tweets.
+        OrganicMetrics => "organic_metrics",
+        ///`possibly_sensitive`: Whether links in the tweet may lead to sensitive content.
+        PossiblySensitive => "possibly_sensitive",
+        ///`public_metrics`: The tweet's retweet, reply, like, and quote counts.
+        PublicMetrics => "public_metrics",
+        ///`referenced_tweets`: The tweets this tweet retweets, quotes, or replies to.
+        ReferencedTweets => "referenced_tweets",
+        ///`reply_settings`: Who is allowed to reply to the tweet.
+        ReplySettings => "reply_settings",
+        ///`source`: The name of the app the tweet was posted with.
+        Source => "source",
+    }
+}
+
+field_names! {
+    ///Related objects that can be loaded into `Includes` with `Fields::expansions`.
+    Expansion {
+        ///`attachments.media_keys`: The media attached to the tweet.
+        AttachmentsMediaKeys => "attachments.media_keys",
+        ///`attachments.poll_ids`: The poll attached to the tweet.
+        AttachmentsPollIds => "attachments.poll_ids",
+        ///`author_id`: The user who posted the tweet.
+        AuthorId => "author_id",
+        ///`in_reply_to_user_id`: The user the tweet replies to.
+        InReplyToUserId => "in_reply_to_user_id",
+        ///`referenced_tweets.id`: The tweets this tweet retweets, quotes, or replies to.
+        ReferencedTweetsId => "referenced_tweets.id",
+        ///`referenced_tweets.id.author_id`: The users who posted the referenced tweets.
+        ReferencedTweetsIdAuthorId => "referenced_tweets.id.author_id",
+        ///`entities.mentions.username`: The users mentioned in the tweet.
+        EntitiesMentionsUsername => "entities.mentions.username",
+        ///`geo.place_id`: The place tagged in the tweet.
+        GeoPlaceId => "geo.place_id",
+    }
+}
+
+field_names! {
+    ///Optional fields of included media that can be requested with `Fields::media_fields`.
+    MediaField {
+        ///`alt_text`: The description given to the media for people who can't see it.
+        AltText => "alt_text",
+        ///`duration_ms`: The length of a video, in milliseconds.
+        DurationMs => "duration_ms",
+        ///`height`: The height of the media, in pixels.
+        Height => "height",
+        ///`preview_image_url`: A still image to show in place of a video.
+        PreviewImageUrl => "preview_image_url",
+        ///`url`: The URL of an image.
+        Url => "url",
+        ///`width`: The width of the media, in pixels.
+        Width => "width",
+    }
+}
+
+field_names! {
+    ///Optional fields of included polls that can be requested with `Fields::poll_fields`.
+    PollField {
+        ///`duration_minutes`: How long the poll is open for, in minutes.
+        DurationMinutes => "duration_minutes",
+        ///`end_datetime`: When the poll closes.
+        EndDatetime => "end_datetime",
+        ///`voting_status`: Whether the poll is still open.
+        VotingStatus => "voting_status",
+    }
+}
+
+field_names! {
+    ///Optional fields of included places that can be requested with `Fields::place_fields`.
+    PlaceField {
+        ///`country`: The name of the country the place is in.
+        Country => "country",
+        ///`country_code`: The ISO code of the country the place is in.
+        CountryCode => "country_code",
+        ///`name`: The short name of the place.
+        Name => "name",
+        ///`place_type`: What kind of place this is, like `city`.
+        PlaceType => "place_type",
+    }
+}
+
+///The fields and expansions to ask for when loading tweets.
+///
+///By default, Twitter only returns each tweet's `id` and `text`, and no `includes`. Each method
+///here adds to the set of fields requested, so they can be called more than once.
+#[derive(Debug, Clone, Default)]
+pub struct Fields {
+    tweet_fields: Vec<TweetField>,
+    expansions: Vec<Expansion>,
+    media_fields: Vec<MediaField>,
+    user_fields: Vec<UserField>,
+    poll_fields: Vec<PollField>,
+    place_fields: Vec<PlaceField>,
+}
+
+impl Fields {
+    ///Creates an empty set of fields, which loads each tweet's `id` and `text` only.
+    pub fn new() -> Fields {
+        Fields::default()
+    }
+
+    ///Asks for the given fields of each tweet.
+    pub fn tweet_fields(mut self, fields: impl IntoIterator<Item = TweetField>) -> Fields {
+        self.tweet_fields.extend(fields);
+        self
+    }
+
+    ///Asks for the given related objects to be loaded into `includes`.
+    pub fn expansions(mut self, expansions: impl IntoIterator<Item = Expansion>) -> Fields {
+        self.expansions.extend(expansions);
+        self
+    }
+
+    ///Asks for the given fields of each included media item. These only have an effect along with
+    ///`Expansion::AttachmentsMediaKeys`.
+    pub fn media_fields(mut self, fields: impl IntoIterator<Item = MediaField>) -> Fields {
+        self.media_fields.extend(fields);
+        self
+    }
+
+    ///Asks for the given fields of each included user. These only have an effect along with an
+    ///expansion that includes users, like `Expansion::AuthorId`.
+    pub fn user_fields(mut self, fields: impl IntoIterator<Item = UserField>) -> Fields {
+        self.user_fields.extend(fields);
+        self
+    }
+
+    ///Asks for the given fields of each included poll. These only have an effect along with
+    ///`Expansion::AttachmentsPollIds`.
+    pub fn poll_fields(mut self, fields: impl IntoIterator<Item = PollField>) -> Fields {
+        self.poll_fields.extend(fields);
+        self
+    }
+
+    ///Asks for the given fields of each included place. These only have an effect along with
+    ///`Expansion::GeoPlaceId`.
+    pub fn place_fields(mut self, fields: impl IntoIterator<Item = PlaceField>) -> Fields {
+        self.place_fields.extend(fields);
+        self
+    }
+
+    ///Builds the query parameters for these fields.
+    pub(crate) fn params(&self) -> ParamList {
+        let tweet_fields = self.tweet_fields.iter().map(|f| f.as_str());
+        let expansions = self.expansions.iter().map(|e| e.as_str());
+        let media_fields = self.media_fields.iter().map(|f| f.as_str());
+        let user_fields = self.user_fields.iter().map(|f| f.as_str());
+        let poll_fields = self.poll_fields.iter().map(|f| f.as_str());
+        let place_fields = self.place_fields.iter().map(|f| f.as_str());
+        ParamList::new()
+            .add_opt_param("tweet.fields", join_field_names(tweet_fields))
+            .add_opt_param("expansions", join_field_names(expansions))
+            .add_opt_param("media.fields", join_field_names(media_fields))
+            .add_opt_param("user.fields", join_field_names(user_fields))
+            .add_opt_param("poll.fields", join_field_names(poll_fields))
+            .add_opt_param("place.fields", join_field_names(place_fields))
+    }
+}
+
+///Represents a single tweet, as returned by version 2 of the Twitter API.
+///
+///Only `id` and `text` are always present. The other fields are only filled in if they were
+///asked for with `Fields::tweet_fields`, and if Twitter has a value for them on this tweet.
+///
+///The fields after `geo` aren't sent by Twitter as part of the tweet. Instead, they're
+///filled in from the `includes` loaded alongside it, for the expansions asked for with
+///`Fields::expansions`. See `Hydrate` for details.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TweetV2 {
+    ///Numeric ID for this tweet.
+    #[serde(with = "serde_via_string")]
+    pub id: u64,
+    ///The text of the tweet.
+    pub text: String,
+    ///The ID of the user who posted this tweet.
+    #[serde(default, deserialize_with = "deserialize_opt_string_id")]
+    pub author_id: Option<u64>,
+    ///The ID of the tweet that started the conversation this tweet is in. For tweets that aren't
+    ///replies, this is the tweet's own ID.
+    #[serde(default, deserialize_with = "deserialize_opt_string_id")]
+    pub conversation_id: Option<u64>,
+    ///When this tweet was posted.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    ///If this tweet is a reply, the ID of the user it replies to.
+    #[serde(default, deserialize_with = "deserialize_opt_string_id")]
+    pub in_reply_to_user_id: Option<u64>,
+    ///The BCP 47 language code Twitter detected in the tweet's text.
+    pub lang: Option<String>,
+    ///Whether links in this tweet may lead to sensitive content.
+    pub possibly_sensitive: Option<bool>,
+    ///Who is allowed to reply to this tweet.
+    pub reply_settings: Option<ReplySettings>,
+    ///The name of the app this tweet was posted with.
+    pub source: Option<String>,
+    ///The media and polls attached to this tweet.
+    pub attachments: Option<Attachments>,
+    ///The tweets this tweet retweets, quotes, or replies to. This is empty if none were given.
+    #[serde(default)]
+    pub referenced_tweets: Vec<ReferencedTweet>,
+    ///The tweet's retweet, reply, like, and quote counts.
+    pub public_metrics: Option<PublicMetrics>,
+    ///The tweet's impression and click counts. These are only given for the authenticated user's
+    ///own tweets.
+    pub non_public_metrics: Option<NonPublicMetrics>,
+    ///The tweet's engagement counts, not counting engagement from when it was promoted. These are
+    ///only given for the authenticated user's own tweets.
+    pub organic_metrics: Option<OrganicMetrics>,
+    ///The place tagged in this tweet.
+    pub geo: Option<TweetGeo>,
+    ///The user who posted this tweet, from `Expansion::AuthorId`.
+    #[serde(skip)]
+    pub author: Option<UserV2>,
+    ///The tweets this tweet retweets, quotes, or replies to, in the same order as
+    ///`referenced_tweets`, from `Expansion::ReferencedTweetsId`. Tweets Twitter didn't include,
+    ///like deleted ones, are left out.
+    #[serde(skip)]
+    pub referenced: Vec<TweetV2>,
+    ///The media attached to this tweet, from `Expansion::AttachmentsMediaKeys`.
+    #[serde(skip)]
+    pub media: Vec<MediaV2>,
+    ///The poll attached to this tweet, from `Expansion::AttachmentsPollIds`.
+    #[serde(skip)]
+    pub poll: Option<PollV2>,
+    ///The place tagged in this tweet, from `Expansion::GeoPlaceId`.
+    #[serde(skip)]
+    pub place: Option<PlaceV2>,
+}
+
+impl TweetV2 {
+    ///Returns the ID of the tweet this one replies to, if it's a reply.
+    pub fn replied_to(&self) -> Option<u64> {
+        self.referenced(ReferenceKind::RepliedTo)
+    }
+
+    ///Returns the ID of the tweet this one quotes, if it's a quote tweet.
+    pub fn quoted(&self) -> Option<u64> {
+        self.referenced(ReferenceKind::Quoted)
+    }
+
+    ///Returns the ID of the tweet this one retweets, if it's a retweet.
+    pub fn retweeted(&self) -> Option<u64> {
+        self.referenced(ReferenceKind::Retweeted)
+    }
+
+    fn referenced(&self, kind: ReferenceKind) -> Option<u64> {
+        let found = self.referenced_tweets.iter().find(|r| r.kind == kind);
+        found.map(|r| r.id)
+    }
+}
+
+///The media and polls attached to a `TweetV2`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Attachments {
+    ///The keys of the attached media, which can be looked up with `Includes::media`.
+    #[serde(default)]
+    pub media_keys: Vec<String>,
+    ///The IDs of attached polls.
+    #[serde(default)]
+    pub poll_ids: Vec<String>,
+}
+
+///The place tagged in a `TweetV2`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TweetGeo {
+    ///The ID of the tagged place, which can be looked up with `Includes::place`.
+    pub place_id: Option<String>,
+}
+
+///A tweet referenced by a `TweetV2`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReferencedTweet {
+    ///How the tweet is referenced.
+    #[serde(rename = "type")]
+    pub kind: ReferenceKind,
+    ///The ID of the referenced tweet.
+    #[serde(with = "serde_via_string")]
+    pub id: u64,
+}
+
+///The ways a tweet can reference another.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize)]
+pub enum ReferenceKind {
+    ///The tweet is a retweet of the referenced tweet.
+    #[serde(rename = "retweeted")]
+    Retweeted,
+    ///The tweet quotes the referenced tweet.
+    #[serde(rename = "quoted")]
+    Quoted,
+    ///The tweet is a reply to the referenced tweet.
+    #[serde(rename = "replied_to")]
+    RepliedTo,
+}
+
+///The engagement counts of a `TweetV2`.
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct PublicMetrics {
+    ///The number of times the tweet has been retweeted.
+    pub retweet_count: u64,
+    ///The number of replies to the tweet.
+    pub reply_count: u64,
+    ///The number of times the tweet has been liked.
+    pub like_count: u64,
+    ///The number of times the tweet has been quoted.
+    pub quote_count: u64,
+}
+
+///The impression and click counts of a `TweetV2`, which only its author can see.
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct NonPublicMetrics {
+    ///The number of times the tweet has been seen.
+    pub impression_count: u64,
+    ///The number of times a link in the tweet has been clicked. This is only given if the tweet
+    ///has a link in it.
+    pub url_link_clicks: Option<u64>,
+    ///The number of times the author's profile has been visited from the tweet.
+    pub user_profile_clicks: u64,
+}
+
+///The engagement counts of a `TweetV2`, not counting engagement from when it was promoted. Only
+///the tweet's author can see these.
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct OrganicMetrics {
+    ///The number of times the tweet has been seen.
+    pub impression_count: u64,
+    ///The number of times the tweet has been retweeted.
+    pub retweet_count: u64,
+    ///The number of replies to the tweet.
+    pub reply_count: u64,
+    ///The number of times the tweet has been liked.
+    pub like_count: u64,
+    ///The number of times a link in the tweet has been clicked. This is only given if the tweet
+    ///has a link in it.
+    pub url_link_clicks: Option<u64>,
+    ///The number of times the author's profile has been visited from the tweet.
+    pub user_profile_clicks: u64,
+}
+
+///The engagement counts of one of the authenticated user's tweets, as returned by `metrics`.
+#[derive(Debug, Copy, Clone)]
+pub struct TweetMetrics {
+    ///Numeric ID of the tweet.
+    pub id: u64,
+    ///The tweet's retweet, reply, like, and quote counts, which anyone can see.
+    pub public: Option<PublicMetrics>,
+    ///The tweet's impression and click counts.
+    pub non_public: Option<NonPublicMetrics>,
+    ///The tweet's engagement counts, not counting engagement from when it was promoted.
+    pub organic: Option<OrganicMetrics>,
+}
+
+impl From<&TweetV2> for TweetMetrics {
+    fn from(tweet: &TweetV2) -> TweetMetrics {
+        TweetMetrics {
+            id: tweet.id,
+            public: tweet.public_metrics,
+            non_public: tweet.non_public_metrics,
+            organic: tweet.organic_metrics,
+        }
+    }
+}
+
+///A media item loaded into `Includes` by `Expansion::AttachmentsMediaKeys`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MediaV2 {
+    ///The key used to refer to this media in `Attachments::media_keys`.
+    pub media_key: String,
+    ///The kind of media this is.
+    #[serde(rename = "type")]
+    pub media_type: MediaType,
+    ///The URL of the image, for photos.
+    pub url: Option<String>,
+    ///A still image to show in place of a video or GIF.
+    pub preview_image_url: Option<String>,
+    ///The width of the media, in pixels.
+    pub width: Option<u32>,
+    ///The height of the media, in pixels.
+    pub height: Option<u32>,
+    ///The length of a video, in milliseconds.
+    pub duration_ms: Option<u64>,
+    ///The description given to the media for people who can't see it.
+    pub alt_text: Option<String>,
+}
+
+///A poll loaded into `Includes` by `Expansion::AttachmentsPollIds`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PollV2 {
+    ///The ID of this poll, used in `Attachments::poll_ids`.
+    pub id: String,
+    ///The choices of the poll, in order.
+    pub options: Vec<PollOption>,
+    ///How long the poll is open for, in minutes.
+    pub duration_minutes: Option<u32>,
+    ///When the poll closes.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub end_datetime: Option<chrono::DateTime<chrono::Utc>>,
+    ///Whether the poll is still open, as `open` or `closed`.
+    pub voting_status: Option<String>,
+}
+
+///One of the choices in a `PollV2`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PollOption {
+    ///Where this choice falls in the poll, starting at 1.
+    pub position: u32,
+    ///The text of this choice.
+    pub label: String,
+    ///The number of votes this choice has received.
+    pub votes: u64,
+}
+
+///A place loaded into `Includes` by `Expansion::GeoPlaceId`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PlaceV2 {
+    ///The ID of this place, used in `TweetGeo::place_id`.
+    pub id: String,
+    ///The full name of this place, like "Manhattan, NY".
+    pub full_name: String,
+    ///The short name of this place, like "Manhattan".
+    pub name: Option<String>,
+    ///What kind of place this is, like `city`.
+    pub place_type: Option<String>,
+    ///The name of the country this place is in.
+    pub country: Option<String>,
+    ///The ISO code of the country this place is in.
+    pub country_code: Option<String>,
+}
+
+///The related objects loaded alongside the tweets in a `TweetLookup`.
+///
+///These are only filled in for the expansions asked for with `Fields::expansions`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Includes {
+    ///Tweets referenced by the loaded tweets.
+    #[serde(default)]
+    pub tweets: Vec<TweetV2>,
+    ///Users who posted, or are mentioned in, the loaded tweets.
+    #[serde(default)]
+    pub users: Vec<UserV2>,
+    ///Media attached to the loaded tweets.
+    #[serde(default)]
+    pub media: Vec<MediaV2>,
+    ///Polls attached to the loaded tweets.
+    #[serde(default)]
+    pub polls: Vec<PollV2>,
+    ///Places tagged in the loaded tweets.
+    #[serde(default)]
+    pub places: Vec<PlaceV2>,
+}
+
+impl Includes {
+    ///Looks up the included tweet with the given ID.
+    pub fn tweet(&self, id: u64) -> Option<&TweetV2> {
+        self.tweets.iter().find(|t| t.id == id)
+    }
+
+    ///Looks up the included user with the given ID.
+    pub fn user(&self, id: u64) -> Option<&UserV2> {
+        self.users.iter().find(|u| u.id == id)
+    }
+
+    ///Looks up the included media with the given media key.
+    pub fn media(&self, media_key: &str) -> Option<&MediaV2> {
+        self.media.iter().find(|m| m.media_key == media_key)
+    }
+
+    ///Looks up the included poll with the given ID.
+    pub fn poll(&self, id: &str) -> Option<&PollV2> {
+        self.polls.iter().find(|p| p.id == id)
+    }
+
+    ///Looks up the included place with the given ID.
+    pub fn place(&self, id: &str) -> Option<&PlaceV2> {
+        self.places.iter().find(|p| p.id == id)
+    }
+}
+
+///Fills in the objects a v2 response refers to by ID, from the `includes` loaded alongside it.
+///
+///Version 2 of the Twitter API doesn't nest related objects inside each other. A tweet only gives
+///the ID of its author, for example, and the author is sent separately in the `includes` section
+///of the response, if `Expansion::AuthorId` was asked for. egg-mode joins these back together
+///when it loads a v2 response, so fields like `TweetV2::author` and `TweetV2::referenced` are
+///filled in without having to look anything up in `includes` by hand. `includes` is still kept
+///in the response, for anything that isn't attached to a particular object.
+///
+///This is implemented for `Vec`s and `Option`s of hydrated types, so the `data` of any response
+///can be hydrated at once. It's only needed when loading v2 responses some other way, like with
+///the `raw` module.
+pub trait Hydrate<I> {
+    ///Fills in the related objects of this value from the given `includes`.
+    fn hydrate(&mut self, includes: &I);
+}
+
+impl<I, T: Hydrate<I>> Hydrate<I> for Vec<T> {
+    fn hydrate(&mut self, includes: &I) {
+        for item in self.iter_mut() {
+            item.hydrate(includes);
+        }
+    }
+}
+
+impl<I, T: Hydrate<I>> Hydrate<I> for Option<T> {
+    fn hydrate(&mut self, includes: &I) {
+        if let Some(item) = self {
+            item.hydrate(includes);
+        }
+    }
+}
+
+impl Hydrate<Includes> for TweetV2 {
+    ///Fills in `author`, `referenced`, `media`, `poll`, and `place`. The referenced tweets get
+    ///their own author, media, poll, and place filled in, but not their own referenced tweets.
+    fn hydrate(&mut self, includes: &Includes) {
+        self.hydrate_attached(includes);
+        self.referenced = self
+            .referenced_tweets
+            .iter()
+            .filter_map(|r| includes.tweet(r.id))
+            .map(|tweet| {
+                let mut tweet = tweet.clone();
+                tweet.hydrate_attached(includes);
+                tweet
+            })
+            .collect();
+    }
+}
+
+impl TweetV2 {
+    ///Fills in every hydrated field but `referenced`.
+    fn hydrate_attached(&mut self, includes: &Includes) {
+        self.author = self.author_id.and_then(|id| includes.user(id)).cloned();
+        if let Some(ref attachments) = self.attachments {
+            self.media = attachments
+                .media_keys
+                .iter()
+                .filter_map(|key| includes.media(key))
+                .cloned()
+                .collect();
+            self.poll = attachments
+                .poll_ids
+                .iter()
+                .find_map(|id| includes.poll(id))
+                .cloned();
+        }
+        self.place = self
+            .geo
+            .as_ref()
+            .and_then(|geo| geo.place_id.as_deref())
+            .and_then(|id| includes.place(id))
+            .cloned();
+    }
+}
+
+///Describes a tweet that couldn't be loaded, or another problem with part of a request.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Problem {
+    ///A short summary of the problem, like "Not Found Error".
+    pub title: String,
+    ///A longer description of the problem.
+    pub detail: Option<String>,
+    ///The ID of the tweet or other object the problem is about, if any.
+    pub resource_id: Option<String>,
+    ///A URL identifying the kind of problem, like
+    ///`https://api.twitter.com/2/problems/resource-not-found`.
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+///The response from a version 2 tweet lookup.
+///
+///`data` holds the tweets that were loaded: a `Vec` from `lookup`, or an `Option` from `show`.
+///The tweets are hydrated from `includes` as they're loaded.
+pub type TweetLookup<T> = Lookup<T, Includes>;
+
+///Describes one page of a longer list of tweets, users, or other results.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PageMeta {
+    ///The number of results in this page.
+    #[serde(default)]
+    pub result_count: u32,
+    ///The token used to load the next page, if there is one.
+    pub next_token: Option<String>,
+}
+
+///A page of tweets from a call that loads a list of tweets a page at a time.
+pub type TweetPage = Response<TweetLookup<Vec<TweetV2>>>;
+
+///Lookup a single tweet by numeric ID, loading the given fields.
+///
+///If the tweet doesn't exist or can't be seen by the authenticated user, `data` will be `None`,
+///and `errors` will say why.
+pub async fn show(
+    id: u64,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<TweetLookup<Option<TweetV2>>>> {
+    let url = format!("{}/{}", links::statuses::TWEETS_V2, id);
+    let req = get(&url, token, Some(&fields.params()));
+    request_with_json_response(req).await
+}
+
+///Lookup tweet information for the given list of tweet IDs, loading the given fields.
+///
+///Twitter accepts up to 100 IDs per call. Tweets that couldn't be loaded are left out of `data`,
+///with a `Problem` in `errors` for each one.
+pub async fn lookup<I: IntoIterator<Item = u64>>(
+    ids: I,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<TweetLookup<Vec<TweetV2>>>> {
+    let ids = ids.into_iter().map(|id| id.to_string()).collect::<Vec<_>>();
+    let params = fields.params().add_param("ids", ids.join(","));
+    let req = get(links::statuses::TWEETS_V2, token, Some(&params));
+    request_with_json_response(req).await
+}
+
+///Load the engagement counts of one of the authenticated user's tweets, including how many times
+///it's been seen and how many times its links and its author's profile have been clicked.
+///
+///Twitter only gives the impression and click counts to the author of a tweet, and only for
+///tweets posted in the last 30 days, so `token` must be a user token for the tweet's author.
+///Asking for the counts of anyone else's tweet makes Twitter reject the whole call. If the tweet
+///couldn't be loaded, such as if it's been deleted, this returns `None`; to see why, load it with
+///`show` instead, with `TweetField::NonPublicMetrics` and `TweetField::OrganicMetrics` in the
+///`Fields`.
+pub async fn metrics(id: u64, token: &auth::Token) -> Result<Response<Option<TweetMetrics>>> {
+    let fields = Fields::new().tweet_fields(vec![
+        TweetField::PublicMetrics,
+        TweetField::NonPublicMetrics,
+        TweetField::OrganicMetrics,
+    ]);
+    let resp = show(id, &fields, token).await?;
+    Ok(Response::map(resp, |lookup| {
+        lookup.data.as_ref().map(TweetMetrics::from)
+    }))
+}
+
+///Why a tweet couldn't be loaded, as returned by `lookup_map`.
+#[derive(Debug, Clone)]
+pub enum Absence {
+    ///The tweet doesn't exist. Either it was deleted, or there was never a tweet with that ID.
+    Deleted,
+    ///The tweet's author has been suspended.
+    Suspended,
+    ///The tweet's author protects their tweets, and the authenticated user doesn't follow them.
+    Protected,
+    ///The tweet has been withheld, such as in the authenticated user's country.
+    Withheld,
+    ///Twitter didn't say why the tweet couldn't be loaded, or gave a reason this library doesn't
+    ///recognize. The `Problem` that Twitter gave, if any, is included. A tweet that's missing
+    ///for an unknown reason may only be unavailable for now.
+    Unknown(Option<Problem>),
+}
+
+impl Absence {
+    ///Works out why a tweet couldn't be loaded from the `Problem` Twitter gave for it.
+    fn from_problem(problem: &Problem) -> Absence {
+        let detail = problem
+            .detail
+            .as_deref()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if detail.contains("suspended") {
+            Absence::Suspended
+        } else if detail.contains("withheld") {
+            Absence::Withheld
+        } else if problem.kind.ends_with("/not-authorized-for-resource") {
+            Absence::Protected
+        } else if problem.kind.ends_with("/resource-not-found") {
+            Absence::Deleted
+        } else {
+            Absence::Unknown(Some(problem.clone()))
+        }
+    }
+}
+
+///Lookup tweet information for the given list of tweet IDs, loading the given fields, and return
+///a map saying why each missing tweet couldn't be loaded.
+///
+///This loads the tweets the same way `lookup` does, but returns a map containing every ID that
+///was asked for. Tweets that were loaded are stored as `Ok`, and tweets that couldn't be loaded
+///are stored as `Err`, with an `Absence` worked out from the `Problem` Twitter gave for them.
+///This tells apart tweets that have been deleted from tweets whose authors are suspended or
+///protected, or tweets that have been withheld. A tweet Twitter gave no reason for is stored as
+///`Absence::Unknown`.
+///
+///Twitter accepts up to 100 IDs per call.
+pub async fn lookup_map<I: IntoIterator<Item = u64>>(
+    ids: I,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<HashMap<u64, std::result::Result<TweetV2, Absence>>>> {
+    let ids = ids.into_iter().collect::<Vec<_>>();
+    let resp = lookup(ids.iter().cloned(), fields, token).await?;
+    Ok(Response::map(resp, |lookup| map_lookup(&ids, lookup)))
+}
+
+///Helper function for `lookup_map` that matches the loaded tweets and problems to the IDs that
+///were asked for.
+fn map_lookup(
+    ids: &[u64],
+    lookup: TweetLookup<Vec<TweetV2>>,
+) -> HashMap<u64, std::result::Result<TweetV2, Absence>> {
+    let mut map = ids
+        .iter()
+        .map(|&id| (id, Err(Absence::Unknown(None))))
+        .collect::<HashMap<_, _>>();
+    for problem in &lookup.errors {
+        let id = problem
+            .resource_id
+            .as_deref()
+            .and_then(|id| id.parse().ok());
+        if let Some(entry) = id.and_then(|id| map.get_mut(&id)) {
+            *entry = Err(Absence::from_problem(problem));
+        }
+    }
+    for tweet in lookup.data {
+        map.insert(tweet.id, Ok(tweet));
+    }
+    map
+}
+
+///Load the tweets the given user has bookmarked, a page at a time, newest first.
+///
+///`user_id` must be the ID of the authenticated user, and `token` must be an OAuth 2.0 user token
+///with the `bookmark.read` scope (as well as `tweet.read` and `users.read`). Each item in the
+///stream is a page of up to 100 tweets, along with the `includes` for that page; the next page is
+///loaded once the stream is polled again. If loading a page fails, the error is yielded and the
+///stream ends.
+pub fn bookmarks_of(
+    user_id: u64,
+    fields: &Fields,
+    token: &auth::Token,
+) -> impl Stream<Item = Result<TweetPage>> {
+    let url = format!("{}/{}/bookmarks", links::users::LOOKUP_V2, user_id);
+    let params = fields.params().add_param("max_results", "100");
+    let token = token.clone();
+    paginate(move |next_token| {
+        let params = params.clone().add_opt_param("pagination_token", next_token);
+        let req = get(&url, &token, Some(&params));
+        request_with_json_response(req)
+    })
+}
+
+///Bookmark the given tweet for the given user.
+///
+///`user_id` must be the ID of the authenticated user, and `token` must be an OAuth 2.0 user token
+///with the `bookmark.write` scope. On success, the response says whether the tweet is now
+///bookmarked.
+pub async fn bookmark(user_id: u64, tweet_id: u64, token: &auth::Token) -> Result<Response<bool>> {
+    let url = format!("{}/{}/bookmarks", links::users::LOOKUP_V2, user_id);
+    let body = serde_json::json!({ "tweet_id": tweet_id.to_string() });
+    let req = post_json(&url, token, body);
+    let resp = request_with_json_response::<BookmarkStatus>(req).await?;
+    Ok(Response::map(resp, |status| status.data.bookmarked))
+}
+
+///Remove the given tweet from the given user's bookmarks.
+///
+///`user_id` must be the ID of the authenticated user, and `token` must be an OAuth 2.0 user token
+///with the `bookmark.write` scope. On success, the response says whether the tweet is still
+///bookmarked.
+pub async fn unbookmark(
+    user_id: u64,
+    tweet_id: u64,
+    token: &auth::Token,
+) -> Result<Response<bool>> {
+    let url = format!(
+        "{}/{}/bookmarks/{}",
+        links::users::LOOKUP_V2,
+        user_id,
+        tweet_id
+    );
+    let req = auth::raw::delete(&url, token, None);
+    let resp = request_with_json_response::<BookmarkStatus>(req).await?;
+    Ok(Response::map(resp, |status| status.data.bookmarked))
+}
+
+///Hide or unhide the given reply to one of the authenticated user's tweets.
+///
+///Hidden replies are still visible, but they're moved behind a "hidden replies" link under the
+///tweet they reply to. `id` must be a reply to a tweet posted by the authenticated user, and
+///`token` must be an Access token or an OAuth 2.0 user token with the `tweet.moderate.write`
+///scope. On success, the response says whether the reply is now hidden.
+pub async fn hide_reply(id: u64, hidden: bool, token: &auth::Token) -> Result<Response<bool>> {
+    let url = format!("{}/{}/hidden", links::statuses::TWEETS_V2, id);
+    let req = RequestBuilder::new(Method::PUT, &url)
+        .with_body_json(serde_json::json!({ "hidden": hidden }))
+        .request_token(token);
+    let resp = request_with_json_response::<HiddenStatus>(req).await?;
+    Ok(Response::map(resp, |status| status.data.hidden))
+}
+
+///Represents an in-progress tweet before it is sent through version 2 of the Twitter API.
+///
+///This works like `DraftTweet`, but posts the tweet through `POST 2/tweets`. This makes it
+///possible to attach a poll to the tweet, or quote another tweet by ID, neither of which can be
+///done through version 1.1. `send` needs an Access token or an OAuth 2.0 user token with the
+///`tweet.write` scope.
+///
+///```rust,no_run
+///# use egg_mode::Token;
+///# #[tokio::main]
+///# async fn main() {
+///# let token: Token = unimplemented!();
+///use egg_mode::tweet::DraftTweetV2;
+///use egg_mode::tweet::ReplySettings;
+///
+///let draft = DraftTweetV2::new("Which do you use more?")
+///    .poll(vec!["Iterators", "for loops"], 24 * 60)
+///    .reply_settings(ReplySettings::Following);
+///let tweet = draft.send(&token).await.unwrap();
+///println!("posted tweet {}", tweet.id);
+///# }
+///```
+#[derive(Debug, Clone)]
+pub struct DraftTweetV2 {
+    ///The text of the draft tweet.
+    pub text: CowStr,
+    ///If present, the ID of the tweet this draft is replying to.
+    pub in_reply_to: Option<u64>,
+    ///The IDs of users to leave out of the reply mentions, if this draft is a reply.
+    pub exclude_reply_user_ids: Vec<u64>,
+    ///If present, the ID of the tweet this draft quotes.
+    pub quote_tweet_id: Option<u64>,
+    ///If present, the options of a poll to attach to the draft, and how many minutes it's open
+    ///for.
+    pub poll: Option<(Vec<CowStr>, u32)>,
+    ///If present, who is allowed to reply to the tweet.
+    pub reply_settings: Option<ReplySettings>,
+    ///The media to attach to the tweet.
+    pub media_ids: Vec<media::MediaId>,
+    ///The IDs of users to tag in the attached photos.
+    pub tagged_user_ids: Vec<u64>,
+    ///Whether the tweet should only be visible to the authenticated user's Super Followers.
+    pub for_super_followers: bool,
+}
+
+impl DraftTweetV2 {
+    ///Creates a new `DraftTweetV2` with the given status text.
+    pub fn new<S: Into<CowStr>>(text: S) -> Self {
+        DraftTweetV2 {
+            text: text.into(),
+            in_reply_to: None,
+            exclude_reply_user_ids: Vec::new(),
+            quote_tweet_id: None,
+            poll: None,
+            reply_settings: None,
+            media_ids: Vec::new(),
+            tagged_user_ids: Vec::new(),
+            for_super_followers: false,
+        }
+    }
+
+    ///Marks this draft tweet as replying to the given status ID.
+    ///
+    ///Twitter fills in the reply mentions from the tweet being replied to; use
+    ///`exclude_reply_user_ids` to leave some of them out.
+    pub fn in_reply_to(self, in_reply_to: u64) -> Self {
+        DraftTweetV2 {
+            in_reply_to: Some(in_reply_to),
+            ..self
+        }
+    }
+
+    ///Leaves the given users out of the reply mentions. This only has an effect along with
+    ///`in_reply_to`.
+    pub fn exclude_reply_user_ids(self, user_ids: impl IntoIterator<Item = u64>) -> Self {
+        DraftTweetV2 {
+            exclude_reply_user_ids: user_ids.into_iter().collect(),
+            ..self
+        }
+    }
+
+    ///Quotes the given tweet.
+    pub fn quote_tweet(self, quote_tweet_id: u64) -> Self {
+        DraftTweetV2 {
+            quote_tweet_id: Some(quote_tweet_id),
+            ..self
+        }
+    }
+
+    ///Attaches a poll with the given options to the tweet, open for the given number of minutes.
+    ///
+    ///Twitter accepts two to four options of up to 25 characters each, and a duration between 5
+    ///minutes and 7 days (10080 minutes). A tweet with a poll can't have media attached as well.
+    ///These limits are checked by `send`, which returns `Error::InvalidPoll` without contacting
+    ///Twitter if the poll is outside them.
+    pub fn poll<I, S>(self, options: I, duration_minutes: u32) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<CowStr>,
+    {
+        let options = options.into_iter().map(Into::into).collect();
+        DraftTweetV2 {
+            poll: Some((options, duration_minutes)),
+            ..self
+        }
+    }
+
+    ///Restricts who can reply to the tweet.
+    ///
+    ///By default, anyone can reply. Note that users mentioned in the tweet can always reply to it.
+    pub fn reply_settings(self, settings: ReplySettings) -> Self {
+        DraftTweetV2 {
+            reply_settings: Some(settings),
+            ..self
+        }
+    }
+
+    ///Add the given media to this tweet.
+    ///
+    ///As with `DraftTweet::add_media`, only the last four IDs will be kept if this is called more
+    ///than four times.
+    pub fn add_media(&mut self, media_id: media::MediaId) {
+        if self.media_ids.len() == 4 {
+            self.media_ids.remove(0);
+        }
+        self.media_ids.push(media_id);
+    }
+
+    ///Tags the given users in the photos attached to this tweet.
+    ///
+    ///Up to 10 users can be tagged, and only in photos. `send` checks this before contacting
+    ///Twitter, and returns `Error::InvalidMediaTags` if no media is attached, if more than 10
+    ///users are tagged, or if any of the media was uploaded as a GIF or video.
+    pub fn tag_users(self, user_ids: impl IntoIterator<Item = u64>) -> Self {
+        DraftTweetV2 {
+            tagged_user_ids: user_ids.into_iter().collect(),
+            ..self
+        }
+    }
+
+    ///Marks whether the tweet should only be visible to the authenticated user's Super Followers.
+    ///
+    ///Only accounts in Twitter's Super Follows program can post these tweets. If the account
+    ///can't, `send` will return `Error::SuperFollowsNotAllowed`.
+    pub fn for_super_followers(self, super_followers: bool) -> Self {
+        DraftTweetV2 {
+            for_super_followers: super_followers,
+            ..self
+        }
+    }
+
+    ///Builds the JSON body for `POST 2/tweets`.
+    pub(crate) fn body(&self) -> serde_json::Value {
+        let ids = |ids: &[u64]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        let mut body = serde_json::json!({ "text": self.text });
+        if let Some(in_reply_to) = self.in_reply_to {
+            body["reply"] = serde_json::json!({
+                "in_reply_to_tweet_id": in_reply_to.to_string(),
+                "exclude_reply_user_ids": ids(&self.exclude_reply_user_ids),
+            });
+        }
+        if let Some(quote_tweet_id) = self.quote_tweet_id {
+            body["quote_tweet_id"] = quote_tweet_id.to_string().into();
+        }
+        if let Some((ref options, duration_minutes)) = self.poll {
+            body["poll"] = serde_json::json!({
+                "options": options,
+                "duration_minutes": duration_minutes,
+            });
+        }
+        // leaving this out is how v2 says that everyone can reply
+        match self.reply_settings {
+            Some(ReplySettings::Everyone) | None => (),
+            Some(settings) => body["reply_settings"] = serde_json::to_value(settings).unwrap(),
+        }
+        if !self.media_ids.is_empty() {
+            let media_ids = self.media_ids.iter().map(|id| id.0.as_str());
+            body["media"] = serde_json::json!({ "media_ids": media_ids.collect::<Vec<_>>() });
+            if !self.tagged_user_ids.is_empty() {
+                body["media"]["tagged_user_ids"] = ids(&self.tagged_user_ids).into();
+            }
+        }
+        if self.for_super_followers {
+            body["for_super_followers_only"] = true.into();
+        }
+        body
+    }
+
+    ///Send the assembled tweet as the authenticated user.
+    ///
+    ///Twitter only returns the new tweet's `id` and `text`; use `show` to load any other fields.
+    pub async fn send(&self, token: &auth::Token) -> Result<Response<TweetV2>> {
+        if let Some((ref options, duration_minutes)) = self.poll {
+            check_poll(options, duration_minutes, !self.media_ids.is_empty())?;
+        }
+        if !self.tagged_user_ids.is_empty() {
+            check_media_tags(self.tagged_user_ids.len(), &self.media_ids)?;
+        }
+        let req = post_json(links::statuses::TWEETS_V2, token, self.body());
+        let resp = if self.for_super_followers {
+            request_with_json_problem::<CreatedTweet, _>(req, super_follows_problem).await?
+        } else {
+            request_with_json_response::<CreatedTweet>(req).await?
+        };
+        Ok(Response::map(resp, |created| created.data))
+    }
+}
+
+///Turns Twitter's rejection of a tweet for Super Followers into `Error::SuperFollowsNotAllowed`.
+///
+///Twitter sends this as a `403 Forbidden` whose problem says the account can't post Super Follows
+///tweets. Any other failure, including other kinds of `403`, is left to return its usual error.
+fn super_follows_problem(status: hyper::StatusCode, body: &[u8]) -> Option<Error> {
+    if status != hyper::StatusCode::FORBIDDEN {
+        return None;
+    }
+    let problem = serde_json::from_slice::<Problem>(body).ok()?;
+    let detail = problem.detail?.to_lowercase();
+    if detail.contains("super follow") {
+        Some(Error::SuperFollowsNotAllowed)
+    } else {
+        None
+    }
+}
+
+///Checks a poll against the limits Twitter places on polls, so a bad poll can be rejected before
+///the draft is sent.
+pub(crate) fn check_poll(options: &[CowStr], duration_minutes: u32, has_media: bool) -> Result<()> {
+    if !(2..=4).contains(&options.len()) {
+        return Err(Error::InvalidPoll("a poll needs between 2 and 4 options"));
+    }
+    if options
+        .iter()
+        .any(|o| o.is_empty() || o.chars().count() > 25)
+    {
+        return Err(Error::InvalidPoll(
+            "poll options must be 1 to 25 characters",
+        ));
+    }
+    if !(5..=7 * 24 * 60).contains(&duration_minutes) {
+        return Err(Error::InvalidPoll(
+            "a poll must be open for 5 minutes to 7 days",
+        ));
+    }
+    if has_media {
+        return Err(Error::InvalidPoll(
+            "a tweet can't have both a poll and media",
+        ));
+    }
+    Ok(())
+}
+
+///Checks that users tagged in a draft's media can be, so a draft with bad tags can be rejected
+///before it's sent.
+pub(crate) fn check_media_tags(tagged: usize, media_ids: &[media::MediaId]) -> Result<()> {
+    if tagged > 10 {
+        return Err(Error::InvalidMediaTags("at most 10 users can be tagged"));
+    }
+    if media_ids.is_empty() {
+        return Err(Error::InvalidMediaTags(
+            "users can only be tagged in attached photos",
+        ));
+    }
+    if media_ids.iter().any(|id| id.is_gif_or_video()) {
+        return Err(Error::InvalidMediaTags(
+            "users can't be tagged in GIFs or videos",
+        ));
+    }
+    Ok(())
+}
+
+///Delete the given tweet through version 2 of the Twitter API.
+///
+///The tweet must have been posted by the authenticated user, and `token` must be an Access token
+///or an OAuth 2.0 user token with the `tweet.write` scope. On success, the response says whether
+///the tweet was deleted.
+pub async fn delete(id: u64, token: &auth::Token) -> Result<Response<bool>> {
+    let url = format!("{}/{}", links::statuses::TWEETS_V2, id);
+    let req = auth::raw::delete(&url, token, None);
+    let resp = request_with_json_response::<DeletedStatus>(req).await?;
+    Ok(Response::map(resp, |status| status.data.deleted))
+}
+
+///Load the tweets the given user has liked, a page at a time, newest first.
+///
+///Each item in the stream is a page of up to 100 tweets, along with the `includes` for that page;
+///the next page is loaded once the stream is polled again. If loading a page fails, the error is
+///yielded and the stream ends.
+pub fn liked_tweets(
+    user_id: u64,
+    fields: &Fields,
+    token: &auth::Token,
+) -> impl Stream<Item = Result<TweetPage>> {
+    let url = format!("{}/{}/liked_tweets", links::users::LOOKUP_V2, user_id);
+    let params = fields.params().add_param("max_results", "100");
+    let token = token.clone();
+    paginate(move |next_token| {
+        let params = params.clone().add_opt_param("pagination_token", next_token);
+        let req = get(&url, &token, Some(&params));
+        request_with_json_response(req)
+    })
+}
+
+///Load the tweets that quote the given tweet, a page at a time, newest first.
+///
+///Each item in the stream is a page of up to 100 tweets, loaded with the given fields, along with
+///the `includes` for that page; the next page is loaded once the stream is polled again. If
+///loading a page fails, the error is yielded and the stream ends.
+pub fn quotes_of(
+    tweet_id: u64,
+    fields: &Fields,
+    token: &auth::Token,
+) -> impl Stream<Item = Result<TweetPage>> {
+    let url = format!("{}/{}/quote_tweets", links::statuses::TWEETS_V2, tweet_id);
+    let params = fields.params().add_param("max_results", "100");
+    let token = token.clone();
+    paginate(move |next_token| {
+        let params = params.clone().add_opt_param("pagination_token", next_token);
+        let req = get(&url, &token, Some(&params));
+        request_with_json_response(req)
+    })
+}
+
+///Load the users who have liked the given tweet, a page at a time.
+///
+///Each item in the stream is a page of up to 100 users, loaded with the given `user::v2::Fields`;
+///the next page is loaded once the stream is polled again. If loading a page fails, the error is
+///yielded and the stream ends.
+pub fn liking_users(
+    tweet_id: u64,
+    fields: &user::v2::Fields,
+    token: &auth::Token,
+) -> impl Stream<Item = Result<user::v2::UserPage>> {
+    let url = format!("{}/{}/liking_users", links::statuses::TWEETS_V2, tweet_id);
+    let params = fields.params().add_param("max_results", "100");
+    let token = token.clone();
+    paginate(move |next_token| {
+        let params = params.clone().add_opt_param("pagination_token", next_token);
+        let req = get(&url, &token, Some(&params));
+        request_with_json_response(req)
+    })
+}
+
+field_names! {
+    ///The size of the time periods tweets are counted in, for `CountsBuilder::granularity`.
+    Granularity {
+        ///`minute`: Count the tweets posted in each minute.
+        Minute => "minute",
+        ///`hour`: Count the tweets posted in each hour. This is the default.
+        Hour => "hour",
+        ///`day`: Count the tweets posted in each day.
+        Day => "day",
+    }
+}
+
+///Begin counting the tweets that match the given search query, without loading them.
+///
+///The query uses the same operators as the rest of version 2 search, like `from:rustlang` or
+///`#rustlang -is:retweet`. The counts can be narrowed down further with the methods on the
+///returned `CountsBuilder`, before loading them with `recent` or `all`.
+pub fn counts<S: Into<CowStr>>(query: S) -> CountsBuilder {
+    CountsBuilder {
+        query: query.into(),
+        granularity: None,
+        start_time: None,
+        end_time: None,
+        since_id: None,
+        until_id: None,
+    }
+}
+
+///Represents a tweet counts query before being sent.
+#[derive(Debug, Clone)]
+#[must_use = "CountsBuilder is lazy and won't do anything unless `recent` or `all` is called"]
+pub struct CountsBuilder {
+    query: CowStr,
+    granularity: Option<Granularity>,
+    start_time: Option<chrono::DateTime<chrono::Utc>>,
+    end_time: Option<chrono::DateTime<chrono::Utc>>,
+    since_id: Option<u64>,
+    until_id: Option<u64>,
+}
+
+impl CountsBuilder {
+    ///Sets the size of the time periods tweets are counted in. The default is `Hour`.
+    pub fn granularity(self, granularity: Granularity) -> Self {
+        CountsBuilder {
+            granularity: Some(granularity),
+            ..self
+        }
+    }
+
+    ///Only counts tweets posted at or after the given time.
+    pub fn start_time(self, start_time: chrono::DateTime<chrono::Utc>) -> Self {
+        CountsBuilder {
+            start_time: Some(start_time),
+            ..self
+        }
+    }
+
+    ///Only counts tweets posted before the given time.
+    pub fn end_time(self, end_time: chrono::DateTime<chrono::Utc>) -> Self {
+        CountsBuilder {
+            end_time: Some(end_time),
+            ..self
+        }
+    }
+
+    ///Only counts tweets with higher IDs than (i.e. that were posted after) the given tweet ID.
+    pub fn since_tweet(self, since_id: u64) -> Self {
+        CountsBuilder {
+            since_id: Some(since_id),
+            ..self
+        }
+    }
+
+    ///Only counts tweets with lower IDs than (i.e. that were posted before) the given tweet ID.
+    pub fn until_tweet(self, until_id: u64) -> Self {
+        CountsBuilder {
+            until_id: Some(until_id),
+            ..self
+        }
+    }
+
+    fn params(&self) -> ParamList {
+        ParamList::new()
+            .add_param("query", self.query.clone())
+            .add_opt_param("granularity", self.granularity.map(|g| g.as_str()))
+            .add_opt_param("start_time", self.start_time.as_ref().map(time_param))
+            .add_opt_param("end_time", self.end_time.as_ref().map(time_param))
+            .add_opt_param("since_id", self.since_id.map_string())
+            .add_opt_param("until_id", self.until_id.map_string())
+    }
+
+    ///Counts the matching tweets from the last seven days.
+    pub async fn recent(&self, token: &auth::Token) -> Result<Response<TweetCounts>> {
+        let req = get(
+            links::statuses::COUNTS_RECENT_V2,
+            token,
+            Some(&self.params()),
+        );
+        request_with_json_response(req).await
+    }
+
+    ///Counts the matching tweets from the whole archive, a page at a time.
+    ///
+    ///This needs a Bearer token for a project with access to the full archive. Each item in the
+    ///stream is a page of counts, oldest period last; the next page is loaded once the stream is
+    ///polled again. If loading a page fails, the error is yielded and the stream ends.
+    pub fn all(&self, token: &auth::Token) -> impl Stream<Item = Result<Response<TweetCounts>>> {
+        let params = self.params();
+        let token = token.clone();
+        paginate(move |next_token| {
+            let params = params.clone().add_opt_param("next_token", next_token);
+            let req = get(links::statuses::COUNTS_ALL_V2, &token, Some(&params));
+            request_with_json_response(req)
+        })
+    }
+}
+
+///The number of tweets posted in one period of time, from `CountsBuilder`.
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct TweetCount {
+    ///The start of the period, inclusive.
+    pub start: chrono::DateTime<chrono::Utc>,
+    ///The end of the period, exclusive.
+    pub end: chrono::DateTime<chrono::Utc>,
+    ///The number of matching tweets posted in the period.
+    pub tweet_count: u64,
+}
+
+///The response from a `CountsBuilder`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TweetCounts {
+    ///The count for each period, in order.
+    #[serde(default)]
+    pub data: Vec<TweetCount>,
+    ///The total across every period, and the token for the next page, if there is one.
+    pub meta: CountsMeta,
+}
+
+///Describes a page of tweet counts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CountsMeta {
+    ///The total number of matching tweets across every period in this page.
+    pub total_tweet_count: u64,
+    ///For `CountsBuilder::all`, the token used to load the next page, if there is one.
+    pub next_token: Option<String>,
+}
+
+impl Paged for TweetCounts {
+    fn next_token(&self) -> Option<String> {
+        self.meta.next_token.clone()
+    }
+}
+
+///The response from `POST 2/tweets`.
+#[derive(Deserialize)]
+struct CreatedTweet {
+    data: TweetV2,
+}
+
+///The response from `DELETE 2/tweets/:id`.
+#[derive(Deserialize)]
+struct DeletedStatus {
+    data: Deleted,
+}
+
+#[derive(Deserialize)]
+struct Deleted {
+    deleted: bool,
+}
+
+///The response from `POST` or `DELETE 2/users/:id/bookmarks`.
+#[derive(Deserialize)]
+struct BookmarkStatus {
+    data: Bookmarked,
+}
+
+#[derive(Deserialize)]
+struct Bookmarked {
+    bookmarked: bool,
+}
+
+///The response from `PUT 2/tweets/:id/hidden`.
+#[derive(Deserialize)]
+struct HiddenStatus {
+    data: Hidden,
+}
+
+#[derive(Deserialize)]
+struct Hidden {
+    hidden: bool,
+}
+
+///Formats the given time the way v2 `start_time` and `end_time` parameters expect, like
+///`2021-05-26T00:00:00Z`.
+pub(crate) fn time_param(time: &chrono::DateTime<chrono::Utc>) -> String {
+    time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+///A v2 response that can be one page of a longer list.
+pub(crate) trait Paged {
+    ///Returns the token used to load the next page, if there is one.
+    fn next_token(&self) -> Option<String>;
+}
+
+///Loads every page of tweets or users from `load`, starting from the first page and following each
+///page's `next_token` until there isn't one.
+///
+///The loader is called with the pagination token for each page, or `None` for the first. If it
+///returns an error, the error is yielded and the stream ends.
+pub(crate) fn paginate<F, Fut, T>(load: F) -> impl Stream<Item = Result<Response<T>>>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = Result<Response<T>>>,
+    T: Paged,
+{
+    stream::unfold((load, Some(None)), |(mut load, next_token)| async move {
+        let next_token = next_token?;
+        match load(next_token).await {
+            Ok(page) => {
+                let next = page.next_token().map(Some);
+                Some((Ok(page), (load, next)))
+            }
+            Err(e) => Some((Err(e), (load, None))),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::load_file;
+
+    #[test]
+    fn parse_lookup() {
+        let sample = load_file("sample_payloads/v2-tweets.json");
+        let lookup: TweetLookup<Vec<TweetV2>> = serde_json::from_str(&sample).unwrap();
+
+        assert_eq!(lookup.data.len(), 2);
+        let first = &lookup.data[0];
+        assert_eq!(first.id, 1212092628029698048);
+        assert_eq!(first.author_id, Some(2244994945));
+        assert_eq!(first.conversation_id, Some(1212092628029698048));
+        assert_eq!(first.created_at.unwrap().timestamp(), 1577820376);
+        assert_eq!(first.reply_settings, Some(ReplySettings::Everyone));
+        assert_eq!(first.public_metrics.unwrap().like_count, 38);
+        assert!(first.referenced_tweets.is_empty());
+        assert_eq!(first.replied_to(), None);
+
+        let reply = &lookup.data[1];
+        assert_eq!(reply.in_reply_to_user_id, Some(2244994945));
+        assert_eq!(reply.conversation_id, Some(first.id));
+        assert_eq!(reply.replied_to(), Some(first.id));
+        assert_eq!(reply.quoted(), None);
+
+        let media_key = &first.attachments.as_ref().unwrap().media_keys[0];
+        let media = lookup.includes.media(media_key).unwrap();
+        assert!(matches!(media.media_type, MediaType::Gif));
+        assert_eq!(media.width, Some(400));
+        let author = lookup.includes.user(reply.author_id.unwrap()).unwrap();
+        assert_eq!(author.username, "Twitter");
+        assert_eq!(author.protected, Some(false));
+        assert!(lookup.includes.tweets.is_empty());
+
+        // the same objects are also hydrated into the tweets themselves
+        assert_eq!(first.author.as_ref().unwrap().username, "TwitterDev");
+        assert_eq!(first.media.len(), 1);
+        assert_eq!(first.media[0].media_key, *media_key);
+        assert_eq!(reply.author.as_ref().unwrap().username, "Twitter");
+        assert!(reply.media.is_empty());
+        // the replied-to tweet is in `data`, not `includes`, so it isn't hydrated
+        assert!(reply.referenced.is_empty());
+
+        assert_eq!(lookup.errors.len(), 1);
+        assert_eq!(lookup.errors[0].resource_id.as_deref(), Some("20"));
+        assert_eq!(lookup.errors[0].title, "Not Found Error");
+    }
+
+    #[test]
+    fn parse_metrics() {
+        let sample = r#"{
+            "data": {
+                "id": "1263145271946551300",
+                "text": "Our new docs: https://t.co/a3Ee6uEmHI",
+                "public_metrics": {"retweet_count": 2, "reply_count": 1, "like_count": 12, "quote_count": 0},
+                "non_public_metrics": {"impression_count": 956, "url_link_clicks": 31, "user_profile_clicks": 8},
+                "organic_metrics": {"impression_count": 956, "like_count": 12, "reply_count": 1, "retweet_count": 2, "url_link_clicks": 31, "user_profile_clicks": 8}
+            }
+        }"#;
+        let lookup: TweetLookup<Option<TweetV2>> = serde_json::from_str(sample).unwrap();
+        let metrics = TweetMetrics::from(lookup.data.as_ref().unwrap());
+
+        assert_eq!(metrics.id, 1263145271946551300);
+        assert_eq!(metrics.public.unwrap().like_count, 12);
+        let non_public = metrics.non_public.unwrap();
+        assert_eq!(non_public.impression_count, 956);
+        assert_eq!(non_public.url_link_clicks, Some(31));
+        assert_eq!(non_public.user_profile_clicks, 8);
+        assert_eq!(metrics.organic.unwrap().retweet_count, 2);
+
+        // tweets without links don't get a link click count
+        let sample = r#"{"data": {"id": "20", "text": "just setting up my twttr", "non_public_metrics": {"impression_count": 5, "user_profile_clicks": 0}}}"#;
+        let lookup: TweetLookup<Option<TweetV2>> = serde_json::from_str(sample).unwrap();
+        let metrics = TweetMetrics::from(lookup.data.as_ref().unwrap());
+        assert_eq!(metrics.non_public.unwrap().url_link_clicks, None);
+        assert!(metrics.organic.is_none());
+    }
+
+    #[test]
+    fn lookup_absences() {
+        let sample = r#"{
+            "data": [{"id": "1212092628029698048", "text": "hello"}],
+            "errors": [
+                {"title": "Not Found Error", "detail": "Could not find tweet with ids: [20].", "resource_id": "20", "type": "https://api.twitter.com/2/problems/resource-not-found"},
+                {"title": "Authorization Error", "detail": "Sorry, you are not authorized to see the Tweet with ids: [21].", "resource_id": "21", "type": "https://api.twitter.com/2/problems/not-authorized-for-resource"},
+                {"title": "Forbidden", "detail": "User has been suspended: [22].", "resource_id": "22", "type": "https://api.twitter.com/2/problems/resource-not-found"},
+                {"title": "Unavailable", "detail": "This Tweet has been withheld in your country.", "resource_id": "23", "type": "https://api.twitter.com/2/problems/resource-unavailable"},
+                {"title": "Service Unavailable", "detail": "Try again later.", "resource_id": "24", "type": "https://api.twitter.com/2/problems/service-unavailable"}
+            ]
+        }"#;
+        let lookup: TweetLookup<Vec<TweetV2>> = serde_json::from_str(sample).unwrap();
+        let map = map_lookup(&[1212092628029698048, 20, 21, 22, 23, 24, 25], lookup);
+
+        assert_eq!(map.len(), 7);
+        assert_eq!(map[&1212092628029698048].as_ref().unwrap().text, "hello");
+        assert!(matches!(map[&20], Err(Absence::Deleted)));
+        assert!(matches!(map[&21], Err(Absence::Protected)));
+        assert!(matches!(map[&22], Err(Absence::Suspended)));
+        assert!(matches!(map[&23], Err(Absence::Withheld)));
+        assert!(
+            matches!(map[&24], Err(Absence::Unknown(Some(ref p))) if p.title == "Service Unavailable")
+        );
+        assert!(matches!(map[&25], Err(Absence::Unknown(None))));
+    }
+
+    #[test]
+    fn parse_missing_tweet() {
+        let sample = r#"{"errors":[{"title":"Not Found Error","detail":"Could not find tweet with id: [20].","resource_id":"20","type":"https://api.twitter.com/2/problems/resource-not-found"}]}"#;
+        let lookup: TweetLookup<Option<TweetV2>> = serde_json::from_str(sample).unwrap();
+        assert!(lookup.data.is_none());
+        assert_eq!(lookup.errors.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn paginate_follows_next_token() {
+        use futures::StreamExt;
+
+        let sample = load_file("sample_payloads/v2-tweets.json");
+        let mut seen = Vec::new();
+        let pages = paginate(|next_token: Option<String>| {
+            seen.push(next_token.clone());
+            let mut page: TweetLookup<Vec<TweetV2>> = serde_json::from_str(&sample).unwrap();
+            page.meta.next_token = match next_token.as_deref() {
+                None => Some("page 2".to_string()),
+                Some("page 2") => Some("page 3".to_string()),
+                _ => None,
+            };
+            let rate = RateLimit {
+                limit: 180,
+                remaining: 179,
+                reset: 0,
+            };
+            futures::future::ready(Ok(Response::new(rate, page)))
+        });
+        let pages = pages.collect::<Vec<_>>().await;
+
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[2].as_ref().unwrap().data.len(), 2);
+        assert_eq!(
+            seen,
+            vec![None, Some("page 2".to_string()), Some("page 3".to_string())]
+        );
+    }
+
+    #[test]
+    fn parse_counts() {
+        let sample = load_file("sample_payloads/v2-tweet-counts.json");
+        let page: TweetCounts = serde_json::from_str(&sample).unwrap();
+
+        assert_eq!(page.data.len(), 3);
+        assert_eq!(page.meta.total_tweet_count, 2181);
+        assert_eq!(page.data[1].tweet_count, 934);
+        assert_eq!(page.data[1].start.timestamp(), 1622073600);
+        assert_eq!(page.data[1].end.timestamp(), 1622160000);
+        assert_eq!(
+            page.next_token().as_deref(),
+            Some("1jzu9lk96gu5npw1z5v0ctmt8een")
+        );
+
+        let start = "2021-05-26T00:00:00.000Z".parse().unwrap();
+        let params = counts("#rustlang")
+            .granularity(Granularity::Day)
+            .start_time(start)
+            .since_tweet(1234)
+            .params();
+        assert_eq!(params.get("query").map(|v| v.as_ref()), Some("#rustlang"));
+        assert_eq!(params.get("granularity").map(|v| v.as_ref()), Some("day"));
+        assert_eq!(
+            params.get("start_time").map(|v| v.as_ref()),
+            Some("2021-05-26T00:00:00Z")
+        );
+        assert_eq!(params.get("since_id").map(|v| v.as_ref()), Some("1234"));
+        assert!(params.get("end_time").is_none());
+    }
+
+    #[test]
+    fn poll_limits() {
+        let options = |opts: &[&'static str]| opts.iter().map(|&o| o.into()).collect::<Vec<_>>();
+        let two = options(&["Tabs", "Spaces"]);
+
+        assert!(check_poll(&two, 5, false).is_ok());
+        assert!(check_poll(&two, 10080, false).is_ok());
+        assert!(check_poll(&options(&["a", "b", "c", "d"]), 60, false).is_ok());
+        // 25 characters, but more than 25 bytes
+        assert!(check_poll(&options(&["ééééééééééééééééééééééééé", "b"]), 60, false).is_ok());
+
+        let invalid = |result: Result<()>| matches!(result, Err(Error::InvalidPoll(_)));
+        assert!(invalid(check_poll(&options(&["Tabs"]), 60, false)));
+        assert!(invalid(check_poll(
+            &options(&["a", "b", "c", "d", "e"]),
+            60,
+            false
+        )));
+        assert!(invalid(check_poll(&options(&["", "Spaces"]), 60, false)));
+        assert!(invalid(check_poll(
+            &options(&["Tabs", "Spaces, as many as you need"]),
+            60,
+            false
+        )));
+        assert!(invalid(check_poll(&two, 4, false)));
+        assert!(invalid(check_poll(&two, 10081, false)));
+        assert!(invalid(check_poll(&two, 60, true)));
+    }
+
+    #[test]
+    fn media_tags() {
+        use crate::media::{MediaCategory, MediaId};
+
+        let photo = MediaId::from("710511363345354753".to_string());
+        let video = MediaId("710511363345354754".to_string(), Some(MediaCategory::Video));
+        let invalid = |result: Result<()>| matches!(result, Err(Error::InvalidMediaTags(_)));
+
+        let both = vec![photo.clone(), video];
+        assert!(check_media_tags(10, &both[..1]).is_ok());
+        assert!(invalid(check_media_tags(11, &both[..1])));
+        assert!(invalid(check_media_tags(1, &[])));
+        assert!(invalid(check_media_tags(1, &both)));
+
+        let mut draft = DraftTweetV2::new("look who it is").tag_users(vec![783214, 2244994945]);
+        draft.add_media(photo);
+        assert_eq!(
+            draft.body()["media"],
+            serde_json::json!({
+                "media_ids": ["710511363345354753"],
+                "tagged_user_ids": ["783214", "2244994945"],
+            })
+        );
+    }
+
+    #[test]
+    fn draft_body() {
+        let body = DraftTweetV2::new("Which do you use more?")
+            .poll(vec!["Iterators", "for loops"], 1440)
+            .reply_settings(ReplySettings::MentionedUsers)
+            .quote_tweet(1293595870563381249)
+            .body();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "text": "Which do you use more?",
+                "poll": {"options": ["Iterators", "for loops"], "duration_minutes": 1440},
+                "reply_settings": "mentionedUsers",
+                "quote_tweet_id": "1293595870563381249",
+            })
+        );
+
+        let mut draft = DraftTweetV2::new("thanks!")
+            .in_reply_to(1212092628029698048)
+            .exclude_reply_user_ids(vec![783214])
+            .reply_settings(ReplySettings::Everyone);
+        draft.add_media(media::MediaId::from("710511363345354753".to_string()));
+        assert_eq!(
+            draft.body(),
+            serde_json::json!({
+                "text": "thanks!",
+                "reply": {
+                    "in_reply_to_tweet_id": "1212092628029698048",
+                    "exclude_reply_user_ids": ["783214"],
+                },
+                "media": {"media_ids": ["710511363345354753"]},
+            })
+        );
+
+        let created: CreatedTweet =
+            serde_json::from_str(r#"{"data":{"id":"1445880548472328192","text":"hi"}}"#).unwrap();
+        assert_eq!(created.data.id, 1445880548472328192);
+        let deleted: DeletedStatus = serde_json::from_str(r#"{"data":{"deleted":true}}"#).unwrap();
+        assert!(deleted.data.deleted);
+    }
+
+    #[test]
+    fn super_followers() {
+        use hyper::StatusCode;
+
+        let body = DraftTweetV2::new("just for you")
+            .for_super_followers(true)
+            .body();
+        assert_eq!(
+            body,
+            serde_json::json!({"text": "just for you", "for_super_followers_only": true})
+        );
+
+        let rejected = br#"{"detail":"You are not permitted to create a Super Follows Tweet.","type":"about:blank","title":"Forbidden","status":403}"#;
+        assert!(matches!(
+            super_follows_problem(StatusCode::FORBIDDEN, rejected),
+            Some(Error::SuperFollowsNotAllowed)
+        ));
+
+        // other 403s keep their usual error
+        let duplicate = br#"{"detail":"You are not allowed to create a Tweet with duplicate content.","type":"about:blank","title":"Forbidden","status":403}"#;
+        assert!(super_follows_problem(StatusCode::FORBIDDEN, duplicate).is_none());
+        assert!(super_follows_problem(StatusCode::FORBIDDEN, b"").is_none());
+        assert!(super_follows_problem(StatusCode::UNAUTHORIZED, rejected).is_none());
+    }
+
+    #[test]
+    fn parse_bookmark_status() {
+        let status: BookmarkStatus =
+            serde_json::from_str(r#"{"data":{"bookmarked":true}}"#).unwrap();
+        assert!(status.data.bookmarked);
+
+        let status: HiddenStatus = serde_json::from_str(r#"{"data":{"hidden":false}}"#).unwrap();
+        assert!(!status.data.hidden);
+
+        let sample = r#"{"data":[],"meta":{"result_count":0}}"#;
+        let page: TweetLookup<Vec<TweetV2>> = serde_json::from_str(sample).unwrap();
+        assert_eq!(page.meta.result_count, 0);
+        assert!(page.meta.next_token.is_none());
+    }
+
+    #[test]
+    fn hydrate_includes() {
+        let sample = load_file("sample_payloads/v2-tweets-hydrated.json");
+        let lookup: TweetLookup<Vec<TweetV2>> = serde_json::from_str(&sample).unwrap();
+        let tweet = &lookup.data[0];
+
+        assert_eq!(tweet.author.as_ref().unwrap().username, "Twitter");
+
+        let poll = tweet.poll.as_ref().unwrap();
+        assert_eq!(poll.options.len(), 2);
+        assert_eq!(poll.options[1].label, "for loops");
+        assert_eq!(poll.options[1].votes, 800);
+        assert_eq!(poll.voting_status.as_deref(), Some("closed"));
+
+        let place = tweet.place.as_ref().unwrap();
+        assert_eq!(place.full_name, "Manhattan, NY");
+        assert_eq!(place.country_code.as_deref(), Some("US"));
+
+        // the deleted reply target isn't in `includes`, so only the quoted tweet is hydrated
+        assert_eq!(tweet.referenced_tweets.len(), 2);
+        assert_eq!(tweet.referenced.len(), 1);
+        let quoted = &tweet.referenced[0];
+        assert_eq!(Some(quoted.id), tweet.quoted());
+        assert_eq!(quoted.author.as_ref().unwrap().username, "TwitterDev");
+        assert_eq!(quoted.media.len(), 1);
+        // the included tweets themselves are left as Twitter sent them
+        assert!(lookup.includes.tweets[0].author.is_none());
+    }
+
+    #[test]
+    fn field_params() {
+        assert!(Fields::new().params().is_empty());
+
+        let fields = Fields::new()
+            .tweet_fields(vec![TweetField::CreatedAt, TweetField::PublicMetrics])
+            .expansions(vec![Expansion::AuthorId, Expansion::AttachmentsMediaKeys])
+            .media_fields(vec![MediaField::Url])
+            .tweet_fields(vec![TweetField::CreatedAt, TweetField::Lang]);
+        let params = fields.params();
+        assert_eq!(
+            params.get("tweet.fields").map(|v| v.as_ref()),
+            Some("created_at,public_metrics,lang")
+        );
+        assert_eq!(
+            params.get("expansions").map(|v| v.as_ref()),
+            Some("author_id,attachments.media_keys")
+        );
+        assert_eq!(params.get("media.fields").map(|v| v.as_ref()), Some("url"));
+        assert!(params.get("user.fields").is_none());
+
+        let params = Fields::new()
+            .poll_fields(vec![PollField::VotingStatus])
+            .place_fields(vec![PlaceField::Country, PlaceField::Name])
+            .params();
+        assert_eq!(
+            params.get("poll.fields").map(|v| v.as_ref()),
+            Some("voting_status")
+        );
+        assert_eq!(
+            params.get("place.fields").map(|v| v.as_ref()),
+            Some("country,name")
+        );
+    }
+}
@@ -90,6 +90,32 @@ where
     request_with_json_response(req).await
 }
 
+/// Lookup user information for any number of users, loading them 100 at a time.
+///
+/// `lookup` can only load 100 users in one call. This function splits the given users into chunks
+/// of 100 and loads each of them with `lookup`, running up to `concurrency` calls at once. Users
+/// given by ID and by screen name can be mixed, as with `lookup`, and any users that couldn't be
+/// loaded are left out. The users from every chunk are returned together, in the same order as
+/// the chunks.
+///
+/// Since all of these calls draw from the same rate limit, if any of them is rate-limited, all of
+/// them wait for the limit to reset before trying again. Any other error ends the lookup and is
+/// returned. The rate-limit information in the returned `Response` is from the last chunk.
+pub async fn lookup_all<T, I>(
+    accts: I,
+    concurrency: usize,
+    token: &auth::Token,
+) -> Result<Response<Vec<TwitterUser>>>
+where
+    T: Into<UserID>,
+    I: IntoIterator<Item = T>,
+{
+    let accts = accts.into_iter().map(Into::into).collect::<Vec<UserID>>();
+    let idempotent = is_idempotent(&hyper::Method::POST, links::users::LOOKUP);
+    let load = |chunk| lookup(chunk, token);
+    lookup_chunked(accts, 100, concurrency, idempotent, load).await
+}
+
 /// Lookup user information for a single user.
 pub async fn show<T: Into<UserID>>(acct: T, token: &auth::Token) -> Result<Response<TwitterUser>> {
     let params = ParamList::new()
@@ -230,6 +256,45 @@ pub fn followers_ids<T: Into<UserID>>(
     cursor::CursorIter::new(links::users::FOLLOWERS_IDS, token, Some(params), Some(500))
 }
 
+/// Lookup the accounts that both of the given users follow, and return their user IDs.
+///
+/// This loads every account each user follows with `friends_ids`, 5000 IDs at a time, and returns
+/// the IDs that show up in both lists. They're returned in the order they appeared in `a`'s list,
+/// which Twitter gives with the most recent follows first.
+///
+/// Since this has to load both users' complete lists, it can take a lot of calls for accounts that
+/// follow a lot of people: `friends/ids` only allows 15 calls per rate-limit window, so two users
+/// who both follow more than 37,500 accounts will run out in a single call to this function. If
+/// that's a concern, use `mutual_friends_up_to` to stop after a certain number of IDs. If Twitter
+/// says the rate limit has been reached, that error is returned.
+pub async fn mutual_friends<A: Into<UserID>, B: Into<UserID>>(
+    a: A,
+    b: B,
+    token: &auth::Token,
+) -> Result<Vec<u64>> {
+    mutual_friends_up_to(a, b, usize::MAX, token).await
+}
+
+/// Lookup the accounts that both of the given users follow, only looking at the first `limit`
+/// accounts each of them follows, and return their user IDs.
+///
+/// This works the same way as `mutual_friends`, except that it stops loading each user's list
+/// after `limit` IDs. Since Twitter lists the most recent follows first, this means only recent
+/// follows are compared.
+pub async fn mutual_friends_up_to<A: Into<UserID>, B: Into<UserID>>(
+    a: A,
+    b: B,
+    limit: usize,
+    token: &auth::Token,
+) -> Result<Vec<u64>> {
+    intersect_ids(
+        friends_ids(a, token).with_page_size(5000),
+        friends_ids(b, token).with_page_size(5000),
+        limit,
+    )
+    .await
+}
+
 /// Lookup the users that have been blocked by the authenticated user.
 ///
 /// Note that while loading a user's blocks list is a cursored search, it does not allow you to set
@@ -418,3 +483,34 @@ pub async fn unmute<T: Into<UserID>>(
     let req = post(links::users::UNMUTE, token, Some(&params));
     request_with_json_response(req).await
 }
+
+/// Begins updating the authenticated user's profile.
+///
+/// This method is exposed using a builder struct. See the [`ProfileUpdate`] docs for details.
+///
+/// [`ProfileUpdate`]: struct.ProfileUpdate.html
+pub fn update_profile() -> ProfileUpdate {
+    ProfileUpdate::default()
+}
+
+/// Updates the authenticated user's profile, but only if the given update would change something.
+///
+/// The fields set in `desired` are compared against `current`, which should be the authenticated
+/// user's profile as recently loaded from Twitter. Fields that already match are left out of the
+/// update (see [`ProfileUpdate::changes_from`] for how they're compared), so that they aren't
+/// rewritten needlessly. If nothing would change, this returns `None` without calling Twitter at
+/// all. Otherwise, the remaining fields are sent, and this returns the updated user.
+///
+/// [`ProfileUpdate::changes_from`]: struct.ProfileUpdate.html#method.changes_from
+pub async fn update_profile_if_changed(
+    current: &TwitterUser,
+    desired: ProfileUpdate,
+    token: &auth::Token,
+) -> Result<Option<Response<TwitterUser>>> {
+    let update = desired.changes_from(current);
+    if update.is_empty() {
+        Ok(None)
+    } else {
+        update.send(token).await.map(Some)
+    }
+}
@@ -20,6 +20,8 @@
 //!   these types (`TwitterUser` contains the other two) describe the content of a user's profile,
 //!   and a handful of settings relating to how their profile is displayed.
 //! - `UserSearch`: returned by `search`, this is a stream of search results.
+//! - `ProfileUpdate`: used by `update_profile` and `update_profile_if_changed`, this builder
+//!   collects the profile fields that the authenticated user wants to change.
 //!
 //! ## Functions
 //!
@@ -31,6 +33,7 @@
 //! - `block`/`report_spam`/`unblock`
 //! - `follow`/`unfollow`/`update_follow`
 //! - `mute`/`unmute`
+//! - `update_profile`/`update_profile_if_changed` (see `ProfileUpdate` for full details)
 //!
 //! ### Direct lookup
 //!
@@ -38,9 +41,16 @@
 //! results.
 //!
 //! - `show`
-//! - `lookup`/`lookup_ids`/`lookup_names`
+//! - `lookup`/`lookup_ids`/`lookup_names` (`lookup_all` loads more than 100 users at once)
 //! - `friends_no_retweets`
 //! - `relation`/`relation_lookup`
+//! - `mutual_friends`/`mutual_friends_up_to` (these page through `friends_ids` for both users, so
+//!   see their docs for how many calls they can make)
+//!
+//! The [`v2`] submodule also has `show` and `lookup_ids`/`lookup_names` functions, which load
+//! users through version 2 of the Twitter API instead, with fields like `pinned_tweet_id`.
+//!
+//! [`v2`]: v2/index.html
 //!
 //! ### Cursored lookup
 //!
@@ -55,13 +65,14 @@
 //! - `mutes`/`mutes_ids`
 //! - `incoming_requests`/`outgoing_requests`
 
+use std::collections::HashSet;
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 use std::vec::IntoIter as VecIter;
 
 use chrono;
-use futures::Stream;
+use futures::{Stream, StreamExt, TryStreamExt};
 use serde::{Deserialize, Serialize};
 
 use crate::common::*;
@@ -69,6 +80,7 @@ use crate::{auth, entities, error, links, tweet};
 
 mod fun;
 mod raw;
+pub mod v2;
 
 pub use self::fun::*;
 
@@ -128,7 +140,7 @@ round_trip! { raw::RawTwitterUser,
     /// * `id`
     /// * `screen_name`
     /// * `name`
-    /// * `verified`
+    /// * `verified`/`verified_type`
     /// * `protected`
     /// * `description`
     /// * `location`
@@ -289,6 +301,12 @@ round_trip! { raw::RawTwitterUser,
         pub utc_offset: Option<i32>,
         /// Indicates whether this user is a verified account.
         pub verified: bool,
+        /// The kind of verification this user has, if Twitter said.
+        ///
+        /// This is only included in responses from Twitter's v2 API, and from v1.1 responses that
+        /// have been updated to include it. If it's missing, `verified` still says whether the user
+        /// is verified.
+        pub verified_type: Option<VerifiedType>,
         /// When present, lists the countries this user has been withheld from.
         pub withheld_in_countries: Option<Vec<String>>,
         /// When present, indicates whether the content being withheld is a "status" or "user".
@@ -351,12 +369,32 @@ impl From<raw::RawTwitterUser> for TwitterUser {
             url: raw.url,
             utc_offset: raw.utc_offset,
             verified: raw.verified,
+            verified_type: raw.verified_type,
             withheld_in_countries: raw.withheld_in_countries,
             withheld_scope: raw.withheld_scope,
         }
     }
 }
 
+/// Represents the kind of verification a user has, as given in `TwitterUser::verified_type`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VerifiedType {
+    /// The user is verified through a Twitter Blue subscription.
+    Blue,
+    /// The user is verified as a business or organization.
+    Business,
+    /// The user is verified as a government or multilateral organization, or an official
+    /// associated with one.
+    Government,
+    /// The user isn't verified.
+    None,
+    /// A kind of verification egg-mode doesn't know about yet. Twitter has added new kinds before,
+    /// so this keeps users with them from failing to load.
+    #[serde(other)]
+    Unknown,
+}
+
 /// Container for URL entity information that may be paired with a user's profile.
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct UserEntities {
@@ -381,6 +419,156 @@ pub struct UserEntityDetail {
     pub urls: Vec<entities::UrlEntity>,
 }
 
+/// Represents a pending update to the authenticated user's profile.
+///
+/// This struct is returned by [`update_profile`][] and can be sent directly with `send`, or
+/// compared against the user's current profile with [`update_profile_if_changed`][]. Every field
+/// is optional; fields that aren't set are left alone by Twitter. To clear a field, set it to an
+/// empty string.
+///
+/// [`update_profile`]: fn.update_profile.html
+/// [`update_profile_if_changed`]: fn.update_profile_if_changed.html
+///
+/// # Example
+///
+/// ```rust,no_run
+/// # use egg_mode::Token;
+/// # #[tokio::main]
+/// # async fn main() {
+/// # let token: Token = unimplemented!();
+/// use egg_mode::user;
+///
+/// let user = user::update_profile()
+///     .description("writing rust, mostly")
+///     .location("Portland, OR")
+///     .send(&token)
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ProfileUpdate {
+    name: Option<String>,
+    url: Option<String>,
+    location: Option<String>,
+    description: Option<String>,
+    link_color: Option<String>,
+}
+
+impl ProfileUpdate {
+    /// Updates the display name of the user. Twitter limits this to 50 characters.
+    pub fn name(self, name: impl Into<String>) -> ProfileUpdate {
+        ProfileUpdate {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Updates the website link of the user. Twitter limits this to 100 characters.
+    pub fn url(self, url: impl Into<String>) -> ProfileUpdate {
+        ProfileUpdate {
+            url: Some(url.into()),
+            ..self
+        }
+    }
+
+    /// Updates the location field of the user. Twitter limits this to 30 characters.
+    pub fn location(self, location: impl Into<String>) -> ProfileUpdate {
+        ProfileUpdate {
+            location: Some(location.into()),
+            ..self
+        }
+    }
+
+    /// Updates the description, or bio, of the user. Twitter limits this to 160 characters.
+    pub fn description(self, description: impl Into<String>) -> ProfileUpdate {
+        ProfileUpdate {
+            description: Some(description.into()),
+            ..self
+        }
+    }
+
+    /// Updates the color used for links in the Twitter UI, as a hex code like `1DA1F2`.
+    pub fn link_color(self, link_color: impl Into<String>) -> ProfileUpdate {
+        ProfileUpdate {
+            link_color: Some(link_color.into()),
+            ..self
+        }
+    }
+
+    /// Returns whether this update doesn't set any fields.
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.url.is_none()
+            && self.location.is_none()
+            && self.description.is_none()
+            && self.link_color.is_none()
+    }
+
+    /// Returns a copy of this update with any fields removed that already match the given user.
+    ///
+    /// Twitter replaces links in a user's description and website with t.co links, so these
+    /// fields are compared against the expanded links instead. Link colors are compared without
+    /// regard to case or a leading `#`. A field that's missing from the user's profile is treated
+    /// as matching an empty string.
+    pub fn changes_from(self, current: &TwitterUser) -> ProfileUpdate {
+        fn keep(desired: Option<String>, current: &str) -> Option<String> {
+            desired.filter(|desired| desired != current)
+        }
+
+        let url = current
+            .entities
+            .url
+            .as_ref()
+            .and_then(|url| url.urls.first())
+            .and_then(|entity| entity.expanded_url.as_deref())
+            .or(current.url.as_deref())
+            .unwrap_or("");
+
+        let mut description = current.description.clone().unwrap_or_default();
+        for entity in &current.entities.description.urls {
+            if let Some(ref expanded) = entity.expanded_url {
+                description = description.replace(&entity.url, expanded);
+            }
+        }
+
+        fn color(color: &str) -> String {
+            color.trim_start_matches('#').to_ascii_uppercase()
+        }
+
+        ProfileUpdate {
+            name: keep(self.name, &current.name),
+            url: keep(self.url, url),
+            location: keep(self.location, current.location.as_deref().unwrap_or("")),
+            description: keep(self.description, &description),
+            link_color: self
+                .link_color
+                .filter(|desired| color(desired) != color(&current.profile_link_color)),
+        }
+    }
+
+    fn params(&self) -> ParamList {
+        ParamList::new()
+            .extended_tweets()
+            .add_opt_param("name", self.name.clone())
+            .add_opt_param("url", self.url.clone())
+            .add_opt_param("location", self.location.clone())
+            .add_opt_param("description", self.description.clone())
+            .add_opt_param(
+                "profile_link_color",
+                self.link_color
+                    .as_ref()
+                    .map(|color| color.trim_start_matches('#').to_string()),
+            )
+    }
+
+    /// Sends the update to Twitter, returning the updated user.
+    pub async fn send(self, token: &auth::Token) -> Result<Response<TwitterUser>, error::Error> {
+        let req = post(links::users::UPDATE_PROFILE, token, Some(&self.params()));
+        request_with_json_response(req).await
+    }
+}
+
 /// Represents an active user search.
 ///
 /// This struct is returned by [`search`][] and is meant to be used as a `Stream`. That means all
@@ -684,9 +872,27 @@ pub enum Connection {
     Muting,
 }
 
+/// Helper function for `mutual_friends` that collects up to `limit` IDs from each of the given
+/// streams, and returns the ones in `a` that are also in `b`.
+async fn intersect_ids<A, B>(a: A, b: B, limit: usize) -> error::Result<Vec<u64>>
+where
+    A: Stream<Item = error::Result<Response<u64>>>,
+    B: Stream<Item = error::Result<Response<u64>>>,
+{
+    let (a, b) = futures::try_join!(
+        a.take(limit)
+            .map_ok(|id| id.response)
+            .try_collect::<Vec<_>>(),
+        b.take(limit)
+            .map_ok(|id| id.response)
+            .try_collect::<HashSet<_>>(),
+    )?;
+    Ok(a.into_iter().filter(|id| b.contains(id)).collect())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::TwitterUser;
+    use super::{intersect_ids, ProfileUpdate, TwitterUser, VerifiedType};
     use crate::common::tests::load_file;
 
     #[test]
@@ -699,4 +905,141 @@ mod tests {
 
         assert_eq!(json1, json2);
     }
+
+    #[test]
+    fn verified_types() {
+        let sample = load_file("sample_payloads/user_array.json");
+        let users: Vec<TwitterUser> = serde_json::from_str(&sample).unwrap();
+        // legacy v1.1 users say whether they're verified, but not how
+        assert_eq!(users[0].screen_name, "Twitter");
+        assert!(users[0].verified);
+        assert_eq!(users[0].verified_type, None);
+        assert!(!users[3].verified);
+        assert_eq!(users[3].verified_type, None);
+
+        let sample = load_file("sample_payloads/user-verified-type.json");
+        let user: TwitterUser = serde_json::from_str(&sample).unwrap();
+        assert!(user.verified);
+        assert_eq!(user.verified_type, Some(VerifiedType::Business));
+
+        let json = serde_json::to_value(&user).unwrap();
+        assert_eq!(json["verified_type"], "business");
+        let roundtrip: TwitterUser = serde_json::from_value(json).unwrap();
+        assert_eq!(roundtrip.verified_type, Some(VerifiedType::Business));
+
+        let mut json = serde_json::to_value(&user).unwrap();
+        json["verified_type"] = "celebrity".into();
+        let unknown: TwitterUser = serde_json::from_value(json).unwrap();
+        assert_eq!(unknown.verified_type, Some(VerifiedType::Unknown));
+    }
+
+    #[tokio::test]
+    async fn mutual_ids() {
+        use crate::common::{RateLimit, Response};
+        use crate::error::Error;
+        use futures::stream;
+
+        let ids = |ids: Vec<u64>| {
+            let rate_limit = RateLimit {
+                limit: 15,
+                remaining: 14,
+                reset: 0,
+            };
+            stream::iter(
+                ids.into_iter()
+                    .map(move |id| Ok(Response::new(rate_limit, id))),
+            )
+        };
+        let a = vec![50, 7, 42, 3, 18, 99, 1];
+        let b = vec![1, 2, 3, 4, 5, 42, 100];
+
+        let mutual = intersect_ids(ids(a.clone()), ids(b.clone()), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(mutual, vec![42, 3, 1]);
+
+        // with a cap, only the first few IDs of each list are compared
+        let mutual = intersect_ids(ids(a.clone()), ids(b), 4).await.unwrap();
+        assert_eq!(mutual, vec![3]);
+
+        let mutual = intersect_ids(ids(a.clone()), ids(vec![]), usize::MAX)
+            .await
+            .unwrap();
+        assert!(mutual.is_empty());
+
+        let limited = stream::iter(vec![Err(Error::RateLimit(0))]);
+        let err = intersect_ids(ids(a), limited, usize::MAX)
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::RateLimit(0)));
+    }
+
+    fn sample_user() -> TwitterUser {
+        let sample = load_file("sample_payloads/user_array.json");
+        let mut users: Vec<TwitterUser> = serde_json::from_str(&sample).unwrap();
+        users.remove(0)
+    }
+
+    #[tokio::test]
+    async fn profile_unchanged() {
+        let user = sample_user();
+        let mut desired = ProfileUpdate::default()
+            .name(user.name.clone())
+            .location(user.location.clone().unwrap_or_default())
+            .link_color(format!("#{}", user.profile_link_color.to_lowercase()));
+        if let Some(ref description) = user.description {
+            desired = desired.description(description.clone());
+        }
+
+        assert!(desired.clone().changes_from(&user).is_empty());
+
+        // there's nothing to send, so this returns without trying to reach Twitter
+        let token = crate::auth::Token::Bearer("bearer".to_string());
+        let resp = super::update_profile_if_changed(&user, desired, &token).await;
+        assert!(resp.unwrap().is_none());
+    }
+
+    #[test]
+    fn profile_bio_changed() {
+        let user = sample_user();
+        let desired = ProfileUpdate::default()
+            .name(user.name.clone())
+            .description("a brand new bio");
+
+        let update = desired.changes_from(&user);
+        assert!(!update.is_empty());
+        assert_eq!(update.name, None);
+        assert_eq!(update.description.as_deref(), Some("a brand new bio"));
+
+        let params = update.params();
+        assert_eq!(
+            params.get("description").map(|v| v.as_ref()),
+            Some("a brand new bio")
+        );
+        assert!(params.get("name").is_none());
+    }
+
+    #[test]
+    fn profile_expanded_links() {
+        let mut user = sample_user();
+        user.url = Some("https://t.co/abc123".to_string());
+        user.description = Some("i write https://t.co/def456 sometimes".to_string());
+        let entity = |url: &str, expanded: &str| crate::entities::UrlEntity {
+            display_url: expanded.to_string(),
+            expanded_url: Some(expanded.to_string()),
+            range: (0, 0),
+            url: url.to_string(),
+        };
+        user.entities.url = Some(super::UserEntityDetail {
+            urls: vec![entity("https://t.co/abc123", "https://example.com")],
+        });
+        user.entities.description.urls =
+            vec![entity("https://t.co/def456", "https://blog.example.com")];
+
+        let update = ProfileUpdate::default()
+            .url("https://example.com")
+            .description("i write https://blog.example.com sometimes")
+            .changes_from(&user);
+        assert!(update.is_empty());
+    }
 }
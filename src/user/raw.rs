@@ -8,7 +8,7 @@ use crate::tweet;
 use chrono;
 use serde::Deserialize;
 
-use super::UserEntities;
+use super::{UserEntities, VerifiedType};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct RawTwitterUser {
@@ -140,6 +140,8 @@ pub struct RawTwitterUser {
     pub utc_offset: Option<i32>,
     /// Indicates whether this user is a verified account.
     pub verified: bool,
+    /// The kind of verification this user has, if Twitter said.
+    pub verified_type: Option<VerifiedType>,
     /// When present, lists the countries this user has been withheld from.
     pub withheld_in_countries: Option<Vec<String>>,
     /// When present, indicates whether the content being withheld is a "status" or "user".
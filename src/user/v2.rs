@@ -0,0 +1,498 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! User lookup through version 2 of the Twitter API.
+//!
+//! Like the tweets in [`tweet::v2`], a `UserV2` only carries a user's ID, name, and username by
+//! default. Any other fields, as well as the user's pinned tweet, need to be asked for with a
+//! `Fields` builder. These functions work with both Bearer tokens and OAuth 2.0 user tokens, as
+//! well as Access tokens.
+//!
+//! [`tweet::v2`]: ../../tweet/v2/index.html
+//!
+//! ```rust,no_run
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let token: egg_mode::Token = unimplemented!();
+//! use egg_mode::tweet::v2::TweetField;
+//! use egg_mode::user::v2::{self, Fields, UserField};
+//!
+//! let fields = Fields::new()
+//!     .user_fields(vec![UserField::CreatedAt, UserField::PublicMetrics])
+//!     .pinned_tweet(vec![TweetField::CreatedAt]);
+//! let lookup = v2::show("TwitterDev", &fields, &token).await.unwrap();
+//! if let Some(user) = &lookup.data {
+//!     let followers = user.public_metrics.map(|m| m.followers_count);
+//!     println!("@{} has {:?} followers", user.username, followers);
+//!     if let Some(pinned) = &user.pinned_tweet {
+//!         println!("pinned: {}", pinned.text);
+//!     }
+//! }
+//! # }
+//! ```
+//!
+//! Users that couldn't be loaded don't make the whole call fail. Instead, they're left out of
+//! `data`, and a `Problem` describing each one is given in `errors`.
+//!
+//! This module also has version 2 equivalents of the `follow`/`unfollow`, `block`/`unblock`, and
+//! `mute`/`unmute` actions from the [`user`] module. These need the ID of the authenticated user
+//! as well as the user to act on, and a token for the authenticated user: either an Access token,
+//! or an OAuth 2.0 user token with the `follows.write`, `block.write`, or `mute.write` scope.
+//!
+//! [`user`]: ../index.html
+
+use serde::Deserialize;
+
+use crate::common::*;
+use crate::error::Result;
+use crate::tweet::v2::{Hydrate, TweetField, TweetV2};
+use crate::{auth, links};
+
+use super::UserID;
+
+field_names! {
+    /// Optional fields of a user that can be requested with `Fields::user_fields`.
+    UserField {
+        /// `created_at`: When the account was created.
+        CreatedAt => "created_at",
+        /// `description`: The user's profile description.
+        Description => "description",
+        /// `location`: The location given in the user's profile.
+        Location => "location",
+        /// `pinned_tweet_id`: The ID of the tweet pinned to the user's profile.
+        PinnedTweetId => "pinned_tweet_id",
+        /// `profile_image_url`: The URL of the user's profile image.
+        ProfileImageUrl => "profile_image_url",
+        /// `protected`: Whether the user's tweets are protected.
+        Protected => "protected",
+        /// `public_metrics`: The user's follower, following, tweet, and listed counts.
+        PublicMetrics => "public_metrics",
+        /// `url`: The URL given in the user's profile.
+        Url => "url",
+        /// `verified`: Whether the user is verified.
+        Verified => "verified",
+    }
+}
+
+/// The fields to ask for when loading users.
+///
+/// By default, Twitter only returns each user's `id`, `name`, and `username`, and no `includes`.
+/// Each method here adds to the set of fields requested, so they can be called more than once.
+#[derive(Debug, Clone, Default)]
+pub struct Fields {
+    user_fields: Vec<UserField>,
+    tweet_fields: Vec<TweetField>,
+    pinned_tweet: bool,
+}
+
+impl Fields {
+    /// Creates an empty set of fields, which loads each user's `id`, `name`, and `username` only.
+    pub fn new() -> Fields {
+        Fields::default()
+    }
+
+    /// Asks for the given fields of each user.
+    pub fn user_fields(mut self, fields: impl IntoIterator<Item = UserField>) -> Fields {
+        self.user_fields.extend(fields);
+        self
+    }
+
+    /// Asks for each user's pinned tweet to be loaded into `includes`, with the given fields.
+    ///
+    /// This also asks for `UserField::PinnedTweetId`, so the pinned tweet can be matched up with
+    /// its user.
+    pub fn pinned_tweet(mut self, fields: impl IntoIterator<Item = TweetField>) -> Fields {
+        self.pinned_tweet = true;
+        self.user_fields.push(UserField::PinnedTweetId);
+        self.tweet_fields.extend(fields);
+        self
+    }
+
+    /// Builds the query parameters for these fields.
+    pub(crate) fn params(&self) -> ParamList {
+        let user_fields = self.user_fields.iter().map(|f| f.as_str());
+        let tweet_fields = self.tweet_fields.iter().map(|f| f.as_str());
+        let expansions = if self.pinned_tweet {
+            Some("pinned_tweet_id")
+        } else {
+            None
+        };
+        ParamList::new()
+            .add_opt_param("user.fields", join_field_names(user_fields))
+            .add_opt_param("expansions", expansions)
+            .add_opt_param("tweet.fields", join_field_names(tweet_fields))
+    }
+}
+
+/// Represents a single user, as returned by version 2 of the Twitter API.
+///
+/// Only `id`, `name`, and `username` are always present. The other fields are only filled in if
+/// they were asked for with `Fields::user_fields`, and if Twitter has a value for them on this
+/// user. `pinned_tweet` is filled in from `includes` when a `UserLookup` is loaded.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserV2 {
+    /// Numeric ID for this user.
+    #[serde(with = "serde_via_string")]
+    pub id: u64,
+    /// The user's display name.
+    pub name: String,
+    /// The user's screen name, without the leading `@`.
+    pub username: String,
+    /// When the account was created.
+    #[serde(default, deserialize_with = "deserialize_opt_iso8601")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// The user's profile description.
+    pub description: Option<String>,
+    /// The location given in the user's profile.
+    pub location: Option<String>,
+    /// The ID of the tweet pinned to the user's profile, if any.
+    #[serde(default, deserialize_with = "deserialize_opt_string_id")]
+    pub pinned_tweet_id: Option<u64>,
+    /// The URL of the user's profile image.
+    pub profile_image_url: Option<String>,
+    /// Whether the user's tweets are protected.
+    pub protected: Option<bool>,
+    /// The user's follower, following, tweet, and listed counts.
+    pub public_metrics: Option<UserMetrics>,
+    /// The URL given in the user's profile, as a t.co link.
+    pub url: Option<String>,
+    /// Whether the user is verified.
+    pub verified: Option<bool>,
+    /// The tweet pinned to the user's profile, if it was asked for with `Fields::pinned_tweet`.
+    #[serde(skip)]
+    pub pinned_tweet: Option<Box<TweetV2>>,
+}
+
+impl Hydrate<UserIncludes> for UserV2 {
+    fn hydrate(&mut self, includes: &UserIncludes) {
+        self.pinned_tweet = self
+            .pinned_tweet_id
+            .and_then(|id| includes.tweet(id))
+            .map(|tweet| Box::new(tweet.clone()));
+    }
+}
+
+/// The follower, following, tweet, and listed counts of a `UserV2`.
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct UserMetrics {
+    /// The number of accounts following this user.
+    pub followers_count: u64,
+    /// The number of accounts this user follows.
+    pub following_count: u64,
+    /// The number of tweets (including retweets) this user has posted.
+    pub tweet_count: u64,
+    /// The number of public lists this user is a member of.
+    pub listed_count: u64,
+}
+
+/// The related objects loaded alongside the users in a `UserLookup`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UserIncludes {
+    /// The pinned tweets of the loaded users, if they were asked for with `Fields::pinned_tweet`.
+    #[serde(default)]
+    pub tweets: Vec<TweetV2>,
+}
+
+impl UserIncludes {
+    /// Looks up the included tweet with the given ID.
+    pub fn tweet(&self, id: u64) -> Option<&TweetV2> {
+        self.tweets.iter().find(|t| t.id == id)
+    }
+}
+
+/// The response from a version 2 user lookup.
+///
+/// `data` holds the users that were loaded: a `Vec` from `lookup_ids` or `lookup_names`, or an
+/// `Option` from `show`. The users are hydrated from `includes` as they're loaded.
+///
+/// `includes` holds the pinned tweets asked for with `Fields::pinned_tweet`.
+pub type UserLookup<T> = Lookup<T, UserIncludes>;
+
+/// A page of users from a call that loads a list of users a page at a time.
+pub type UserPage = Response<UserLookup<Vec<UserV2>>>;
+
+/// Lookup a single user by numeric ID or screen name, loading the given fields.
+///
+/// If the user doesn't exist or has been suspended, `data` will be `None`, and `errors` will say
+/// why.
+pub async fn show<T: Into<UserID>>(
+    acct: T,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<UserLookup<Option<UserV2>>>> {
+    let url = match acct.into() {
+        UserID::ID(id) => format!("{}/{}", links::users::LOOKUP_V2, id),
+        UserID::ScreenName(name) => format!("{}/{}", links::users::SHOW_BY_USERNAME_V2, name),
+    };
+    let req = get(&url, token, Some(&fields.params()));
+    request_with_json_response(req).await
+}
+
+/// Lookup several users by numeric ID, loading the given fields.
+///
+/// Twitter accepts up to 100 IDs per call. Users that couldn't be loaded are left out of `data`,
+/// with a `Problem` in `errors` for each one.
+pub async fn lookup_ids<I: IntoIterator<Item = u64>>(
+    ids: I,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<UserLookup<Vec<UserV2>>>> {
+    let ids = ids.into_iter().map(|id| id.to_string()).collect::<Vec<_>>();
+    let params = fields.params().add_param("ids", ids.join(","));
+    let req = get(links::users::LOOKUP_V2, token, Some(&params));
+    request_with_json_response(req).await
+}
+
+/// Lookup several users by screen name, loading the given fields.
+///
+/// Twitter accepts up to 100 screen names per call. Users that couldn't be loaded are left out of
+/// `data`, with a `Problem` in `errors` for each one.
+pub async fn lookup_names<S, I>(
+    names: I,
+    fields: &Fields,
+    token: &auth::Token,
+) -> Result<Response<UserLookup<Vec<UserV2>>>>
+where
+    S: AsRef<str>,
+    I: IntoIterator<Item = S>,
+{
+    let names = names.into_iter().collect::<Vec<_>>();
+    let names = names.iter().map(|n| n.as_ref()).collect::<Vec<_>>();
+    let params = fields.params().add_param("usernames", names.join(","));
+    let req = get(links::users::LOOKUP_BY_V2, token, Some(&params));
+    request_with_json_response(req).await
+}
+
+//---User actions---
+
+/// The result of following a user through version 2 of the API.
+#[derive(Debug, Copy, Clone, Deserialize)]
+pub struct FollowStatus {
+    /// Whether the authenticated user now follows the given user.
+    pub following: bool,
+    /// Whether a follow request was sent instead, because the given user's tweets are protected.
+    pub pending_follow: bool,
+}
+
+/// Follow the given user with the authenticated user.
+///
+/// `source_user_id` must be the ID of the authenticated user. If the given user's tweets are
+/// protected, a follow request is sent instead, and the returned status will say so.
+pub async fn follow(
+    source_user_id: u64,
+    target_user_id: u64,
+    token: &auth::Token,
+) -> Result<Response<FollowStatus>> {
+    let resp = relate(source_user_id, "following", target_user_id, token).await?;
+    Ok(Response::map(resp, |r| FollowStatus {
+        following: r.following,
+        pending_follow: r.pending_follow,
+    }))
+}
+
+/// Unfollow the given user with the authenticated user.
+///
+/// `source_user_id` must be the ID of the authenticated user. Upon success, the response says
+/// whether the authenticated user still follows the given user.
+pub async fn unfollow(
+    source_user_id: u64,
+    target_user_id: u64,
+    token: &auth::Token,
+) -> Result<Response<bool>> {
+    let resp = unrelate(source_user_id, "following", target_user_id, token).await?;
+    Ok(Response::map(resp, |r| r.following))
+}
+
+/// Block the given user with the authenticated user.
+///
+/// `source_user_id` must be the ID of the authenticated user. Upon success, the response says
+/// whether the given user is now blocked.
+pub async fn block(
+    source_user_id: u64,
+    target_user_id: u64,
+    token: &auth::Token,
+) -> Result<Response<bool>> {
+    let resp = relate(source_user_id, "blocking", target_user_id, token).await?;
+    Ok(Response::map(resp, |r| r.blocking))
+}
+
+/// Unblock the given user with the authenticated user.
+///
+/// `source_user_id` must be the ID of the authenticated user. Upon success, the response says
+/// whether the given user is still blocked.
+pub async fn unblock(
+    source_user_id: u64,
+    target_user_id: u64,
+    token: &auth::Token,
+) -> Result<Response<bool>> {
+    let resp = unrelate(source_user_id, "blocking", target_user_id, token).await?;
+    Ok(Response::map(resp, |r| r.blocking))
+}
+
+/// Mute the given user with the authenticated user.
+///
+/// `source_user_id` must be the ID of the authenticated user. Upon success, the response says
+/// whether the given user is now muted.
+pub async fn mute(
+    source_user_id: u64,
+    target_user_id: u64,
+    token: &auth::Token,
+) -> Result<Response<bool>> {
+    let resp = relate(source_user_id, "muting", target_user_id, token).await?;
+    Ok(Response::map(resp, |r| r.muting))
+}
+
+/// Unmute the given user with the authenticated user.
+///
+/// `source_user_id` must be the ID of the authenticated user. Upon success, the response says
+/// whether the given user is still muted.
+pub async fn unmute(
+    source_user_id: u64,
+    target_user_id: u64,
+    token: &auth::Token,
+) -> Result<Response<bool>> {
+    let resp = unrelate(source_user_id, "muting", target_user_id, token).await?;
+    Ok(Response::map(resp, |r| r.muting))
+}
+
+/// Sends `POST 2/users/:id/{kind}` to start following, blocking, or muting the given user.
+async fn relate(
+    source_user_id: u64,
+    kind: &str,
+    target_user_id: u64,
+    token: &auth::Token,
+) -> Result<Response<Relation>> {
+    let url = format!("{}/{}/{}", links::users::LOOKUP_V2, source_user_id, kind);
+    let body = serde_json::json!({ "target_user_id": target_user_id.to_string() });
+    let req = post_json(&url, token, body);
+    let resp = request_with_json_response::<RelationStatus>(req).await?;
+    Ok(Response::map(resp, |status| status.data))
+}
+
+/// Sends `DELETE 2/users/:id/{kind}/:target_user_id` to stop following, blocking, or muting the
+/// given user.
+async fn unrelate(
+    source_user_id: u64,
+    kind: &str,
+    target_user_id: u64,
+    token: &auth::Token,
+) -> Result<Response<Relation>> {
+    let url = format!(
+        "{}/{}/{}/{}",
+        links::users::LOOKUP_V2,
+        source_user_id,
+        kind,
+        target_user_id
+    );
+    let req = auth::raw::delete(&url, token, None);
+    let resp = request_with_json_response::<RelationStatus>(req).await?;
+    Ok(Response::map(resp, |status| status.data))
+}
+
+/// The response from the v2 follow, block, and mute endpoints.
+#[derive(Deserialize)]
+struct RelationStatus {
+    data: Relation,
+}
+
+/// Each endpoint only sends the flag it changes, so the rest default to `false`.
+#[derive(Deserialize)]
+struct Relation {
+    #[serde(default)]
+    following: bool,
+    #[serde(default)]
+    pending_follow: bool,
+    #[serde(default)]
+    blocking: bool,
+    #[serde(default)]
+    muting: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::tests::load_file;
+    use crate::tweet::v2::Paged;
+
+    #[test]
+    fn parse_lookup() {
+        let sample = load_file("sample_payloads/v2-users.json");
+        let lookup: UserLookup<Vec<UserV2>> = serde_json::from_str(&sample).unwrap();
+
+        assert_eq!(lookup.data.len(), 2);
+        let dev = &lookup.data[0];
+        assert_eq!(dev.id, 2244994945);
+        assert_eq!(dev.username, "TwitterDev");
+        assert_eq!(dev.created_at.unwrap().timestamp(), 1386995755);
+        assert_eq!(dev.pinned_tweet_id, Some(1293595870563381249));
+        assert_eq!(dev.public_metrics.unwrap().followers_count, 513962);
+        assert_eq!(dev.verified, Some(true));
+
+        let pinned = lookup.includes.tweet(dev.pinned_tweet_id.unwrap()).unwrap();
+        assert!(pinned.text.starts_with("Twitter API v2"));
+        assert_eq!(dev.pinned_tweet.as_ref().map(|t| t.id), Some(pinned.id));
+        assert_eq!(lookup.data[1].pinned_tweet_id, None);
+        assert!(lookup.data[1].pinned_tweet.is_none());
+
+        assert_eq!(lookup.errors.len(), 1);
+        assert_eq!(
+            lookup.errors[0].resource_id.as_deref(),
+            Some("nosuchuser_eggmode")
+        );
+    }
+
+    #[test]
+    fn parse_liking_users_page() {
+        let sample = r#"{
+            "data": [{"id": "2244994945", "name": "Twitter Dev", "username": "TwitterDev"}],
+            "meta": {"result_count": 1, "next_token": "7140dibdnow9c7btw3w29grvxfcgvpb9n9coehpk7xz5i"}
+        }"#;
+        let page: UserLookup<Vec<UserV2>> = serde_json::from_str(sample).unwrap();
+        assert_eq!(page.data[0].username, "TwitterDev");
+        assert_eq!(page.meta.result_count, 1);
+        assert_eq!(
+            page.next_token().as_deref(),
+            Some("7140dibdnow9c7btw3w29grvxfcgvpb9n9coehpk7xz5i")
+        );
+
+        // lookups by ID or name don't send any `meta`
+        let sample = load_file("sample_payloads/v2-users.json");
+        let lookup: UserLookup<Vec<UserV2>> = serde_json::from_str(&sample).unwrap();
+        assert!(lookup.next_token().is_none());
+    }
+
+    #[test]
+    fn parse_relation_status() {
+        let sample = r#"{"data":{"following":false,"pending_follow":true}}"#;
+        let status: RelationStatus = serde_json::from_str(sample).unwrap();
+        assert!(!status.data.following);
+        assert!(status.data.pending_follow);
+
+        let status: RelationStatus = serde_json::from_str(r#"{"data":{"muting":true}}"#).unwrap();
+        assert!(status.data.muting);
+        assert!(!status.data.blocking);
+    }
+
+    #[test]
+    fn field_params() {
+        assert!(Fields::new().params().is_empty());
+
+        let fields = Fields::new()
+            .user_fields(vec![UserField::CreatedAt, UserField::PinnedTweetId])
+            .pinned_tweet(vec![TweetField::CreatedAt]);
+        let params = fields.params();
+        assert_eq!(
+            params.get("user.fields").map(|v| v.as_ref()),
+            Some("created_at,pinned_tweet_id")
+        );
+        assert_eq!(
+            params.get("expansions").map(|v| v.as_ref()),
+            Some("pinned_tweet_id")
+        );
+        assert_eq!(
+            params.get("tweet.fields").map(|v| v.as_ref()),
+            Some("created_at")
+        );
+    }
+}